@@ -0,0 +1 @@
+drop table if exists network_failed_blocks;
\ No newline at end of file
@@ -0,0 +1,12 @@
+create table network_failed_blocks
+(
+    id              bigserial constraint network_failed_blocks_pk primary key,
+    chain           text not null,
+    network         text not null default '',
+    block_number    bigint not null,
+    handlers        text[] not null,    -- Handlers which failed for this block
+    error           text not null default '',
+    attempts        int not null default 1
+);
+create unique index network_failed_blocks_chain_network_block_uindex
+    on network_failed_blocks (chain, network, block_number);
@@ -0,0 +1,10 @@
+drop view if exists solana_token_top_holders;
+drop trigger if exists solana_token_holders_changed on solana_token_holders;
+drop function if exists solana_token_holders_changed();
+drop function if exists solana_token_owner_add(varchar, varchar, numeric);
+drop view if exists solana_daily_token_stats;
+drop table if exists solana_daily_token_supplies;
+drop table if exists solana_daily_token_changes;
+drop table if exists solana_token_supplies;
+drop table if exists solana_token_owners;
+drop table if exists solana_token_holders;
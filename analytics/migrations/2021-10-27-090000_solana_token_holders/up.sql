@@ -0,0 +1,130 @@
+-- Current balance of every token account which has been seen in a block
+create table solana_token_holders
+(
+    id              bigserial constraint solana_token_holders_pk primary key,
+    mint            varchar(88) not null,
+    account         varchar(88) not null,
+    owner           varchar(88),
+    decimals        smallint,
+    balance         numeric not null default 0,
+    block_slot      bigint not null,    -- Slot of the latest balance change
+    block_time      bigint not null,
+    constraint solana_token_holders_mint_account_uindex
+        unique (mint, account)
+);
+create index solana_token_holders_mint_balance_index
+    on solana_token_holders (mint, balance desc);
+create index solana_token_holders_owner_index
+    on solana_token_holders (owner);
+
+-- Balance of every owner per mint, summed over its token accounts. Accounts
+-- without a known owner count as their own owner
+create table solana_token_owners
+(
+    mint            varchar(88) not null,
+    owner           varchar(88) not null,
+    balance         numeric not null default 0,
+    constraint solana_token_owners_pk
+        primary key (mint, owner)
+);
+
+-- Current holder count (owners with a positive balance) per mint, maintained
+-- from solana_token_holders, and supply per mint read from the mint account
+create table solana_token_supplies
+(
+    mint            varchar(88) constraint solana_token_supplies_pk primary key,
+    holder_count    bigint not null default 0,
+    supply          numeric not null default 0,
+    decimals        smallint,
+    supply_slot     bigint not null default 0   -- Slot of the block which read the supply
+);
+
+-- Changes of the holder count per mint by the day of the blocks which made
+-- them (date as timestamp of 00:00 UTC)
+create table solana_daily_token_changes
+(
+    mint            varchar(88) not null,
+    date            bigint not null,
+    holder_change   bigint not null default 0,
+    constraint solana_daily_token_changes_pk
+        primary key (mint, date)
+);
+
+-- Supply per mint read by the latest block of each day with balance changes
+create table solana_daily_token_supplies
+(
+    mint            varchar(88) not null,
+    date            bigint not null,
+    supply          numeric not null default 0,
+    block_slot      bigint not null,
+    constraint solana_daily_token_supplies_pk
+        primary key (mint, date)
+);
+
+-- Holder count and supply per mint at the end of each day with balance
+-- changes, independent of the order in which blocks are processed
+create view solana_daily_token_stats as
+select s.mint, s.date,
+       sum(coalesce(c.holder_change, 0)) over w as holder_count,
+       s.supply
+from solana_daily_token_supplies s
+left join solana_daily_token_changes c on c.mint = s.mint and c.date = s.date
+window w as (partition by s.mint order by s.date);
+
+-- Add amount to the balance of an owner and return the change of the holder count
+create function solana_token_owner_add(_mint varchar, _owner varchar, _amount numeric) returns bigint as $$
+declare
+    new_balance numeric;
+begin
+    if _amount = 0 then
+        return 0;
+    end if;
+    insert into solana_token_owners as o (mint, owner, balance)
+    values (_mint, _owner, _amount)
+    on conflict (mint, owner) do update
+        set balance = o.balance + excluded.balance
+    returning balance into new_balance;
+    return (case when new_balance > 0 then 1 else 0 end)
+         - (case when new_balance - _amount > 0 then 1 else 0 end);
+end;
+$$ language plpgsql;
+
+create function solana_token_holders_changed() returns trigger as $$
+declare
+    holder_change bigint := 0;
+begin
+    if TG_OP = 'UPDATE' then
+        holder_change := solana_token_owner_add(OLD.mint, coalesce(OLD.owner, OLD.account), -OLD.balance);
+    end if;
+    holder_change := holder_change
+        + solana_token_owner_add(NEW.mint, coalesce(NEW.owner, NEW.account), NEW.balance);
+    if holder_change = 0 then
+        return NEW;
+    end if;
+    insert into solana_token_supplies as s (mint, holder_count)
+    values (NEW.mint, holder_change)
+    on conflict (mint) do update
+        set holder_count = s.holder_count + excluded.holder_count;
+    insert into solana_daily_token_changes as d (mint, date, holder_change)
+    values (NEW.mint, NEW.block_time / 86400 * 86400, holder_change)
+    on conflict (mint, date) do update
+        set holder_change = d.holder_change + excluded.holder_change;
+    return NEW;
+end;
+$$ language plpgsql;
+
+create trigger solana_token_holders_changed
+    after insert or update of balance, owner on solana_token_holders
+    for each row execute procedure solana_token_holders_changed();
+
+-- The 100 largest owners of each mint, summed over their token accounts
+create view solana_token_top_holders as
+select o.mint, o.owner, o.balance, s.decimals, o.rank
+from (
+    select mint, owner, balance,
+           rank() over (partition by mint order by balance desc) as rank
+    from solana_token_owners
+    where balance > 0
+) o
+left join solana_token_supplies s on s.mint = o.mint
+where o.rank <= 100;
@@ -0,0 +1,11 @@
+drop view if exists solana_epoch_stats;
+drop view if exists solana_validator_daily_blocks;
+drop view if exists solana_validator_daily_votes;
+drop table if exists solana_validator_slots;
+drop table if exists solana_validator_block_votes;
+drop view if exists solana_validator_commissions;
+drop table if exists solana_inst_vote_update_commissions;
+drop table if exists solana_inst_vote_update_validator_identities;
+drop table if exists solana_inst_vote_withdraws;
+drop table if exists solana_inst_vote_authorizes;
+drop table if exists solana_inst_vote_initializes;
@@ -0,0 +1,152 @@
+create table solana_inst_vote_initializes
+(
+    id                      bigserial constraint solana_inst_vote_initializes_pk primary key,
+    tx_hash                 varchar(100),
+    block_slot              bigint,
+    block_time              bigint,
+    inst_order              int,        --instruction order in transaction
+    vote_account            varchar(88),
+    node                    varchar(88),
+    authorized_voter        varchar(88),
+    authorized_withdrawer   varchar(88),
+    commission              smallint
+);
+
+create table solana_inst_vote_authorizes
+(
+    id                      bigserial constraint solana_inst_vote_authorizes_pk primary key,
+    tx_hash                 varchar(100),
+    block_slot              bigint,
+    block_time              bigint,
+    inst_order              int,        --instruction order in transaction
+    vote_account            varchar(88),
+    authority               varchar(88),
+    new_authority           varchar(88),
+    authority_type          varchar(20)
+);
+
+create table solana_inst_vote_withdraws
+(
+    id                      bigserial constraint solana_inst_vote_withdraws_pk primary key,
+    tx_hash                 varchar(100),
+    block_slot              bigint,
+    block_time              bigint,
+    inst_order              int,        --instruction order in transaction
+    vote_account            varchar(88),
+    destination             varchar(88),
+    withdraw_authority      varchar(88),
+    lamports                bigint
+);
+
+create table solana_inst_vote_update_validator_identities
+(
+    id                      bigserial constraint solana_inst_vote_update_validator_identities_pk primary key,
+    tx_hash                 varchar(100),
+    block_slot              bigint,
+    block_time              bigint,
+    inst_order              int,        --instruction order in transaction
+    vote_account            varchar(88),
+    new_validator_identity  varchar(88),
+    withdraw_authority      varchar(88)
+);
+
+create table solana_inst_vote_update_commissions
+(
+    id                      bigserial constraint solana_inst_vote_update_commissions_pk primary key,
+    tx_hash                 varchar(100),
+    block_slot              bigint,
+    block_time              bigint,
+    inst_order              int,        --instruction order in transaction
+    vote_account            varchar(88),
+    commission              smallint,
+    withdraw_authority      varchar(88)
+);
+
+-- Commission of vote accounts over time
+create view solana_validator_commissions as
+select vote_account, commission, block_slot, block_time, tx_hash
+from solana_inst_vote_initializes
+union all
+select vote_account, commission, block_slot, block_time, tx_hash
+from solana_inst_vote_update_commissions;
+
+-- Votes cast by each vote account in each block, replaced when the block is processed again
+create table solana_validator_block_votes
+(
+    id                      bigserial constraint solana_validator_block_votes_pk primary key,
+    network                 varchar(100),
+    block_slot              bigint,
+    date                    bigint,
+    epoch                   bigint,
+    node                    varchar(88),    -- Validator identity of the vote account
+    vote_account            varchar(88),
+    votes                   bigint,     -- Number of vote instructions
+    vote_slot_count         bigint,     -- Number of slots voted on, not the earned credits
+    last_vote_slot          bigint,
+    constraint solana_validator_block_votes_uindex
+        unique (network, block_slot, vote_account)
+);
+create index solana_validator_block_votes_date_index
+    on solana_validator_block_votes (network, date);
+
+-- Leader of each slot, from the fee reward of produced blocks and the leader schedule of skipped slots
+create table solana_validator_slots
+(
+    id                      bigserial constraint solana_validator_slots_pk primary key,
+    network                 varchar(100),
+    slot                    bigint,
+    block_slot              bigint,     -- Slot of the block which produced or skipped this slot
+    date                    bigint,
+    epoch                   bigint,
+    node                    varchar(88),
+    produced                boolean,
+    reward                  bigint,
+    constraint solana_validator_slots_uindex
+        unique (network, slot)
+);
+create index solana_validator_slots_date_index
+    on solana_validator_slots (network, date);
+
+create view solana_validator_daily_votes as
+select network, date, vote_account,
+       max(node) as node,
+       sum(votes) as votes,
+       sum(vote_slot_count) as vote_slot_count,
+       max(last_vote_slot) as last_vote_slot
+from solana_validator_block_votes
+group by network, date, vote_account;
+
+create view solana_validator_daily_blocks as
+select network, date, node,
+       count(*) as leader_slots,
+       count(*) filter (where produced) as produced_blocks,
+       count(*) filter (where not produced) as skipped_slots,
+       sum(reward) as total_reward
+from solana_validator_slots
+group by network, date, node;
+
+create view solana_epoch_stats as
+select coalesce(slots.network, votes.network) as network,
+       coalesce(slots.epoch, votes.epoch) as epoch,
+       slots.min_block_slot,
+       slots.max_block_slot,
+       coalesce(slots.produced_blocks, 0) as produced_blocks,
+       coalesce(slots.skipped_slots, 0) as skipped_slots,
+       coalesce(slots.total_reward, 0) as total_reward,
+       coalesce(votes.votes, 0) as votes,
+       coalesce(votes.vote_slot_count, 0) as vote_slot_count
+from (
+    select network, epoch,
+           min(slot) filter (where produced) as min_block_slot,
+           max(slot) filter (where produced) as max_block_slot,
+           count(*) filter (where produced) as produced_blocks,
+           count(*) filter (where not produced) as skipped_slots,
+           sum(reward) as total_reward
+    from solana_validator_slots
+    group by network, epoch
+) slots
+full join (
+    select network, epoch, sum(votes) as votes, sum(vote_slot_count) as vote_slot_count
+    from solana_validator_block_votes
+    group by network, epoch
+) votes on slots.network = votes.network and slots.epoch = votes.epoch;
@@ -0,0 +1 @@
+drop table if exists network_backfill_chunks;
\ No newline at end of file
@@ -0,0 +1,11 @@
+create table network_backfill_chunks
+(
+    id              bigserial constraint network_backfill_chunks_pk primary key,
+    chain           text not null,
+    network         text not null default '',
+    start_block     bigint not null,
+    end_block       bigint not null,    -- Exclusive
+    next_block      bigint not null,    -- First block of the chunk which is not processed yet
+    constraint network_backfill_chunks_uindex
+        unique (chain, network, start_block)
+);
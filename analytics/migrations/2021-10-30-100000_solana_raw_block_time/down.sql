@@ -0,0 +1,2 @@
+alter table solana_transactions drop column block_time;
+alter table solana_token_balances drop column block_time;
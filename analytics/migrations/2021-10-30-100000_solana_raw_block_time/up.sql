@@ -0,0 +1,4 @@
+alter table solana_transactions
+    add block_time bigint;
+alter table solana_token_balances
+    add block_time bigint;
@@ -0,0 +1,7 @@
+drop trigger if exists ethereum_token_transfers_inserted on ethereum_token_transfers;
+drop function if exists ethereum_token_transfers_inserted();
+drop function if exists ethereum_token_holder_add(varchar, varchar, varchar, numeric);
+drop table if exists ethereum_daily_token_stats;
+drop table if exists ethereum_tokens;
+drop table if exists ethereum_token_holders;
+drop table if exists ethereum_token_transfers;
@@ -0,0 +1,116 @@
+-- ERC-20 and ERC-721 Transfer events
+create table ethereum_token_transfers
+(
+    id                  bigserial constraint ethereum_token_transfers_pk primary key,
+    network             varchar(40) not null,
+    transaction_hash    varchar(64) not null,
+    log_index           bigint not null,
+    block_number        bigint not null,
+    timestamp           bigint not null,
+    token_address       varchar(40) not null,
+    token_type          varchar(8) not null,    -- erc20 or erc721
+    sender              varchar(40) not null,
+    receiver            varchar(40) not null,
+    value               numeric not null,       -- Amount, 1 for ERC-721
+    token_id            numeric,                -- ERC-721 only
+    constraint ethereum_token_transfers_uindex
+        unique (network, transaction_hash, log_index)
+);
+create index ethereum_token_transfers_token_block_index
+    on ethereum_token_transfers (network, token_address, block_number);
+create index ethereum_token_transfers_sender_index
+    on ethereum_token_transfers (sender);
+create index ethereum_token_transfers_receiver_index
+    on ethereum_token_transfers (receiver);
+
+-- Current balance of every holder (token count for ERC-721), maintained from ethereum_token_transfers
+create table ethereum_token_holders
+(
+    id              bigserial constraint ethereum_token_holders_pk primary key,
+    network         varchar(40) not null,
+    token_address   varchar(40) not null,
+    holder          varchar(40) not null,
+    balance         numeric not null default 0,
+    constraint ethereum_token_holders_uindex
+        unique (network, token_address, holder)
+);
+create index ethereum_token_holders_token_balance_index
+    on ethereum_token_holders (network, token_address, balance desc);
+
+-- Holder count and transfer count per token
+create table ethereum_tokens
+(
+    id              bigserial constraint ethereum_tokens_pk primary key,
+    network         varchar(40) not null,
+    token_address   varchar(40) not null,
+    token_type      varchar(8) not null,
+    holder_count    bigint not null default 0,
+    transfer_count  bigint not null default 0,
+    constraint ethereum_tokens_uindex
+        unique (network, token_address)
+);
+
+-- Transfers and volume per token and day, with the holder count at the end of the day
+create table ethereum_daily_token_stats
+(
+    id                  bigserial constraint ethereum_daily_token_stats_pk primary key,
+    network             varchar(40) not null,
+    token_address       varchar(40) not null,
+    transaction_date    date not null,
+    transfer_count      bigint not null,
+    volume              numeric not null,
+    holder_count        bigint not null,
+    constraint ethereum_daily_token_stats_uindex
+        unique (network, token_address, transaction_date)
+);
+
+-- Add delta to the balance of a holder, returns the change of the holder count
+create function ethereum_token_holder_add(_network varchar, _token varchar, _holder varchar, _delta numeric)
+    returns integer as $$
+declare
+    new_balance numeric;
+begin
+    insert into ethereum_token_holders as h (network, token_address, holder, balance)
+    values (_network, _token, _holder, _delta)
+    on conflict (network, token_address, holder) do update
+        set balance = h.balance + excluded.balance
+    returning balance into new_balance;
+    return (case when new_balance > 0 then 1 else 0 end)
+        - (case when new_balance - _delta > 0 then 1 else 0 end);
+end;
+$$ language plpgsql;
+
+-- Transfers which already exist are skipped by the handler, so each transfer is applied once
+create function ethereum_token_transfers_inserted() returns trigger as $$
+declare
+    zero_address constant varchar := '0000000000000000000000000000000000000000';
+    holder_delta integer := 0;
+begin
+    if NEW.sender <> zero_address then
+        holder_delta := holder_delta
+            + ethereum_token_holder_add(NEW.network, NEW.token_address, NEW.sender, -NEW.value);
+    end if;
+    if NEW.receiver <> zero_address then
+        holder_delta := holder_delta
+            + ethereum_token_holder_add(NEW.network, NEW.token_address, NEW.receiver, NEW.value);
+    end if;
+    insert into ethereum_tokens as s (network, token_address, token_type, holder_count, transfer_count)
+    values (NEW.network, NEW.token_address, NEW.token_type, holder_delta, 1)
+    on conflict (network, token_address) do update
+        set holder_count = s.holder_count + excluded.holder_count,
+            transfer_count = s.transfer_count + 1;
+    insert into ethereum_daily_token_stats as d
+        (network, token_address, transaction_date, transfer_count, volume, holder_count)
+    select network, token_address, (to_timestamp(NEW.timestamp) at time zone 'UTC')::date, 1, NEW.value, holder_count
+    from ethereum_tokens where network = NEW.network and token_address = NEW.token_address
+    on conflict (network, token_address, transaction_date) do update
+        set transfer_count = d.transfer_count + 1,
+            volume = d.volume + excluded.volume,
+            holder_count = excluded.holder_count;
+    return NEW;
+end;
+$$ language plpgsql;
+
+create trigger ethereum_token_transfers_inserted
+    after insert on ethereum_token_transfers
+    for each row execute procedure ethereum_token_transfers_inserted();
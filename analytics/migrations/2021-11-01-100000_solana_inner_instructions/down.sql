@@ -0,0 +1,31 @@
+alter table solana_inst_create_accounts drop column if exists parent_order;
+alter table solana_inst_assigns drop column if exists parent_order;
+alter table solana_inst_transfers drop column if exists parent_order;
+alter table solana_inst_advance_nonces drop column if exists parent_order;
+alter table solana_inst_withdraw_from_nonces drop column if exists parent_order;
+alter table solana_inst_initialize_nonces drop column if exists parent_order;
+alter table solana_inst_authorize_nonces drop column if exists parent_order;
+alter table solana_inst_allocates drop column if exists parent_order;
+alter table solana_spl_token_initialize_mints drop column if exists parent_order;
+alter table solana_spl_token_initialize_accounts drop column if exists parent_order;
+alter table solana_spl_token_initialize_account2s drop column if exists parent_order;
+alter table solana_spl_token_initialize_multisigs drop column if exists parent_order;
+alter table solana_spl_token_transfers drop column if exists parent_order;
+alter table solana_spl_token_transfer_checkeds drop column if exists parent_order;
+alter table solana_spl_token_approves drop column if exists parent_order;
+alter table solana_spl_token_approve_checkeds drop column if exists parent_order;
+alter table solana_spl_token_revokes drop column if exists parent_order;
+alter table solana_spl_token_set_authorities drop column if exists parent_order;
+alter table solana_spl_token_mint_tos drop column if exists parent_order;
+alter table solana_spl_token_min_to_checkeds drop column if exists parent_order;
+alter table solana_spl_token_burns drop column if exists parent_order;
+alter table solana_spl_token_burn_checkeds drop column if exists parent_order;
+alter table solana_spl_token_close_accounts drop column if exists parent_order;
+alter table solana_spl_token_freeze_accounts drop column if exists parent_order;
+alter table solana_spl_token_thaw_accounts drop column if exists parent_order;
+alter table solana_spl_token_sync_natives drop column if exists parent_order;
+alter table solana_inst_vote_initializes drop column if exists parent_order;
+alter table solana_inst_vote_authorizes drop column if exists parent_order;
+alter table solana_inst_vote_withdraws drop column if exists parent_order;
+alter table solana_inst_vote_update_validator_identities drop column if exists parent_order;
+alter table solana_inst_vote_update_commissions drop column if exists parent_order;
@@ -0,0 +1,33 @@
+-- Inner instructions are stored with the order of the instruction which invoked them,
+-- inst_order is then the order among the inner instructions of that instruction
+alter table solana_inst_create_accounts add column parent_order int;
+alter table solana_inst_assigns add column parent_order int;
+alter table solana_inst_transfers add column parent_order int;
+alter table solana_inst_advance_nonces add column parent_order int;
+alter table solana_inst_withdraw_from_nonces add column parent_order int;
+alter table solana_inst_initialize_nonces add column parent_order int;
+alter table solana_inst_authorize_nonces add column parent_order int;
+alter table solana_inst_allocates add column parent_order int;
+alter table solana_spl_token_initialize_mints add column parent_order int;
+alter table solana_spl_token_initialize_accounts add column parent_order int;
+alter table solana_spl_token_initialize_account2s add column parent_order int;
+alter table solana_spl_token_initialize_multisigs add column parent_order int;
+alter table solana_spl_token_transfers add column parent_order int;
+alter table solana_spl_token_transfer_checkeds add column parent_order int;
+alter table solana_spl_token_approves add column parent_order int;
+alter table solana_spl_token_approve_checkeds add column parent_order int;
+alter table solana_spl_token_revokes add column parent_order int;
+alter table solana_spl_token_set_authorities add column parent_order int;
+alter table solana_spl_token_mint_tos add column parent_order int;
+alter table solana_spl_token_min_to_checkeds add column parent_order int;
+alter table solana_spl_token_burns add column parent_order int;
+alter table solana_spl_token_burn_checkeds add column parent_order int;
+alter table solana_spl_token_close_accounts add column parent_order int;
+alter table solana_spl_token_freeze_accounts add column parent_order int;
+alter table solana_spl_token_thaw_accounts add column parent_order int;
+alter table solana_spl_token_sync_natives add column parent_order int;
+alter table solana_inst_vote_initializes add column parent_order int;
+alter table solana_inst_vote_authorizes add column parent_order int;
+alter table solana_inst_vote_withdraws add column parent_order int;
+alter table solana_inst_vote_update_validator_identities add column parent_order int;
+alter table solana_inst_vote_update_commissions add column parent_order int;
@@ -0,0 +1 @@
+drop table if exists solana_program_events;
@@ -0,0 +1,16 @@
+-- Events decoded from the logs of the programs with an IDL in the analytics config
+create table solana_program_events
+(
+    id              bigserial constraint solana_program_events_pk primary key,
+    block_slot      bigint not null,
+    block_time      bigint,
+    tx_hash         varchar(100) not null,
+    log_index       int not null,       -- Index of the log message of the event
+    program_id      varchar(88) not null,
+    event_name      varchar(100) not null,
+    data            text not null,      -- JSON object with the fields of the event
+    constraint solana_program_events_uindex
+        unique (tx_hash, log_index)
+);
+create index solana_program_events_program_name_index
+    on solana_program_events (program_id, event_name, block_slot);
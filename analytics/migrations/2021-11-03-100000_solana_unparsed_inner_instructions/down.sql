@@ -0,0 +1 @@
+alter table solana_instructions drop column if exists parent_order;
@@ -0,0 +1,2 @@
+-- Unparsed inner instructions have the order of the instruction which invoked them
+alter table solana_instructions add column parent_order int;
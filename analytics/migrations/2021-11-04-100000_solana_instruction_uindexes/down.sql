@@ -0,0 +1,32 @@
+drop index if exists solana_inst_create_accounts_uindex;
+drop index if exists solana_inst_assigns_uindex;
+drop index if exists solana_inst_transfers_uindex;
+drop index if exists solana_inst_advance_nonces_uindex;
+drop index if exists solana_inst_withdraw_from_nonces_uindex;
+drop index if exists solana_inst_initialize_nonces_uindex;
+drop index if exists solana_inst_authorize_nonces_uindex;
+drop index if exists solana_inst_allocates_uindex;
+drop index if exists solana_spl_token_initialize_mints_uindex;
+drop index if exists solana_spl_token_initialize_accounts_uindex;
+drop index if exists solana_spl_token_initialize_account2s_uindex;
+drop index if exists solana_spl_token_initialize_multisigs_uindex;
+drop index if exists solana_spl_token_transfers_uindex;
+drop index if exists solana_spl_token_transfer_checkeds_uindex;
+drop index if exists solana_spl_token_approves_uindex;
+drop index if exists solana_spl_token_approve_checkeds_uindex;
+drop index if exists solana_spl_token_revokes_uindex;
+drop index if exists solana_spl_token_set_authorities_uindex;
+drop index if exists solana_spl_token_mint_tos_uindex;
+drop index if exists solana_spl_token_min_to_checkeds_uindex;
+drop index if exists solana_spl_token_burns_uindex;
+drop index if exists solana_spl_token_burn_checkeds_uindex;
+drop index if exists solana_spl_token_close_accounts_uindex;
+drop index if exists solana_spl_token_freeze_accounts_uindex;
+drop index if exists solana_spl_token_thaw_accounts_uindex;
+drop index if exists solana_spl_token_sync_natives_uindex;
+drop index if exists solana_inst_vote_initializes_uindex;
+drop index if exists solana_inst_vote_authorizes_uindex;
+drop index if exists solana_inst_vote_withdraws_uindex;
+drop index if exists solana_inst_vote_update_validator_identities_uindex;
+drop index if exists solana_inst_vote_update_commissions_uindex;
+drop index if exists solana_instructions_uindex;
@@ -0,0 +1,162 @@
+-- Instructions are only stored once when a block is processed again. Top level instructions
+-- have no parent_order, so the indexes use -1 for them. Rows stored twice before are removed first
+delete from solana_inst_create_accounts a using solana_inst_create_accounts b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_create_accounts_uindex
+    on solana_inst_create_accounts (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_assigns a using solana_inst_assigns b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_assigns_uindex
+    on solana_inst_assigns (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_transfers a using solana_inst_transfers b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_transfers_uindex
+    on solana_inst_transfers (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_advance_nonces a using solana_inst_advance_nonces b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_advance_nonces_uindex
+    on solana_inst_advance_nonces (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_withdraw_from_nonces a using solana_inst_withdraw_from_nonces b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_withdraw_from_nonces_uindex
+    on solana_inst_withdraw_from_nonces (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_initialize_nonces a using solana_inst_initialize_nonces b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_initialize_nonces_uindex
+    on solana_inst_initialize_nonces (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_authorize_nonces a using solana_inst_authorize_nonces b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_authorize_nonces_uindex
+    on solana_inst_authorize_nonces (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_allocates a using solana_inst_allocates b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_allocates_uindex
+    on solana_inst_allocates (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_initialize_mints a using solana_spl_token_initialize_mints b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_initialize_mints_uindex
+    on solana_spl_token_initialize_mints (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_initialize_accounts a using solana_spl_token_initialize_accounts b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_initialize_accounts_uindex
+    on solana_spl_token_initialize_accounts (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_initialize_account2s a using solana_spl_token_initialize_account2s b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_initialize_account2s_uindex
+    on solana_spl_token_initialize_account2s (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_initialize_multisigs a using solana_spl_token_initialize_multisigs b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_initialize_multisigs_uindex
+    on solana_spl_token_initialize_multisigs (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_transfers a using solana_spl_token_transfers b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_transfers_uindex
+    on solana_spl_token_transfers (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_transfer_checkeds a using solana_spl_token_transfer_checkeds b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_transfer_checkeds_uindex
+    on solana_spl_token_transfer_checkeds (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_approves a using solana_spl_token_approves b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_approves_uindex
+    on solana_spl_token_approves (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_approve_checkeds a using solana_spl_token_approve_checkeds b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_approve_checkeds_uindex
+    on solana_spl_token_approve_checkeds (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_revokes a using solana_spl_token_revokes b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_revokes_uindex
+    on solana_spl_token_revokes (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_set_authorities a using solana_spl_token_set_authorities b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_set_authorities_uindex
+    on solana_spl_token_set_authorities (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_mint_tos a using solana_spl_token_mint_tos b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_mint_tos_uindex
+    on solana_spl_token_mint_tos (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_min_to_checkeds a using solana_spl_token_min_to_checkeds b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_min_to_checkeds_uindex
+    on solana_spl_token_min_to_checkeds (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_burns a using solana_spl_token_burns b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_burns_uindex
+    on solana_spl_token_burns (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_burn_checkeds a using solana_spl_token_burn_checkeds b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_burn_checkeds_uindex
+    on solana_spl_token_burn_checkeds (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_close_accounts a using solana_spl_token_close_accounts b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_close_accounts_uindex
+    on solana_spl_token_close_accounts (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_freeze_accounts a using solana_spl_token_freeze_accounts b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_freeze_accounts_uindex
+    on solana_spl_token_freeze_accounts (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_thaw_accounts a using solana_spl_token_thaw_accounts b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_thaw_accounts_uindex
+    on solana_spl_token_thaw_accounts (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_spl_token_sync_natives a using solana_spl_token_sync_natives b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_spl_token_sync_natives_uindex
+    on solana_spl_token_sync_natives (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_vote_initializes a using solana_inst_vote_initializes b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_vote_initializes_uindex
+    on solana_inst_vote_initializes (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_vote_authorizes a using solana_inst_vote_authorizes b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_vote_authorizes_uindex
+    on solana_inst_vote_authorizes (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_vote_withdraws a using solana_inst_vote_withdraws b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_vote_withdraws_uindex
+    on solana_inst_vote_withdraws (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_vote_update_validator_identities a using solana_inst_vote_update_validator_identities b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_vote_update_validator_identities_uindex
+    on solana_inst_vote_update_validator_identities (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_inst_vote_update_commissions a using solana_inst_vote_update_commissions b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_hash = b.tx_hash
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_order = b.inst_order;
+create unique index solana_inst_vote_update_commissions_uindex
+    on solana_inst_vote_update_commissions (block_slot, tx_hash, coalesce(parent_order, -1), inst_order);
+delete from solana_instructions a using solana_instructions b
+where a.id > b.id and a.block_slot = b.block_slot and a.tx_index = b.tx_index
+  and coalesce(a.parent_order, -1) = coalesce(b.parent_order, -1) and a.inst_index = b.inst_index;
+create unique index solana_instructions_uindex
+    on solana_instructions (block_slot, tx_index, coalesce(parent_order, -1), inst_index);
@@ -0,0 +1,335 @@
+//! Ranged backfill of historical blocks. A block range is split into chunks
+//! stored in `network_backfill_chunks`, which workers process in parallel.
+//! The writes of the blocks are buffered by a [`BufferedAdapter`] and
+//! committed per batch of blocks in one transaction together with the
+//! progress of their chunk, so a stopped backfill resumes exactly after the
+//! last committed batch. Tables exported to Parquet are exported after their
+//! batch is committed, so rows of failed attempts and of batches which are
+//! processed again are not exported.
+use crate::models::{BackfillChunk, CommandData};
+use crate::postgres_queries::{OwnedConflictFragment, UpsertConflictFragment};
+use crate::relational::{Column, ColumnType, Table};
+use crate::schema::network_backfill_chunks;
+use crate::storage_adapter::StorageAdapter;
+use crate::{create_columns, create_entity};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use massbit::prelude::{Attribute, Entity, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const DEFAULT_BACKFILL_WORKERS: usize = 4;
+pub const DEFAULT_BACKFILL_CHUNK_SIZE: u64 = 10_000;
+/// Number of blocks committed together. The handler storage is flushed
+/// before each commit
+pub const COMMIT_BATCH_SIZE: u64 = 100;
+
+/// Split `start..end` into consecutive ranges of at most `chunk_size` blocks
+pub fn split_range(start: u64, end: u64, chunk_size: u64) -> Vec<(u64, u64)> {
+    let chunk_size = chunk_size.max(1);
+    (start..end)
+        .step_by(chunk_size as usize)
+        .map(|chunk_start| (chunk_start, (chunk_start + chunk_size).min(end)))
+        .collect()
+}
+
+/// The sub-ranges of `start..end` which are not covered by `chunks`, the
+/// `(start_block, end_block)` of the existing chunks sorted by start block
+pub fn missing_ranges(chunks: &[(u64, u64)], start: u64, end: u64) -> Vec<(u64, u64)> {
+    let mut ranges = vec![];
+    let mut next = start;
+    for &(chunk_start, chunk_end) in chunks {
+        if chunk_start > next {
+            ranges.push((next, chunk_start.min(end)));
+        }
+        next = next.max(chunk_end);
+        if next >= end {
+            return ranges;
+        }
+    }
+    if next < end {
+        ranges.push((next, end));
+    }
+    ranges
+}
+
+/// The chunks of the backfill of `start..end` which are not finished yet.
+/// Chunks are created for the parts of the range which are not covered by
+/// a previous backfill; existing chunks are kept even if the chunk size
+/// changed, so a resumed backfill continues where it stopped
+pub fn prepare_chunks(
+    conn: &PgConnection,
+    chain: &str,
+    network: &str,
+    start: u64,
+    end: u64,
+    chunk_size: u64,
+) -> QueryResult<Vec<BackfillChunk>> {
+    use crate::schema::network_backfill_chunks::dsl;
+    let existing: Vec<(u64, u64)> = dsl::network_backfill_chunks
+        .filter(dsl::chain.eq(chain))
+        .filter(dsl::network.eq(network))
+        .filter(dsl::start_block.lt(end as i64))
+        .filter(dsl::end_block.gt(start as i64))
+        .order(dsl::start_block.asc())
+        .select((dsl::start_block, dsl::end_block))
+        .load::<(i64, i64)>(conn)?
+        .into_iter()
+        .map(|(chunk_start, chunk_end)| (chunk_start as u64, chunk_end as u64))
+        .collect();
+    let missing = missing_ranges(&existing, start, end);
+    if !existing.is_empty() {
+        log::info!(
+            "Resume the backfill of {}..{} with {} existing chunks, missing ranges {:?}",
+            start,
+            end,
+            existing.len(),
+            &missing
+        );
+    }
+    let rows: Vec<_> = missing
+        .into_iter()
+        .flat_map(|(range_start, range_end)| split_range(range_start, range_end, chunk_size))
+        .map(|(chunk_start, chunk_end)| {
+            (
+                dsl::chain.eq(chain),
+                dsl::network.eq(network),
+                dsl::start_block.eq(chunk_start as i64),
+                dsl::end_block.eq(chunk_end as i64),
+                dsl::next_block.eq(chunk_start as i64),
+            )
+        })
+        .collect();
+    if !rows.is_empty() {
+        diesel::insert_into(network_backfill_chunks::table)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+    }
+    dsl::network_backfill_chunks
+        .filter(dsl::chain.eq(chain))
+        .filter(dsl::network.eq(network))
+        .filter(dsl::start_block.lt(end as i64))
+        .filter(dsl::end_block.gt(start as i64))
+        .filter(dsl::next_block.lt(dsl::end_block))
+        .order(dsl::start_block.asc())
+        .load::<BackfillChunk>(conn)
+}
+
+/// Upsert which moves the progress of `chunk` to `next_block`
+pub fn chunk_progress(chunk: &BackfillChunk, next_block: u64) -> (Table<'static>, Entity) {
+    let table = Table::new(
+        "network_backfill_chunks",
+        create_columns!(
+            "chain" => ColumnType::String,
+            "network" => ColumnType::String,
+            "start_block" => ColumnType::BigInt,
+            "end_block" => ColumnType::BigInt,
+            "next_block" => ColumnType::BigInt
+        ),
+    );
+    let entity = create_entity!(
+        "chain" => chunk.chain.clone(),
+        "network" => chunk.network.clone(),
+        "start_block" => chunk.start_block as u64,
+        "end_block" => chunk.end_block as u64,
+        "next_block" => next_block
+    );
+    (table, entity)
+}
+
+/// A `StorageAdapter` upsert kept by [`BufferedAdapter`] or by a
+/// `MirrorAdapter` until it is mirrored
+#[derive(Debug, Clone)]
+pub struct BufferedUpsert {
+    table_name: String,
+    columns: Vec<Column>,
+    alias: Option<String>,
+    values: Vec<Entity>,
+    conflict_fragment: Option<OwnedConflictFragment>,
+}
+
+impl BufferedUpsert {
+    pub fn new(
+        table: &Table,
+        values: &Vec<Entity>,
+        conflict_fragment: &Option<UpsertConflictFragment>,
+    ) -> Self {
+        BufferedUpsert {
+            table_name: table.name.as_str().to_string(),
+            columns: table.columns.clone(),
+            alias: table.alias.map(String::from),
+            values: values.clone(),
+            conflict_fragment: conflict_fragment.as_ref().map(OwnedConflictFragment::from),
+        }
+    }
+
+    pub fn table_name(&self) -> &str {
+        self.table_name.as_str()
+    }
+
+    /// Execute the upsert with `storage_adapter`
+    pub fn apply(&self, storage_adapter: &dyn StorageAdapter) -> Result<(), anyhow::Error> {
+        let conflict_fragment = self
+            .conflict_fragment
+            .as_ref()
+            .map(OwnedConflictFragment::as_fragment);
+        storage_adapter.upsert(&self.table(), &self.values, &conflict_fragment)
+    }
+
+    fn table(&self) -> Table {
+        Table::new_with_alias(
+            self.table_name.as_str(),
+            self.columns.clone(),
+            self.alias.as_deref(),
+        )
+    }
+}
+
+/// Collects the upserts of the handlers instead of executing them, so that
+/// the caller decides which of them are committed and when
+#[derive(Default)]
+pub struct BufferedAdapter {
+    upserts: Mutex<Vec<BufferedUpsert>>,
+}
+
+impl BufferedAdapter {
+    pub fn new() -> Self {
+        BufferedAdapter::default()
+    }
+
+    /// Remove and return the upserts collected so far
+    pub fn take(&self) -> Vec<BufferedUpsert> {
+        std::mem::take(&mut *self.upserts.lock().unwrap())
+    }
+}
+
+impl StorageAdapter for BufferedAdapter {
+    fn insert(&self, _table_name: &str, _value: HashMap<&str, Value>) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("Backfill only supports upserts"))
+    }
+
+    fn upsert(
+        &self,
+        table: &Table,
+        values: &Vec<Entity>,
+        conflict_fragment: &Option<UpsertConflictFragment>,
+    ) -> Result<(), anyhow::Error> {
+        if values.len() > 0 {
+            self.upserts.lock().unwrap().push(BufferedUpsert::new(
+                table,
+                values,
+                conflict_fragment,
+            ));
+        }
+        Ok(())
+    }
+
+    fn transact_upserts(&self, commands: Vec<CommandData>) -> Result<(), anyhow::Error> {
+        for cmd in commands {
+            self.upsert(cmd.table, cmd.values, cmd.conflict_fragment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Execute `upserts` and the progress of `chunk` in one transaction of
+/// `storage_adapter`, then write them into `exporter`. Errors of the export
+/// are only logged, like those of a mirror: the batch is already committed
+pub fn commit_upserts(
+    storage_adapter: &dyn StorageAdapter,
+    exporter: Option<&dyn StorageAdapter>,
+    upserts: &[BufferedUpsert],
+    chunk: &BackfillChunk,
+    next_block: u64,
+) -> Result<(), anyhow::Error> {
+    let tables: Vec<Table> = upserts.iter().map(BufferedUpsert::table).collect();
+    let conflict_fragments: Vec<Option<UpsertConflictFragment>> = upserts
+        .iter()
+        .map(|upsert| {
+            upsert
+                .conflict_fragment
+                .as_ref()
+                .map(OwnedConflictFragment::as_fragment)
+        })
+        .collect();
+    let (progress_table, progress_entity) = chunk_progress(chunk, next_block);
+    let progress_values = vec![progress_entity];
+    let mut progress_conflict = UpsertConflictFragment::new("network_backfill_chunks_uindex");
+    progress_conflict.add_expression("next_block", "GREATEST(t.next_block, EXCLUDED.next_block)");
+    let progress_conflict = Some(progress_conflict);
+    let mut commands: Vec<CommandData> = upserts
+        .iter()
+        .enumerate()
+        .map(|(index, upsert)| {
+            CommandData::new(&tables[index], &upsert.values, &conflict_fragments[index])
+        })
+        .collect();
+    commands.push(CommandData::new(
+        &progress_table,
+        &progress_values,
+        &progress_conflict,
+    ));
+    storage_adapter.transact_upserts(commands)?;
+    if let Some(exporter) = exporter {
+        export_upserts(exporter, upserts);
+    }
+    Ok(())
+}
+
+fn export_upserts(exporter: &dyn StorageAdapter, upserts: &[BufferedUpsert]) {
+    for upsert in upserts {
+        if let Err(err) = upsert.apply(exporter) {
+            log::error!(
+                "Can not export upsert into {}: {:?}",
+                &upsert.table_name,
+                &err
+            );
+        }
+    }
+    if let Err(err) = exporter.flush() {
+        log::error!("Can not flush export: {:?}", &err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{missing_ranges, split_range};
+
+    #[test]
+    fn splits_range_into_chunks() {
+        assert_eq!(split_range(0, 25, 10), vec![(0, 10), (10, 20), (20, 25)]);
+        assert_eq!(split_range(5, 5, 10), vec![]);
+    }
+
+    #[test]
+    fn whole_range_is_missing_without_chunks() {
+        assert_eq!(missing_ranges(&[], 100, 200), vec![(100, 200)]);
+    }
+
+    #[test]
+    fn finds_gaps_between_existing_chunks() {
+        let chunks = [(100, 110), (130, 140)];
+        assert_eq!(
+            missing_ranges(&chunks, 100, 150),
+            vec![(110, 130), (140, 150)]
+        );
+    }
+
+    #[test]
+    fn extends_covered_range() {
+        // A previous backfill of 100..120 is extended to 50..200
+        let chunks = [(100, 110), (110, 120)];
+        assert_eq!(
+            missing_ranges(&chunks, 50, 200),
+            vec![(50, 100), (120, 200)]
+        );
+    }
+
+    #[test]
+    fn chunks_overlapping_the_bounds_cover_them() {
+        let chunks = [(90, 110), (190, 210)];
+        assert_eq!(missing_ranges(&chunks, 100, 200), vec![(110, 190)]);
+        assert_eq!(missing_ranges(&[(0, 1000)], 100, 200), vec![]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Checkpointing of block processing. The checkpoint stored in
+//! `network_states.got_block` is a contiguous watermark: every block up to
+//! and including it has been handled, either successfully or by recording
+//! it in `network_failed_blocks` so that it can be repaired later.
+use crate::models::FailedBlock;
+use crate::schema::{network_failed_blocks, network_states};
+use diesel::pg::upsert::excluded;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::BTreeSet;
+
+/// Tracks the contiguous watermark of a stream of blocks which are started
+/// in ascending order but may finish in any order
+#[derive(Debug, Default)]
+pub struct BlockWatermark {
+    watermark: Option<u64>,
+    latest: Option<u64>,
+    pending: BTreeSet<u64>,
+    /// Last watermark returned by `checkpoint`
+    saved: Option<u64>,
+    /// Blocks finished after `saved`
+    finished: BTreeSet<u64>,
+}
+
+/// A watermark to save, see `BlockWatermark::checkpoint_with_finished`
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    /// The previous checkpoint
+    pub previous: Option<u64>,
+    pub block: u64,
+    /// The blocks finished after `previous` up to and including `block`
+    pub finished: BTreeSet<u64>,
+}
+
+impl BlockWatermark {
+    /// Create a watermark for a stream in which every block up to and
+    /// including `watermark` is already processed
+    pub fn new(watermark: Option<u64>) -> Self {
+        BlockWatermark {
+            watermark,
+            latest: watermark,
+            pending: BTreeSet::new(),
+            saved: watermark,
+            finished: BTreeSet::new(),
+        }
+    }
+
+    pub fn watermark(&self) -> Option<u64> {
+        self.watermark
+    }
+
+    pub fn start(&mut self, block: u64) {
+        self.pending.insert(block);
+    }
+
+    /// Mark `block` as handled and return the new watermark if it advanced.
+    /// Blocks which were never started, like skipped Solana slots, do not
+    /// hold the watermark back
+    pub fn finish(&mut self, block: u64) -> Option<u64> {
+        self.finished.insert(block);
+        self.abandon(block)
+    }
+
+    /// Stop waiting for `block` without marking it as handled. The watermark
+    /// moves past it, and `checkpoint_with_finished` reports it as missing
+    pub fn abandon(&mut self, block: u64) -> Option<u64> {
+        self.pending.remove(&block);
+        self.latest = self.latest.max(Some(block));
+        let candidate = match self.pending.iter().next() {
+            Some(first) => first.checked_sub(1),
+            None => self.latest,
+        };
+        if candidate > self.watermark {
+            self.watermark = candidate;
+            candidate
+        } else {
+            None
+        }
+    }
+
+    /// Return the watermark if it moved at least `batch` blocks past the
+    /// last returned one, so that checkpoints are saved per batch of blocks
+    pub fn checkpoint(&mut self, batch: u64) -> Option<u64> {
+        self.checkpoint_with_finished(batch)
+            .map(|checkpoint| checkpoint.block)
+    }
+
+    /// Like `checkpoint`, together with the blocks finished since the last
+    /// checkpoint, so that blocks which were dropped on their way can be
+    /// told apart from blocks which do not exist, like skipped Solana slots
+    pub fn checkpoint_with_finished(&mut self, batch: u64) -> Option<Checkpoint> {
+        let watermark = self.watermark?;
+        match self.saved {
+            Some(saved) if watermark < saved + batch => None,
+            previous => {
+                self.saved = Some(watermark);
+                let later = self.finished.split_off(&(watermark + 1));
+                Some(Checkpoint {
+                    previous,
+                    block: watermark,
+                    finished: std::mem::replace(&mut self.finished, later),
+                })
+            }
+        }
+    }
+
+    /// Return to the state before `checkpoint` was taken, because it could
+    /// not be saved
+    pub fn undo_checkpoint(&mut self, checkpoint: Checkpoint) {
+        self.saved = checkpoint.previous;
+        self.finished.extend(checkpoint.finished);
+    }
+}
+
+pub fn save_watermark(
+    conn: &PgConnection,
+    chain: &str,
+    network: &str,
+    block: u64,
+) -> QueryResult<usize> {
+    diesel::insert_into(network_states::table)
+        .values((
+            network_states::chain.eq(chain),
+            network_states::network.eq(network),
+            network_states::got_block.eq(block as i64),
+        ))
+        .on_conflict((network_states::chain, network_states::network))
+        .do_update()
+        .set(network_states::got_block.eq(excluded(network_states::got_block)))
+        .execute(conn)
+}
+
+/// Remember that `handlers` failed for `block`. Recording the same block
+/// again replaces the failed handlers and counts another attempt
+pub fn save_failed_block(
+    conn: &PgConnection,
+    chain: &str,
+    network: &str,
+    block: u64,
+    handlers: &[String],
+    error: &str,
+) -> QueryResult<usize> {
+    diesel::insert_into(network_failed_blocks::table)
+        .values((
+            network_failed_blocks::chain.eq(chain),
+            network_failed_blocks::network.eq(network),
+            network_failed_blocks::block_number.eq(block as i64),
+            network_failed_blocks::handlers.eq(handlers),
+            network_failed_blocks::error.eq(error),
+        ))
+        .on_conflict((
+            network_failed_blocks::chain,
+            network_failed_blocks::network,
+            network_failed_blocks::block_number,
+        ))
+        .do_update()
+        .set((
+            network_failed_blocks::handlers.eq(excluded(network_failed_blocks::handlers)),
+            network_failed_blocks::error.eq(excluded(network_failed_blocks::error)),
+            network_failed_blocks::attempts.eq(network_failed_blocks::attempts + 1),
+        ))
+        .execute(conn)
+}
+
+pub fn remove_failed_block(
+    conn: &PgConnection,
+    chain: &str,
+    network: &str,
+    block: u64,
+) -> QueryResult<usize> {
+    use crate::schema::network_failed_blocks::dsl;
+    diesel::delete(
+        dsl::network_failed_blocks
+            .filter(dsl::chain.eq(chain))
+            .filter(dsl::network.eq(network))
+            .filter(dsl::block_number.eq(block as i64)),
+    )
+    .execute(conn)
+}
+
+pub fn get_failed_blocks(
+    conn: &PgConnection,
+    chain: &str,
+    network: &str,
+) -> QueryResult<Vec<FailedBlock>> {
+    use crate::schema::network_failed_blocks::dsl;
+    dsl::network_failed_blocks
+        .filter(dsl::chain.eq(chain))
+        .filter(dsl::network.eq(network))
+        .order(dsl::block_number.asc())
+        .load::<FailedBlock>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockWatermark, Checkpoint};
+
+    #[test]
+    fn advances_over_contiguous_blocks() {
+        let mut watermark = BlockWatermark::new(Some(9));
+        watermark.start(10);
+        watermark.start(11);
+        assert_eq!(watermark.finish(10), Some(10));
+        assert_eq!(watermark.finish(11), Some(11));
+        assert_eq!(watermark.watermark(), Some(11));
+    }
+
+    #[test]
+    fn holds_back_until_earlier_blocks_finish() {
+        let mut watermark = BlockWatermark::new(None);
+        watermark.start(0);
+        watermark.start(1);
+        watermark.start(2);
+        assert_eq!(watermark.finish(2), None);
+        assert_eq!(watermark.finish(1), None);
+        assert_eq!(watermark.watermark(), None);
+        assert_eq!(watermark.finish(0), Some(2));
+    }
+
+    #[test]
+    fn passes_skipped_slots() {
+        let mut watermark = BlockWatermark::new(Some(99));
+        // Slots 101 and 102 have no block and are never started
+        watermark.start(100);
+        watermark.start(103);
+        assert_eq!(watermark.finish(100), Some(102));
+        assert_eq!(watermark.finish(103), Some(103));
+    }
+
+    #[test]
+    fn stops_before_gap_of_pending_block() {
+        let mut watermark = BlockWatermark::new(Some(9));
+        watermark.start(10);
+        watermark.start(12);
+        watermark.start(15);
+        assert_eq!(watermark.finish(15), None);
+        assert_eq!(watermark.finish(10), Some(11));
+        assert_eq!(watermark.watermark(), Some(11));
+        assert_eq!(watermark.finish(12), Some(15));
+    }
+
+    #[test]
+    fn checkpoints_per_batch() {
+        let mut watermark = BlockWatermark::new(Some(9));
+        for block in 10..25 {
+            watermark.start(block);
+        }
+        for block in 10..19 {
+            watermark.finish(block);
+            assert_eq!(watermark.checkpoint(10), None);
+        }
+        watermark.finish(19);
+        assert_eq!(watermark.checkpoint(10), Some(19));
+        assert_eq!(watermark.checkpoint(10), None);
+        for block in 20..25 {
+            watermark.finish(block);
+        }
+        assert_eq!(watermark.checkpoint(10), None);
+        assert_eq!(watermark.checkpoint(1), Some(24));
+    }
+
+    #[test]
+    fn first_checkpoint_without_start_block() {
+        let mut watermark = BlockWatermark::new(None);
+        assert_eq!(watermark.checkpoint(10), None);
+        watermark.start(0);
+        watermark.finish(0);
+        assert_eq!(watermark.checkpoint(10), Some(0));
+    }
+
+    #[test]
+    fn reports_finished_blocks_per_checkpoint() {
+        let mut watermark = BlockWatermark::new(Some(9));
+        for block in 10..14 {
+            watermark.start(block);
+        }
+        watermark.finish(10);
+        watermark.finish(11);
+        // Block 12 is given up on, it is not finished
+        watermark.abandon(12);
+        watermark.finish(13);
+        let checkpoint = watermark.checkpoint_with_finished(2).unwrap();
+        assert_eq!(
+            checkpoint,
+            Checkpoint {
+                previous: Some(9),
+                block: 13,
+                finished: vec![10, 11, 13].into_iter().collect(),
+            }
+        );
+
+        // A checkpoint which could not be saved is taken again
+        watermark.undo_checkpoint(checkpoint);
+        watermark.start(14);
+        watermark.finish(14);
+        assert_eq!(
+            watermark.checkpoint_with_finished(2),
+            Some(Checkpoint {
+                previous: Some(9),
+                block: 14,
+                finished: vec![10, 11, 13, 14].into_iter().collect(),
+            })
+        );
+        assert_eq!(watermark.checkpoint_with_finished(1), None);
+    }
+
+    #[test]
+    fn never_moves_back() {
+        let mut watermark = BlockWatermark::new(Some(20));
+        watermark.start(21);
+        assert_eq!(watermark.finish(21), Some(21));
+        // A block behind the watermark, like a repaired one, changes nothing
+        watermark.start(5);
+        assert_eq!(watermark.finish(5), None);
+        assert_eq!(watermark.watermark(), Some(21));
+    }
+}
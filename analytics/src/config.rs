@@ -0,0 +1,108 @@
+//! Configuration of the analytics handlers. The file is a YAML document with
+//! one section per chain, keyed by network:
+//!
+//! ```yaml
+//! solana:
+//!   mainnet:
+//!     handlers:
+//!       raw_log: true
+//!       token_balance: false
+//!     plugins:
+//!       - name: dex_metrics
+//!         path: /opt/analytics/libdex_metrics.so
+//!     parquet:
+//!       path: /data/analytics/parquet
+//!     events:
+//!       - program_id: 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin
+//!         idl: /opt/analytics/idl/serum_dex.json
+//! ethereum:
+//!   matic:
+//!     handlers:
+//!       daily_address_transaction: false
+//! ```
+//!
+//! Handlers which are not mentioned keep their default state.
+use massbit_common::prelude::anyhow::{self, Context};
+use massbit_common::NetworkType;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AnalyticsConfig {
+    #[serde(default)]
+    pub solana: HashMap<NetworkType, NetworkConfig>,
+    #[serde(default)]
+    pub ethereum: HashMap<NetworkType, NetworkConfig>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct NetworkConfig {
+    /// Turn built-in handlers on or off by their key
+    #[serde(default)]
+    pub handlers: HashMap<String, bool>,
+    /// Shared libraries with additional metric handlers
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+    /// Also export tables to Parquet files
+    #[serde(default)]
+    pub parquet: Option<ParquetConfig>,
+    /// Programs whose logged events are decoded by their IDL
+    #[serde(default)]
+    pub events: Vec<EventConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventConfig {
+    pub program_id: String,
+    /// Path of the Anchor IDL in JSON
+    pub idl: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ParquetConfig {
+    /// Root directory of the exported files
+    pub path: String,
+    /// Exported tables, a trailing `*` matches any suffix. The default are
+    /// the raw block, transaction, token balance and instruction tables
+    #[serde(default)]
+    pub tables: Vec<String>,
+    /// Rows buffered per partition before a file is written
+    pub rows_per_file: Option<usize>,
+}
+
+impl AnalyticsConfig {
+    /// Load the configuration from `path`; without a path every network
+    /// runs the default handlers
+    pub fn load(path: Option<&str>) -> Result<AnalyticsConfig, anyhow::Error> {
+        match path {
+            None => Ok(AnalyticsConfig::default()),
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .with_context(|| format!("Can not read analytics config {}", path))?;
+                serde_yaml::from_str(&content)
+                    .with_context(|| format!("Invalid analytics config {}", path))
+            }
+        }
+    }
+
+    pub fn solana_network(&self, network: &str) -> NetworkConfig {
+        self.solana.get(network).cloned().unwrap_or_default()
+    }
+
+    pub fn ethereum_network(&self, network: &str) -> NetworkConfig {
+        self.ethereum.get(network).cloned().unwrap_or_default()
+    }
+}
+
+impl NetworkConfig {
+    pub fn is_enabled(&self, handler: &str, default: bool) -> bool {
+        self.handlers.get(handler).cloned().unwrap_or(default)
+    }
+}
@@ -0,0 +1,237 @@
+use super::{create_web3, decode_block, get_block_logs, CHAIN, DEFAULT_NETWORK};
+
+use crate::backfill::{commit_upserts, prepare_chunks, BufferedAdapter, COMMIT_BATCH_SIZE};
+use crate::config::NetworkConfig;
+use crate::ethereum::handler::create_ethereum_handler_manager;
+use crate::models::BackfillChunk;
+use crate::parquet_adapter::{parquet_exporter, ETHEREUM_PARQUET_TABLES};
+use crate::postgres_adapter::PostgresAdapter;
+use crate::storage_adapter::StorageAdapter;
+use crate::{try_create_stream, GET_BLOCK_TIMEOUT_SEC, GET_STREAM_TIMEOUT_SEC};
+use core::ops::Deref;
+
+use massbit::firehose::bstream::{stream_client::StreamClient, BlockResponse, ChainType};
+use massbit::prelude::LightEthereumBlockExt;
+use massbit_common::prelude::anyhow;
+use massbit_common::prelude::tokio::time::{sleep, timeout, Duration};
+use massbit_common::NetworkType;
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tonic::{transport::Channel, Streaming};
+use tower::timeout::Timeout;
+
+/// How often a block is retried before the backfill stops at it
+const MAX_BLOCK_RETRIES: u64 = 3;
+const BLOCK_RETRY_DELAY_MS: u64 = 1000;
+/// How often the stream of a chunk is recreated without receiving a block
+const MAX_STREAM_RETRIES: u64 = 5;
+
+/// Process the blocks in `range` from the chain-reader with `workers`
+/// parallel workers, each streaming chunks of `chunk_size` blocks. Progress
+/// is stored per chunk, so running the same range again resumes where the
+/// last run stopped
+pub async fn backfill_ethereum_range(
+    client: StreamClient<Timeout<Channel>>,
+    storage_adapter: Arc<PostgresAdapter>,
+    network: Option<NetworkType>,
+    config: &NetworkConfig,
+    range: Range<u64>,
+    workers: usize,
+    chunk_size: u64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let network_name = network
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_NETWORK));
+    let chunks = {
+        let conn = storage_adapter.get_connection()?;
+        prepare_chunks(
+            conn.deref(),
+            CHAIN.as_str(),
+            &network_name,
+            range.start,
+            range.end,
+            chunk_size,
+        )?
+    };
+    log::info!(
+        "Start backfill of blocks {:?} with {} pending chunks and {} workers",
+        range,
+        chunks.len(),
+        workers
+    );
+    let queue = Arc::new(Mutex::new(chunks.into_iter().collect::<VecDeque<_>>()));
+    let mut tasks = vec![];
+    for worker in 0..workers.max(1) {
+        let queue = queue.clone();
+        let client = client.clone();
+        let storage_adapter = storage_adapter.clone();
+        let network = network.clone();
+        let config = config.clone();
+        tasks.push(tokio::spawn(async move {
+            run_worker(worker, queue, client, storage_adapter, &network, &config).await
+        }));
+    }
+    let mut result = Ok(());
+    for task in futures03::future::join_all(tasks).await {
+        match task {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                log::error!("Backfill worker stopped with error {:?}", &err);
+                result = Err(err.into());
+            }
+            Err(err) => {
+                log::error!("Backfill worker panicked {:?}", &err);
+                result = Err(err.into());
+            }
+        }
+    }
+    result
+}
+
+/// Take chunks from `queue` until it is empty. Every worker has its own
+/// handlers writing into its own buffer
+async fn run_worker(
+    worker: usize,
+    queue: Arc<Mutex<VecDeque<BackfillChunk>>>,
+    mut client: StreamClient<Timeout<Channel>>,
+    storage_adapter: Arc<PostgresAdapter>,
+    network: &Option<NetworkType>,
+    config: &NetworkConfig,
+) -> Result<(), anyhow::Error> {
+    // The handlers do not export, the upserts are exported once committed
+    let exporter = parquet_exporter(network, &config.parquet, &ETHEREUM_PARQUET_TABLES)?;
+    let config = NetworkConfig {
+        parquet: None,
+        ..config.clone()
+    };
+    let buffer = Arc::new(BufferedAdapter::new());
+    let handler_manager = create_ethereum_handler_manager(network, buffer.clone(), &config)?;
+    let exporter = exporter
+        .as_ref()
+        .map(|exporter| exporter as &dyn StorageAdapter);
+    let web3 = create_web3();
+    loop {
+        let chunk = match queue.lock().unwrap().pop_front() {
+            Some(chunk) => chunk,
+            None => return Ok(()),
+        };
+        let start = Instant::now();
+        log::info!(
+            "Worker {} starts chunk {}..{} at block {}",
+            worker,
+            chunk.start_block,
+            chunk.end_block,
+            chunk.next_block
+        );
+        let end_block = chunk.end_block as u64;
+        let mut next_block = chunk.next_block as u64;
+        let mut opt_stream: Option<Streaming<BlockResponse>> = None;
+        let mut stream_retries = 0;
+        let mut upserts = vec![];
+        let mut committed_block = next_block;
+        while next_block < end_block {
+            let stream = match opt_stream {
+                Some(ref mut stream) => stream,
+                None => {
+                    if stream_retries >= MAX_STREAM_RETRIES {
+                        return Err(anyhow::anyhow!(
+                            "Can not stream block {} from the chain reader",
+                            next_block
+                        ));
+                    }
+                    stream_retries += 1;
+                    opt_stream = try_create_stream(
+                        &mut client,
+                        ChainType::Ethereum,
+                        Some(next_block),
+                        network,
+                    )
+                    .await;
+                    if opt_stream.is_none() {
+                        sleep(Duration::from_secs(GET_STREAM_TIMEOUT_SEC)).await;
+                    }
+                    continue;
+                }
+            };
+            let data =
+                match timeout(Duration::from_secs(GET_BLOCK_TIMEOUT_SEC), stream.message()).await {
+                    Ok(Ok(Some(data))) => data,
+                    response => {
+                        log::info!(
+                            "Error while get message from reader stream {:?}. Recreate stream",
+                            &response
+                        );
+                        opt_stream = None;
+                        continue;
+                    }
+                };
+            stream_retries = 0;
+            let block = decode_block(&data.payload)?;
+            let block_number = block.number() as u64;
+            if block_number < next_block {
+                continue;
+            }
+            if block_number >= end_block {
+                break;
+            }
+            let mut attempt = 0;
+            loop {
+                let result = match get_block_logs(&web3, &block).await {
+                    Ok(logs) => handler_manager.handle_block(block.clone(), Arc::new(logs)),
+                    Err(err) => Err(err),
+                };
+                match result {
+                    Ok(_) => {
+                        upserts.extend(buffer.take());
+                        break;
+                    }
+                    Err(err) => {
+                        // Drop what the handlers wrote before one of them failed
+                        buffer.take();
+                        if attempt >= MAX_BLOCK_RETRIES {
+                            // The chunk stays at the last committed block, so
+                            // the next run starts with it
+                            return Err(err.context(format!(
+                                "Block {} failed after {} retries",
+                                block_number, attempt
+                            )));
+                        }
+                        attempt += 1;
+                        sleep(Duration::from_millis(BLOCK_RETRY_DELAY_MS * attempt)).await;
+                    }
+                }
+            }
+            next_block = block_number + 1;
+            if next_block >= committed_block + COMMIT_BATCH_SIZE {
+                handler_manager.flush()?;
+                commit_upserts(
+                    storage_adapter.as_ref(),
+                    exporter,
+                    &upserts,
+                    &chunk,
+                    next_block,
+                )?;
+                upserts.clear();
+                committed_block = next_block;
+            }
+        }
+        handler_manager.flush()?;
+        commit_upserts(
+            storage_adapter.as_ref(),
+            exporter,
+            &upserts,
+            &chunk,
+            end_block,
+        )?;
+        log::info!(
+            "Worker {} finished chunk {}..{} in {:?}",
+            worker,
+            chunk.start_block,
+            chunk.end_block,
+            start.elapsed()
+        );
+    }
+}
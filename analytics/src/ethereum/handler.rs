@@ -1,6 +1,10 @@
 //Public trait for ethereum metric
 use super::metrics::*;
+use crate::config::NetworkConfig;
+use crate::parquet_adapter::{with_parquet_export, ETHEREUM_PARQUET_TABLES};
+use crate::plugin::PluginHandlers;
 use crate::storage_adapter::StorageAdapter;
+use massbit::prelude::web3::types::Log;
 use massbit::prelude::LightEthereumBlock;
 use massbit_common::prelude::anyhow;
 use massbit_common::NetworkType;
@@ -10,55 +14,146 @@ pub trait EthereumHandler: Sync + Send {
     fn handle_block(&self, _block: Arc<LightEthereumBlock>) -> Result<(), anyhow::Error> {
         Ok(())
     }
+    /// The `Transfer` logs of the block, read with `get_block_logs`
+    fn handle_logs(
+        &self,
+        _block: Arc<LightEthereumBlock>,
+        _logs: Arc<Vec<Arc<Log>>>,
+    ) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
 }
 
 #[derive(Default)]
 pub struct EthereumHandlerManager {
     pub handlers: Vec<Arc<dyn EthereumHandler>>,
+    /// Storage the handlers write into
+    storage_adapter: Option<Arc<dyn StorageAdapter>>,
 }
 impl EthereumHandlerManager {
     pub fn new() -> EthereumHandlerManager {
         EthereumHandlerManager::default()
     }
+    pub fn with_storage_adapter(mut self, storage_adapter: Arc<dyn StorageAdapter>) -> Self {
+        self.storage_adapter = Some(storage_adapter);
+        self
+    }
+    /// Write the rows buffered by the storage of the handlers
+    pub fn flush(&self) -> Result<(), anyhow::Error> {
+        match &self.storage_adapter {
+            Some(storage_adapter) => storage_adapter.flush(),
+            None => Ok(()),
+        }
+    }
     pub fn add_handler(mut self, handler: Arc<dyn EthereumHandler>) -> Self {
         self.handlers.push(handler);
         self
     }
-    pub fn handle_block(&self, block: Arc<LightEthereumBlock>) -> Result<(), anyhow::Error> {
-        self.handlers.iter().for_each(|handler| {
-            let clone_handler = handler.clone();
-            let clone_block = Arc::clone(&block);
-            tokio::spawn(async move {
-                match clone_handler.handle_block(clone_block) {
-                    Ok(_) => {}
-                    Err(err) => log::error!("{:?}", &err),
-                }
-            });
-        });
-        Ok(())
+    /// Run every handler for `block`. All handlers run even if one of them
+    /// fails, the returned error describes each failure. A failed block is
+    /// retried with all handlers, so the block is one unit of the storage
+    pub fn handle_block(
+        &self,
+        block: Arc<LightEthereumBlock>,
+        logs: Arc<Vec<Arc<Log>>>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(storage_adapter) = &self.storage_adapter {
+            storage_adapter.begin_unit();
+        }
+        let failures = self
+            .handlers
+            .iter()
+            .filter_map(|handler| {
+                handler
+                    .handle_block(block.clone())
+                    .and_then(|_| handler.handle_logs(block.clone(), logs.clone()))
+                    .err()
+            })
+            .map(|err| format!("{:?}", err))
+            .collect::<Vec<String>>();
+        if let Some(storage_adapter) = &self.storage_adapter {
+            storage_adapter.end_unit(failures.is_empty());
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "{} handlers failed: {}",
+                failures.len(),
+                failures.join("; ")
+            ))
+        }
     }
 }
 
+/// Create the manager with the built-in handlers enabled in `config` and
+/// the handlers of its plugins
 pub fn create_ethereum_handler_manager(
     network: &Option<NetworkType>,
     storate_adapter: Arc<dyn StorageAdapter>,
-) -> EthereumHandlerManager {
-    let handler_manager = EthereumHandlerManager::new();
-    handler_manager
-        .add_handler(Arc::new(EthereumRawBlockHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(EthereumRawTransactionHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(EthereumDailyTransactionHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(EthereumDailyAddressTransactionHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
+    config: &NetworkConfig,
+) -> Result<EthereumHandlerManager, anyhow::Error> {
+    let storate_adapter = with_parquet_export(
+        storate_adapter,
+        network,
+        &config.parquet,
+        &ETHEREUM_PARQUET_TABLES,
+    )?;
+    // Key in the config, enabled by default and the handler
+    let handlers: Vec<(&str, bool, Arc<dyn EthereumHandler>)> = vec![
+        (
+            "raw_block",
+            true,
+            Arc::new(EthereumRawBlockHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "raw_transaction",
+            true,
+            Arc::new(EthereumRawTransactionHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "daily_transaction",
+            true,
+            Arc::new(EthereumDailyTransactionHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "daily_address_transaction",
+            true,
+            Arc::new(EthereumDailyAddressTransactionHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "token_transfer",
+            true,
+            Arc::new(EthereumTokenTransferHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+    ];
+    let mut handler_manager =
+        EthereumHandlerManager::new().with_storage_adapter(storate_adapter.clone());
+    for (key, default, handler) in handlers {
+        if config.is_enabled(key, default) {
+            handler_manager = handler_manager.add_handler(handler);
+        } else {
+            log::info!("Ethereum handler {} is disabled", key);
+        }
+    }
+    let plugins = PluginHandlers::load(&config.plugins, network, storate_adapter)?;
+    for handler in plugins.ethereum_handlers {
+        handler_manager = handler_manager.add_handler(handler);
+    }
+    Ok(handler_manager)
 }
@@ -2,8 +2,10 @@ pub mod daily_address_transaction;
 pub mod daily_transaction;
 pub mod raw_block;
 pub mod raw_transaction;
+pub mod token_transfer;
 
 pub use daily_address_transaction::EthereumDailyAddressTransactionHandler;
 pub use daily_transaction::EthereumDailyTransactionHandler;
 pub use raw_block::EthereumRawBlockHandler;
 pub use raw_transaction::EthereumRawTransactionHandler;
+pub use token_transfer::EthereumTokenTransferHandler;
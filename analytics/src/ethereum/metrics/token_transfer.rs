@@ -0,0 +1,163 @@
+use crate::ethereum::handler::EthereumHandler;
+use crate::postgres_queries::UpsertConflictFragment;
+use crate::relational::{Column, ColumnType, Table};
+use crate::storage_adapter::StorageAdapter;
+use crate::{create_columns, create_entity};
+use chain_ethereum::ethabi::{Event, EventParam, ParamType, RawLog};
+use lazy_static::lazy_static;
+use massbit::prelude::web3::types::{Address, Log, U256};
+use massbit::prelude::{Attribute, BigInt, Entity, LightEthereumBlock, Value};
+use massbit_common::NetworkType;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// keccak256 of `Transfer(address,address,uint256)`, the topic of both the
+/// ERC-20 and the ERC-721 `Transfer` event
+pub const TRANSFER_TOPIC: &str =
+    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+lazy_static! {
+    /// `Transfer(address indexed from, address indexed to, uint256 value)`
+    static ref ERC20_TRANSFER: Event = transfer_event("value", false);
+    /// `Transfer(address indexed from, address indexed to, uint256 indexed tokenId)`
+    static ref ERC721_TRANSFER: Event = transfer_event("tokenId", true);
+}
+
+/// Stores the ERC-20 and ERC-721 `Transfer` events of a block in
+/// `ethereum_token_transfers`. Holder balances, holder counts and daily
+/// volumes per token are derived from it in the database.
+pub struct EthereumTokenTransferHandler {
+    pub network: Option<NetworkType>,
+    pub storage_adapter: Arc<dyn StorageAdapter>,
+}
+
+impl EthereumTokenTransferHandler {
+    pub fn new(network: &Option<NetworkType>, storage_adapter: Arc<dyn StorageAdapter>) -> Self {
+        EthereumTokenTransferHandler {
+            network: network.clone(),
+            storage_adapter,
+        }
+    }
+}
+
+impl EthereumHandler for EthereumTokenTransferHandler {
+    fn handle_logs(
+        &self,
+        block: Arc<LightEthereumBlock>,
+        logs: Arc<Vec<Arc<Log>>>,
+    ) -> Result<(), anyhow::Error> {
+        let network = match &self.network {
+            None => "",
+            Some(val) => val.as_str(),
+        };
+        let values = logs
+            .iter()
+            .filter_map(|log| create_entity(network, &block, log))
+            .collect::<Vec<Entity>>();
+        if values.is_empty() {
+            return Ok(());
+        }
+        let table = create_table();
+        // A transfer is only counted once when a block is processed again
+        let conflict_frag = UpsertConflictFragment::new("ethereum_token_transfers_uindex");
+        self.storage_adapter
+            .upsert(&table, &values, &Some(conflict_frag))
+    }
+}
+
+fn transfer_event(value_name: &str, indexed_value: bool) -> Event {
+    let param = |name: &str, kind: ParamType, indexed: bool| EventParam {
+        name: name.to_string(),
+        kind,
+        indexed,
+    };
+    Event {
+        name: String::from("Transfer"),
+        inputs: vec![
+            param("from", ParamType::Address, true),
+            param("to", ParamType::Address, true),
+            param(value_name, ParamType::Uint(256), indexed_value),
+        ],
+        anonymous: false,
+    }
+}
+
+fn create_table<'a>() -> Table<'a> {
+    let columns = create_columns!(
+        "network" => ColumnType::Varchar,
+        "transaction_hash" => ColumnType::Varchar,
+        "log_index" => ColumnType::BigInt,
+        "block_number" => ColumnType::BigInt,
+        "timestamp" => ColumnType::BigInt,
+        "token_address" => ColumnType::Varchar,
+        "token_type" => ColumnType::Varchar,
+        "sender" => ColumnType::Varchar,
+        "receiver" => ColumnType::Varchar,
+        "value" => ColumnType::BigInt,
+        "token_id" => ColumnType::BigInt
+    );
+    Table::new("ethereum_token_transfers", columns)
+}
+
+struct TokenTransfer {
+    token_type: &'static str,
+    from: Address,
+    to: Address,
+    value: U256,
+    token_id: Option<U256>,
+}
+
+/// Both events have the same signature, ERC-721 logs have the token id as
+/// fourth topic instead of the amount in the data
+fn decode_transfer(log: &Log) -> Option<TokenTransfer> {
+    let raw_log = || RawLog {
+        topics: log.topics.clone(),
+        data: log.data.0.clone(),
+    };
+    let (token_type, params) = match ERC20_TRANSFER.parse_log(raw_log()) {
+        Ok(parsed) => ("erc20", parsed.params),
+        Err(_) => ("erc721", ERC721_TRANSFER.parse_log(raw_log()).ok()?.params),
+    };
+    let mut values = params.into_iter().map(|param| param.value);
+    let from = values.next()?.into_address()?;
+    let to = values.next()?.into_address()?;
+    let value = values.next()?.into_uint()?;
+    let transfer = match token_type {
+        "erc20" => TokenTransfer {
+            token_type,
+            from,
+            to,
+            value,
+            token_id: None,
+        },
+        _ => TokenTransfer {
+            token_type,
+            from,
+            to,
+            value: U256::one(),
+            token_id: Some(value),
+        },
+    };
+    Some(transfer)
+}
+
+fn create_entity(network: &str, block: &LightEthereumBlock, log: &Log) -> Option<Entity> {
+    if log.removed == Some(true) {
+        return None;
+    }
+    let transfer = decode_transfer(log)?;
+    let block_number = log.block_number.or(block.number)?.as_u64();
+    Some(create_entity!(
+        "network" => network,
+        "transaction_hash" => format!("{:x}", log.transaction_hash?),
+        "log_index" => log.log_index?.as_u64(),
+        "block_number" => block_number,
+        "timestamp" => block.timestamp.as_u64(),
+        "token_address" => format!("{:x}", &log.address),
+        "token_type" => transfer.token_type,
+        "sender" => format!("{:x}", &transfer.from),
+        "receiver" => format!("{:x}", &transfer.to),
+        "value" => BigInt::from_unsigned_u256(&transfer.value),
+        "token_id" => transfer.token_id.map(|token_id| BigInt::from_unsigned_u256(&token_id))
+    ))
+}
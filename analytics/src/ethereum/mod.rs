@@ -1,7 +1,8 @@
+pub mod backfill;
 pub mod handler;
 pub mod metrics;
 pub mod models;
-use diesel::{self, RunQueryDsl};
+pub use backfill::backfill_ethereum_range;
 pub use handler::EthereumHandlerManager;
 use lazy_static::lazy_static;
 use massbit_common::prelude::tokio::time::{sleep, timeout, Duration};
@@ -13,53 +14,68 @@ use tonic::{
     Request, Response, Status, Streaming,
 };
 
-use crate::{
-    establish_connection, get_block_number, try_create_stream, GET_BLOCK_TIMEOUT_SEC,
-    GET_STREAM_TIMEOUT_SEC,
-};
+use crate::{get_block_number, try_create_stream, GET_BLOCK_TIMEOUT_SEC, GET_STREAM_TIMEOUT_SEC};
 use massbit::firehose::bstream::{stream_client::StreamClient, BlockResponse, ChainType};
 
+use crate::checkpoint::{save_watermark, BlockWatermark};
+use crate::config::NetworkConfig;
 use crate::ethereum::handler::create_ethereum_handler_manager;
+use crate::ethereum::metrics::token_transfer::TRANSFER_TOPIC;
 use crate::postgres_adapter::PostgresAdapter;
-use crate::schema::*;
 use chain_ethereum::chain::BlockFinality;
-use chain_ethereum::Chain;
+use chain_ethereum::{Chain, Transport};
+use core::ops::Deref;
+use http::HeaderMap;
 use massbit::blockchain::block_stream::BlockWithTriggers;
-use massbit::blockchain::Block;
-use massbit_common::prelude::diesel::pg::upsert::excluded;
-use massbit_common::prelude::diesel::ExpressionMethods;
+use massbit::prelude::web3::types::Log;
+use massbit::prelude::web3::{Transport as _, Web3};
+use massbit::prelude::{Future01CompatExt, LightEthereumBlock, LightEthereumBlockExt};
+use massbit_common::prelude::anyhow;
+use std::env;
 use std::sync::Arc;
 use tower::timeout::Timeout;
 
 lazy_static! {
     pub static ref CHAIN: String = String::from("ethereum");
+    /// Node the logs of the streamed blocks are read from
+    pub static ref ETHEREUM_URL: String = env::var("ETHEREUM_URL").unwrap_or(String::from("https://polygon-rpc.com"));
 }
 const START_ETHEREUM_BLOCK: i64 = 15_000_000_i64;
 const DEFAULT_NETWORK: &str = "matic";
+/// How often a block is processed again before the stream is restarted at
+/// the last checkpoint
+const MAX_BLOCK_RETRIES: u64 = 3;
+const BLOCK_RETRY_DELAY_MS: u64 = 1000;
+/// Number of blocks between saved checkpoints. The buffered rows of the
+/// handlers are written before each checkpoint
+const CHECKPOINT_BATCH_SIZE: u64 = 100;
 
 pub async fn process_ethereum_stream(
     client: &mut StreamClient<Timeout<Channel>>,
     storage_adapter: Arc<PostgresAdapter>,
     network: Option<NetworkType>,
     block: Option<u64>,
+    config: &NetworkConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let handler_manager = Arc::new(create_ethereum_handler_manager(&network, storage_adapter));
-    //Todo: remove this simple connection
-    let conn = establish_connection();
-    let current_state = get_block_number(
-        &conn,
-        CHAIN.clone(),
-        network.clone().unwrap_or(String::from(DEFAULT_NETWORK)),
-    );
+    let network_name = network.clone().unwrap_or(String::from(DEFAULT_NETWORK));
+    let handler_manager =
+        create_ethereum_handler_manager(&network, storage_adapter.clone(), config)?;
+    let web3 = create_web3();
+    let conn = storage_adapter.get_connection()?;
+    let current_state = get_block_number(conn.deref(), CHAIN.clone(), network_name.clone());
     let start_block = current_state
         .and_then(|state| Some(state.got_block as u64 + 1))
         .or(block);
+    // Every block before the first requested one counts as processed
+    let mut watermark = BlockWatermark::new(start_block.and_then(|block| block.checked_sub(1)));
     let mut opt_stream: Option<Streaming<BlockResponse>> = None;
     loop {
         match opt_stream {
             None => {
+                // Continue after the last handled block when the stream is recreated
+                let next_block = watermark.watermark().map(|block| block + 1).or(start_block);
                 opt_stream =
-                    try_create_stream(client, ChainType::Ethereum, start_block, &network).await;
+                    try_create_stream(client, ChainType::Ethereum, next_block, &network).await;
                 if opt_stream.is_none() {
                     //Sleep for a while and reconnect
                     sleep(Duration::from_secs(GET_STREAM_TIMEOUT_SEC)).await;
@@ -72,37 +88,61 @@ pub async fn process_ethereum_stream(
                     Ok(Ok(res)) => {
                         if let Some(data) = res {
                             let start = Instant::now();
-                            let block: BlockWithTriggers<Chain> =
-                                serde_json::from_slice(&data.payload).unwrap();
-                            let block_number = block.block.number() as i64;
-                            let BlockFinality::Final(light_block) = block.block;
+                            let light_block = match decode_block(&data.payload) {
+                                Ok(block) => block,
+                                Err(err) => {
+                                    log::error!("Can not decode Ethereum block: {:?}", &err);
+                                    continue;
+                                }
+                            };
+                            let block_number = light_block.number() as u64;
+                            if watermark.watermark() >= Some(block_number) {
+                                continue;
+                            }
                             let transaction_count = light_block.transactions.len();
-                            let handler = handler_manager.clone();
-                            tokio::spawn(async move {
-                                match handler.handle_block(light_block) {
-                                    Ok(_) => {}
-                                    Err(err) => log::error!("{:?}", &err),
+                            let mut attempt = 0;
+                            loop {
+                                let result = match get_block_logs(&web3, &light_block).await {
+                                    Ok(logs) => handler_manager
+                                        .handle_block(light_block.clone(), Arc::new(logs)),
+                                    Err(err) => Err(err),
                                 };
-                            });
-
-                            match diesel::insert_into(network_states::table)
-                                .values((
-                                    network_states::chain.eq(CHAIN.clone()),
-                                    network_states::network
-                                        .eq(network.clone().unwrap_or(DEFAULT_NETWORK.to_string())),
-                                    network_states::got_block.eq(block_number.clone()),
-                                ))
-                                .on_conflict((network_states::chain, network_states::network))
-                                .do_update()
-                                .set(
-                                    network_states::got_block
-                                        .eq(excluded(network_states::got_block)),
-                                )
-                                .execute(&conn)
-                            {
-                                Ok(_) => {}
-                                Err(err) => log::error!("{:?}", &err),
-                            };
+                                match result {
+                                    Ok(_) => break,
+                                    Err(err) if attempt >= MAX_BLOCK_RETRIES => {
+                                        // The stream is restarted at the last checkpoint
+                                        return Err(err
+                                            .context(format!(
+                                                "Block {} failed after {} retries",
+                                                block_number, attempt
+                                            ))
+                                            .into());
+                                    }
+                                    Err(err) => {
+                                        log::warn!(
+                                            "Block {} failed, retry it: {:?}",
+                                            block_number,
+                                            &err
+                                        );
+                                        attempt += 1;
+                                        sleep(Duration::from_millis(
+                                            BLOCK_RETRY_DELAY_MS * attempt,
+                                        ))
+                                        .await;
+                                    }
+                                }
+                            }
+                            watermark.start(block_number);
+                            watermark.finish(block_number);
+                            if let Some(got_block) = watermark.checkpoint(CHECKPOINT_BATCH_SIZE) {
+                                handler_manager.flush()?;
+                                save_watermark(
+                                    conn.deref(),
+                                    CHAIN.as_str(),
+                                    &network_name,
+                                    got_block,
+                                )?;
+                            }
                             log::info!(
                                 "Block {} with {} transactions is processed in {:?}",
                                 block_number,
@@ -124,6 +164,47 @@ pub async fn process_ethereum_stream(
     }
 }
 
+/// The block of a chain-reader response. Its logs are read with
+/// `get_block_logs`
+pub fn decode_block(payload: &[u8]) -> Result<Arc<LightEthereumBlock>, serde_json::Error> {
+    let block: BlockWithTriggers<Chain> = serde_json::from_slice(payload)?;
+    let BlockFinality::Final(light_block) = block.block;
+    Ok(light_block)
+}
+
+/// Client for the node at `ETHEREUM_URL`
+pub fn create_web3() -> Web3<Transport> {
+    let (event_loop, transport) = Transport::new_rpc(ETHEREUM_URL.as_str(), HeaderMap::new());
+    // If we drop the event loop the transport will stop working
+    std::mem::forget(event_loop);
+    Web3::new(transport)
+}
+
+/// The `Transfer` logs of `block`, which the handlers decode, from one
+/// `eth_getLogs` request by block hash. The chain-reader only delivers the
+/// logs matching the filters of its subscribers, so they can not be taken
+/// from the block stream
+pub async fn get_block_logs(
+    web3: &Web3<Transport>,
+    block: &LightEthereumBlock,
+) -> Result<Vec<Arc<Log>>, anyhow::Error> {
+    let hash = block
+        .hash
+        .ok_or_else(|| anyhow::anyhow!("Block {} has no hash", block.number()))?;
+    let filter = serde_json::json!({
+        "blockHash": hash,
+        "topics": [TRANSFER_TOPIC],
+    });
+    let logs = web3
+        .transport()
+        .execute("eth_getLogs", vec![filter])
+        .compat()
+        .await
+        .map_err(|err| anyhow::anyhow!("Can not get logs of block {:?}: {:?}", hash, err))?;
+    let logs: Vec<Log> = serde_json::from_value(logs)?;
+    Ok(logs.into_iter().map(Arc::new).collect())
+}
+
 // pub async fn _process_ethereum_stream(client: &mut StreamoutClient<Timeout<Channel>>,
 //                                     storage_adapter: &dyn StorageAdapter,
 //                                     network: &Option<NetworkType>,
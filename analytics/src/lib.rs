@@ -5,8 +5,13 @@ extern crate diesel_migrations;
 use diesel::{prelude::*, Connection, PgConnection};
 use dotenv::dotenv;
 use std::env;
+pub mod backfill;
+pub mod checkpoint;
+pub mod config;
 pub mod ethereum;
 pub mod models;
+pub mod parquet_adapter;
+pub mod plugin;
 pub mod postgres_adapter;
 pub mod postgres_queries;
 pub mod relational;
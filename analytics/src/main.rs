@@ -1,6 +1,9 @@
 #[macro_use]
 extern crate diesel_migrations;
-use analytics::ethereum::process_ethereum_stream;
+use analytics::backfill::{DEFAULT_BACKFILL_CHUNK_SIZE, DEFAULT_BACKFILL_WORKERS};
+use analytics::config::AnalyticsConfig;
+use analytics::ethereum::{backfill_ethereum_range, process_ethereum_stream};
+use analytics::solana::{process_solana_stream, DEFAULT_NETWORK as SOLANA_DEFAULT_NETWORK};
 use clap::{App, Arg};
 use diesel_migrations::embed_migrations;
 use lazy_static::lazy_static;
@@ -62,6 +65,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
                 .help("Input start block value")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("config")
+                .help("Analytics handler config file, default is env ANALYTICS_CONFIG")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("end-block")
+                .long("end-block")
+                .value_name("end-block")
+                .help("Backfill the Ethereum blocks from start block up to this one (exclusive) and exit")
+                .takes_value(true)
+                .requires("block"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .long("workers")
+                .value_name("workers")
+                .help("Number of parallel backfill workers")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chunk-size")
+                .long("chunk-size")
+                .value_name("chunk-size")
+                .help("Number of blocks in a backfill chunk")
+                .takes_value(true),
+        )
         .get_matches();
     {
         let conn = establish_connection();
@@ -73,10 +105,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
     let chain_type = matches.value_of("chain").unwrap_or("ethereum");
     let network = matches.value_of("network").unwrap_or("matic");
     let block: Option<u64> = matches.value_of("block").and_then(|val| val.parse().ok());
+    let end_block: Option<u64> = matches
+        .value_of("end-block")
+        .and_then(|val| val.parse().ok());
     info!(
         "Start client for chain {} and network {}",
         chain_type, network
     );
+    let config_path = matches
+        .value_of("config")
+        .map(String::from)
+        .or_else(|| env::var("ANALYTICS_CONFIG").ok());
+    let config = AnalyticsConfig::load(config_path.as_deref())?;
+    let ethereum_config = config.ethereum_network(network);
+    let solana_network = matches
+        .value_of("network")
+        .unwrap_or(SOLANA_DEFAULT_NETWORK)
+        .to_string();
+    let solana_config = config.solana_network(&solana_network);
     let storage_adapter = Arc::new(create_postgres_storage());
     loop {
         match Channel::from_static(CHAIN_READER_URL.as_str())
@@ -86,24 +132,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
             Ok(channel) => {
                 let timeout_channel =
                     Timeout::new(channel, Duration::from_secs(GET_BLOCK_TIMEOUT_SEC));
-                let mut client = StreamClient::new(timeout_channel);
+                let mut client = StreamClient::new(timeout_channel.clone());
                 let network = match matches.value_of("network") {
                     None => None,
                     Some(val) => Some(String::from(val)),
                 };
                 match chain_type {
+                    "solana" if end_block.is_some() => {
+                        error!("Backfill Solana with solana_scanner --end-block");
+                        return Ok(());
+                    }
                     "solana" => {
-                        // match process_solana_stream(
-                        //     &mut client,
-                        //     storage_adapter.clone(),
-                        //     network,
-                        //     block,
-                        // )
-                        // .await
-                        // {
-                        //     Err(err) => log::error!("{:?}", &err),
-                        //     Ok(_) => {}
-                        // }
+                        match process_solana_stream(
+                            timeout_channel.clone(),
+                            storage_adapter.clone(),
+                            &solana_network,
+                            block,
+                            &solana_config,
+                        )
+                        .await
+                        {
+                            Err(err) => log::error!("{:?}", &err),
+                            Ok(_) => {}
+                        }
+                    }
+                    _ if end_block.is_some() => {
+                        let workers = matches
+                            .value_of("workers")
+                            .and_then(|val| val.parse().ok())
+                            .unwrap_or(DEFAULT_BACKFILL_WORKERS);
+                        let chunk_size = matches
+                            .value_of("chunk-size")
+                            .and_then(|val| val.parse().ok())
+                            .unwrap_or(DEFAULT_BACKFILL_CHUNK_SIZE);
+                        return backfill_ethereum_range(
+                            client,
+                            storage_adapter.clone(),
+                            network,
+                            &ethereum_config,
+                            block.unwrap_or_default()..end_block.unwrap_or_default(),
+                            workers,
+                            chunk_size,
+                        )
+                        .await;
                     }
                     _ => {
                         match process_ethereum_stream(
@@ -111,6 +182,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
                             storage_adapter.clone(),
                             network,
                             block,
+                            &ethereum_config,
                         )
                         .await
                         {
@@ -12,6 +12,27 @@ pub struct NetworkState {
     pub got_block: i64,
 }
 
+#[derive(Debug, Clone, Queryable)]
+pub struct FailedBlock {
+    pub id: i64,
+    pub chain: String,
+    pub network: String,
+    pub block_number: i64,
+    pub handlers: Vec<String>,
+    pub error: String,
+    pub attempts: i32,
+}
+
+#[derive(Debug, Clone, Queryable)]
+pub struct BackfillChunk {
+    pub id: i64,
+    pub chain: String,
+    pub network: String,
+    pub start_block: i64,
+    pub end_block: i64,
+    pub next_block: i64,
+}
+
 pub struct CommandData<'a> {
     pub table: &'a Table<'a>,
     pub values: &'a Vec<Entity>,
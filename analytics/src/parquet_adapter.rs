@@ -0,0 +1,628 @@
+//! Export of analytics tables to Parquet files, partitioned by network and
+//! day for local columnar tools:
+//!
+//! ```text
+//! <path>/<table>/network=<network>/date=<YYYY-MM-DD>/part-<millis>-<seq>.parquet
+//! ```
+//!
+//! The file schema is derived from the `Table` the handlers upsert into.
+//! Rows are buffered per partition and written when a partition is large
+//! or old enough, and at the latest when the processor flushes its storage
+//! before saving a checkpoint, which it does per batch of blocks so files
+//! stay reasonably sized. Parquet files are append only: conflict fragments
+//! are ignored and the export is meant for the insert-only raw tables. Rows
+//! are exported once the handler which wrote them succeeded, so failed
+//! attempts are not exported, but rows of blocks which are processed again
+//! after a restart are exported again.
+use crate::config::ParquetConfig;
+use crate::models::CommandData;
+use crate::postgres_queries::UpsertConflictFragment;
+use crate::relational::{Column, ColumnType, Table};
+use crate::storage_adapter::{MirrorAdapter, StorageAdapter};
+use chrono::NaiveDateTime;
+use massbit::prelude::{Entity, Value};
+use massbit_common::prelude::anyhow::{self, Context};
+use massbit_common::NetworkType;
+use parquet::basic::Compression;
+use parquet::column::writer::{ColumnWriter, ColumnWriterImpl};
+use parquet::data_type::{ByteArray, ByteArrayType, DataType};
+use parquet::errors::Result as ParquetResult;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const SOLANA_PARQUET_TABLES: [&str; 4] = [
+    "solana_blocks",
+    "solana_transactions",
+    "solana_token_balances",
+    "solana_inst_*",
+];
+pub const ETHEREUM_PARQUET_TABLES: [&str; 3] = [
+    "ethereum_blocks",
+    "ethereum_transactions",
+    "ethereum_token_transfers",
+];
+pub const DEFAULT_ROWS_PER_FILE: usize = 100_000;
+/// Partitions which did not reach the row limit are written after this time
+const MAX_PARTITION_AGE: Duration = Duration::from_secs(300);
+/// Columns holding the unix time of a row, in order of preference
+const TIME_COLUMNS: [&str; 2] = ["block_time", "timestamp"];
+/// Shared by all exporters of the process, e.g. the backfill workers, so
+/// that file names never collide
+static FILE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Write the upserts of the handlers into `storage_adapter` and also export
+/// the configured tables to Parquet if the network has a `parquet` section
+pub fn with_parquet_export(
+    storage_adapter: Arc<dyn StorageAdapter>,
+    network: &Option<NetworkType>,
+    parquet: &Option<ParquetConfig>,
+    default_tables: &[&str],
+) -> Result<Arc<dyn StorageAdapter>, anyhow::Error> {
+    match parquet_exporter(network, parquet, default_tables)? {
+        None => Ok(storage_adapter),
+        Some(exporter) => Ok(Arc::new(MirrorAdapter::new(
+            storage_adapter,
+            Arc::new(exporter),
+        ))),
+    }
+}
+
+/// The exporter of the configured tables if the network has a `parquet`
+/// section
+pub fn parquet_exporter(
+    network: &Option<NetworkType>,
+    parquet: &Option<ParquetConfig>,
+    default_tables: &[&str],
+) -> Result<Option<ParquetAdapter>, anyhow::Error> {
+    let config = match parquet {
+        None => return Ok(None),
+        Some(config) => config,
+    };
+    let tables = if config.tables.is_empty() {
+        default_tables
+            .iter()
+            .map(|table| table.to_string())
+            .collect()
+    } else {
+        config.tables.clone()
+    };
+    let exporter = ParquetAdapter::new(
+        &config.path,
+        network.clone().unwrap_or_default(),
+        tables,
+        config.rows_per_file.unwrap_or(DEFAULT_ROWS_PER_FILE),
+    )?;
+    log::info!("Export tables {:?} to {}", &exporter.tables, &config.path);
+    Ok(Some(exporter))
+}
+
+struct Partition {
+    columns: Vec<Column>,
+    rows: Vec<Entity>,
+    since: Instant,
+}
+
+pub struct ParquetAdapter {
+    root: PathBuf,
+    network: String,
+    /// Exported table names, a trailing `*` matches any suffix
+    tables: Vec<String>,
+    rows_per_file: usize,
+    partitions: Mutex<HashMap<(String, String), Partition>>,
+}
+
+impl ParquetAdapter {
+    pub fn new(
+        root: &str,
+        network: String,
+        tables: Vec<String>,
+        rows_per_file: usize,
+    ) -> Result<Self, anyhow::Error> {
+        fs::create_dir_all(root).with_context(|| format!("Can not create directory {}", root))?;
+        Ok(ParquetAdapter {
+            root: PathBuf::from(root),
+            network,
+            tables,
+            rows_per_file: rows_per_file.max(1),
+            partitions: Mutex::new(HashMap::default()),
+        })
+    }
+
+    pub fn is_exported(&self, table: &str) -> bool {
+        self.tables
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => table.starts_with(prefix),
+                None => table == pattern,
+            })
+    }
+
+    /// Take the partitions which are full or too old out of the buffer
+    fn take_ready(&self) -> Vec<((String, String), Partition)> {
+        let mut partitions = self.partitions.lock().unwrap();
+        let ready: Vec<(String, String)> = partitions
+            .iter()
+            .filter(|(_, partition)| {
+                partition.rows.len() >= self.rows_per_file
+                    || partition.since.elapsed() >= MAX_PARTITION_AGE
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        ready
+            .into_iter()
+            .filter_map(|key| partitions.remove(&key).map(|partition| (key, partition)))
+            .collect()
+    }
+
+    /// Write `partitions` to files. Partitions which can not be written are
+    /// put back into the buffer, so that the next flush writes them
+    fn write_partitions(
+        &self,
+        partitions: Vec<((String, String), Partition)>,
+    ) -> Result<(), anyhow::Error> {
+        let mut result = Ok(());
+        for ((table, date), partition) in partitions {
+            if let Err(err) = self.write_file(&table, &date, &partition) {
+                self.restore((table, date), partition);
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    fn restore(&self, key: (String, String), mut partition: Partition) {
+        let mut partitions = self.partitions.lock().unwrap();
+        if let Some(buffered) = partitions.remove(&key) {
+            partition.rows.extend(buffered.rows);
+        }
+        partitions.insert(key, partition);
+    }
+
+    fn write_file(
+        &self,
+        table: &str,
+        date: &str,
+        partition: &Partition,
+    ) -> Result<(), anyhow::Error> {
+        let dir = self
+            .root
+            .join(table)
+            .join(format!("network={}", &self.network))
+            .join(format!("date={}", date));
+        fs::create_dir_all(&dir)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let sequence = FILE_SEQUENCE.fetch_add(1, Ordering::SeqCst);
+        let path = dir.join(format!("part-{}-{}.parquet", millis, sequence));
+        // Readers never see a partially written file
+        let tmp_path = dir.join(format!(".part-{}-{}.parquet.tmp", millis, sequence));
+        write_parquet(&tmp_path, table, &partition.columns, &partition.rows)
+            .with_context(|| format!("Can not write {:?}", &path))?;
+        fs::rename(&tmp_path, &path)?;
+        log::info!(
+            "Export {} rows of table {} to {:?}",
+            partition.rows.len(),
+            table,
+            &path
+        );
+        Ok(())
+    }
+}
+
+impl StorageAdapter for ParquetAdapter {
+    fn insert(&self, _table_name: &str, _value: HashMap<&str, Value>) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("Parquet export only supports upserts"))
+    }
+
+    fn upsert(
+        &self,
+        table: &Table,
+        values: &Vec<Entity>,
+        _conflict_fragment: &Option<UpsertConflictFragment>,
+    ) -> Result<(), anyhow::Error> {
+        let table_name = table.name.as_str();
+        if values.is_empty() || !self.is_exported(table_name) {
+            return Ok(());
+        }
+        {
+            let mut partitions = self.partitions.lock().unwrap();
+            for entity in values {
+                let key = (table_name.to_string(), partition_date(entity));
+                partitions
+                    .entry(key)
+                    .or_insert_with(|| Partition {
+                        columns: table.columns.clone(),
+                        rows: Vec::default(),
+                        since: Instant::now(),
+                    })
+                    .rows
+                    .push(entity.clone());
+            }
+        }
+        self.write_partitions(self.take_ready())
+    }
+
+    fn transact_upserts(&self, commands: Vec<CommandData>) -> Result<(), anyhow::Error> {
+        for cmd in commands {
+            self.upsert(cmd.table, cmd.values, cmd.conflict_fragment)?;
+        }
+        Ok(())
+    }
+
+    /// Write every buffered partition
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        let partitions: Vec<_> = self.partitions.lock().unwrap().drain().collect();
+        self.write_partitions(partitions)
+    }
+}
+
+impl Drop for ParquetAdapter {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Can not export buffered rows: {:?}", &err);
+        }
+    }
+}
+
+/// Day of the row as `YYYY-MM-DD`, from its block time
+fn partition_date(entity: &Entity) -> String {
+    let seconds = TIME_COLUMNS
+        .iter()
+        .find_map(|column| match entity.get(column) {
+            Some(Value::BigInt(value)) => value.to_string().parse::<i64>().ok(),
+            Some(Value::Int(value)) => Some(*value as i64),
+            _ => None,
+        });
+    match seconds {
+        Some(seconds) => NaiveDateTime::from_timestamp(seconds, 0).date().to_string(),
+        None => String::from("unknown"),
+    }
+}
+
+/// Parquet schema of `columns`, every column is optional
+fn message_type(table: &str, columns: &[Column]) -> String {
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let name = column.name.as_str();
+            match &column.column_type {
+                ColumnType::Boolean => format!("OPTIONAL BOOLEAN {};", name),
+                ColumnType::Int => format!("OPTIONAL INT32 {};", name),
+                ColumnType::BigInt => format!("OPTIONAL INT64 {};", name),
+                ColumnType::Bytes | ColumnType::BytesId => format!("OPTIONAL BINARY {};", name),
+                ColumnType::TextArray => format!(
+                    "OPTIONAL GROUP {} (LIST) {{ REPEATED BINARY element (UTF8); }}",
+                    name
+                ),
+                ColumnType::BigDecimal
+                | ColumnType::String
+                | ColumnType::Varchar
+                | ColumnType::Enum(_) => format!("OPTIONAL BINARY {} (UTF8);", name),
+            }
+        })
+        .collect();
+    format!("message {} {{ {} }}", table, fields.join(" "))
+}
+
+fn write_parquet(
+    path: &Path,
+    table: &str,
+    columns: &[Column],
+    rows: &[Entity],
+) -> Result<(), anyhow::Error> {
+    let schema = Arc::new(parse_message_type(&message_type(table, columns))?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+    let mut columns = columns.iter();
+    while let Some(mut column_writer) = row_group.next_column()? {
+        if let Some(column) = columns.next() {
+            let values: Vec<Option<&Value>> = rows
+                .iter()
+                .map(|row| row.get(column.name.as_str()))
+                .collect();
+            write_column(&mut column_writer, &column.column_type, &values)?;
+        }
+        row_group.close_column(column_writer)?;
+    }
+    writer.close_row_group(row_group)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn write_column(
+    writer: &mut ColumnWriter,
+    column_type: &ColumnType,
+    values: &[Option<&Value>],
+) -> ParquetResult<()> {
+    match writer {
+        ColumnWriter::BoolColumnWriter(writer) => write_optional(
+            writer,
+            values.iter().map(|value| match value {
+                Some(Value::Bool(value)) => Some(*value),
+                _ => None,
+            }),
+        ),
+        ColumnWriter::Int32ColumnWriter(writer) => write_optional(
+            writer,
+            values.iter().map(|value| match value {
+                Some(Value::Int(value)) => Some(*value),
+                _ => None,
+            }),
+        ),
+        // Values which do not fit into 64 bits are exported as null
+        ColumnWriter::Int64ColumnWriter(writer) => write_optional(
+            writer,
+            values.iter().map(|value| match value {
+                Some(Value::BigInt(value)) => value.to_string().parse::<i64>().ok(),
+                Some(Value::Int(value)) => Some(*value as i64),
+                _ => None,
+            }),
+        ),
+        ColumnWriter::ByteArrayColumnWriter(writer) if *column_type == ColumnType::TextArray => {
+            write_list(writer, values)
+        }
+        ColumnWriter::ByteArrayColumnWriter(writer) => write_optional(
+            writer,
+            values.iter().map(|value| value.and_then(to_byte_array)),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn write_optional<T: DataType>(
+    writer: &mut ColumnWriterImpl<T>,
+    values: impl Iterator<Item = Option<T::T>>,
+) -> ParquetResult<()> {
+    let mut def_levels = Vec::default();
+    let mut present = Vec::default();
+    for value in values {
+        match value {
+            Some(value) => {
+                def_levels.push(1);
+                present.push(value);
+            }
+            None => def_levels.push(0),
+        }
+    }
+    writer.write_batch(&present, Some(&def_levels), None)?;
+    Ok(())
+}
+
+/// Write a two level list: null rows have definition level 0, empty lists
+/// 1 and elements 2; the first element of a row starts a new record
+fn write_list(
+    writer: &mut ColumnWriterImpl<ByteArrayType>,
+    values: &[Option<&Value>],
+) -> ParquetResult<()> {
+    let mut def_levels = Vec::default();
+    let mut rep_levels = Vec::default();
+    let mut present = Vec::default();
+    for value in values {
+        match value {
+            Some(Value::List(items)) if !items.is_empty() => {
+                for (index, item) in items.iter().enumerate() {
+                    def_levels.push(2);
+                    rep_levels.push(if index == 0 { 0 } else { 1 });
+                    present.push(to_byte_array(item).unwrap_or_else(|| ByteArray::from("")));
+                }
+            }
+            Some(Value::List(_)) => {
+                def_levels.push(1);
+                rep_levels.push(0);
+            }
+            _ => {
+                def_levels.push(0);
+                rep_levels.push(0);
+            }
+        }
+    }
+    writer.write_batch(&present, Some(&def_levels), Some(&rep_levels))?;
+    Ok(())
+}
+
+fn to_byte_array(value: &Value) -> Option<ByteArray> {
+    match value {
+        Value::String(value) => Some(ByteArray::from(value.as_str())),
+        Value::Bytes(value) => Some(ByteArray::from(value.as_slice().to_vec())),
+        Value::BigInt(value) => Some(ByteArray::from(value.to_string().as_str())),
+        Value::BigDecimal(value) => Some(ByteArray::from(value.to_string().as_str())),
+        Value::Int(value) => Some(ByteArray::from(value.to_string().as_str())),
+        Value::Bool(value) => Some(ByteArray::from(value.to_string().as_str())),
+        Value::List(_) | Value::Null => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{message_type, partition_date, ParquetAdapter};
+    use crate::relational::{Column, ColumnType, Table};
+    use crate::storage_adapter::StorageAdapter;
+    use crate::{create_columns, create_entity};
+    use massbit::prelude::{Attribute, Entity, Value};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use std::collections::HashMap;
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("parquet_adapter_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn blocks_table() -> Table<'static> {
+        Table::new(
+            "solana_blocks",
+            create_columns!(
+                "block_slot" => ColumnType::BigInt,
+                "block_hash" => ColumnType::String,
+                "block_time" => ColumnType::BigInt
+            ),
+        )
+    }
+
+    fn block(slot: u64, time: u64) -> Entity {
+        create_entity!(
+            "block_slot" => slot,
+            "block_hash" => format!("hash{}", slot),
+            "block_time" => time
+        )
+    }
+
+    /// Number of rows in all Parquet files below `dir`
+    fn exported_rows(dir: &Path) -> i64 {
+        let mut rows = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                rows += exported_rows(&path);
+            } else if path.extension().map_or(false, |ext| ext == "parquet") {
+                let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+                rows += reader.metadata().file_metadata().num_rows();
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn matches_table_patterns() {
+        let dir = test_dir("patterns");
+        let adapter = ParquetAdapter::new(
+            dir.to_str().unwrap(),
+            String::from("mainnet"),
+            vec![String::from("solana_blocks"), String::from("solana_inst_*")],
+            10,
+        )
+        .unwrap();
+        assert!(adapter.is_exported("solana_blocks"));
+        assert!(adapter.is_exported("solana_inst_transfer"));
+        assert!(!adapter.is_exported("solana_blocks_daily"));
+        assert!(!adapter.is_exported("solana_transactions"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partitions_by_block_day() {
+        assert_eq!(partition_date(&block(1, 1_635_724_800)), "2021-11-01");
+        let without_time = create_entity!("block_slot" => 1_u64);
+        assert_eq!(partition_date(&without_time), "unknown");
+    }
+
+    #[test]
+    fn derives_schema_from_columns() {
+        let table = blocks_table();
+        assert_eq!(
+            message_type("solana_blocks", &table.columns),
+            "message solana_blocks { OPTIONAL INT64 block_slot; \
+             OPTIONAL BINARY block_hash (UTF8); OPTIONAL INT64 block_time; }"
+        );
+    }
+
+    #[test]
+    fn writes_buffered_rows_on_flush() {
+        let dir = test_dir("flush");
+        let adapter = ParquetAdapter::new(
+            dir.to_str().unwrap(),
+            String::from("mainnet"),
+            vec![String::from("solana_blocks")],
+            100,
+        )
+        .unwrap();
+        let table = blocks_table();
+        let rows = vec![block(1, 1_635_724_800), block(2, 1_635_724_801)];
+        adapter.upsert(&table, &rows, &None).unwrap();
+        assert_eq!(exported_rows(&dir), 0);
+        adapter.flush().unwrap();
+        assert_eq!(exported_rows(&dir), 2);
+        assert!(dir
+            .join("solana_blocks/network=mainnet/date=2021-11-01")
+            .is_dir());
+        // Nothing stays buffered after a flush
+        adapter.flush().unwrap();
+        assert_eq!(exported_rows(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_full_partitions_on_upsert() {
+        let dir = test_dir("full");
+        let adapter = ParquetAdapter::new(
+            dir.to_str().unwrap(),
+            String::from("mainnet"),
+            vec![String::from("solana_blocks")],
+            2,
+        )
+        .unwrap();
+        let table = blocks_table();
+        let rows = vec![
+            block(1, 1_635_724_800),
+            block(2, 1_635_724_801),
+            block(3, 1_635_811_200),
+        ];
+        adapter.upsert(&table, &rows, &None).unwrap();
+        // Only the first day has reached the row limit
+        assert_eq!(exported_rows(&dir), 2);
+        adapter.flush().unwrap();
+        assert_eq!(exported_rows(&dir), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_rows_which_can_not_be_written() {
+        let dir = test_dir("restore");
+        let adapter = ParquetAdapter::new(
+            dir.to_str().unwrap(),
+            String::from("mainnet"),
+            vec![String::from("solana_blocks")],
+            100,
+        )
+        .unwrap();
+        let table = blocks_table();
+        adapter
+            .upsert(&table, &vec![block(1, 1_635_724_800)], &None)
+            .unwrap();
+        // A file in place of the table directory makes the write fail
+        File::create(dir.join("solana_blocks")).unwrap();
+        assert!(adapter.flush().is_err());
+        adapter
+            .upsert(&table, &vec![block(2, 1_635_724_801)], &None)
+            .unwrap();
+        fs::remove_file(dir.join("solana_blocks")).unwrap();
+        adapter.flush().unwrap();
+        assert_eq!(exported_rows(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ignores_other_tables() {
+        let dir = test_dir("ignore");
+        let adapter = ParquetAdapter::new(
+            dir.to_str().unwrap(),
+            String::from("mainnet"),
+            vec![String::from("solana_transactions")],
+            1,
+        )
+        .unwrap();
+        adapter
+            .upsert(&blocks_table(), &vec![block(1, 1_635_724_800)], &None)
+            .unwrap();
+        adapter.flush().unwrap();
+        assert_eq!(exported_rows(&dir), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Custom metric handlers loaded from shared libraries. A plugin crate is a
+//! `cdylib` depending on `analytics` which declares its registration
+//! function with [`export_analytics_plugin!`]:
+//!
+//! ```ignore
+//! analytics::export_analytics_plugin!(register);
+//!
+//! unsafe extern "C" fn register(registrar: &mut dyn PluginRegistrar) {
+//!     let handler = DexMetricHandler::new(registrar.network(), registrar.storage_adapter());
+//!     registrar.register_solana_handler(Box::new(handler));
+//! }
+//! ```
+//!
+//! The macro also exports the versions of `analytics` and of the compiler
+//! the plugin is built with, a plugin which does not match the service is
+//! refused before its declaration is read.
+use crate::config::PluginConfig;
+use crate::ethereum::handler::EthereumHandler;
+use crate::solana::handler::SolanaHandler;
+use crate::storage_adapter::StorageAdapter;
+use libloading::Library;
+use massbit::prelude::web3::types::Log;
+use massbit::prelude::LightEthereumBlock;
+use massbit_common::prelude::anyhow::{self, Context};
+use massbit_common::NetworkType;
+use massbit_solana_sdk::plugin::version::{layout_fingerprint, PluginVersion};
+pub use massbit_solana_sdk::plugin::{version_field, VersionField, RUSTC_VERSION};
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::mem::{align_of, size_of};
+use std::sync::Arc;
+
+/// Version of analytics, exported by the plugins built with
+/// `export_analytics_plugin!`
+pub const ANALYTICS_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Fingerprint of the compiler, the target and the layouts of the types
+/// shared with the plugins
+pub const ABI_FINGERPRINT: u64 = layout_fingerprint(&[
+    size_of::<PluginDeclaration>(),
+    size_of::<Box<dyn SolanaHandler>>(),
+    size_of::<Box<dyn EthereumHandler>>(),
+    size_of::<Arc<dyn StorageAdapter>>(),
+    size_of::<Option<NetworkType>>(),
+    size_of::<EncodedConfirmedBlock>(),
+    align_of::<EncodedConfirmedBlock>(),
+    size_of::<LightEthereumBlock>(),
+    align_of::<LightEthereumBlock>(),
+    size_of::<Log>(),
+]);
+
+#[macro_export]
+macro_rules! export_analytics_plugin {
+    ($register:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static analytics_plugin_declaration: $crate::plugin::PluginDeclaration =
+            $crate::plugin::PluginDeclaration {
+                register: $register,
+            };
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_sdk_version: $crate::plugin::VersionField =
+            $crate::plugin::version_field($crate::plugin::ANALYTICS_VERSION);
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_rustc_version: $crate::plugin::VersionField =
+            $crate::plugin::version_field($crate::plugin::RUSTC_VERSION);
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_abi_fingerprint: u64 = $crate::plugin::ABI_FINGERPRINT;
+    };
+}
+
+#[derive(Copy, Clone)]
+pub struct PluginDeclaration {
+    pub register: unsafe extern "C" fn(&mut dyn PluginRegistrar),
+}
+
+/// Passed to the registration function of a plugin. Handlers write their
+/// metrics through the storage adapter of the analytics service
+pub trait PluginRegistrar {
+    fn network(&self) -> &Option<NetworkType>;
+    fn storage_adapter(&self) -> Arc<dyn StorageAdapter>;
+    fn register_solana_handler(&mut self, handler: Box<dyn SolanaHandler>);
+    fn register_ethereum_handler(&mut self, handler: Box<dyn EthereumHandler>);
+}
+
+/// The handlers registered by the loaded plugins. Solana handlers are named
+/// `<plugin>/<index>` by the order in which the plugin registers them
+#[derive(Default)]
+pub struct PluginHandlers {
+    pub solana_handlers: Vec<(String, Arc<dyn SolanaHandler>)>,
+    pub ethereum_handlers: Vec<Arc<dyn EthereumHandler>>,
+}
+
+impl PluginHandlers {
+    /// Load every plugin in `plugins` and collect the handlers they register
+    pub fn load(
+        plugins: &[PluginConfig],
+        network: &Option<NetworkType>,
+        storage_adapter: Arc<dyn StorageAdapter>,
+    ) -> Result<PluginHandlers, anyhow::Error> {
+        let mut handlers = PluginHandlers::default();
+        for plugin in plugins {
+            // Safety: the library must declare itself with
+            // `export_analytics_plugin!`
+            let lib = unsafe { Library::new(&plugin.path) }.with_context(|| {
+                format!("Can not load plugin {} from {}", plugin.name, plugin.path)
+            })?;
+            // Safety: the version symbols have the same layout whatever the
+            // compiler of the plugin
+            unsafe { PluginVersion::read(&lib) }
+                .check_with("analytics", ANALYTICS_VERSION, ABI_FINGERPRINT)
+                .map_err(|err| {
+                    anyhow::anyhow!(
+                        "Can not load plugin {} from {}: {}",
+                        plugin.name,
+                        plugin.path,
+                        err
+                    )
+                })?;
+            let lib = Arc::new(lib);
+            let declaration = unsafe {
+                lib.get::<*mut PluginDeclaration>(b"analytics_plugin_declaration\0")
+                    .with_context(|| format!("{} is not an analytics plugin", plugin.path))?
+                    .read()
+            };
+            let mut registrar = Registrar {
+                plugin: &plugin.name,
+                solana_handlers: 0,
+                network,
+                storage_adapter: storage_adapter.clone(),
+                lib,
+                handlers: &mut handlers,
+            };
+            unsafe { (declaration.register)(&mut registrar) };
+            log::info!(
+                "Loaded analytics plugin {} from {}",
+                plugin.name,
+                plugin.path
+            );
+        }
+        Ok(handlers)
+    }
+}
+
+struct Registrar<'a> {
+    plugin: &'a str,
+    solana_handlers: usize,
+    network: &'a Option<NetworkType>,
+    storage_adapter: Arc<dyn StorageAdapter>,
+    lib: Arc<Library>,
+    handlers: &'a mut PluginHandlers,
+}
+
+impl PluginRegistrar for Registrar<'_> {
+    fn network(&self) -> &Option<NetworkType> {
+        self.network
+    }
+    fn storage_adapter(&self) -> Arc<dyn StorageAdapter> {
+        self.storage_adapter.clone()
+    }
+    fn register_solana_handler(&mut self, handler: Box<dyn SolanaHandler>) {
+        let name = format!("{}/{}", self.plugin, self.solana_handlers);
+        self.solana_handlers += 1;
+        self.handlers.solana_handlers.push((
+            name,
+            Arc::new(SolanaHandlerProxy {
+                handler,
+                _lib: self.lib.clone(),
+            }),
+        ));
+    }
+    fn register_ethereum_handler(&mut self, handler: Box<dyn EthereumHandler>) {
+        self.handlers
+            .ethereum_handlers
+            .push(Arc::new(EthereumHandlerProxy {
+                handler,
+                _lib: self.lib.clone(),
+            }));
+    }
+}
+
+/// A proxy object which wraps a handler and makes sure it can't outlive
+/// the library it came from.
+struct SolanaHandlerProxy {
+    handler: Box<dyn SolanaHandler>,
+    _lib: Arc<Library>,
+}
+
+impl SolanaHandler for SolanaHandlerProxy {
+    fn handle_block(
+        &self,
+        block_slot: u64,
+        block: Arc<EncodedConfirmedBlock>,
+    ) -> Result<(), anyhow::Error> {
+        self.handler.handle_block(block_slot, block)
+    }
+}
+
+struct EthereumHandlerProxy {
+    handler: Box<dyn EthereumHandler>,
+    _lib: Arc<Library>,
+}
+
+impl EthereumHandler for EthereumHandlerProxy {
+    fn handle_block(&self, block: Arc<LightEthereumBlock>) -> Result<(), anyhow::Error> {
+        self.handler.handle_block(block)
+    }
+    fn handle_logs(
+        &self,
+        block: Arc<LightEthereumBlock>,
+        logs: Arc<Vec<Arc<Log>>>,
+    ) -> Result<(), anyhow::Error> {
+        self.handler.handle_logs(block, logs)
+    }
+}
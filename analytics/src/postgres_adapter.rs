@@ -74,7 +74,9 @@ impl StorageAdapter for PostgresAdapter {
         let start = Instant::now();
         match self.pool.get() {
             Ok(conn) => conn.transaction::<(), anyhow::Error, _>(|| {
-                commands.iter().for_each(|cmd| {
+                // Stop at the first failing command so that the whole
+                // transaction is rolled back and the caller sees the error
+                for cmd in commands.iter() {
                     let upsert_query = UpsertQuery::from(cmd);
                     match upsert_query.execute(conn.deref()) {
                         Ok(_val) => {
@@ -91,9 +93,10 @@ impl StorageAdapter for PostgresAdapter {
                                 &cmd.table.name,
                                 &err
                             );
+                            return Err(err.into());
                         }
                     }
-                });
+                }
                 Ok(())
             }),
             Err(err) => {
@@ -144,6 +144,11 @@ impl<'a> UpsertConflictFragment<'a> {
             expressions: Vec::default(),
         }
     }
+    /// Skip rows which conflict with any unique constraint or index, e.g.
+    /// an index on expressions, which `on constraint` can not name
+    pub fn do_nothing() -> Self {
+        UpsertConflictFragment::new("")
+    }
     pub fn add_expression(&mut self, field: &'a str, expression: &'a str) -> &mut Self {
         self.expressions
             .push(UpdateExpression { field, expression });
@@ -157,8 +162,11 @@ impl<'a> QueryFragment<Pg> for UpsertConflictFragment<'a> {
         //   on conflict (name)
         //   do
         //   update set field = expression;
-        out.push_sql("\non conflict ON CONSTRAINT ");
-        out.push_sql(self.constraint);
+        out.push_sql("\non conflict");
+        if !self.constraint.is_empty() {
+            out.push_sql(" ON CONSTRAINT ");
+            out.push_sql(self.constraint);
+        }
         if self.expressions.len() == 0 {
             out.push_sql(" DO NOTHING ");
         } else {
@@ -178,6 +186,34 @@ impl<'a> QueryFragment<Pg> for UpsertConflictFragment<'a> {
         Ok(())
     }
 }
+/// An `UpsertConflictFragment` which owns its strings, so that an upsert can
+/// be kept and executed after the fragment it was built from is dropped
+#[derive(Debug, Clone)]
+pub struct OwnedConflictFragment {
+    constraint: String,
+    expressions: Vec<(String, String)>,
+}
+impl OwnedConflictFragment {
+    pub fn as_fragment(&self) -> UpsertConflictFragment {
+        let mut fragment = UpsertConflictFragment::new(self.constraint.as_str());
+        for (field, expression) in self.expressions.iter() {
+            fragment.add_expression(field.as_str(), expression.as_str());
+        }
+        fragment
+    }
+}
+impl<'a> From<&UpsertConflictFragment<'a>> for OwnedConflictFragment {
+    fn from(fragment: &UpsertConflictFragment<'a>) -> Self {
+        OwnedConflictFragment {
+            constraint: fragment.constraint.to_string(),
+            expressions: fragment
+                .expressions
+                .iter()
+                .map(|exp| (exp.field.to_string(), exp.expression.to_string()))
+                .collect(),
+        }
+    }
+}
 /// A `QueryValue` makes it possible to bind a `Value` into a SQL query
 /// using the metadata from Column
 struct QueryValue<'a>(&'a Value, &'a ColumnType);
@@ -53,6 +53,29 @@ table! {
     }
 }
 
+table! {
+    network_backfill_chunks (id) {
+        id -> Int8,
+        chain -> Text,
+        network -> Text,
+        start_block -> Int8,
+        end_block -> Int8,
+        next_block -> Int8,
+    }
+}
+
+table! {
+    network_failed_blocks (id) {
+        id -> Int8,
+        chain -> Text,
+        network -> Text,
+        block_number -> Int8,
+        handlers -> Array<Text>,
+        error -> Text,
+        attempts -> Int4,
+    }
+}
+
 table! {
     network_states (id) {
         id -> Int8,
@@ -67,5 +90,7 @@ allow_tables_to_appear_in_same_query!(
     ethereum_daily_address_transactions,
     ethereum_daily_transactions,
     ethereum_transactions,
+    network_backfill_chunks,
+    network_failed_blocks,
     network_states,
 );
@@ -0,0 +1,236 @@
+use super::CHAIN;
+
+use crate::backfill::{commit_upserts, prepare_chunks, BufferedAdapter, COMMIT_BATCH_SIZE};
+use crate::checkpoint::save_failed_block;
+use crate::config::NetworkConfig;
+use crate::models::BackfillChunk;
+use crate::parquet_adapter::{parquet_exporter, SOLANA_PARQUET_TABLES};
+use crate::postgres_adapter::PostgresAdapter;
+use crate::solana::handler::{create_solana_handler_manager, describe_failures};
+use crate::solana::reader::fetch_block;
+use crate::storage_adapter::StorageAdapter;
+use core::ops::Deref;
+
+use massbit_common::prelude::anyhow;
+use massbit_common::NetworkType;
+
+use solana_client::rpc_client::RpcClient;
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How often a block or a handler is retried before the block is recorded
+/// as failed
+const MAX_BLOCK_RETRIES: u64 = 3;
+const BLOCK_RETRY_DELAY_MS: u64 = 1000;
+/// `getBlocks` does not accept longer ranges
+const MAX_CHUNK_SIZE: u64 = 500_000;
+
+/// Process the slots in `range` with `workers` parallel workers, each
+/// taking chunks of `chunk_size` slots. Progress is stored per chunk, so
+/// running the same range again resumes where the last run stopped
+pub async fn backfill_solana_range(
+    storage_adapter: Arc<PostgresAdapter>,
+    network_name: &NetworkType,
+    client: Arc<RpcClient>,
+    config: &NetworkConfig,
+    range: Range<u64>,
+    workers: usize,
+    chunk_size: u64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let chunks = {
+        let conn = storage_adapter.get_connection()?;
+        prepare_chunks(
+            conn.deref(),
+            CHAIN.as_str(),
+            network_name,
+            range.start,
+            range.end,
+            chunk_size.min(MAX_CHUNK_SIZE),
+        )?
+    };
+    log::info!(
+        "Start backfill of slots {:?} with {} pending chunks and {} workers",
+        range,
+        chunks.len(),
+        workers
+    );
+    let queue = Arc::new(Mutex::new(chunks.into_iter().collect::<VecDeque<_>>()));
+    let mut tasks = vec![];
+    for worker in 0..workers.max(1) {
+        let queue = queue.clone();
+        let storage_adapter = storage_adapter.clone();
+        let network_name = network_name.clone();
+        let client = client.clone();
+        let config = config.clone();
+        tasks.push(tokio::task::spawn_blocking(move || {
+            run_worker(
+                worker,
+                queue,
+                storage_adapter,
+                &network_name,
+                &client,
+                &config,
+            )
+        }));
+    }
+    let mut result = Ok(());
+    for task in futures03::future::join_all(tasks).await {
+        match task {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                log::error!("Backfill worker stopped with error {:?}", &err);
+                result = Err(err.into());
+            }
+            Err(err) => {
+                log::error!("Backfill worker panicked {:?}", &err);
+                result = Err(err.into());
+            }
+        }
+    }
+    result
+}
+
+/// Take chunks from `queue` until it is empty. Every worker has its own
+/// handlers writing into its own buffer
+fn run_worker(
+    worker: usize,
+    queue: Arc<Mutex<VecDeque<BackfillChunk>>>,
+    storage_adapter: Arc<PostgresAdapter>,
+    network_name: &NetworkType,
+    client: &RpcClient,
+    config: &NetworkConfig,
+) -> Result<(), anyhow::Error> {
+    let network = Some(network_name.clone());
+    // The handlers do not export, the upserts are exported once committed
+    let exporter = parquet_exporter(&network, &config.parquet, &SOLANA_PARQUET_TABLES)?;
+    let config = NetworkConfig {
+        parquet: None,
+        ..config.clone()
+    };
+    let buffer = Arc::new(BufferedAdapter::new());
+    let handler_manager = create_solana_handler_manager(&network, buffer.clone(), &config)?;
+    let exporter = exporter
+        .as_ref()
+        .map(|exporter| exporter as &dyn StorageAdapter);
+    loop {
+        let chunk = match queue.lock().unwrap().pop_front() {
+            Some(chunk) => chunk,
+            None => return Ok(()),
+        };
+        let start = Instant::now();
+        log::info!(
+            "Worker {} starts chunk {}..{} at slot {}",
+            worker,
+            chunk.start_block,
+            chunk.end_block,
+            chunk.next_block
+        );
+        let slots = client.get_blocks(chunk.next_block as u64, Some(chunk.end_block as u64 - 1))?;
+        let mut upserts = vec![];
+        let mut committed_slot = chunk.next_block as u64;
+        for block_slot in slots {
+            if block_slot >= committed_slot + COMMIT_BATCH_SIZE {
+                handler_manager.flush()?;
+                commit_upserts(
+                    storage_adapter.as_ref(),
+                    exporter,
+                    &upserts,
+                    &chunk,
+                    block_slot,
+                )?;
+                upserts.clear();
+                committed_slot = block_slot;
+            }
+            let block = match fetch_block_with_retries(client, block_slot) {
+                Ok(block) => Arc::new(block),
+                Err(err) => {
+                    let handlers = handler_manager.handler_names();
+                    let conn = storage_adapter.get_connection()?;
+                    save_failed_block(
+                        conn.deref(),
+                        CHAIN.as_str(),
+                        network_name,
+                        block_slot,
+                        &handlers,
+                        &err.to_string(),
+                    )?;
+                    continue;
+                }
+            };
+            let mut failures = vec![];
+            for (name, handler) in handler_manager.handlers.iter() {
+                let mut attempt = 0;
+                loop {
+                    match handler.handle_block(block_slot, block.clone()) {
+                        Ok(_) => {
+                            upserts.extend(buffer.take());
+                            break;
+                        }
+                        Err(err) => {
+                            // Drop what the handler wrote before it failed
+                            buffer.take();
+                            if attempt >= MAX_BLOCK_RETRIES {
+                                failures.push((name.clone(), err));
+                                break;
+                            }
+                            attempt += 1;
+                            sleep(Duration::from_millis(BLOCK_RETRY_DELAY_MS * attempt));
+                        }
+                    }
+                }
+            }
+            if !failures.is_empty() {
+                let failed_handlers: Vec<String> =
+                    failures.iter().map(|(name, _)| name.clone()).collect();
+                let conn = storage_adapter.get_connection()?;
+                save_failed_block(
+                    conn.deref(),
+                    CHAIN.as_str(),
+                    network_name,
+                    block_slot,
+                    &failed_handlers,
+                    &describe_failures(&failures),
+                )?;
+                log::warn!("Block slot {} is recorded as failed", block_slot);
+            }
+        }
+        handler_manager.flush()?;
+        commit_upserts(
+            storage_adapter.as_ref(),
+            exporter,
+            &upserts,
+            &chunk,
+            chunk.end_block as u64,
+        )?;
+        log::info!(
+            "Worker {} finished chunk {}..{} in {:?}",
+            worker,
+            chunk.start_block,
+            chunk.end_block,
+            start.elapsed()
+        );
+    }
+}
+
+fn fetch_block_with_retries(
+    client: &RpcClient,
+    block_slot: u64,
+) -> Result<EncodedConfirmedBlock, anyhow::Error> {
+    let mut attempt = 0;
+    loop {
+        match fetch_block(client, block_slot) {
+            Ok(data) => return Ok(data.block),
+            Err(err) => {
+                if attempt >= MAX_BLOCK_RETRIES {
+                    return Err(anyhow::anyhow!("{}", err));
+                }
+                attempt += 1;
+                sleep(Duration::from_millis(BLOCK_RETRY_DELAY_MS * attempt));
+            }
+        }
+    }
+}
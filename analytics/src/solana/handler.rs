@@ -1,10 +1,13 @@
 //Public trait for ethereum metric
 use super::metrics::*;
+use crate::config::NetworkConfig;
+use crate::parquet_adapter::{with_parquet_export, SOLANA_PARQUET_TABLES};
+use crate::plugin::PluginHandlers;
 use crate::storage_adapter::StorageAdapter;
 
 use massbit_common::prelude::anyhow;
 use massbit_common::NetworkType;
-use solana_transaction_status::{EncodedConfirmedBlock};
+use solana_transaction_status::EncodedConfirmedBlock;
 use std::sync::Arc;
 
 pub trait SolanaHandler: Sync + Send {
@@ -18,63 +21,185 @@ pub trait SolanaHandler: Sync + Send {
 
 #[derive(Default)]
 pub struct SolanaHandlerManager {
-    pub handlers: Vec<Arc<dyn SolanaHandler>>,
+    /// The handlers with the names under which their failures are
+    /// recorded, so that a failed block can be retried with only the
+    /// handlers that failed. Built-in handlers are named by their config key
+    pub handlers: Vec<(String, Arc<dyn SolanaHandler>)>,
+    /// Storage the handlers write into
+    storage_adapter: Option<Arc<dyn StorageAdapter>>,
 }
 impl SolanaHandlerManager {
     pub fn new() -> SolanaHandlerManager {
         SolanaHandlerManager::default()
     }
-    pub fn add_handler(mut self, handler: Arc<dyn SolanaHandler>) -> Self {
-        self.handlers.push(handler);
+    pub fn with_storage_adapter(mut self, storage_adapter: Arc<dyn StorageAdapter>) -> Self {
+        self.storage_adapter = Some(storage_adapter);
         self
     }
+    /// Write the rows buffered by the storage of the handlers
+    pub fn flush(&self) -> Result<(), anyhow::Error> {
+        match &self.storage_adapter {
+            Some(storage_adapter) => storage_adapter.flush(),
+            None => Ok(()),
+        }
+    }
+    fn begin_unit(&self) {
+        if let Some(storage_adapter) = &self.storage_adapter {
+            storage_adapter.begin_unit();
+        }
+    }
+    fn end_unit(&self, committed: bool) {
+        if let Some(storage_adapter) = &self.storage_adapter {
+            storage_adapter.end_unit(committed);
+        }
+    }
+    pub fn add_handler(mut self, name: &str, handler: Arc<dyn SolanaHandler>) -> Self {
+        self.handlers.push((name.to_string(), handler));
+        self
+    }
+    pub fn handler_names(&self) -> Vec<String> {
+        self.handlers.iter().map(|(name, _)| name.clone()).collect()
+    }
     pub fn handle_block(
         &self,
         block_slot: u64,
         block: Arc<EncodedConfirmedBlock>,
     ) -> Result<(), anyhow::Error> {
-        self.handlers.iter().for_each(|handler| {
-            let clone_handler = handler.clone();
-            let clone_block = Arc::clone(&block);
-            tokio::spawn(async move {
-                match clone_handler.handle_block(block_slot, clone_block) {
-                    Ok(_) => {}
-                    Err(err) => log::error!("{:?}", &err),
+        let failures = self.handle_block_with(block_slot, block, None);
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Block {} failed in handlers {}",
+                block_slot,
+                describe_failures(&failures)
+            ))
+        }
+    }
+    /// Run the handlers whose names are in `handlers`, or all handlers if
+    /// it is `None`, on the block and return the handlers that failed
+    /// together with their errors
+    pub fn handle_block_with(
+        &self,
+        block_slot: u64,
+        block: Arc<EncodedConfirmedBlock>,
+        handlers: Option<&[String]>,
+    ) -> Vec<(String, anyhow::Error)> {
+        self.handlers
+            .iter()
+            .filter(|(name, _)| handlers.map_or(true, |names| names.contains(name)))
+            .filter_map(|(name, handler)| {
+                // Only the failed handlers run again, each handler is a unit
+                self.begin_unit();
+                let result = handler.handle_block(block_slot, Arc::clone(&block));
+                self.end_unit(result.is_ok());
+                match result {
+                    Ok(_) => None,
+                    Err(err) => {
+                        log::error!("{} failed for block {}: {:?}", name, block_slot, &err);
+                        Some((name.clone(), err))
+                    }
                 }
-            });
-        });
-        Ok(())
+            })
+            .collect()
     }
 }
 
+pub fn describe_failures(failures: &[(String, anyhow::Error)]) -> String {
+    failures
+        .iter()
+        .map(|(name, err)| format!("{}: {}", name, err))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+/// Create the manager with the built-in handlers enabled in `config` and
+/// the handlers of its plugins
 pub fn create_solana_handler_manager(
     network: &Option<NetworkType>,
     storate_adapter: Arc<dyn StorageAdapter>,
-) -> SolanaHandlerManager {
-    let handler_manager = SolanaHandlerManager::new();
-    handler_manager
-        .add_handler(Arc::new(SolanaRawBlockHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(SolanaRawTransactionHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        // .add_handler(Arc::new(SolanaRawLogHandler::new(
-        //     network,
-        //     storate_adapter.clone(),
-        // )))
-        .add_handler(Arc::new(SolanaInstructionHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(SolanaTokenBalanceHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
-        .add_handler(Arc::new(SolanaStatBlockHandler::new(
-            network,
-            storate_adapter.clone(),
-        )))
+    config: &NetworkConfig,
+) -> Result<SolanaHandlerManager, anyhow::Error> {
+    let storate_adapter = with_parquet_export(
+        storate_adapter,
+        network,
+        &config.parquet,
+        &SOLANA_PARQUET_TABLES,
+    )?;
+    // Key in the config, enabled by default and the handler
+    let handlers: Vec<(&str, bool, Arc<dyn SolanaHandler>)> = vec![
+        (
+            "raw_block",
+            true,
+            Arc::new(SolanaRawBlockHandler::new(network, storate_adapter.clone())),
+        ),
+        (
+            "raw_transaction",
+            true,
+            Arc::new(SolanaRawTransactionHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "raw_log",
+            false,
+            Arc::new(SolanaRawLogHandler::new(network, storate_adapter.clone())),
+        ),
+        (
+            "instruction",
+            true,
+            Arc::new(
+                SolanaInstructionHandler::new(network, storate_adapter.clone())
+                    .with_unparsed_instructions(config.is_enabled("raw_instruction", false)),
+            ),
+        ),
+        (
+            "token_balance",
+            true,
+            Arc::new(SolanaTokenBalanceHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "stat_block",
+            true,
+            Arc::new(SolanaStatBlockHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "token_holder",
+            true,
+            Arc::new(SolanaTokenHolderHandler::new(
+                network,
+                storate_adapter.clone(),
+            )),
+        ),
+        (
+            "program_event",
+            true,
+            Arc::new(SolanaProgramEventHandler::new(
+                network,
+                storate_adapter.clone(),
+                &config.events,
+            )?),
+        ),
+    ];
+    let mut handler_manager =
+        SolanaHandlerManager::new().with_storage_adapter(storate_adapter.clone());
+    for (key, default, handler) in handlers {
+        if config.is_enabled(key, default) {
+            handler_manager = handler_manager.add_handler(key, handler);
+        } else {
+            log::info!("Solana handler {} is disabled", key);
+        }
+    }
+    let plugins = PluginHandlers::load(&config.plugins, network, storate_adapter)?;
+    for (name, handler) in plugins.solana_handlers {
+        handler_manager = handler_manager.add_handler(&name, handler);
+    }
+    Ok(handler_manager)
 }
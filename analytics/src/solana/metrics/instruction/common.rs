@@ -1,7 +1,8 @@
 use crate::create_entity;
-use crate::relational::Table;
+use crate::relational::{Column, ColumnType, Table};
 use crate::solana::metrics::instruction::spltoken_instruction::create_spltoken_inst_table;
 use crate::solana::metrics::instruction::system_instruction::create_system_inst_table;
+use crate::solana::metrics::instruction::vote_instruction::{create_vote_inst_table, VOTE_TYPES};
 use core::str::FromStr;
 use lazy_static::lazy_static;
 use massbit::prelude::{Attribute, Entity, Value};
@@ -65,12 +66,26 @@ impl From<&ParsedInstruction> for InstructionKey {
 impl InstructionKey {
     pub fn create_table(&self) -> Option<Table> {
         let program_key = Pubkey::from_str(self.program_id.as_str()).unwrap();
-        match PARSABLE_PROGRAM_IDS.get(&program_key) {
+        let table = match PARSABLE_PROGRAM_IDS.get(&program_key) {
             Some(ParsableProgram::System) => create_system_inst_table(self.inst_type.as_str()),
             Some(ParsableProgram::SplToken) => create_spltoken_inst_table(self.inst_type.as_str()),
-            Some(ParsableProgram::Vote) => None,
+            Some(ParsableProgram::Vote) => create_vote_inst_table(self.inst_type.as_str()),
             _ => None,
-        }
+        };
+        //Order of the invoking instruction, null for top level instructions
+        table.map(|mut table| {
+            table
+                .columns
+                .push(Column::new("parent_order", ColumnType::Int));
+            table
+        })
+    }
+    ///
+    /// Instructions which cast votes, they feed the validator stats
+    ///
+    pub fn is_vote(&self) -> bool {
+        self.program_id == VOTE_PROGRAM_ID.to_string()
+            && VOTE_TYPES.contains(&self.inst_type.as_str())
     }
     ///
     /// Create entity for each program
@@ -4,17 +4,22 @@ use crate::postgres_queries::UpsertConflictFragment;
 use crate::relational::{Column, ColumnType, Table};
 use crate::solana::handler::SolanaHandler;
 use crate::solana::metrics::instruction::common::InstructionKey;
-use crate::solana::metrics::instruction::raw_instruction::create_unparsed_instruction;
+use crate::solana::metrics::instruction::raw_instruction::{
+    create_unparsed_instruction, create_unparsed_instruction_table,
+};
 use crate::solana::metrics::instruction::spltoken_instruction::create_spltoken_entity;
 use crate::solana::metrics::instruction::system_instruction::create_system_entity;
 use crate::solana::metrics::instruction::vote_instruction::create_vote_entity;
+use crate::solana::metrics::validator::{get_epoch_schedule, upsert_vote_stats, VoteAccounts};
+use crate::solana::SOLANA_URL;
 use crate::storage_adapter::StorageAdapter;
 use massbit::prelude::Entity;
-use massbit_chain_solana::data_type::Pubkey;
+use massbit_chain_solana::data_type::{to_ui_instructions, Pubkey};
 use massbit_common::NetworkType;
+use solana_sdk::epoch_schedule::EpochSchedule;
 use solana_sdk::transaction::Transaction;
 use solana_transaction_status::parse_instruction::{ParsableProgram, ParsedInstruction};
-use solana_transaction_status::{parse_instruction, EncodedConfirmedBlock};
+use solana_transaction_status::{parse_instruction, EncodedConfirmedBlock, InnerInstructions};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
@@ -22,6 +27,10 @@ use std::time::Instant;
 pub struct SolanaInstructionHandler {
     pub network: Option<NetworkType>,
     pub storage_adapter: Arc<dyn StorageAdapter>,
+    epoch_schedule: EpochSchedule,
+    vote_accounts: VoteAccounts,
+    /// Store the instructions without parser in `solana_instructions`
+    store_unparsed: bool,
 }
 
 impl SolanaInstructionHandler {
@@ -29,8 +38,15 @@ impl SolanaInstructionHandler {
         SolanaInstructionHandler {
             network: network.clone(),
             storage_adapter,
+            epoch_schedule: get_epoch_schedule(SOLANA_URL.as_str()),
+            vote_accounts: VoteAccounts::new(SOLANA_URL.as_str()),
+            store_unparsed: false,
         }
     }
+    pub fn with_unparsed_instructions(mut self, store_unparsed: bool) -> Self {
+        self.store_unparsed = store_unparsed;
+        self
+    }
 }
 
 impl SolanaHandler for SolanaInstructionHandler {
@@ -46,29 +62,52 @@ impl SolanaHandler for SolanaInstructionHandler {
         let mut total_instruction = 0;
         for (tx_index, tran) in block.transactions.iter().enumerate() {
             if let Some(transaction) = tran.transaction.decode() {
-                total_instruction = total_instruction + transaction.message.instructions.len();
-                let entities =
-                    create_instructions(block_slot, block.clone(), &transaction, tx_index as i32);
-                parsed_entities.extend(entities.0);
+                let inner_instructions: Vec<InnerInstructions> = tran
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.inner_instructions.clone())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(to_ui_instructions)
+                    .collect();
+                total_instruction = total_instruction
+                    + transaction.message.instructions.len()
+                    + inner_instructions
+                        .iter()
+                        .map(|inner| inner.instructions.len())
+                        .sum::<usize>();
+                let entities = create_instructions(
+                    block_slot,
+                    block.clone(),
+                    &transaction,
+                    &inner_instructions,
+                    tx_index as i32,
+                );
+                for (key, entities) in entities.0 {
+                    parsed_entities.entry(key).or_default().extend(entities);
+                }
                 unparsed_entities.extend(entities.1);
             }
-            //create_inner_instructions(&block.block, tran);
         }
 
         let mut program_entities = Vec::default();
-        parsed_entities.into_iter().for_each(|(key, entities)| {
+        let mut vote_entities = Vec::default();
+        // Upsert in the handler's call so that failures are reported and the
+        // writes of a block can be committed together
+        for (key, entities) in parsed_entities.into_iter() {
             //Store program info from InstructionKey
             program_entities.push(key.create_program_entity());
-            let adapter = self.storage_adapter.clone();
-            tokio::spawn(async move {
-                match key.create_table() {
-                    Some(table) => {
-                        adapter.upsert(&table, &entities, &None);
-                    }
-                    None => {}
-                }
-            });
-        });
+            if key.is_vote() {
+                vote_entities.extend(entities);
+                continue;
+            }
+            if let Some(table) = key.create_table() {
+                // An instruction is only stored once when a block is processed again
+                let conflict_frag = Some(UpsertConflictFragment::do_nothing());
+                self.storage_adapter
+                    .upsert(&table, &entities, &conflict_frag)?;
+            }
+        }
         if program_entities.len() > 0 {
             log::info!("Store instruction programs info");
             let prog_columns = create_columns!(
@@ -79,44 +118,50 @@ impl SolanaHandler for SolanaInstructionHandler {
             let table = Table::new("solana_programs", prog_columns);
             let conflict_frag = Some(UpsertConflictFragment::new("solana_programs_type_uindex"));
             self.storage_adapter
-                .upsert(&table, &program_entities, &conflict_frag);
+                .upsert(&table, &program_entities, &conflict_frag)?;
         }
         log::info!(
             "Parsing {} instructions in {:?}",
             total_instruction,
             start.elapsed()
         );
-        Ok(())
-        //Don't store unpased instruction due to huge amount of data
-        // if unparsed_entities.len() > 0 {
-        //     let columns = create_columns!(
-        //         "block_slot" => ColumnType::BigInt,
-        //         "tx_index" => ColumnType::Int,
-        //         "block_time" => ColumnType::BigInt,
-        //         //Index of instruction in transaction
-        //         "inst_index" => ColumnType::Int,
-        //         "program_name" => ColumnType::String,
-        //         "accounts" => ColumnType::TextArray,
-        //         "data" => ColumnType::Bytes
-        //     );
-        //     let table = Table::new("solana_instructions", columns);
-        //     //let table = create_unparsed_instruction_table();
-        //     self.storage_adapter
-        //         .upsert(&table, &unparsed_entities, &None)
-        // } else {
-        //     Ok(())
-        // }
+        //Votes are aggregated per vote account instead of stored one by one
+        let network = match &self.network {
+            None => "",
+            Some(val) => val.as_str(),
+        };
+        upsert_vote_stats(
+            &self.storage_adapter,
+            network,
+            block_slot,
+            block.block_time.unwrap_or_default() as u64,
+            &self.epoch_schedule,
+            &self.vote_accounts,
+            &vote_entities,
+        )?;
+        //Unparsed instructions are only stored on demand due to huge amount of data
+        if self.store_unparsed && unparsed_entities.len() > 0 {
+            let table = create_unparsed_instruction_table();
+            let conflict_frag = Some(UpsertConflictFragment::do_nothing());
+            self.storage_adapter
+                .upsert(&table, &unparsed_entities, &conflict_frag)
+        } else {
+            Ok(())
+        }
     }
 }
 
 ///
 /// For each transaction try to parse instructions and create correspond entities,
-/// Unparsed instructions are converted to common entities
+/// Unparsed instructions are converted to common entities.
+/// Inner instructions are ordered within the instruction which invoked them,
+/// their entities have the order of that instruction as `parent_order`
 ///
 fn create_instructions(
     block_slot: u64,
     block: Arc<EncodedConfirmedBlock>,
     tran: &Transaction,
+    inner_instructions: &[InnerInstructions],
     tx_index: i32,
 ) -> (HashMap<InstructionKey, Vec<Entity>>, Vec<Entity>) {
     let timestamp = match block.block_time {
@@ -129,12 +174,25 @@ fn create_instructions(
     };
     let mut unparsed_instructions = Vec::default();
     let mut parsed_instrucions: HashMap<InstructionKey, Vec<Entity>> = HashMap::default();
-    for (ind, inst) in tran.message.instructions.iter().enumerate() {
+    let instructions = tran
+        .message
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(ind, inst)| (None, ind, inst))
+        .chain(inner_instructions.iter().flat_map(|inner| {
+            inner
+                .instructions
+                .iter()
+                .enumerate()
+                .map(move |(ind, inst)| (Some(inner.index as i32), ind, inst))
+        }));
+    for (parent_order, ind, inst) in instructions {
         let program_key = inst.program_id(tran.message.account_keys.as_slice());
         match parse_instruction::parse(program_key, inst, tran.message.account_keys.as_slice()) {
             Ok(parsed_inst) => {
                 let key = InstructionKey::from(&parsed_inst);
-                if let Some(entity) = create_parsed_entity(
+                if let Some(mut entity) = create_parsed_entity(
                     block_slot,
                     program_key,
                     tx_hash.clone(),
@@ -142,14 +200,10 @@ fn create_instructions(
                     ind as i32,
                     &parsed_inst,
                 ) {
-                    match parsed_instrucions.get_mut(&key) {
-                        None => {
-                            parsed_instrucions.insert(key, vec![entity]);
-                        }
-                        Some(vec) => {
-                            vec.push(entity);
-                        }
-                    };
+                    if let Some(parent_order) = parent_order {
+                        entity.set("parent_order", parent_order);
+                    }
+                    parsed_instrucions.entry(key).or_default().push(entity);
                 };
             }
             Err(_) => {
@@ -158,6 +212,7 @@ fn create_instructions(
                     tx_index,
                     timestamp,
                     ind as i32,
+                    parent_order,
                     program_key.to_string(),
                     tran,
                     inst,
@@ -1,24 +1,24 @@
 
 
 
-use crate::{create_entity};
+use crate::relational::{Column, ColumnType, Table};
+use crate::{create_columns, create_entity};
 use massbit::data::store::scalar::Bytes;
 use massbit::prelude::{Attribute, Entity, Value};
 
 
 use solana_sdk::instruction::CompiledInstruction;
 use solana_sdk::transaction::Transaction;
-
-use solana_transaction_status::{
-    ConfirmedBlock, TransactionWithStatusMeta,
-};
 use std::collections::HashMap;
 
+/// Entity of an instruction no parser is known for. Inner instructions have
+/// the index of the instruction which invoked them as `parent_order`
 pub fn create_unparsed_instruction(
     block_slot: u64,
     tx_index: i32,
     block_time: u64,
     inst_index: i32,
+    parent_order: Option<i32>,
     program_name: String,
     trans: &Transaction,
     inst: &CompiledInstruction,
@@ -38,7 +38,7 @@ pub fn create_unparsed_instruction(
     };
 
     inst.visit_each_account(&mut work);
-    create_entity!(
+    let mut entity = create_entity!(
         "block_slot" => block_slot,
         "tx_index" => tx_index,
         "block_time" => block_time,
@@ -46,20 +46,24 @@ pub fn create_unparsed_instruction(
         "program_name" => program_name,
         "accounts" => accounts,
         "data" => Bytes::from(inst.data.as_slice())
-    )
+    );
+    if let Some(parent_order) = parent_order {
+        entity.set("parent_order", parent_order);
+    }
+    entity
 }
-fn create_inner_instructions(
-    _block: &ConfirmedBlock,
-    tran: &TransactionWithStatusMeta,
-) -> Vec<Entity> {
-    tran.meta
-        .as_ref()
-        .and_then(|meta| meta.inner_instructions.as_ref())
-        .and_then(|vec| {
-            vec.iter().map(|_inner| {
-                //println!("{:?}", inner);
-            });
-            Some(0_u64)
-        });
-    Vec::default()
+
+pub fn create_unparsed_instruction_table() -> Table {
+    let columns = create_columns!(
+        "block_slot" => ColumnType::BigInt,
+        "tx_index" => ColumnType::Int,
+        "block_time" => ColumnType::BigInt,
+        //Index of instruction in transaction or below its parent
+        "inst_index" => ColumnType::Int,
+        "parent_order" => ColumnType::Int,
+        "program_name" => ColumnType::String,
+        "accounts" => ColumnType::TextArray,
+        "data" => ColumnType::Bytes
+    );
+    Table::new("solana_instructions", columns)
 }
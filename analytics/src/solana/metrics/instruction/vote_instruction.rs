@@ -1,13 +1,184 @@
-use massbit::prelude::Entity;
+use crate::relational::{Column, ColumnType, Table};
+use crate::{create_columns, create_entity};
+use massbit::prelude::{Attribute, Entity, Value};
 use solana_transaction_status::parse_instruction::ParsedInstruction;
+use std::collections::HashMap;
+
+/// Instruction types which cast votes. They are aggregated into the
+/// validator stats instead of being stored one row per instruction
+pub const VOTE_TYPES: [&str; 2] = ["vote", "voteSwitch"];
+
+pub fn create_vote_inst_table(inst_type: &str) -> Option<Table> {
+    match inst_type {
+        "initialize" => {
+            let columns = create_columns!(
+                "tx_hash" => ColumnType::String,
+                "block_slot" => ColumnType::BigInt,
+                "block_time" => ColumnType::BigInt,
+                "inst_order" => ColumnType::Int,
+                "vote_account" => ColumnType::String,
+                "node" => ColumnType::String,
+                "authorized_voter" => ColumnType::String,
+                "authorized_withdrawer" => ColumnType::String,
+                "commission" => ColumnType::Int
+            );
+            Some(Table::new("solana_inst_vote_initializes", columns))
+        }
+        "authorize" => {
+            let columns = create_columns!(
+                "tx_hash" => ColumnType::String,
+                "block_slot" => ColumnType::BigInt,
+                "block_time" => ColumnType::BigInt,
+                "inst_order" => ColumnType::Int,
+                "vote_account" => ColumnType::String,
+                "authority" => ColumnType::String,
+                "new_authority" => ColumnType::String,
+                "authority_type" => ColumnType::String
+            );
+            Some(Table::new("solana_inst_vote_authorizes", columns))
+        }
+        "withdraw" => {
+            let columns = create_columns!(
+                "tx_hash" => ColumnType::String,
+                "block_slot" => ColumnType::BigInt,
+                "block_time" => ColumnType::BigInt,
+                "inst_order" => ColumnType::Int,
+                "vote_account" => ColumnType::String,
+                "destination" => ColumnType::String,
+                "withdraw_authority" => ColumnType::String,
+                "lamports" => ColumnType::BigInt
+            );
+            Some(Table::new("solana_inst_vote_withdraws", columns))
+        }
+        "updateValidatorIdentity" => {
+            let columns = create_columns!(
+                "tx_hash" => ColumnType::String,
+                "block_slot" => ColumnType::BigInt,
+                "block_time" => ColumnType::BigInt,
+                "inst_order" => ColumnType::Int,
+                "vote_account" => ColumnType::String,
+                "new_validator_identity" => ColumnType::String,
+                "withdraw_authority" => ColumnType::String
+            );
+            Some(Table::new(
+                "solana_inst_vote_update_validator_identities",
+                columns,
+            ))
+        }
+        "updateCommission" => {
+            let columns = create_columns!(
+                "tx_hash" => ColumnType::String,
+                "block_slot" => ColumnType::BigInt,
+                "block_time" => ColumnType::BigInt,
+                "inst_order" => ColumnType::Int,
+                "vote_account" => ColumnType::String,
+                "commission" => ColumnType::Int,
+                "withdraw_authority" => ColumnType::String
+            );
+            Some(Table::new("solana_inst_vote_update_commissions", columns))
+        }
+        _ => None,
+    }
+}
 
-//[WIP]
 pub fn create_vote_entity(
-    _block_slot: u64,
-    _tx_hash: String,
-    _block_time: u64,
-    _inst_order: i32,
-    _inst: &ParsedInstruction,
+    block_slot: u64,
+    tx_hash: String,
+    block_time: u64,
+    inst_order: i32,
+    inst: &ParsedInstruction,
 ) -> Option<Entity> {
-    None
+    let info = &inst.parsed["info"];
+    let mut entity = create_entity!(
+        "tx_hash" => tx_hash,
+        "block_slot" => block_slot,
+        "block_time" => block_time,
+        "inst_order" => inst_order,
+        "vote_account" => info["voteAccount"].as_str().unwrap_or("")
+    );
+    let mut insert = |attr: &str, value: Value| {
+        entity.insert(Attribute::from(attr), value);
+    };
+    match inst.parsed["type"].as_str().unwrap_or_default() {
+        "initialize" => {
+            insert("node", Value::from(info["node"].as_str().unwrap_or("")));
+            insert(
+                "authorized_voter",
+                Value::from(info["authorizedVoter"].as_str().unwrap_or("")),
+            );
+            insert(
+                "authorized_withdrawer",
+                Value::from(info["authorizedWithdrawer"].as_str().unwrap_or("")),
+            );
+            insert(
+                "commission",
+                Value::from(info["commission"].as_u64().unwrap_or_default() as i32),
+            );
+        }
+        "authorize" => {
+            insert(
+                "authority",
+                Value::from(info["authority"].as_str().unwrap_or("")),
+            );
+            insert(
+                "new_authority",
+                Value::from(info["newAuthority"].as_str().unwrap_or("")),
+            );
+            insert(
+                "authority_type",
+                Value::from(info["authorityType"].as_str().unwrap_or("")),
+            );
+        }
+        "withdraw" => {
+            insert(
+                "destination",
+                Value::from(info["destination"].as_str().unwrap_or("")),
+            );
+            insert(
+                "withdraw_authority",
+                Value::from(info["withdrawAuthority"].as_str().unwrap_or("")),
+            );
+            insert(
+                "lamports",
+                Value::from(info["lamports"].as_u64().unwrap_or_default()),
+            );
+        }
+        "updateValidatorIdentity" => {
+            insert(
+                "new_validator_identity",
+                Value::from(info["newValidatorIdentity"].as_str().unwrap_or("")),
+            );
+            insert(
+                "withdraw_authority",
+                Value::from(info["withdrawAuthority"].as_str().unwrap_or("")),
+            );
+        }
+        "updateCommission" => {
+            insert(
+                "commission",
+                Value::from(info["commission"].as_u64().unwrap_or_default() as i32),
+            );
+            insert(
+                "withdraw_authority",
+                Value::from(info["withdrawAuthority"].as_str().unwrap_or("")),
+            );
+        }
+        "vote" | "voteSwitch" => {
+            let slots: Vec<u64> = info["vote"]["slots"]
+                .as_array()
+                .map(|slots| slots.iter().filter_map(|slot| slot.as_u64()).collect())
+                .unwrap_or_else(Vec::new);
+            insert(
+                "vote_authority",
+                Value::from(info["voteAuthority"].as_str().unwrap_or("")),
+            );
+            insert("slot_count", Value::from(slots.len() as i32));
+            insert(
+                "last_slot",
+                Value::from(slots.iter().max().cloned().unwrap_or_default()),
+            );
+        }
+        _ => return None,
+    }
+    Some(entity)
 }
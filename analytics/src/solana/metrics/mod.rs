@@ -1,13 +1,18 @@
 pub mod instruction;
+pub mod program_event;
 pub mod raw_block;
 pub mod raw_log;
 pub mod raw_token_balance;
 pub mod raw_transaction;
 pub mod stat_block;
+pub mod token_holder;
+pub mod validator;
 
 pub use instruction::SolanaInstructionHandler;
+pub use program_event::SolanaProgramEventHandler;
 pub use raw_block::SolanaRawBlockHandler;
 pub use raw_log::SolanaRawLogHandler;
 pub use raw_token_balance::SolanaTokenBalanceHandler;
 pub use raw_transaction::SolanaRawTransactionHandler;
 pub use stat_block::SolanaStatBlockHandler;
+pub use token_holder::SolanaTokenHolderHandler;
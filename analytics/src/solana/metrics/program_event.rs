@@ -0,0 +1,111 @@
+use crate::config::EventConfig;
+use crate::postgres_queries::UpsertConflictFragment;
+use crate::relational::{Column, ColumnType, Table};
+use crate::solana::handler::SolanaHandler;
+use crate::storage_adapter::StorageAdapter;
+use crate::{create_columns, create_entity};
+use massbit::prelude::{Attribute, Entity, Value};
+use massbit_chain_solana::data_type::Pubkey;
+use massbit_common::prelude::anyhow::{self, Context};
+use massbit_common::NetworkType;
+use massbit_solana_sdk::event::EventDecoder;
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Decodes the events which the configured programs emit into the logs of
+/// their successful transactions into `solana_program_events`
+pub struct SolanaProgramEventHandler {
+    pub network: Option<NetworkType>,
+    pub storage_adapter: Arc<dyn StorageAdapter>,
+    pub decoders: Vec<EventDecoder>,
+}
+
+impl SolanaProgramEventHandler {
+    pub fn new(
+        network: &Option<NetworkType>,
+        storage_adapter: Arc<dyn StorageAdapter>,
+        events: &[EventConfig],
+    ) -> Result<Self, anyhow::Error> {
+        let decoders = events
+            .iter()
+            .map(|config| {
+                let program_id = Pubkey::from_str(config.program_id.as_str())
+                    .with_context(|| format!("Invalid program id {}", &config.program_id))?;
+                let idl = fs::read_to_string(&config.idl)
+                    .with_context(|| format!("Can not read IDL {}", &config.idl))?;
+                EventDecoder::from_idl(program_id, &idl)
+                    .with_context(|| format!("Invalid IDL {}", &config.idl))
+            })
+            .collect::<Result<Vec<EventDecoder>, anyhow::Error>>()?;
+        Ok(SolanaProgramEventHandler {
+            network: network.clone(),
+            storage_adapter,
+            decoders,
+        })
+    }
+}
+
+impl SolanaHandler for SolanaProgramEventHandler {
+    fn handle_block(
+        &self,
+        block_slot: u64,
+        block: Arc<EncodedConfirmedBlock>,
+    ) -> Result<(), anyhow::Error> {
+        if self.decoders.is_empty() {
+            return Ok(());
+        }
+        let block_time = block.block_time.unwrap_or_default() as u64;
+        let mut entities = Vec::default();
+        for tran in block.transactions.iter() {
+            let log_messages = match &tran.meta {
+                Some(meta) if meta.err.is_none() => match &meta.log_messages {
+                    Some(log_messages) => log_messages,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let tx_hash = tran
+                .transaction
+                .decode()
+                .as_ref()
+                .and_then(|decoded_tran| decoded_tran.signatures.get(0))
+                .map(|sig| format!("{:?}", sig))
+                .unwrap_or_default();
+            for decoder in self.decoders.iter() {
+                for event in decoder.decode_logs(log_messages) {
+                    entities.push(create_entity!(
+                        "block_slot" => block_slot,
+                        "block_time" => block_time,
+                        "tx_hash" => tx_hash.clone(),
+                        "log_index" => event.log_index as i32,
+                        "program_id" => event.program_id,
+                        "event_name" => event.name,
+                        "data" => event.data.to_string()
+                    ));
+                }
+            }
+        }
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let conflict_frag = UpsertConflictFragment::new("solana_program_events_uindex");
+        self.storage_adapter
+            .upsert(&create_table(), &entities, &Some(conflict_frag))
+    }
+}
+
+fn create_table<'a>() -> Table<'a> {
+    let columns = create_columns!(
+        "block_slot" => ColumnType::BigInt,
+        "block_time" => ColumnType::BigInt,
+        "tx_hash" => ColumnType::String,
+        "log_index" => ColumnType::Int,
+        "program_id" => ColumnType::String,
+        "event_name" => ColumnType::String,
+        "data" => ColumnType::String
+    );
+    Table::new("solana_program_events", columns)
+}
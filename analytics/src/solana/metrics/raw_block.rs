@@ -1,5 +1,7 @@
 use crate::relational::{Column, ColumnType, Table};
 use crate::solana::handler::SolanaHandler;
+use crate::solana::metrics::validator::{upsert_block_stats, LeaderSchedule};
+use crate::solana::SOLANA_URL;
 use crate::storage_adapter::StorageAdapter;
 use crate::{create_columns, create_entity};
 use massbit::prelude::{Attribute, Entity, Error, Value};
@@ -11,6 +13,7 @@ use std::sync::Arc;
 pub struct SolanaRawBlockHandler {
     pub network: Option<NetworkType>,
     pub storage_adapter: Arc<dyn StorageAdapter>,
+    leader_schedule: LeaderSchedule,
 }
 
 impl SolanaRawBlockHandler {
@@ -18,6 +21,7 @@ impl SolanaRawBlockHandler {
         SolanaRawBlockHandler {
             network: network.clone(),
             storage_adapter,
+            leader_schedule: LeaderSchedule::new(SOLANA_URL.as_str()),
         }
     }
 }
@@ -29,9 +33,20 @@ impl SolanaHandler for SolanaRawBlockHandler {
         block: Arc<EncodedConfirmedBlock>,
     ) -> Result<(), Error> {
         let table = create_table();
-        let entity = create_entity(block_slot, block);
+        let entity = create_entity(block_slot, block.clone());
         //println!("Block {:?} has reward {:?}", &block.block.block_height, &block.block.rewards);
-        self.storage_adapter.upsert(&table, &vec![entity], &None)
+        self.storage_adapter.upsert(&table, &vec![entity], &None)?;
+        let network = match &self.network {
+            None => "",
+            Some(val) => val.as_str(),
+        };
+        upsert_block_stats(
+            &self.storage_adapter,
+            network,
+            block_slot,
+            &block,
+            &self.leader_schedule,
+        )
     }
 }
 fn create_table<'a>() -> Table<'a> {
@@ -34,6 +34,7 @@ impl SolanaHandler for SolanaTokenBalanceHandler {
         block: Arc<EncodedConfirmedBlock>,
     ) -> Result<(), Error> {
         let table = create_table();
+        let block_time = block.block_time.unwrap_or_default() as u64;
         let entities = block
             .transactions
             .iter()
@@ -44,6 +45,7 @@ impl SolanaHandler for SolanaTokenBalanceHandler {
                         tran,
                         meta,
                         block_slot,
+                        block_time,
                         tran_order as i32,
                     ))
                 })
@@ -69,7 +71,8 @@ fn create_table<'a>() -> Table<'a> {
         "token_address" => ColumnType::String,
         "decimals" => ColumnType::Int,
         "pre_amount" => ColumnType::BigInt,
-        "post_amount" => ColumnType::BigInt
+        "post_amount" => ColumnType::BigInt,
+        "block_time" => ColumnType::BigInt
     );
     Table::new("solana_token_balances", columns)
 }
@@ -78,6 +81,7 @@ fn create_token_balances(
     tran: &EncodedTransactionWithStatusMeta,
     meta: &UiTransactionStatusMeta,
     block_slot: u64,
+    block_time: u64,
     tran_index: i32,
 ) -> Vec<Entity> {
     // let tx_hash = match tran.transaction.signatures.get(0) {
@@ -126,7 +130,8 @@ fn create_token_balances(
                     "token_address" => token_balance.mint.clone(),
                     "decimals" => token_balance.ui_token_amount.decimals as i32,
                     "pre_amount" => pre_amount,
-                    "post_amount" => post_amount
+                    "post_amount" => post_amount,
+                    "block_time" => block_time
                 )
             })
             .collect::<Vec<Entity>>()
@@ -105,7 +105,8 @@ fn create_trans_table<'a>() -> Table<'a> {
         "instructions" => ColumnType::TextArray,
         "reward" => ColumnType::BigInt,
         "fee" => ColumnType::BigInt,
-        "status" => ColumnType::String
+        "status" => ColumnType::String,
+        "block_time" => ColumnType::BigInt
     );
     Table::new("solana_transactions", columns)
 }
@@ -126,7 +127,7 @@ fn create_entity(
     tran_meta: &Option<UiTransactionStatusMeta>,
     ind: i32,
 ) -> Entity {
-    let _timestamp = match block.block_time {
+    let timestamp = match block.block_time {
         None => 0_u64,
         Some(val) => val as u64,
     };
@@ -169,7 +170,8 @@ fn create_entity(
         "instructions" => instructions,
         "status" => tran_status,
         "reward" => 0_u64,
-        "fee" => tran_fee
+        "fee" => tran_fee,
+        "block_time" => timestamp
     )
 }
 
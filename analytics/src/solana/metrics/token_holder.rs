@@ -0,0 +1,270 @@
+use crate::models::CommandData;
+use crate::postgres_queries::UpsertConflictFragment;
+use crate::relational::{Column, ColumnType, Table};
+use crate::solana::handler::SolanaHandler;
+use crate::solana::SOLANA_URL;
+use crate::storage_adapter::StorageAdapter;
+use crate::{create_columns, create_entity};
+use massbit::data::store::scalar::BigInt;
+use massbit::prelude::{Attribute, Entity, Value};
+
+use massbit_common::prelude::serde_json;
+use massbit_common::NetworkType;
+use solana_account_decoder::parse_token::{parse_token, TokenAccountType};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::MAX_MULTIPLE_ACCOUNTS;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::{EncodedConfirmedBlock, UiTransactionTokenBalance};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Maintains the current balance of each token account in
+/// `solana_token_holders`. Balances per owner, holder counts and their daily
+/// changes per mint are derived from it in the database, supplies are read
+/// from the mint accounts of the tokens whose balances changed.
+pub struct SolanaTokenHolderHandler {
+    pub network: Option<NetworkType>,
+    pub storage_adapter: Arc<dyn StorageAdapter>,
+    client: RpcClient,
+    /// Blocks are handled concurrently, but the trigger on
+    /// `solana_token_holders` updates owners in another order than the
+    /// holders are written, so concurrent writes deadlock. Holders are
+    /// written by one block at a time
+    writer: Mutex<()>,
+}
+
+impl SolanaTokenHolderHandler {
+    pub fn new(network: &Option<NetworkType>, storage_adapter: Arc<dyn StorageAdapter>) -> Self {
+        SolanaTokenHolderHandler {
+            network: network.clone(),
+            storage_adapter,
+            client: RpcClient::new(SOLANA_URL.clone()),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Supply and decimals of the mints, read from their accounts
+    fn get_supplies(
+        &self,
+        mints: &BTreeSet<String>,
+    ) -> Result<BTreeMap<String, (BigInt, i32)>, anyhow::Error> {
+        let mut supplies = BTreeMap::default();
+        let mints = mints.iter().collect::<Vec<&String>>();
+        for chunk in mints.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let pubkeys = chunk
+                .iter()
+                .map(|mint| Pubkey::from_str(mint))
+                .collect::<Result<Vec<Pubkey>, _>>()?;
+            let accounts = self.client.get_multiple_accounts(&pubkeys)?;
+            for (mint, account) in chunk.iter().zip(accounts) {
+                let account = match account {
+                    Some(account) => account,
+                    None => continue,
+                };
+                if let Ok(TokenAccountType::Mint(ui_mint)) = parse_token(&account.data, None) {
+                    let supply =
+                        BigInt::from_str(ui_mint.supply.as_str()).unwrap_or(BigInt::from(0_u64));
+                    supplies.insert(mint.to_string(), (supply, ui_mint.decimals as i32));
+                }
+            }
+        }
+        Ok(supplies)
+    }
+}
+
+impl SolanaHandler for SolanaTokenHolderHandler {
+    fn handle_block(
+        &self,
+        block_slot: u64,
+        block: Arc<EncodedConfirmedBlock>,
+    ) -> Result<(), anyhow::Error> {
+        let entities = create_holder_entities(block_slot, &block);
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let mints = entities
+            .iter()
+            .filter_map(|entity| match entity.get("mint") {
+                Some(Value::String(mint)) => Some(mint.clone()),
+                _ => None,
+            })
+            .collect::<BTreeSet<String>>();
+        // Read before the lock is taken, the supply is the one when the block is handled
+        let supplies = self.get_supplies(&mints)?;
+        let block_time = block.block_time.unwrap_or_default() as u64;
+        let date = block_time / SECONDS_PER_DAY * SECONDS_PER_DAY;
+        let supply_entities = supplies
+            .iter()
+            .map(|(mint, (supply, decimals))| {
+                create_entity!(
+                    "mint" => mint.clone(),
+                    "supply" => supply.clone(),
+                    "decimals" => *decimals,
+                    "supply_slot" => block_slot
+                )
+            })
+            .collect::<Vec<Entity>>();
+        let daily_supply_entities = supplies
+            .iter()
+            .map(|(mint, (supply, _))| {
+                create_entity!(
+                    "mint" => mint.clone(),
+                    "date" => date,
+                    "supply" => supply.clone(),
+                    "block_slot" => block_slot
+                )
+            })
+            .collect::<Vec<Entity>>();
+        let table = create_table();
+        // Blocks may be handled out of order, the balance of the latest slot wins
+        let mut conflict_frag =
+            UpsertConflictFragment::new("solana_token_holders_mint_account_uindex");
+        conflict_frag
+            .add_expression(
+                "balance",
+                "CASE WHEN EXCLUDED.block_slot >= t.block_slot THEN EXCLUDED.balance ELSE t.balance END",
+            )
+            .add_expression(
+                "owner",
+                "CASE WHEN EXCLUDED.block_slot >= t.block_slot THEN COALESCE(EXCLUDED.owner, t.owner) ELSE t.owner END",
+            )
+            .add_expression("decimals", "COALESCE(t.decimals, EXCLUDED.decimals)")
+            .add_expression("block_time", "GREATEST(t.block_time, EXCLUDED.block_time)")
+            .add_expression("block_slot", "GREATEST(t.block_slot, EXCLUDED.block_slot)");
+        let conflict_frag = Some(conflict_frag);
+        let supply_table = create_supply_table();
+        let mut supply_conflict_frag = UpsertConflictFragment::new("solana_token_supplies_pk");
+        supply_conflict_frag
+            .add_expression(
+                "supply",
+                "CASE WHEN EXCLUDED.supply_slot >= t.supply_slot THEN EXCLUDED.supply ELSE t.supply END",
+            )
+            .add_expression("decimals", "EXCLUDED.decimals")
+            .add_expression("supply_slot", "GREATEST(t.supply_slot, EXCLUDED.supply_slot)");
+        let supply_conflict_frag = Some(supply_conflict_frag);
+        let daily_supply_table = create_daily_supply_table();
+        let mut daily_supply_conflict_frag =
+            UpsertConflictFragment::new("solana_daily_token_supplies_pk");
+        daily_supply_conflict_frag
+            .add_expression(
+                "supply",
+                "CASE WHEN EXCLUDED.block_slot >= t.block_slot THEN EXCLUDED.supply ELSE t.supply END",
+            )
+            .add_expression("block_slot", "GREATEST(t.block_slot, EXCLUDED.block_slot)");
+        let daily_supply_conflict_frag = Some(daily_supply_conflict_frag);
+        let mut commands = vec![CommandData::new(&table, &entities, &conflict_frag)];
+        if !supply_entities.is_empty() {
+            commands.push(CommandData::new(
+                &supply_table,
+                &supply_entities,
+                &supply_conflict_frag,
+            ));
+            commands.push(CommandData::new(
+                &daily_supply_table,
+                &daily_supply_entities,
+                &daily_supply_conflict_frag,
+            ));
+        }
+        let _writer = self.writer.lock().unwrap();
+        self.storage_adapter.transact_upserts(commands)
+    }
+}
+
+fn create_table<'a>() -> Table<'a> {
+    let columns = create_columns!(
+        "mint" => ColumnType::String,
+        "account" => ColumnType::String,
+        "owner" => ColumnType::String,
+        "decimals" => ColumnType::Int,
+        "balance" => ColumnType::BigInt,
+        "block_slot" => ColumnType::BigInt,
+        "block_time" => ColumnType::BigInt
+    );
+    Table::new("solana_token_holders", columns)
+}
+
+fn create_supply_table<'a>() -> Table<'a> {
+    let columns = create_columns!(
+        "mint" => ColumnType::String,
+        "supply" => ColumnType::BigInt,
+        "decimals" => ColumnType::Int,
+        "supply_slot" => ColumnType::BigInt
+    );
+    Table::new("solana_token_supplies", columns)
+}
+
+fn create_daily_supply_table<'a>() -> Table<'a> {
+    let columns = create_columns!(
+        "mint" => ColumnType::String,
+        "date" => ColumnType::BigInt,
+        "supply" => ColumnType::BigInt,
+        "block_slot" => ColumnType::BigInt
+    );
+    Table::new("solana_daily_token_supplies", columns)
+}
+
+/// The balance of every token account at the end of the block. Accounts
+/// which only have a pre balance were closed by the transaction
+fn create_holder_entities(block_slot: u64, block: &EncodedConfirmedBlock) -> Vec<Entity> {
+    let block_time = block.block_time.unwrap_or_default() as u64;
+    // Sorted so that concurrent blocks lock rows in the same order
+    let mut balances: BTreeMap<(String, String), Entity> = BTreeMap::default();
+    for tran in block.transactions.iter() {
+        let (meta, decoded_tran) = match (&tran.meta, tran.transaction.decode()) {
+            (Some(meta), Some(decoded_tran)) => (meta, decoded_tran),
+            _ => continue,
+        };
+        let account_keys = &decoded_tran.message.account_keys;
+        let post_balances = meta.post_token_balances.clone().unwrap_or_default();
+        let closed_balances = meta
+            .pre_token_balances
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pre| {
+                post_balances
+                    .iter()
+                    .all(|post| post.account_index != pre.account_index)
+            })
+            .map(|mut pre| {
+                pre.ui_token_amount.amount = String::from("0");
+                pre
+            })
+            .collect::<Vec<UiTransactionTokenBalance>>();
+        for token_balance in post_balances.iter().chain(closed_balances.iter()) {
+            let account = match account_keys.get(token_balance.account_index as usize) {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+            let balance = BigInt::from_str(token_balance.ui_token_amount.amount.as_str())
+                .unwrap_or(BigInt::from(0_u64));
+            let entity = create_entity!(
+                "mint" => token_balance.mint.clone(),
+                "account" => account.clone(),
+                "owner" => token_owner(token_balance),
+                "decimals" => token_balance.ui_token_amount.decimals as i32,
+                "balance" => balance,
+                "block_slot" => block_slot,
+                "block_time" => block_time
+            );
+            balances.insert((token_balance.mint.clone(), account), entity);
+        }
+    }
+    balances.into_iter().map(|(_, entity)| entity).collect()
+}
+
+/// The owner of the token account if the node reports it. Older nodes do
+/// not have the field, so it is read from the serialized balance
+fn token_owner(token_balance: &UiTransactionTokenBalance) -> Option<String> {
+    serde_json::to_value(token_balance)
+        .ok()
+        .and_then(|value| {
+            value
+                .get("owner")
+                .and_then(|owner| owner.as_str().map(String::from))
+        })
+        .filter(|owner| !owner.is_empty())
+}
@@ -0,0 +1,297 @@
+//! Validator metrics: votes of each vote account and its validator identity
+//! from the parsed vote instructions, and leader slots of each validator
+//! identity from block rewards and the leader schedule. Both are stored per
+//! slot, so that a block which is processed again replaces its rows; the
+//! daily and epoch summaries are views over them. The leader schedule and
+//! the vote accounts are fetched by a thread once per epoch, handlers only
+//! read them.
+use crate::postgres_queries::UpsertConflictFragment;
+use crate::relational::{Column, ColumnType, Table};
+use crate::storage_adapter::StorageAdapter;
+use crate::{create_columns, create_entity};
+use massbit::prelude::{Attribute, Entity, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_transaction_status::{EncodedConfirmedBlock, RewardType};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u64 = 86400;
+/// How often the thread of `watch_epochs` checks for a new epoch
+const EPOCH_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Epoch schedule of the cluster at `url`, mainnet uses epochs without
+/// warmup. Called when the handlers are created, not while they handle blocks
+pub fn get_epoch_schedule(url: &str) -> EpochSchedule {
+    RpcClient::new(url.to_string())
+        .get_epoch_schedule()
+        .unwrap_or_else(|err| {
+            log::warn!("Can not get epoch schedule, use the default one: {:?}", err);
+            EpochSchedule::without_warmup()
+        })
+}
+
+/// Call `refresh` from a thread whenever the cluster at `url` enters a new
+/// epoch. A failed refresh is retried after `EPOCH_POLL_INTERVAL`
+fn watch_epochs<F>(url: &str, mut refresh: F)
+where
+    F: FnMut(&RpcClient, u64) -> Result<(), anyhow::Error> + Send + 'static,
+{
+    let client = RpcClient::new(url.to_string());
+    thread::spawn(move || {
+        let mut refreshed = None;
+        loop {
+            match client.get_epoch_info() {
+                Ok(info) if refreshed != Some(info.epoch) => match refresh(&client, info.epoch) {
+                    Ok(_) => refreshed = Some(info.epoch),
+                    Err(err) => log::warn!("Can not refresh epoch {}: {:?}", info.epoch, err),
+                },
+                Ok(_) => {}
+                Err(err) => log::warn!("Can not get epoch info: {:?}", err),
+            }
+            thread::sleep(EPOCH_POLL_INTERVAL);
+        }
+    });
+}
+
+/// Leader of each slot of the current and the next epoch, fetched once per
+/// epoch. The cluster does not keep the schedules of older epochs, so their
+/// skipped slots are not attributed
+pub struct LeaderSchedule {
+    epoch_schedule: EpochSchedule,
+    epochs: Arc<RwLock<HashMap<u64, Arc<HashMap<u64, String>>>>>,
+}
+
+impl LeaderSchedule {
+    pub fn new(url: &str) -> Self {
+        let epoch_schedule = get_epoch_schedule(url);
+        let epochs: Arc<RwLock<HashMap<u64, Arc<HashMap<u64, String>>>>> = Arc::default();
+        let cache = epochs.clone();
+        let schedule = epoch_schedule.clone();
+        watch_epochs(url, move |client, current| {
+            for epoch in current..=current + 1 {
+                if cache.read().unwrap().contains_key(&epoch) {
+                    continue;
+                }
+                let leaders = fetch_epoch_leaders(client, &schedule, epoch)?;
+                let mut epochs = cache.write().unwrap();
+                epochs.retain(|cached, _| cached + 1 >= current);
+                epochs.insert(epoch, Arc::new(leaders));
+            }
+            Ok(())
+        });
+        LeaderSchedule {
+            epoch_schedule,
+            epochs,
+        }
+    }
+
+    pub fn epoch_schedule(&self) -> &EpochSchedule {
+        &self.epoch_schedule
+    }
+
+    pub fn leader(&self, slot: u64) -> Option<String> {
+        let epoch = self.epoch_schedule.get_epoch(slot);
+        self.epoch_leaders(epoch)
+            .and_then(|leaders| leaders.get(&slot).cloned())
+    }
+
+    fn epoch_leaders(&self, epoch: u64) -> Option<Arc<HashMap<u64, String>>> {
+        self.epochs.read().unwrap().get(&epoch).cloned()
+    }
+}
+
+fn fetch_epoch_leaders(
+    client: &RpcClient,
+    epoch_schedule: &EpochSchedule,
+    epoch: u64,
+) -> Result<HashMap<u64, String>, anyhow::Error> {
+    let first_slot = epoch_schedule.get_first_slot_in_epoch(epoch);
+    let schedule = client
+        .get_leader_schedule(Some(first_slot))?
+        .ok_or_else(|| anyhow::anyhow!("No leader schedule for epoch {}", epoch))?;
+    Ok(schedule
+        .into_iter()
+        .flat_map(|(node, indexes)| {
+            indexes
+                .into_iter()
+                .map(move |index| (first_slot + index as u64, node.clone()))
+        })
+        .collect())
+}
+
+/// Validator identity of each vote account, fetched once per epoch. Vote
+/// accounts created since then have no identity until the next epoch
+pub struct VoteAccounts {
+    nodes: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl VoteAccounts {
+    pub fn new(url: &str) -> Self {
+        let nodes: Arc<RwLock<HashMap<String, String>>> = Arc::default();
+        let cache = nodes.clone();
+        watch_epochs(url, move |client, _| {
+            let status = client.get_vote_accounts()?;
+            let mut nodes = cache.write().unwrap();
+            for account in status.current.into_iter().chain(status.delinquent) {
+                nodes.insert(account.vote_pubkey, account.node_pubkey);
+            }
+            Ok(())
+        });
+        VoteAccounts { nodes }
+    }
+
+    pub fn node(&self, vote_account: &str) -> Option<String> {
+        self.nodes.read().unwrap().get(vote_account).cloned()
+    }
+}
+
+/// Upsert the leader slots of `block`: the slot of the block, produced by
+/// the validator which got its fee reward, and the slots between the parent
+/// slot and `block_slot`, skipped by their scheduled leaders
+pub fn upsert_block_stats(
+    storage_adapter: &Arc<dyn StorageAdapter>,
+    network: &str,
+    block_slot: u64,
+    block: &EncodedConfirmedBlock,
+    leader_schedule: &LeaderSchedule,
+) -> Result<(), anyhow::Error> {
+    let block_time = block.block_time.unwrap_or_default() as u64;
+    let date = block_time / SECONDS_PER_DAY * SECONDS_PER_DAY;
+    let epoch_schedule = leader_schedule.epoch_schedule();
+    let mut entities = vec![];
+    let producer = block
+        .rewards
+        .iter()
+        .find(|reward| Some(RewardType::Fee) == reward.reward_type);
+    if let Some(reward) = producer {
+        entities.push(create_entity!(
+            "network" => network,
+            "slot" => block_slot,
+            "block_slot" => block_slot,
+            "date" => date,
+            "epoch" => epoch_schedule.get_epoch(block_slot),
+            "node" => reward.pubkey.clone(),
+            "produced" => true,
+            "reward" => reward.lamports as u64
+        ));
+    }
+    let first_skipped = if block.parent_slot > 0 {
+        block.parent_slot + 1
+    } else {
+        block_slot
+    };
+    for slot in first_skipped..block_slot {
+        if let Some(node) = leader_schedule.leader(slot) {
+            entities.push(create_entity!(
+                "network" => network,
+                "slot" => slot,
+                "block_slot" => block_slot,
+                "date" => date,
+                "epoch" => epoch_schedule.get_epoch(slot),
+                "node" => node,
+                "produced" => false,
+                "reward" => 0_u64
+            ));
+        }
+    }
+    if entities.is_empty() {
+        return Ok(());
+    }
+    let table = Table::new(
+        "solana_validator_slots",
+        create_columns!(
+            "network" => ColumnType::String,
+            "slot" => ColumnType::BigInt,
+            "block_slot" => ColumnType::BigInt,
+            "date" => ColumnType::BigInt,
+            "epoch" => ColumnType::BigInt,
+            "node" => ColumnType::String,
+            "produced" => ColumnType::Boolean,
+            "reward" => ColumnType::BigInt
+        ),
+    );
+    let mut conflict_frag = UpsertConflictFragment::new("solana_validator_slots_uindex");
+    conflict_frag
+        .add_expression("node", "EXCLUDED.node")
+        .add_expression("produced", "EXCLUDED.produced")
+        .add_expression("reward", "EXCLUDED.reward");
+    storage_adapter.upsert(&table, &entities, &Some(conflict_frag))
+}
+
+/// Upsert the votes of each vote account in the block at `block_slot`.
+/// `votes` are the entities of its vote instructions
+pub fn upsert_vote_stats(
+    storage_adapter: &Arc<dyn StorageAdapter>,
+    network: &str,
+    block_slot: u64,
+    block_time: u64,
+    epoch_schedule: &EpochSchedule,
+    vote_accounts: &VoteAccounts,
+    votes: &[Entity],
+) -> Result<(), anyhow::Error> {
+    if votes.is_empty() {
+        return Ok(());
+    }
+    let date = block_time / SECONDS_PER_DAY * SECONDS_PER_DAY;
+    let epoch = epoch_schedule.get_epoch(block_slot);
+    // votes, number of slots voted on and last voted slot by vote account
+    let mut accounts: HashMap<String, (u64, u64, u64)> = HashMap::default();
+    for vote in votes {
+        let vote_account = match vote.get("vote_account") {
+            Some(Value::String(vote_account)) => vote_account.clone(),
+            _ => continue,
+        };
+        let slot_count = match vote.get("slot_count") {
+            Some(Value::Int(count)) => *count as u64,
+            _ => 0,
+        };
+        let last_slot = match vote.get("last_slot") {
+            Some(Value::BigInt(slot)) => slot.to_u64(),
+            _ => 0,
+        };
+        let stats = accounts.entry(vote_account).or_default();
+        stats.0 += 1;
+        stats.1 += slot_count;
+        stats.2 = stats.2.max(last_slot);
+    }
+    let entities = accounts
+        .into_iter()
+        .map(|(vote_account, (votes, vote_slot_count, last_vote_slot))| {
+            create_entity!(
+                "network" => network,
+                "block_slot" => block_slot,
+                "date" => date,
+                "epoch" => epoch,
+                "node" => vote_accounts.node(&vote_account),
+                "vote_account" => vote_account,
+                "votes" => votes,
+                "vote_slot_count" => vote_slot_count,
+                "last_vote_slot" => last_vote_slot
+            )
+        })
+        .collect::<Vec<Entity>>();
+    let table = Table::new(
+        "solana_validator_block_votes",
+        create_columns!(
+            "network" => ColumnType::String,
+            "block_slot" => ColumnType::BigInt,
+            "date" => ColumnType::BigInt,
+            "epoch" => ColumnType::BigInt,
+            "node" => ColumnType::String,
+            "vote_account" => ColumnType::String,
+            "votes" => ColumnType::BigInt,
+            "vote_slot_count" => ColumnType::BigInt,
+            "last_vote_slot" => ColumnType::BigInt
+        ),
+    );
+    let mut conflict_frag = UpsertConflictFragment::new("solana_validator_block_votes_uindex");
+    conflict_frag
+        .add_expression("node", "COALESCE(EXCLUDED.node, t.node)")
+        .add_expression("votes", "EXCLUDED.votes")
+        .add_expression("vote_slot_count", "EXCLUDED.vote_slot_count")
+        .add_expression("last_vote_slot", "EXCLUDED.last_vote_slot");
+    storage_adapter.upsert(&table, &entities, &Some(conflict_frag))
+}
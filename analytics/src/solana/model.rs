@@ -6,6 +6,18 @@ pub struct EncodedConfirmedBlockWithSlot {
     pub block: EncodedConfirmedBlock,
 }
 
+/// A slot with a block, as sent by the block readers to the processor
+#[derive(Debug)]
+pub enum SolanaSlot {
+    Block(EncodedConfirmedBlockWithSlot),
+    /// The block could not be fetched after retrying. It is recorded as
+    /// failed for every handler, so that the repair fetches it again
+    FetchFailed {
+        block_slot: u64,
+        error: String,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub struct Account {
     account: String,
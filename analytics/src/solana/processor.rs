@@ -1,65 +1,164 @@
 use super::CHAIN;
 
+use crate::checkpoint::{
+    get_failed_blocks, remove_failed_block, save_failed_block, save_watermark, BlockWatermark,
+    Checkpoint,
+};
+use crate::config::NetworkConfig;
 use crate::postgres_adapter::PostgresAdapter;
-use crate::schema::network_states;
-use crate::solana::handler::create_solana_handler_manager;
-use crate::solana::model::EncodedConfirmedBlockWithSlot;
+use crate::solana::handler::{
+    create_solana_handler_manager, describe_failures, SolanaHandlerManager,
+};
+use crate::solana::model::SolanaSlot;
+use crate::solana::reader::fetch_block;
+use crate::solana::SOLANA_URL;
 use core::ops::Deref;
 
-
-use massbit_common::prelude::diesel::pg::upsert::excluded;
-use massbit_common::prelude::diesel::{ExpressionMethods, RunQueryDsl};
+use massbit_common::prelude::anyhow;
+use massbit_common::prelude::tokio::time::{sleep, Duration};
 use massbit_common::NetworkType;
 
-use std::sync::{Arc, Mutex};
+use solana_client::rpc_client::RpcClient;
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::Mutex;
 
+/// How often the handlers that failed for a block are retried before the
+/// block is recorded as failed
+const MAX_BLOCK_RETRIES: u64 = 3;
+const BLOCK_RETRY_DELAY_MS: u64 = 1000;
+/// Number of slots between saved checkpoints. The buffered rows of the
+/// handlers are written before each checkpoint
+const CHECKPOINT_BATCH_SIZE: u64 = 100;
 
 pub async fn process_solana_channel(
-    rx: &mut Receiver<EncodedConfirmedBlockWithSlot>,
+    rx: &mut Receiver<SolanaSlot>,
     storage_adapter: Arc<PostgresAdapter>,
     network_name: &NetworkType,
     block: &Option<u64>,
+    config: &NetworkConfig,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
     let network = Some(network_name.clone());
     let handler_manager = Arc::new(create_solana_handler_manager(
         &network,
         storage_adapter.clone(),
-    ));
-    let last_block = Arc::new(Mutex::new(block.unwrap_or_default()));
-    while let Some(data) = rx.recv().await {
-        let block_slot = data.block_slot;
+        config,
+    )?);
+    // Used to tell blocks which never reached the processor apart from
+    // skipped slots
+    let client = Arc::new(RpcClient::new(SOLANA_URL.clone()));
+    // Every slot before the first requested one counts as processed. The
+    // lock is held while a checkpoint is saved, so that checkpoints are
+    // saved in order
+    let watermark = Arc::new(Mutex::new(BlockWatermark::new(
+        block.and_then(|block| block.checked_sub(1)),
+    )));
+    while let Some(slot) = rx.recv().await {
+        let (block_slot, block) = match slot {
+            SolanaSlot::Block(data) => (data.block_slot, Ok(data.block)),
+            SolanaSlot::FetchFailed { block_slot, error } => (block_slot, Err(error)),
+        };
+        watermark.lock().await.start(block_slot);
         let handler = handler_manager.clone();
         let storage_adapter = storage_adapter.clone();
-        let network_name = network.clone();
-        let last_block = Arc::clone(&last_block);
-        let transaction_counter = data.block.transactions.len();
+        let network_name = network_name.clone();
+        let client = client.clone();
+        let watermark = Arc::clone(&watermark);
         tokio::spawn(async move {
             let start = Instant::now();
-            match handler.handle_block(block_slot, Arc::new(data.block)) {
-                Ok(_) => {}
-                Err(err) => log::error!("{:?}", &err),
+            let mut attempt = 0;
+            let (failures, transaction_counter) = match block {
+                Ok(block) => {
+                    let transaction_counter = block.transactions.len();
+                    let block = Arc::new(block);
+                    let mut failures = handler.handle_block_with(block_slot, block.clone(), None);
+                    while !failures.is_empty() && attempt < MAX_BLOCK_RETRIES {
+                        attempt += 1;
+                        sleep(Duration::from_millis(BLOCK_RETRY_DELAY_MS * attempt)).await;
+                        let failed_handlers: Vec<String> =
+                            failures.iter().map(|(name, _)| name.clone()).collect();
+                        failures = handler.handle_block_with(
+                            block_slot,
+                            block.clone(),
+                            Some(&failed_handlers),
+                        );
+                    }
+                    (failures, transaction_counter)
+                }
+                // The reader already retried the fetch
+                Err(error) => {
+                    let failures = handler
+                        .handler_names()
+                        .into_iter()
+                        .map(|name| (name, anyhow::anyhow!("{}", error)))
+                        .collect();
+                    (failures, 0)
+                }
+            };
+            let mut recorded = Ok(());
+            if !failures.is_empty() {
+                let failed_handlers: Vec<String> =
+                    failures.iter().map(|(name, _)| name.clone()).collect();
+                recorded = storage_adapter
+                    .get_connection()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|conn| {
+                        save_failed_block(
+                            conn.deref(),
+                            CHAIN.as_str(),
+                            &network_name,
+                            block_slot,
+                            &failed_handlers,
+                            &describe_failures(&failures),
+                        )
+                        .map(|_| ())
+                        .map_err(anyhow::Error::from)
+                    });
+                match &recorded {
+                    Ok(_) => log::warn!(
+                        "Block slot {} is recorded as failed after {} retries",
+                        block_slot,
+                        attempt
+                    ),
+                    Err(err) => log::error!(
+                        "Can not record failed block slot {} with failures {}: {:?}",
+                        block_slot,
+                        describe_failures(&failures),
+                        err
+                    ),
+                }
+            }
+
+            let mut watermark = watermark.lock().await;
+            match recorded {
+                Ok(_) => watermark.finish(block_slot),
+                // The block is recorded again by the next checkpoint, which
+                // finds it missing
+                Err(_) => watermark.abandon(block_slot),
             };
-            let mut last_block = last_block.lock().unwrap();
-            if *last_block < block_slot {
-                *last_block = block_slot;
-                if let Ok(conn) = storage_adapter.get_connection() {
-                    match diesel::insert_into(network_states::table)
-                        .values((
-                            network_states::chain.eq(CHAIN.clone()),
-                            network_states::network.eq(network_name.unwrap_or_default()),
-                            network_states::got_block.eq(block_slot as i64),
-                        ))
-                        .on_conflict((network_states::chain, network_states::network))
-                        .do_update()
-                        .set(network_states::got_block.eq(excluded(network_states::got_block)))
-                        .execute(conn.deref())
-                    {
-                        Ok(_) => {}
-                        Err(err) => log::error!("{:?}", &err),
-                    };
-                };
+            if let Some(checkpoint) = watermark.checkpoint_with_finished(CHECKPOINT_BATCH_SIZE) {
+                // Saving writes to the database and calls the RPC node,
+                // which would block the workers of the runtime
+                let saved = tokio::task::spawn_blocking(move || {
+                    let saved = save_checkpoint(
+                        &handler,
+                        &storage_adapter,
+                        &client,
+                        &network_name,
+                        &checkpoint,
+                    );
+                    (checkpoint, saved)
+                })
+                .await;
+                match saved {
+                    Ok((_, Ok(()))) => {}
+                    Ok((checkpoint, Err(err))) => {
+                        log::error!("Can not save checkpoint {}: {:?}", checkpoint.block, &err);
+                        watermark.undo_checkpoint(checkpoint);
+                    }
+                    Err(err) => log::error!("Can not save checkpoint: {:?}", &err),
+                }
             }
 
             log::info!(
@@ -72,3 +171,107 @@ pub async fn process_solana_channel(
     }
     Ok(())
 }
+
+/// Save `checkpoint` once the buffered rows of the handlers are written.
+/// Blocks which the node has in the range of the checkpoint but which were
+/// not finished, unlike skipped slots, were dropped on their way and are
+/// recorded as failed first, so that the repair processes them
+fn save_checkpoint(
+    handler: &SolanaHandlerManager,
+    storage_adapter: &PostgresAdapter,
+    client: &RpcClient,
+    network_name: &str,
+    checkpoint: &Checkpoint,
+) -> Result<(), anyhow::Error> {
+    let conn = storage_adapter.get_connection()?;
+    let first = match checkpoint.previous {
+        Some(previous) => previous + 1,
+        None => checkpoint
+            .finished
+            .iter()
+            .next()
+            .copied()
+            .unwrap_or(checkpoint.block),
+    };
+    let dropped: Vec<u64> = client
+        .get_blocks(first, Some(checkpoint.block))?
+        .into_iter()
+        .filter(|block_slot| !checkpoint.finished.contains(block_slot))
+        .collect();
+    if !dropped.is_empty() {
+        log::warn!(
+            "Block slots {:?} were not processed, they are recorded as failed",
+            &dropped
+        );
+        let handlers = handler.handler_names();
+        for block_slot in dropped {
+            save_failed_block(
+                conn.deref(),
+                CHAIN.as_str(),
+                network_name,
+                block_slot,
+                &handlers,
+                "The block was not processed",
+            )?;
+        }
+    }
+    handler.flush()?;
+    save_watermark(conn.deref(), CHAIN.as_str(), network_name, checkpoint.block)?;
+    Ok(())
+}
+
+/// Process the blocks recorded in `network_failed_blocks` again, running
+/// only the handlers that failed for each of them. Repaired blocks are
+/// removed from the table, the others stay with their latest error
+pub fn repair_failed_blocks(
+    storage_adapter: Arc<PostgresAdapter>,
+    network_name: &NetworkType,
+    client: &RpcClient,
+    config: &NetworkConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let network = Some(network_name.clone());
+    let handler_manager = create_solana_handler_manager(&network, storage_adapter.clone(), config)?;
+    let conn = storage_adapter.get_connection()?;
+    let failed_blocks = get_failed_blocks(conn.deref(), CHAIN.as_str(), network_name)?;
+    log::info!("Start repairing {} failed blocks", failed_blocks.len());
+    for failed_block in failed_blocks {
+        let block_slot = failed_block.block_number as u64;
+        let failures = match fetch_block(client, block_slot) {
+            Ok(data) => handler_manager.handle_block_with(
+                block_slot,
+                Arc::new(data.block),
+                Some(&failed_block.handlers),
+            ),
+            Err(err) => failed_block
+                .handlers
+                .iter()
+                .map(|name| (name.clone(), anyhow::anyhow!("{}", err)))
+                .collect(),
+        };
+        if failures.is_empty() {
+            // The rows of the block are written before it stops being
+            // recorded as failed
+            handler_manager.flush()?;
+            remove_failed_block(conn.deref(), CHAIN.as_str(), network_name, block_slot)?;
+            log::info!("Block slot {} is repaired", block_slot);
+        } else {
+            let failed_handlers: Vec<String> =
+                failures.iter().map(|(name, _)| name.clone()).collect();
+            save_failed_block(
+                conn.deref(),
+                CHAIN.as_str(),
+                network_name,
+                block_slot,
+                &failed_handlers,
+                &describe_failures(&failures),
+            )?;
+            log::warn!(
+                "Block slot {} is still failing after {} attempts",
+                block_slot,
+                failed_block.attempts + 1
+            );
+        }
+    }
+    handler_manager.flush()?;
+    Ok(())
+}
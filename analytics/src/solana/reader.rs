@@ -1,4 +1,4 @@
-use crate::solana::model::EncodedConfirmedBlockWithSlot;
+use crate::solana::model::{EncodedConfirmedBlockWithSlot, SolanaSlot};
 use log::{debug, info, warn};
 
 use massbit::prelude::tokio::sync::{OwnedSemaphorePermit, Semaphore};
@@ -8,6 +8,7 @@ use massbit_common::prelude::tokio::time::{timeout, Duration};
 use massbit_common::NetworkType;
 use solana_client::rpc_client::RpcClient;
 use solana_transaction_status::UiTransactionEncoding;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::{sync::Arc, time::Instant};
 use tokio::sync::mpsc;
@@ -18,15 +19,20 @@ const RPC_BLOCK_ENCODING: UiTransactionEncoding = UiTransactionEncoding::Base64;
 const GET_BLOCK_TIMEOUT_SEC: u64 = 60;
 const BLOCK_BATCH_SIZE: u64 = 10;
 const GET_NEW_SLOT_DELAY_MS: u64 = 500;
+/// How often a block is fetched again before it is recorded as failed
+const MAX_GET_BLOCK_RETRIES: u64 = 3;
+const GET_BLOCK_RETRY_DELAY_MS: u64 = 1000;
 
 pub async fn loop_get_block(
-    chan: mpsc::Sender<EncodedConfirmedBlockWithSlot>,
+    chan: mpsc::Sender<SolanaSlot>,
     start_block: &Option<u64>,
     _network: &NetworkType,
     client: &Arc<RpcClient>,
 ) -> Result<(), Box<dyn Error>> {
     info!("Start get block Solana from: {:?}", start_block);
-    let mut last_indexed_slot: Option<u64> = start_block.map(|start_block| start_block + 1);
+    // Next slot to fetch; `start_block` itself must not be skipped or it
+    // would be a gap behind the checkpoint
+    let mut last_indexed_slot: Option<u64> = *start_block;
     //fix_one_thread_not_receive(&chan);
     let sem = Arc::new(Semaphore::new(2 * BLOCK_BATCH_SIZE as usize));
     loop {
@@ -36,11 +42,11 @@ pub async fn loop_get_block(
         match client.get_slot() {
             Ok(new_slot) => {
                 // Root is finalized block in Solana
-                let current_root = new_slot - BLOCK_AVAILABLE_MARGIN;
+                let current_root = new_slot.saturating_sub(BLOCK_AVAILABLE_MARGIN);
                 //info!("Root: {:?}",new_info.root);
                 match last_indexed_slot {
                     Some(value_last_indexed_slot) => {
-                        if current_root == value_last_indexed_slot {
+                        if current_root <= value_last_indexed_slot {
                             sleep(Duration::from_millis(GET_NEW_SLOT_DELAY_MS)).await;
                             continue;
                         }
@@ -49,43 +55,53 @@ pub async fn loop_get_block(
                             current_root,
                             current_root - value_last_indexed_slot
                         );
-                        let mut tasks = vec![];
                         let number_get_slot =
                             (current_root - value_last_indexed_slot).min(BLOCK_BATCH_SIZE);
-                        let block_range =
-                            value_last_indexed_slot..(value_last_indexed_slot + number_get_slot);
-                        for block_slot in block_range {
-                            let new_client = client.clone();
-                            let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
-                            tasks.push(tokio::spawn(async move {
-                                let res = timeout(
-                                    Duration::from_secs(GET_BLOCK_TIMEOUT_SEC),
-                                    get_block(new_client, permit, block_slot),
-                                )
-                                .await;
-                                if res.is_err() {
-                                    warn!("get_block timed out at block height {}", &block_slot);
-                                };
-                                info!(
-                                    "Finish tokio::spawn for getting block height: {:?}",
-                                    &block_slot
-                                );
-                                res.unwrap()
-                            }));
+                        let last_slot = value_last_indexed_slot + number_get_slot - 1;
+                        // Slots which are not listed were skipped by their leader
+                        let block_slots =
+                            match client.get_blocks(value_last_indexed_slot, Some(last_slot)) {
+                                Ok(block_slots) => block_slots,
+                                Err(err) => {
+                                    warn!(
+                                        "Cannot get blocks {}..={}: {:?}",
+                                        value_last_indexed_slot, last_slot, err
+                                    );
+                                    sleep(Duration::from_millis(GET_NEW_SLOT_DELAY_MS)).await;
+                                    continue;
+                                }
+                            };
+                        let mut blocks = get_blocks(client, &sem, block_slots).await;
+                        // Blocks are sent in order, so a block which can not be
+                        // fetched holds back the later ones while it is retried
+                        let mut attempt = 0;
+                        loop {
+                            let failed_slots: Vec<u64> = blocks
+                                .iter()
+                                .filter(|(_, block)| block.is_err())
+                                .map(|(block_slot, _)| *block_slot)
+                                .collect();
+                            if failed_slots.is_empty() || attempt >= MAX_GET_BLOCK_RETRIES {
+                                break;
+                            }
+                            attempt += 1;
+                            warn!("Retry getting blocks {:?}", &failed_slots);
+                            sleep(Duration::from_millis(GET_BLOCK_RETRY_DELAY_MS * attempt)).await;
+                            blocks.extend(get_blocks(client, &sem, failed_slots).await);
                         }
-                        let blocks: Vec<Result<_, _>> = futures03::future::join_all(tasks).await;
-                        let mut blocks: Vec<EncodedConfirmedBlockWithSlot> = blocks
-                            .into_iter()
-                            .filter_map(|res_block| res_block.ok().and_then(|res| res.ok()))
-                            .collect();
-                        blocks.sort_by(|a, b| a.block_slot.cmp(&b.block_slot));
 
-                        for block in blocks.into_iter() {
-                            let block_slot = block.block_slot;
+                        for (block_slot, block) in blocks.into_iter() {
+                            let slot = match block {
+                                Ok(block) => SolanaSlot::Block(block),
+                                Err(error) => {
+                                    warn!("Cannot get block {}: {}", &block_slot, &error);
+                                    SolanaSlot::FetchFailed { block_slot, error }
+                                }
+                            };
                             debug!("gRPC sending block {}", &block_slot);
                             if !chan.is_closed() {
                                 let start = Instant::now();
-                                let send_res = chan.send(block).await;
+                                let send_res = chan.send(slot).await;
                                 if send_res.is_ok() {
                                     info!(
                                         "gRPC successfully sending block {} in {:?}",
@@ -99,8 +115,7 @@ pub async fn loop_get_block(
                                 return Err("Stream is closed!".into());
                             }
                         }
-                        last_indexed_slot = last_indexed_slot
-                            .map(|last_indexed_slot| last_indexed_slot + number_get_slot);
+                        last_indexed_slot = Some(last_slot + 1);
                     }
                     _ => last_indexed_slot = Some(current_root),
                 };
@@ -115,12 +130,63 @@ pub async fn loop_get_block(
     Ok(())
 }
 
+/// Get the blocks of `block_slots` in parallel. A block which can not be
+/// fetched in time is returned with its error
+async fn get_blocks(
+    client: &Arc<RpcClient>,
+    sem: &Arc<Semaphore>,
+    block_slots: Vec<u64>,
+) -> BTreeMap<u64, Result<EncodedConfirmedBlockWithSlot, String>> {
+    let mut tasks = vec![];
+    for &block_slot in block_slots.iter() {
+        let new_client = client.clone();
+        let permit = Arc::clone(sem).acquire_owned().await.unwrap();
+        tasks.push(tokio::spawn(async move {
+            let res = timeout(
+                Duration::from_secs(GET_BLOCK_TIMEOUT_SEC),
+                get_block(new_client, permit, block_slot),
+            )
+            .await;
+            info!(
+                "Finish tokio::spawn for getting block height: {:?}",
+                &block_slot
+            );
+            match res {
+                Ok(block) => block.map_err(|err| err.to_string()),
+                Err(_) => {
+                    warn!("get_block timed out at block height {}", &block_slot);
+                    Err(format!(
+                        "get_block timed out after {}s",
+                        GET_BLOCK_TIMEOUT_SEC
+                    ))
+                }
+            }
+        }));
+    }
+    let blocks = futures03::future::join_all(tasks).await;
+    block_slots
+        .into_iter()
+        .zip(blocks)
+        .map(|(block_slot, block)| {
+            let block = block.unwrap_or_else(|err| Err(err.to_string()));
+            (block_slot, block)
+        })
+        .collect()
+}
+
 async fn get_block(
     client: Arc<RpcClient>,
     permit: OwnedSemaphorePermit,
     block_slot: u64,
 ) -> Result<EncodedConfirmedBlockWithSlot, Box<dyn Error + Send + Sync + 'static>> {
     let _permit = permit;
+    fetch_block(&client, block_slot)
+}
+
+pub fn fetch_block(
+    client: &RpcClient,
+    block_slot: u64,
+) -> Result<EncodedConfirmedBlockWithSlot, Box<dyn Error + Send + Sync + 'static>> {
     //info!("Starting RPC get Block {}", block_slot);
     let now = Instant::now();
     let block = client.get_block_with_encoding(block_slot, RPC_BLOCK_ENCODING);
@@ -133,12 +199,12 @@ async fn get_block(
             );
             Ok(EncodedConfirmedBlockWithSlot { block_slot, block })
         }
-        _ => {
+        Err(err) => {
             info!(
                 "Cannot get RPC get Block: {:?}, Error:{:?}, time: {:?}",
-                block_slot, block, elapsed
+                block_slot, err, elapsed
             );
-            Err(format!("Error cannot get block").into())
+            Err(format!("Error cannot get block {}: {}", block_slot, err).into())
         }
     }
 }
@@ -0,0 +1,132 @@
+use super::CHAIN;
+
+use crate::config::NetworkConfig;
+use crate::postgres_adapter::PostgresAdapter;
+use crate::solana::model::{EncodedConfirmedBlockWithSlot, SolanaSlot};
+use crate::solana::process_solana_channel;
+use crate::{get_block_number, GET_BLOCK_TIMEOUT_SEC, GET_STREAM_TIMEOUT_SEC};
+use core::ops::Deref;
+
+use massbit_chain_solana::data_type::SolanaBlock;
+use massbit_common::prelude::tokio::time::{sleep, timeout, Duration};
+use massbit_common::NetworkType;
+// The chain-reader serves Solana blocks with the messages of core/grpc
+use massbit_grpc::firehose::bstream::{
+    stream_client::StreamClient, BlockRequest, BlockResponse, ChainType,
+};
+use solana_transaction_status::UiTransactionEncoding;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tonic::{transport::Channel, Request, Streaming};
+use tower::timeout::Timeout;
+
+const QUEUE_BUFFER: usize = 1024;
+/// Handlers read transactions from the encoded form the RPC returns
+const BLOCK_ENCODING: UiTransactionEncoding = UiTransactionEncoding::Base64;
+
+/// Process the unfiltered Solana blocks of the chain-reader stream. The
+/// stream starts after the checkpoint of `network_name`, or at `block` on
+/// the first run, and is recreated after the last received block if it breaks
+pub async fn process_solana_stream(
+    channel: Timeout<Channel>,
+    storage_adapter: Arc<PostgresAdapter>,
+    network_name: &NetworkType,
+    block: Option<u64>,
+    config: &NetworkConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let start_block = storage_adapter
+        .get_connection()
+        .ok()
+        .and_then(|conn| get_block_number(conn.deref(), CHAIN.clone(), network_name.clone()))
+        .map(|state| state.got_block as u64 + 1)
+        .or(block);
+    let (tx, mut rx) = mpsc::channel(QUEUE_BUFFER);
+    let processed =
+        process_solana_channel(&mut rx, storage_adapter, network_name, &start_block, config);
+    let mut client = StreamClient::new(channel);
+    let received = loop_get_stream_block(tx, &mut client, start_block, network_name);
+    // The channel is closed when the stream loop stops and the processor
+    // returns once the remaining blocks are taken from it
+    let (processed, _) = futures03::join!(processed, received);
+    processed
+}
+
+async fn loop_get_stream_block(
+    chan: mpsc::Sender<SolanaSlot>,
+    client: &mut StreamClient<Timeout<Channel>>,
+    start_block: Option<u64>,
+    network_name: &NetworkType,
+) {
+    let mut next_block = start_block;
+    let mut opt_stream: Option<Streaming<BlockResponse>> = None;
+    loop {
+        match opt_stream {
+            None => {
+                opt_stream = try_create_solana_stream(client, next_block, network_name).await;
+                if opt_stream.is_none() {
+                    //Sleep for a while and reconnect
+                    sleep(Duration::from_secs(GET_STREAM_TIMEOUT_SEC)).await;
+                }
+            }
+            Some(ref mut stream) => {
+                let response =
+                    timeout(Duration::from_secs(GET_BLOCK_TIMEOUT_SEC), stream.message()).await;
+                match response {
+                    Ok(Ok(Some(data))) => {
+                        let blocks: Vec<SolanaBlock> = match serde_json::from_slice(&data.payload) {
+                            Ok(blocks) => blocks,
+                            Err(err) => {
+                                log::error!("Can not decode Solana blocks: {:?}", &err);
+                                continue;
+                            }
+                        };
+                        for block in blocks {
+                            let block_slot = block.block_number;
+                            let data = EncodedConfirmedBlockWithSlot {
+                                block_slot,
+                                block: block.block.encode(BLOCK_ENCODING),
+                            };
+                            if chan.send(SolanaSlot::Block(data)).await.is_err() {
+                                log::error!("Solana block processor is stopped");
+                                return;
+                            }
+                            next_block = Some(block_slot + 1);
+                        }
+                    }
+                    _ => {
+                        log::info!(
+                            "Error while get message from reader stream {:?}. Recreate stream",
+                            &response
+                        );
+                        opt_stream = None;
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// Request the blocks from `start_block` without filter, so that blocks
+/// without transactions are sent too
+async fn try_create_solana_stream(
+    client: &mut StreamClient<Timeout<Channel>>,
+    start_block: Option<u64>,
+    network_name: &NetworkType,
+) -> Option<Streaming<BlockResponse>> {
+    log::info!("Create new stream from block {:?}", start_block);
+    let request = BlockRequest {
+        indexer_hash: format!("analytics_{}", network_name),
+        start_block_number: start_block,
+        chain_type: ChainType::Solana as i32,
+        network: network_name.clone(),
+        filter: vec![],
+        replay_history: true,
+    };
+    match client.blocks(Request::new(request)).await {
+        Ok(res) => Some(res.into_inner()),
+        Err(err) => {
+            log::info!("Create new stream with error {:?}", &err);
+            None
+        }
+    }
+}
@@ -1,7 +1,9 @@
 #[macro_use]
 extern crate diesel_migrations;
-use analytics::solana::process_solana_channel;
+use analytics::backfill::{DEFAULT_BACKFILL_CHUNK_SIZE, DEFAULT_BACKFILL_WORKERS};
+use analytics::config::AnalyticsConfig;
 use analytics::solana::reader::loop_get_block;
+use analytics::solana::{backfill_solana_range, process_solana_channel, repair_failed_blocks};
 use analytics::{create_postgres_storage, establish_connection, get_block_number};
 use clap::{App, Arg};
 use log::{error, info};
@@ -12,6 +14,7 @@ use tokio::sync::mpsc;
 use tokio::task;
 
 use analytics::solana::{CHAIN, DEFAULT_NETWORK, SOLANA_URL};
+use std::env;
 use std::ops::Deref;
 
 embed_migrations!("./migrations");
@@ -40,6 +43,40 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
                 .help("Input start block value")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("config")
+                .help("Analytics handler config file, default is env ANALYTICS_CONFIG")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("repair")
+                .long("repair")
+                .help("Re-process the blocks which failed before and exit"),
+        )
+        .arg(
+            Arg::with_name("end-block")
+                .long("end-block")
+                .value_name("end-block")
+                .help("Backfill the slots from start block up to this one (exclusive) and exit")
+                .takes_value(true)
+                .requires("block"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .long("workers")
+                .value_name("workers")
+                .help("Number of parallel backfill workers")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chunk-size")
+                .long("chunk-size")
+                .value_name("chunk-size")
+                .help("Number of slots in a backfill chunk")
+                .takes_value(true),
+        )
         .get_matches();
     {
         let conn = establish_connection();
@@ -58,6 +95,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
         CHAIN.as_str(),
         network
     );
+    let config_path = matches
+        .value_of("config")
+        .map(String::from)
+        .or_else(|| env::var("ANALYTICS_CONFIG").ok());
+    let config = AnalyticsConfig::load(config_path.as_deref())?.solana_network(&network);
     let storage_adapter = Arc::new(create_postgres_storage());
     // Decode filter
     // let filter: SolanaFilter = SolanaFilter::new(vec![]);
@@ -65,6 +107,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
     info!("Init Solana client, url: {}", json_rpc_url);
     let client = Arc::new(RpcClient::new(json_rpc_url.clone()));
     info!("Finished init Solana client");
+    if matches.is_present("repair") {
+        return repair_failed_blocks(storage_adapter, &network, &client, &config);
+    }
+    if let (Some(start), Some(end)) = (
+        block,
+        matches
+            .value_of("end-block")
+            .and_then(|val| val.parse::<u64>().ok()),
+    ) {
+        let workers = matches
+            .value_of("workers")
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(DEFAULT_BACKFILL_WORKERS);
+        let chunk_size = matches
+            .value_of("chunk-size")
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(DEFAULT_BACKFILL_CHUNK_SIZE);
+        return backfill_solana_range(
+            storage_adapter,
+            &network,
+            client,
+            &config,
+            start..end,
+            workers,
+            chunk_size,
+        )
+        .await;
+    }
     let name = "deployment_solana".to_string();
     let (tx, mut rx) = mpsc::channel(QUEUE_BUFFER);
     let start_block = storage_adapter
@@ -84,7 +154,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>
         }))
     });
     //Main thread process received blocks
-    match process_solana_channel(&mut rx, storage_adapter.clone(), &network, &start_block).await {
+    match process_solana_channel(
+        &mut rx,
+        storage_adapter.clone(),
+        &network,
+        &start_block,
+        &config,
+    )
+    .await
+    {
         Err(err) => log::error!("{:?}", &err),
         Ok(_) => {}
     }
@@ -1,8 +1,11 @@
+use crate::backfill::BufferedUpsert;
 use crate::models::CommandData;
 use crate::postgres_queries::UpsertConflictFragment;
 use crate::relational::Table;
 use massbit::prelude::{Entity, Value};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
 
 pub trait StorageAdapter: Sync + Send {
     //fn get_connection(&self) -> Result<Connection, anyhow::Error>;
@@ -16,6 +19,287 @@ pub trait StorageAdapter: Sync + Send {
 
     /// Insert or update on conflict into some tables in one transaction
     fn transact_upserts(&self, _commands: Vec<CommandData>) -> Result<(), anyhow::Error>;
+
+    /// Write buffered rows. Called before the checkpoint of the processed
+    /// blocks is saved, so that no buffered row is lost after a restart
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// The following writes of the current thread belong to one unit, e.g.
+    /// the handling of a block by a handler, until `end_unit` is called
+    fn begin_unit(&self) {}
+
+    /// End the unit of the current thread. Its writes are kept if it is
+    /// `committed`, otherwise the unit is retried later
+    fn end_unit(&self, _committed: bool) {}
+}
+
+/// Writes into `primary` and then into `mirror`, so that an export can run
+/// next to the main storage. Writes of a unit are only mirrored when the
+/// unit is committed, so that failed attempts which are retried are not
+/// mirrored twice. Errors of the mirror are only logged: the primary write
+/// already succeeded and a retry would repeat it
+pub struct MirrorAdapter {
+    primary: Arc<dyn StorageAdapter>,
+    mirror: Arc<dyn StorageAdapter>,
+    /// Writes of the units in progress by their thread
+    units: Mutex<HashMap<ThreadId, Vec<BufferedUpsert>>>,
+}
+
+impl MirrorAdapter {
+    pub fn new(primary: Arc<dyn StorageAdapter>, mirror: Arc<dyn StorageAdapter>) -> Self {
+        MirrorAdapter {
+            primary,
+            mirror,
+            units: Mutex::new(HashMap::default()),
+        }
+    }
+
+    /// Keep the upserts if the current thread is in a unit and return
+    /// whether they were kept
+    fn keep_in_unit(&self, upserts: impl Iterator<Item = BufferedUpsert>) -> bool {
+        match self.units.lock().unwrap().get_mut(&thread::current().id()) {
+            Some(unit) => {
+                unit.extend(upserts);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl StorageAdapter for MirrorAdapter {
+    fn insert(&self, table_name: &str, value: HashMap<&str, Value>) -> Result<(), anyhow::Error> {
+        self.primary.insert(table_name, value)
+    }
+
+    fn upsert(
+        &self,
+        table: &Table,
+        values: &Vec<Entity>,
+        conflict_fragment: &Option<UpsertConflictFragment>,
+    ) -> Result<(), anyhow::Error> {
+        self.primary.upsert(table, values, conflict_fragment)?;
+        let upsert = || BufferedUpsert::new(table, values, conflict_fragment);
+        if self.keep_in_unit(std::iter::once_with(upsert)) {
+            return Ok(());
+        }
+        if let Err(err) = self.mirror.upsert(table, values, conflict_fragment) {
+            log::error!(
+                "Can not mirror upsert into {}: {:?}",
+                table.name.as_str(),
+                &err
+            );
+        }
+        Ok(())
+    }
+
+    fn transact_upserts(&self, commands: Vec<CommandData>) -> Result<(), anyhow::Error> {
+        let mirrored: Vec<CommandData> = commands
+            .iter()
+            .map(|cmd| CommandData::new(cmd.table, cmd.values, cmd.conflict_fragment))
+            .collect();
+        self.primary.transact_upserts(commands)?;
+        let upserts = mirrored
+            .iter()
+            .map(|cmd| BufferedUpsert::new(cmd.table, cmd.values, cmd.conflict_fragment));
+        if self.keep_in_unit(upserts) {
+            return Ok(());
+        }
+        if let Err(err) = self.mirror.transact_upserts(mirrored) {
+            log::error!("Can not mirror upserts: {:?}", &err);
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), anyhow::Error> {
+        self.primary.flush()?;
+        if let Err(err) = self.mirror.flush() {
+            log::error!("Can not flush mirror: {:?}", &err);
+        }
+        Ok(())
+    }
+
+    fn begin_unit(&self) {
+        self.primary.begin_unit();
+        self.units
+            .lock()
+            .unwrap()
+            .insert(thread::current().id(), Vec::default());
+    }
+
+    fn end_unit(&self, committed: bool) {
+        self.primary.end_unit(committed);
+        let upserts = self
+            .units
+            .lock()
+            .unwrap()
+            .remove(&thread::current().id())
+            .unwrap_or_default();
+        if !committed {
+            return;
+        }
+        for upsert in upserts {
+            if let Err(err) = upsert.apply(self.mirror.as_ref()) {
+                log::error!(
+                    "Can not mirror upsert into {}: {:?}",
+                    upsert.table_name(),
+                    &err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MirrorAdapter, StorageAdapter};
+    use crate::models::CommandData;
+    use crate::postgres_queries::UpsertConflictFragment;
+    use crate::relational::{Column, ColumnType, Table};
+    use crate::{create_columns, create_entity};
+    use massbit::prelude::{Attribute, Entity, Value};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Counts the written rows, or fails every call if `fail` is set
+    #[derive(Default)]
+    struct TestAdapter {
+        fail: bool,
+        rows: AtomicUsize,
+        flushes: AtomicUsize,
+    }
+
+    impl TestAdapter {
+        fn result(&self) -> Result<(), anyhow::Error> {
+            if self.fail {
+                Err(anyhow::anyhow!("Storage is not available"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl StorageAdapter for TestAdapter {
+        fn insert(
+            &self,
+            _table_name: &str,
+            _value: HashMap<&str, Value>,
+        ) -> Result<(), anyhow::Error> {
+            self.result()
+        }
+
+        fn upsert(
+            &self,
+            _table: &Table,
+            values: &Vec<Entity>,
+            _conflict_fragment: &Option<UpsertConflictFragment>,
+        ) -> Result<(), anyhow::Error> {
+            self.result()?;
+            self.rows.fetch_add(values.len(), Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn transact_upserts(&self, commands: Vec<CommandData>) -> Result<(), anyhow::Error> {
+            self.result()?;
+            for cmd in commands {
+                self.rows.fetch_add(cmd.values.len(), Ordering::SeqCst);
+            }
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), anyhow::Error> {
+            self.result()?;
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn adapters(primary_fails: bool, mirror_fails: bool) -> (Arc<TestAdapter>, Arc<TestAdapter>) {
+        let primary = TestAdapter {
+            fail: primary_fails,
+            ..Default::default()
+        };
+        let mirror = TestAdapter {
+            fail: mirror_fails,
+            ..Default::default()
+        };
+        (Arc::new(primary), Arc::new(mirror))
+    }
+
+    fn table() -> Table<'static> {
+        Table::new(
+            "solana_blocks",
+            create_columns!("block_slot" => ColumnType::BigInt),
+        )
+    }
+
+    #[test]
+    fn writes_into_both_adapters() {
+        let (primary, mirror) = adapters(false, false);
+        let adapter = MirrorAdapter::new(primary.clone(), mirror.clone());
+        let table = table();
+        let values = vec![create_entity!("block_slot" => 1_u64)];
+        adapter.upsert(&table, &values, &None).unwrap();
+        adapter
+            .transact_upserts(vec![CommandData::new(&table, &values, &None)])
+            .unwrap();
+        adapter.flush().unwrap();
+        assert_eq!(primary.rows.load(Ordering::SeqCst), 2);
+        assert_eq!(mirror.rows.load(Ordering::SeqCst), 2);
+        assert_eq!(primary.flushes.load(Ordering::SeqCst), 1);
+        assert_eq!(mirror.flushes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn mirror_errors_do_not_fail_the_write() {
+        let (primary, mirror) = adapters(false, true);
+        let adapter = MirrorAdapter::new(primary.clone(), mirror);
+        let table = table();
+        let values = vec![create_entity!("block_slot" => 1_u64)];
+        assert!(adapter.upsert(&table, &values, &None).is_ok());
+        assert!(adapter
+            .transact_upserts(vec![CommandData::new(&table, &values, &None)])
+            .is_ok());
+        assert!(adapter.flush().is_ok());
+        assert_eq!(primary.rows.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn primary_errors_skip_the_mirror() {
+        let (primary, mirror) = adapters(true, false);
+        let adapter = MirrorAdapter::new(primary, mirror.clone());
+        let table = table();
+        let values = vec![create_entity!("block_slot" => 1_u64)];
+        assert!(adapter.upsert(&table, &values, &None).is_err());
+        assert!(adapter.flush().is_err());
+        assert_eq!(mirror.rows.load(Ordering::SeqCst), 0);
+        assert_eq!(mirror.flushes.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn mirrors_committed_units_only() {
+        let (primary, mirror) = adapters(false, false);
+        let adapter = MirrorAdapter::new(primary.clone(), mirror.clone());
+        let table = table();
+        let values = vec![create_entity!("block_slot" => 1_u64)];
+        // A failed attempt which is retried
+        adapter.begin_unit();
+        adapter.upsert(&table, &values, &None).unwrap();
+        adapter.end_unit(false);
+        assert_eq!(mirror.rows.load(Ordering::SeqCst), 0);
+        adapter.begin_unit();
+        adapter.upsert(&table, &values, &None).unwrap();
+        adapter
+            .transact_upserts(vec![CommandData::new(&table, &values, &None)])
+            .unwrap();
+        assert_eq!(mirror.rows.load(Ordering::SeqCst), 0);
+        adapter.end_unit(true);
+        assert_eq!(primary.rows.load(Ordering::SeqCst), 3);
+        assert_eq!(mirror.rows.load(Ordering::SeqCst), 2);
+    }
 }
 
 // enum StorageAdapterType {
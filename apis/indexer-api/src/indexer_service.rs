@@ -1,17 +1,21 @@
 use crate::git_helper::GitHelper;
-use crate::model::{IndexerData, ListOptions};
+use crate::model::{IndexerData, IpfsDeployParam, ListOptions};
+use crate::plugin_library::check_plugin_file;
 
 use crate::server_builder::DeployParam;
 use crate::API_LIST_LIMIT;
 use crate::INDEXER_MANAGER_DEPLOY_ENDPOINT;
 use crate::{
     models::Indexer,
+    schema::indexer_deployments,
     schema::indexers::{self, dsl},
 };
+use crate::{SCHEMA_FILE_NAME, SO_FILE_NAME, SUBGRAPH_FILE_NAME};
+use chain_solana::validation::{validate_manifest, validate_schema, ValidationError};
 use chain_solana::SolanaIndexerManifest;
 use diesel::sql_types::BigInt;
 use futures::lock::Mutex;
-use indexer_orm::models::IndexerStatus;
+use indexer_orm::models::{IndexerHealth, IndexerStatus};
 use log::debug;
 use massbit::ipfs_client::IpfsClient;
 use massbit::ipfs_link_resolver::LinkResolver;
@@ -27,6 +31,7 @@ use massbit_common::prelude::r2d2::PooledConnection;
 use massbit_common::prelude::serde_json::json;
 use massbit_common::prelude::tokio::macros::support::Future;
 use octocrab::models::activity::Reason;
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
 use warp::reply::Json;
@@ -132,6 +137,76 @@ impl IndexerService {
         Ok(warp::reply::json(&json!("{'error': 'Cannot store to DB'}")))
     }
 
+    /// for api validate indexer: checks the mapping, schema and manifest parts like massbit-sol validate
+    pub async fn validate_indexer(&self, form: FormData) -> Result<impl Reply, Rejection> {
+        let parts: Vec<Part> = form.try_collect().await.map_err(|e| {
+            eprintln!("form error: {}", e);
+            warp::reject::reject()
+        })?;
+        let mut files = HashMap::new();
+        for p in parts {
+            let name = p.name().to_string();
+            let value = p
+                .stream()
+                .try_fold(Vec::new(), |mut vec, data| {
+                    vec.put(data);
+                    async move { Ok(vec) }
+                })
+                .await
+                .map_err(|e| {
+                    eprintln!("reading file error: {}", e);
+                    warp::reject::reject()
+                })?;
+            files.insert(name, value);
+        }
+        let mut errors = vec![];
+        match files.get("manifest") {
+            Some(content) => errors.append(&mut validate_manifest(SUBGRAPH_FILE_NAME, content)),
+            None => errors.push(ValidationError::new(SUBGRAPH_FILE_NAME, "File is missing")),
+        }
+        match files.get("schema") {
+            Some(content) => errors.append(&mut validate_schema(
+                SCHEMA_FILE_NAME,
+                &String::from_utf8_lossy(content),
+            )),
+            None => errors.push(ValidationError::new(SCHEMA_FILE_NAME, "File is missing")),
+        }
+        match files.get("mapping") {
+            Some(content) => {
+                // The library is only parsed, it is loaded by the indexer-manager once deployed
+                errors.extend(
+                    check_plugin_file(content)
+                        .into_iter()
+                        .map(|message| ValidationError::new(SO_FILE_NAME, message)),
+                );
+            }
+            None => errors.push(ValidationError::new(SO_FILE_NAME, "File is missing")),
+        }
+        Ok(warp::reply::json(
+            &json!({ "valid": errors.is_empty(), "errors": errors }),
+        ))
+    }
+
+    /// for api deploy indexer from massbit-sol cli, with the files pinned to ipfs by the cli
+    pub async fn deploy_ipfs_indexer(
+        &self,
+        owner_id: String,
+        content: IpfsDeployParam,
+    ) -> Result<impl Reply, Rejection> {
+        log::info!("Deploy new indexer from ipfs {:?}.", &content);
+        let mut indexer = Indexer::default();
+        indexer.got_block = -1_i64;
+        indexer.name = content.name.unwrap_or_default();
+        indexer.mapping = content.mapping;
+        indexer.graphql = content.schema;
+        indexer.manifest = content.manifest;
+        indexer.owner_id = owner_id;
+        match self.store_indexer(indexer).await {
+            Ok(indexer) => self.call_deploy_indexer_manager(indexer).await,
+            Err(err) => Ok(warp::reply::json(&json!({ "error": err.to_string() }))),
+        }
+    }
+
     async fn call_deploy_indexer_manager(
         &self,
         indexer: Indexer,
@@ -306,7 +381,7 @@ impl IndexerService {
             Ok(warp::reply::json(&content))
         }
     }
-    /// for api get indexer detail: /indexers/hash
+    /// for api get indexer detail: /indexers/hash, with the health and the fatal error of its deployment
     pub async fn get_indexer(&self, hash: String) -> Result<impl Reply, Rejection> {
         if let Ok(conn) = self.get_connection() {
             let results = dsl::indexers
@@ -315,13 +390,32 @@ impl IndexerService {
                 .load::<Indexer>(conn.deref())
                 .expect("Error loading indexers");
             match results.get(0) {
-                Some(res) => Ok(warp::reply::json(&res)),
+                Some(res) => {
+                    let mut value = json!(res);
+                    let deployment = self.get_deployment_health(&conn, &res.hash);
+                    value["health"] = json!(deployment.as_ref().map(|(health, _)| health));
+                    value["fatal_error"] = json!(deployment.and_then(|(_, error)| error));
+                    Ok(warp::reply::json(&value))
+                }
                 None => Ok(warp::reply::json(&String::from(""))),
             }
         } else {
             Ok(warp::reply::json(&String::from("")))
         }
     }
+    /// Health of the deployment of an indexer and its fatal error, if its runtime has stopped
+    fn get_deployment_health(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        hash: &str,
+    ) -> Option<(IndexerHealth, Option<String>)> {
+        use indexer_deployments::dsl as d;
+        d::indexer_deployments
+            .filter(d::hash.eq(hash))
+            .select((d::health, d::fatal_error))
+            .first::<(IndexerHealth, Option<String>)>(conn.deref())
+            .ok()
+    }
     fn get_next_sequence(
         &self,
         conn: &PooledConnection<ConnectionManager<PgConnection>>,
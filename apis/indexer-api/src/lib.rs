@@ -7,6 +7,7 @@ pub mod git_helper;
 pub mod indexer_service;
 pub mod model;
 pub mod opt;
+pub mod plugin_library;
 pub mod server_builder;
 pub mod user_managerment;
 
@@ -21,6 +22,9 @@ use warp::Rejection;
 pub const IPFS_TIME_OUT: u64 = 10_u64;
 pub const API_LIST_LIMIT: i64 = 100_i64;
 pub const MAX_JSON_BODY_SIZE: u64 = 1024 * 1024;
+pub const SO_FILE_NAME: &str = "libblock.so";
+pub const SCHEMA_FILE_NAME: &str = "schema.graphql";
+pub const SUBGRAPH_FILE_NAME: &str = "subgraph.yaml";
 lazy_static! {
     pub static ref COMPONENT_NAME: String = String::from("[IndexerInfo]");
     pub static ref CONNECTION_POOL_SIZE: u32 = env::var("CONNECTION_POOL_SIZE")
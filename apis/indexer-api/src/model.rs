@@ -15,3 +15,12 @@ pub struct IndexerData {
     pub repository: Option<String>,
     pub description: Option<String>,
 }
+
+/// Deploy request of massbit-sol cli, with the ipfs hashes of the indexer files
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IpfsDeployParam {
+    pub name: Option<String>,
+    pub mapping: String,
+    pub schema: String,
+    pub manifest: String,
+}
@@ -0,0 +1,65 @@
+//! Checks of the uploaded mapping libraries. A library is read as an ELF file and never loaded:
+//! loading it would run its initializers in the indexer-api.
+use massbit_solana_sdk::plugin::version::{
+    read_version_field, symbol_name, VersionField, ABI_FINGERPRINT_SYMBOL, RUSTC_VERSION_SYMBOL,
+    SDK_VERSION_SYMBOL,
+};
+use massbit_solana_sdk::plugin::{check_plugin_exports, PluginVersion};
+use object::{BinaryFormat, Object, ObjectSection, ObjectSymbol, Symbol};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::mem::size_of;
+
+/// Same checks as `check_plugin_library` of the SDK, with the exported symbols
+/// and the version fields read from the content of the library
+pub fn check_plugin_file(content: &[u8]) -> Vec<String> {
+    let file = match object::File::parse(content) {
+        Ok(file) if file.format() == BinaryFormat::Elf => file,
+        Ok(file) => {
+            return vec![format!(
+                "The library is a {:?} file, build it for Linux",
+                file.format()
+            )]
+        }
+        Err(err) => {
+            return vec![format!(
+                "Cannot read the library: {}, build it with `cargo build --release`",
+                err
+            )]
+        }
+    };
+    let exports: HashMap<&str, Symbol> = file
+        .dynamic_symbols()
+        .filter(|symbol| symbol.is_definition())
+        .filter_map(|symbol| symbol.name().ok().map(|name| (name, symbol)))
+        .collect();
+    // Initial value of an exported static, from the section of its symbol
+    let symbol_data = |symbol: &[u8], size: usize| -> Option<&[u8]> {
+        let symbol = exports.get(symbol_name(symbol))?;
+        let section = file.section_by_index(symbol.section_index()?).ok()?;
+        section
+            .data_range(symbol.address(), size as u64)
+            .ok()
+            .flatten()
+    };
+    let read_field = |symbol: &[u8]| {
+        symbol_data(symbol, size_of::<VersionField>())
+            .and_then(|data| data.try_into().ok())
+            .and_then(|field: &VersionField| read_version_field(field).map(String::from))
+    };
+    let abi_fingerprint = symbol_data(ABI_FINGERPRINT_SYMBOL, size_of::<u64>())
+        .and_then(|data| data.try_into().ok())
+        .map(|bytes| {
+            if file.is_little_endian() {
+                u64::from_le_bytes(bytes)
+            } else {
+                u64::from_be_bytes(bytes)
+            }
+        });
+    let version = PluginVersion {
+        sdk_version: read_field(SDK_VERSION_SYMBOL),
+        rustc_version: read_field(RUSTC_VERSION_SYMBOL),
+        abi_fingerprint,
+    };
+    check_plugin_exports(|symbol| exports.contains_key(symbol_name(symbol)), &version)
+}
@@ -2,8 +2,9 @@ use super::model::ListOptions;
 use super::MAX_UPLOAD_FILE_SIZE;
 use crate::config::AccessControl;
 use crate::indexer_service::IndexerService;
-use crate::model::IndexerData;
+use crate::model::{IndexerData, IpfsDeployParam};
 use crate::user_managerment::auth::{with_auth, Role};
+use crate::user_managerment::error::Error as AuthError;
 use crate::MAX_JSON_BODY_SIZE;
 use futures::lock::Mutex;
 use log::info;
@@ -59,6 +60,12 @@ impl<'a> IndexerServer {
         let router = self
             .create_route_indexer_cli_deploy(self.indexer_service.clone())
             .with(&cors)
+            .or(self
+                .create_route_indexer_validate(self.indexer_service.clone())
+                .with(&cors))
+            .or(self
+                .create_route_indexer_ipfs_deploy(self.indexer_service.clone())
+                .with(&cors))
             .or(self
                 .create_route_indexer_github_deploy(self.indexer_service.clone())
                 .with(&cors))
@@ -92,6 +99,34 @@ impl<'a> IndexerServer {
                 async move { clone_service.deploy_indexer_cli(form).await }
             })
     }
+    /// Indexer dry-run api: validates the files of an indexer without deploying it
+    fn create_route_indexer_validate(
+        &self,
+        service: Arc<IndexerService>,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("indexers" / "validate")
+            .and(warp::post())
+            .and(with_auth(Role::User))
+            .and(warp::multipart::form().max_length(MAX_UPLOAD_FILE_SIZE.clone()))
+            .and_then(move |_owner_id: String, form: FormData| {
+                let clone_service = service.clone();
+                async move { clone_service.validate_indexer(form).await }
+            })
+    }
+    /// Indexer deploy from cli api, with the files pinned to ipfs by the cli
+    fn create_route_indexer_ipfs_deploy(
+        &self,
+        service: Arc<IndexerService>,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+        warp::path!("indexers" / "ipfsdeploy")
+            .and(warp::post())
+            .and(with_auth(Role::User))
+            .and(json_ipfs_deploy_body())
+            .and_then(move |owner_id: String, content: IpfsDeployParam| {
+                let clone_service = service.clone();
+                async move { clone_service.deploy_ipfs_indexer(owner_id, content).await }
+            })
+    }
     /// Indexer deploy from github api
     fn create_route_indexer_github_deploy(
         &self,
@@ -210,11 +245,18 @@ fn json_deploy_body() -> impl Filter<Extract = (DeployParam,), Error = warp::Rej
     warp::body::content_length_limit(MAX_JSON_BODY_SIZE).and(warp::body::json())
 }
 
+fn json_ipfs_deploy_body(
+) -> impl Filter<Extract = (IpfsDeployParam,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(MAX_JSON_BODY_SIZE).and(warp::body::json())
+}
+
 async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply, Infallible> {
     let (code, message) = if err.is_not_found() {
         (StatusCode::NOT_FOUND, "Not Found".to_string())
     } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
         (StatusCode::BAD_REQUEST, "Payload too large".to_string())
+    } else if let Some(err) = err.find::<AuthError>() {
+        (StatusCode::UNAUTHORIZED, err.to_string())
     } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         (
             StatusCode::BAD_REQUEST,
@@ -14,7 +14,7 @@ use massbit_common::prelude::diesel::r2d2::{ConnectionManager, PooledConnection}
 use massbit_common::prelude::diesel::{Connection, PgConnection, RunQueryDsl};
 use massbit_common::prelude::tokio::time::Instant;
 use massbit_common::prelude::{anyhow, r2d2};
-use massbit_solana_sdk::entity::Entity;
+use massbit_solana_sdk::entity::{Entity, EntityFilter, EntityOrder, EntityRange};
 use massbit_solana_sdk::model::{EntityKey, EntityModification, BLOCK_NUMBER_MAX};
 use massbit_solana_sdk::store::IndexStore;
 use massbit_store_postgres::relational::Layout;
@@ -328,6 +328,30 @@ impl IndexStore for CacheableStore {
         })
     }
 
+    fn remove(&mut self, entity_name: String, entity_id: &String) {
+        let key = EntityKey {
+            indexer_id: self.indexer_id.clone(),
+            entity_type: entity_name,
+            entity_id: entity_id.clone(),
+        };
+        self.entity_cache.remove(key);
+    }
+
+    /// The indexer api does not run the indexers, their queries are served by the indexer manager
+    fn query(
+        &self,
+        entity_name: String,
+        _filter: Option<EntityFilter>,
+        _order: EntityOrder,
+        _range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>> {
+        Err(format!(
+            "Query of {} entities is not supported by this store",
+            &entity_name
+        )
+        .into())
+    }
+
     fn flush(&mut self, block_hash: &String, block_slot: u64) -> Result<(), Box<dyn Error>> {
         //let mut data = self.entity_cache.lock().unwrap();
         let entity_cache =
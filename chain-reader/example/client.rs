@@ -50,6 +50,7 @@ pub async fn print_blocks(
         chain_type: chain_type as i32,
         network,
         filter: encoded_filter,
+        replay_history: start_block.is_some(),
     };
     println!("Creating Stream with {:?}", &get_blocks_request);
     let mut stream = Some(
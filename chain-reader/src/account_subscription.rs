@@ -0,0 +1,198 @@
+//! Account changes of the programs which the indexers filter on. The node notifies the state of
+//! an account owned by a subscribed program when a slot which changed it is confirmed, before the
+//! block of the slot is finalized and read by the chain reader. The snapshots are kept until the
+//! block of their slot is broadcast and sent with it. A program is unsubscribed when the last
+//! indexer filtering on it is dropped.
+use chain_solana::SOLANA_NETWORKS;
+use log::{info, warn};
+use massbit_chain_solana::data_type::AccountSnapshot;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::slot_history::Slot;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Slots whose snapshots are kept while their blocks are not broadcast
+const MAX_PENDING_SLOTS: usize = 10000;
+const RESUBSCRIBE_DELAY_MS: u64 = 1000;
+/// How long a subscription thread waits for a notification before it checks if it is stopped
+const STOP_CHECK_INTERVAL_MS: u64 = 1000;
+
+type ProgramSubscriptions = Arc<Mutex<HashMap<Pubkey, ProgramSubscription>>>;
+
+struct ProgramSubscription {
+    subscribers: usize,
+    /// Tells the thread of the subscription to close it
+    stopped: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct AccountSubscriptions {
+    ws_url: Option<String>,
+    programs: ProgramSubscriptions,
+    /// Map slot => last state of the accounts changed in the slot
+    snapshots: Arc<Mutex<BTreeMap<Slot, BTreeMap<Pubkey, AccountSnapshot>>>>,
+}
+
+impl AccountSubscriptions {
+    /// Subscriptions to the websocket of a node of `network`
+    pub fn new(network: &str) -> Self {
+        let ws_url = SOLANA_NETWORKS
+            .values()
+            .find(|config| config.network.as_str() == network)
+            .map(|config| config.ws.clone());
+        AccountSubscriptions {
+            ws_url,
+            ..Default::default()
+        }
+    }
+    /// Subscribe to the accounts owned by the `programs` which are not subscribed yet.
+    /// The programs stay subscribed until the returned subscription is dropped
+    pub fn subscribe(&self, programs: &Vec<Pubkey>) -> AccountSubscription {
+        let ws_url = match &self.ws_url {
+            Some(ws_url) => ws_url.clone(),
+            None => return AccountSubscription::default(),
+        };
+        let programs = programs.iter().cloned().collect::<BTreeSet<Pubkey>>();
+        let mut subscriptions = self.programs.lock().unwrap();
+        for program in programs.iter() {
+            if let Some(subscription) = subscriptions.get_mut(program) {
+                subscription.subscribers += 1;
+                continue;
+            }
+            let stopped = Arc::new(AtomicBool::new(false));
+            subscriptions.insert(
+                program.clone(),
+                ProgramSubscription {
+                    subscribers: 1,
+                    stopped: stopped.clone(),
+                },
+            );
+            let ws_url = ws_url.clone();
+            let program = program.clone();
+            let snapshots = self.snapshots.clone();
+            thread::spawn(move || {
+                while !stopped.load(Ordering::Relaxed) {
+                    subscribe_program(&ws_url, &program, &snapshots, &stopped);
+                    thread::sleep(Duration::from_millis(RESUBSCRIBE_DELAY_MS));
+                }
+            });
+        }
+        AccountSubscription {
+            programs: programs.into_iter().collect(),
+            subscriptions: Some(self.programs.clone()),
+        }
+    }
+    /// Take the snapshots of `slot`. Snapshots of older slots either came after their block
+    /// was broadcast or belong to an abandoned fork, they are dropped
+    pub fn take(&self, slot: Slot) -> Vec<AccountSnapshot> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let newer = snapshots.split_off(&(slot + 1));
+        let mut older = std::mem::replace(&mut *snapshots, newer);
+        older
+            .remove(&slot)
+            .map(|accounts| accounts.into_iter().map(|(_, account)| account).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Subscription of an indexer to the accounts of the programs it filters on
+#[derive(Default)]
+pub struct AccountSubscription {
+    programs: Vec<Pubkey>,
+    subscriptions: Option<ProgramSubscriptions>,
+}
+
+impl Drop for AccountSubscription {
+    /// Stop the subscriptions of the programs which have no other subscriber
+    fn drop(&mut self) {
+        let mut subscriptions = match &self.subscriptions {
+            Some(subscriptions) => subscriptions.lock().unwrap(),
+            None => return,
+        };
+        for program in self.programs.iter() {
+            let last = match subscriptions.get_mut(program) {
+                Some(subscription) => {
+                    subscription.subscribers -= 1;
+                    subscription.subscribers == 0
+                }
+                None => false,
+            };
+            if last {
+                if let Some(subscription) = subscriptions.remove(program) {
+                    subscription.stopped.store(true, Ordering::Relaxed);
+                    info!("Accounts of {} have no more subscriber", program);
+                }
+            }
+        }
+    }
+}
+
+/// Keep the notified account states until the subscription is closed or stopped
+fn subscribe_program(
+    ws_url: &str,
+    program: &Pubkey,
+    snapshots: &Mutex<BTreeMap<Slot, BTreeMap<Pubkey, AccountSnapshot>>>,
+    stopped: &AtomicBool,
+) {
+    let config = RpcProgramAccountsConfig {
+        filters: None,
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..Default::default()
+        },
+        with_context: Some(true),
+    };
+    let (mut subscription, receiver) =
+        match PubsubClient::program_subscribe(ws_url, program, Some(config)) {
+            Ok(subscription) => subscription,
+            Err(err) => {
+                warn!("Cannot subscribe to accounts of {}: {:?}", program, err);
+                return;
+            }
+        };
+    info!("Subscribed to accounts of {}", program);
+    while !stopped.load(Ordering::Relaxed) {
+        let response = match receiver.recv_timeout(Duration::from_millis(STOP_CHECK_INTERVAL_MS)) {
+            Ok(response) => response,
+            Err(err) if err.is_timeout() => continue,
+            Err(_) => {
+                warn!("Subscription to accounts of {} is closed", program);
+                return;
+            }
+        };
+        let slot = response.context.slot;
+        let keyed_account = response.value;
+        let pubkey = Pubkey::from_str(&keyed_account.pubkey);
+        let account = keyed_account.account.decode::<Account>();
+        if let (Ok(pubkey), Some(account)) = (pubkey, account) {
+            let mut snapshots = snapshots.lock().unwrap();
+            snapshots.entry(slot).or_default().insert(
+                pubkey,
+                AccountSnapshot {
+                    pubkey,
+                    owner: account.owner,
+                    lamports: account.lamports,
+                    data: account.data,
+                },
+            );
+            while snapshots.len() > MAX_PENDING_SLOTS {
+                let first_slot = *snapshots.keys().next().unwrap();
+                snapshots.remove(&first_slot);
+            }
+        }
+    }
+    if let Err(err) = subscription.shutdown() {
+        warn!("Cannot unsubscribe from accounts of {}: {:?}", program, err);
+    }
+    info!("Unsubscribed from accounts of {}", program);
+}
@@ -1,20 +1,25 @@
+use crate::account_subscription::{AccountSubscription, AccountSubscriptions};
 use chain_solana::types::{BlockInfo, ConfirmedBlockWithSlot};
-use log::{debug, info};
+use log::{debug, info, warn};
 use massbit::prelude::Future;
 use massbit::slog::log;
-use massbit_chain_solana::data_type::{ExtBlock, SolanaBlock, SolanaFilter};
+use massbit_chain_solana::data_type::{
+    AccountSnapshot, ExtBlock, Pubkey, SolanaBlock, SolanaFilter,
+};
 use massbit_grpc::firehose::bstream::BlockResponse;
 use solana_sdk::slot_history::Slot;
 use solana_transaction_status::ConfirmedBlock;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::iter::FromIterator;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::task;
+use tokio::time::{timeout, Duration};
 use tonic::Status;
 
 const VERSION: &str = "1.7.0";
 const MAX_BUFFER_SIZE: usize = 1000_usize;
+/// How long the broadcaster waits for an indexer with a full channel
+const SEND_TIMEOUT_SEC: u64 = 10;
 #[derive(Default)]
 pub struct BlockBuffer {
     /// Map parent_slot => ConfirmedBlock
@@ -91,22 +96,145 @@ pub struct IndexerInfo {
     hash: String, //Indexer hash
     filter: SolanaFilter,
     filter_hashes: HashSet<String>, //For quickly filter ConfirmedBlock
+    /// Blocks before this slot are not sent to the indexer
+    start_slot: Option<u64>,
     sender: Sender<Result<BlockResponse, Status>>,
+    /// Keeps the accounts of the filtered programs subscribed while the indexer is registered
+    account_subscription: AccountSubscription,
+}
+
+impl IndexerInfo {
+    pub fn new(
+        hash: &String,
+        encoded_filter: &Vec<u8>,
+        start_slot: Option<u64>,
+        indexer_sender: Sender<Result<BlockResponse, Status>>,
+    ) -> Self {
+        // Decode filter
+        let filter: SolanaFilter = serde_json::from_slice(&encoded_filter).unwrap_or_default();
+        let mut filter_hashes = HashSet::default();
+        filter.keys.iter().for_each(|key| {
+            filter_hashes.insert(key.to_string());
+        });
+        IndexerInfo {
+            hash: hash.clone(),
+            filter,
+            filter_hashes,
+            start_slot,
+            sender: indexer_sender,
+            account_subscription: AccountSubscription::default(),
+        }
+    }
+    pub fn with_account_subscription(mut self, account_subscription: AccountSubscription) -> Self {
+        self.account_subscription = account_subscription;
+        self
+    }
+    pub fn hash(&self) -> &String {
+        &self.hash
+    }
+    pub fn filter_keys(&self) -> &Vec<Pubkey> {
+        &self.filter.keys
+    }
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+    ///Indexer without filter keys gets all blocks, even ones without transactions
+    pub fn is_unfiltered(&self) -> bool {
+        self.filter_hashes.is_empty()
+    }
+    ///Return copies of the blocks the indexer is interested in, with matched transactions only.
+    ///Slots whose block could not be got are left out, the broadcaster logs them
+    pub fn filter_blocks(
+        &self,
+        block_with_slots: &Vec<ConfirmedBlockWithSlot>,
+    ) -> Vec<ConfirmedBlockWithSlot> {
+        block_with_slots
+            .iter()
+            .filter(|block| block.block.is_some())
+            .filter(|block| {
+                self.start_slot
+                    .map_or(true, |start_slot| block.block_slot >= start_slot)
+            })
+            .filter_map(|block| {
+                let ref_block = block.block.as_ref().unwrap();
+                //Clone ConfirmedBlock with empty transactions
+                let mut indexer_block = block.cheap_clone();
+                let transactions = &mut indexer_block.block.as_mut().unwrap().transactions;
+                //Iterate throw transactions and clone the matched ones
+                ref_block
+                    .transactions
+                    .iter()
+                    .filter(|tran| {
+                        self.is_unfiltered()
+                            || tran
+                                .transaction
+                                .message
+                                .account_keys
+                                .iter()
+                                .any(|key| self.filter_hashes.contains(&key.to_string()))
+                    })
+                    .for_each(|tran| transactions.push(tran.clone()));
+                if self.is_unfiltered() || transactions.len() > 0 {
+                    Some(indexer_block)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    ///Send filtered blocks to the indexer with the snapshots of the accounts owned by the filter keys.
+    ///Return false if there is no block for the indexer
+    pub async fn send_blocks(
+        &self,
+        block_with_slots: &Vec<ConfirmedBlockWithSlot>,
+        snapshots: &HashMap<Slot, Vec<AccountSnapshot>>,
+    ) -> bool {
+        let blocks = self.filter_blocks(block_with_slots);
+        if blocks.is_empty() {
+            return false;
+        }
+        let block_response = IndexerBroadcast::create_block_response(blocks, |slot| {
+            snapshots
+                .get(&slot)
+                .map(|accounts| self.filter.filter_accounts(accounts.clone()))
+                .unwrap_or_default()
+        });
+        info!(
+            "*** GRPC Send block_response to indexer {:?}. Channel capacity:  {:?}",
+            &self.hash,
+            self.sender.capacity()
+        );
+        self.sender.send(Ok(block_response)).await;
+        true
+    }
 }
 pub struct IndexerBroadcast {
     block_receiver: Receiver<BlockInfo>,
     block_buffer: BlockBuffer,
     ind_senders: Mutex<Vec<IndexerInfo>>,
+    /// Last slot sent to indexers
+    last_slot: Option<u64>,
+    /// Account changes sent with the blocks of their slots
+    account_subscriptions: Arc<AccountSubscriptions>,
 }
 
 impl IndexerBroadcast {
-    pub fn new(receiver: Receiver<BlockInfo>) -> Self {
+    pub fn new(
+        receiver: Receiver<BlockInfo>,
+        account_subscriptions: Arc<AccountSubscriptions>,
+    ) -> Self {
         IndexerBroadcast {
             block_receiver: receiver,
             block_buffer: BlockBuffer::default(),
             ind_senders: Mutex::new(vec![]),
+            last_slot: None,
+            account_subscriptions,
         }
     }
+    ///Last slot sent to indexers. Slots of the following broadcast blocks are greater
+    pub fn last_slot(&self) -> Option<u64> {
+        self.last_slot
+    }
     ///Init broadcast thread
     pub async fn try_recv(&mut self) -> bool {
         match self.block_receiver.try_recv() {
@@ -130,101 +258,63 @@ impl IndexerBroadcast {
             }
         }
     }
-    ///Call from main thread to add new indexer
-    pub fn register_indexer(
-        &mut self,
-        hash: &String,
-        encoded_filter: &Vec<u8>,
-        indexer_sender: Sender<Result<BlockResponse, Status>>,
-    ) {
-        // Decode filter
-        let filter: SolanaFilter = serde_json::from_slice(&encoded_filter).unwrap_or_default();
-        let mut filter_hashes = HashSet::default();
-        filter.keys.iter().for_each(|key| {
-            filter_hashes.insert(key.to_string());
-        });
-        ///Create block buffer to store received block from ChainDispatcher
+    ///Call from main thread to add new indexer.
+    /// The indexer gets the broadcast blocks from its start slot,
+    /// older blocks must be sent before registration
+    pub fn register_indexer(&mut self, indexer: IndexerInfo) {
         let mut senders = self.ind_senders.lock().unwrap();
-        senders.push(IndexerInfo {
-            hash: hash.clone(),
-            filter,
-            filter_hashes,
-            sender: indexer_sender,
-        });
+        senders.push(indexer);
     }
     async fn broadcast_blocks(&mut self, block_with_slots: Vec<ConfirmedBlockWithSlot>) {
         debug!("*** broadcast_blocks");
-        let mut filtered_blocks: HashMap<String, Vec<ConfirmedBlockWithSlot>> = HashMap::default();
+        if let Some(block) = block_with_slots.last() {
+            self.last_slot = Some(block.block_slot);
+        }
+        let missing_slots = block_with_slots
+            .iter()
+            .filter(|block| block.block.is_none())
+            .map(|block| block.block_slot)
+            .collect::<Vec<Slot>>();
+        if !missing_slots.is_empty() {
+            warn!(
+                "Blocks of slots {:?} could not be got, indexers get them as gaps",
+                &missing_slots
+            );
+        }
+        let snapshots = block_with_slots
+            .iter()
+            .map(|block| {
+                (
+                    block.block_slot,
+                    self.account_subscriptions.take(block.block_slot),
+                )
+            })
+            .collect::<HashMap<Slot, Vec<AccountSnapshot>>>();
         let mut indexers = self.ind_senders.lock().unwrap();
         //Remove stop indexers
-        indexers.retain(|indexer| !indexer.sender.is_closed());
-        block_with_slots
-            .iter()
-            .filter(|block| block.block.is_some())
-            .for_each(|block| {
-                let ref_block = block.block.as_ref().unwrap();
-                //Clone ConfirmedBlock for each indexer with empty transactions
-                let mut indexer_blocks: HashMap<_, _> = HashMap::from_iter(
-                    indexers
-                        .iter()
-                        .map(|indexer| (indexer.hash.clone(), block.cheap_clone())),
-                );
-                //Iterate throw transactions and clone it for interested indexer
-                ref_block.transactions.iter().for_each(|tran| {
-                    let keys = tran
-                        .transaction
-                        .message
-                        .account_keys
-                        .iter()
-                        .map(|key| key.to_string())
-                        .collect::<Vec<String>>();
-                    indexers.iter().for_each(|indexer| {
-                        if keys.iter().any(|key| indexer.filter_hashes.contains(key)) {
-                            indexer_blocks
-                                .get_mut(&indexer.hash)
-                                .unwrap()
-                                .block
-                                .as_mut()
-                                .unwrap()
-                                .transactions
-                                .push(tran.clone());
-                        }
-                    });
-                });
-                indexer_blocks.into_iter().for_each(|(hash, block)| {
-                    if block.block.as_ref().unwrap().transactions.len() > 0 {
-                        filtered_blocks
-                            .entry(hash)
-                            .or_insert_with(Vec::new)
-                            .push(block);
-                    }
-                });
-            });
+        indexers.retain(|indexer| !indexer.is_closed());
         for indexer in indexers.iter() {
-            if indexer.sender.capacity() == 0 {
-                info!(
-                    "Channel buffer for indexer {:?} is full. {:?} blocks: {:?} are not send.",
+            let sent = timeout(
+                Duration::from_secs(SEND_TIMEOUT_SEC),
+                indexer.send_blocks(&block_with_slots, &snapshots),
+            )
+            .await;
+            if sent.is_err() {
+                warn!(
+                    "Channel buffer for indexer {:?} is full. Blocks of slots {:?} are not sent, the indexer gets them as gaps",
                     &indexer.hash,
-                    block_with_slots.len(),
                     block_with_slots
                         .iter()
                         .map(|block| block.block_slot)
                         .collect::<Vec<Slot>>()
                 );
-                break;
-            }
-            if let Some(blocks) = filtered_blocks.remove(&indexer.hash) {
-                let block_response = Self::create_block_response(blocks);
-                info!(
-                    "*** GRPC Send block_response to indexer {:?}. Channel capacity:  {:?}",
-                    &indexer.hash,
-                    indexer.sender.capacity()
-                );
-                indexer.sender.send(Ok(block_response)).await;
             }
         }
     }
-    fn create_block_response(blocks: Vec<ConfirmedBlockWithSlot>) -> BlockResponse {
+    fn create_block_response(
+        blocks: Vec<ConfirmedBlockWithSlot>,
+        accounts: impl Fn(Slot) -> Vec<AccountSnapshot>,
+    ) -> BlockResponse {
         let ext_blocks = blocks
             .into_iter()
             .map(|block_with_slot| {
@@ -243,6 +333,7 @@ impl IndexerBroadcast {
                     block_number: block_slot,
                     block: block.unwrap(),
                     list_log_messages,
+                    accounts: accounts(block_slot),
                 }
             })
             .collect::<Vec<ExtBlock>>();
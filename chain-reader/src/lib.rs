@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate clap;
 
+pub mod account_subscription;
 pub mod command;
 pub mod grpc_stream;
 pub mod indexer_broadcast;
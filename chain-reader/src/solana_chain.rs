@@ -170,7 +170,8 @@ fn decode_and_filter(
 ) -> Option<solana_transaction_status::ConfirmedBlock> {
     let decode_block = decode_encoded_block(block);
     let filtered_block = filter.filter_block(decode_block);
-    if filtered_block.transactions.is_empty() {
+    // Unfiltered consumers like analytics need every block, even without transactions
+    if filtered_block.transactions.is_empty() && !filter.keys.is_empty() {
         println!(
             "Block slot {} has no match Transaction",
             &filtered_block.parent_slot + 1
@@ -350,9 +351,11 @@ pub async fn loop_get_block(
     }
 
     // start from the last indexed block
-    let mut last_indexed_slot: u64 = match filter_txs.last().map(|tx| tx.slot) {
-        Some(last_indexed_slot) => last_indexed_slot,
-        None => {
+    let mut last_indexed_slot: u64 = match (filter_txs.last().map(|tx| tx.slot), start_block) {
+        (Some(last_indexed_slot), _) => last_indexed_slot,
+        // Without filter every block from the start block is sent
+        (None, Some(start_block)) if filter.keys.is_empty() => *start_block,
+        (None, _) => {
             // let get_slot = || {
             //     client.get_slot().unwrap_or_else(get_slot)
             // };
@@ -361,7 +364,8 @@ pub async fn loop_get_block(
             loop {
                 match client.get_slot() {
                     Ok(_last_indexed_slot) => {
-                        last_indexed_slot = _last_indexed_slot - BLOCK_AVAILABLE_MARGIN;
+                        last_indexed_slot =
+                            _last_indexed_slot.saturating_sub(BLOCK_AVAILABLE_MARGIN);
                         break;
                     }
                     Err(_) => continue,
@@ -388,9 +392,10 @@ pub async fn loop_get_block(
         match client.get_slot() {
             Ok(new_slot) => {
                 // Root is finalized block in Solana
-                let current_root = new_slot - BLOCK_AVAILABLE_MARGIN;
+                let current_root = new_slot.saturating_sub(BLOCK_AVAILABLE_MARGIN);
                 //info!("Root: {:?}",new_info.root);
-                if current_root == last_indexed_slot {
+                // The start block can be ahead of the root
+                if current_root <= last_indexed_slot {
                     sleep(Duration::from_millis(GET_NEW_SLOT_DELAY_MS)).await;
                     continue;
                 }
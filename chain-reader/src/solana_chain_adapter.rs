@@ -50,7 +50,7 @@ impl ChainAdapter {
             match self.client.get_slot() {
                 Ok(new_slot) => {
                     // Root is finalized block in Solana
-                    let current_root = new_slot - BLOCK_AVAILABLE_MARGIN;
+                    let current_root = new_slot.saturating_sub(BLOCK_AVAILABLE_MARGIN);
                     //Send current slot to broadcaster only in first time
                     if first_notification_to_broadcast {
                         self.sender.send(BlockInfo::from(current_root)).await;
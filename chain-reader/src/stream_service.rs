@@ -1,12 +1,13 @@
 //use crate::command::Config;
-use crate::indexer_broadcast::IndexerBroadcast;
+use crate::account_subscription::AccountSubscriptions;
+use crate::indexer_broadcast::{IndexerBroadcast, IndexerInfo};
 use crate::solana_chain_adapter::ChainAdapter;
 use crate::{solana_chain, DEFAULT_NETWORK};
 use chain_ethereum::{Chain, TriggerFilter};
 use chain_solana::adapter::{SolanaNetworkAdapter, SolanaNetworkAdapters};
 use chain_solana::types::{ChainConfig, ConfirmedBlockWithSlot};
 use chain_solana::SOLANA_NETWORKS;
-use log::{error, info};
+use log::{error, info, warn};
 use massbit::prelude::tokio::sync::mpsc::Sender;
 use massbit_chain_solana::data_type::SolanaFilter;
 use massbit_common::prelude::tokio::sync::RwLock;
@@ -27,6 +28,11 @@ use web3::api::Net;
 use web3::types::BlockId::Hash;
 
 const QUEUE_BUFFER: usize = 1024;
+/// Number of slots in a getBlocks request for history blocks
+const HISTORY_SLOT_RANGE: u64 = 1000;
+/// Number of history blocks in a response
+const HISTORY_BATCH_SIZE: usize = 10;
+const HISTORY_DELAY_MS: u64 = 500;
 
 pub struct StreamService {
     network_services: RwLock<HashMap<String, NetworkService>>,
@@ -69,18 +75,29 @@ impl Stream for StreamService {
 struct NetworkService {
     network: String,
     chain_adapters: Arc<Mutex<SolanaNetworkAdapters>>,
+    /// Copy of chain_adapters for history blocks, the chain reader thread keeps them locked.
+    /// Both copies share the request permits and the rate limit of each adapter
+    history_adapters: SolanaNetworkAdapters,
     broadcaster: Arc<Mutex<IndexerBroadcast>>,
+    account_subscriptions: Arc<AccountSubscriptions>,
 }
 
 impl NetworkService {
     fn new(network: &str) -> Self {
         let (tx, rx) = mpsc::channel(QUEUE_BUFFER);
         //let chain_adapter = Arc::new(Mutex::new(ChainAdapter::new(config, tx)));
-        let broadcaster = Arc::new(Mutex::new(IndexerBroadcast::new(rx)));
+        let account_subscriptions = Arc::new(AccountSubscriptions::new(network));
+        let broadcaster = Arc::new(Mutex::new(IndexerBroadcast::new(
+            rx,
+            account_subscriptions.clone(),
+        )));
+        let chain_adapters = SolanaNetworkAdapters::new(network, Some(tx));
         NetworkService {
             network: network.to_string(),
-            chain_adapters: Arc::new(Mutex::new(SolanaNetworkAdapters::new(network, Some(tx)))),
+            history_adapters: chain_adapters.clone(),
+            chain_adapters: Arc::new(Mutex::new(chain_adapters)),
             broadcaster,
+            account_subscriptions,
         }
     }
     fn init(&mut self) {
@@ -113,10 +130,91 @@ impl NetworkService {
         request: &BlockRequest,
         indexer_sender: Sender<Result<BlockResponse, Status>>,
     ) {
-        self.broadcaster.lock().unwrap().register_indexer(
+        let indexer = IndexerInfo::new(
             &request.indexer_hash,
             &request.filter,
+            request.start_block_number,
             indexer_sender,
         );
+        let account_subscription = self.account_subscriptions.subscribe(indexer.filter_keys());
+        let indexer = indexer.with_account_subscription(account_subscription);
+        // Without the flag the broadcast blocks are sent from the start block on
+        let history_start = request
+            .start_block_number
+            .filter(|_| request.replay_history);
+        match history_start {
+            None => self.broadcaster.lock().unwrap().register_indexer(indexer),
+            Some(start_slot) => {
+                // history thread, registers the indexer when it reaches the broadcast blocks
+                let adapters = self.history_adapters.clone();
+                let broadcaster = self.broadcaster.clone();
+                let name = format!("{:?}_history_{}", &self.network, indexer.hash());
+                massbit::spawn_thread(name, move || {
+                    massbit::block_on(task::unconstrained(async move {
+                        send_history_blocks(indexer, start_slot, adapters, broadcaster).await;
+                    }))
+                });
+            }
+        }
+    }
+}
+
+/// Send the blocks from `start_slot` to the last broadcast slot to the indexer,
+/// then register it to the broadcaster for the following blocks
+async fn send_history_blocks(
+    indexer: IndexerInfo,
+    start_slot: u64,
+    mut adapters: SolanaNetworkAdapters,
+    broadcaster: Arc<Mutex<IndexerBroadcast>>,
+) {
+    let mut next_slot = start_slot;
+    loop {
+        if indexer.is_closed() {
+            info!("Indexer {:?} is stopped", indexer.hash());
+            return;
+        }
+        let last_slot = broadcaster.lock().unwrap().last_slot();
+        let end_slot = match last_slot {
+            //Wait for the first broadcast blocks
+            None => {
+                sleep(Duration::from_millis(HISTORY_DELAY_MS)).await;
+                continue;
+            }
+            Some(last_slot) if last_slot < next_slot => {
+                let mut broadcaster = broadcaster.lock().unwrap();
+                //Blocks may be broadcast since the last check
+                if broadcaster
+                    .last_slot()
+                    .map_or(false, |last_slot| last_slot < next_slot)
+                {
+                    info!(
+                        "Indexer {:?} gets broadcast blocks from slot {}",
+                        indexer.hash(),
+                        next_slot
+                    );
+                    broadcaster.register_indexer(indexer);
+                    return;
+                }
+                continue;
+            }
+            Some(last_slot) => last_slot.min(next_slot + HISTORY_SLOT_RANGE - 1),
+        };
+        let slots = match adapters.get_block_slots_until(next_slot, end_slot) {
+            Ok(slots) => slots,
+            Err(err) => {
+                warn!(
+                    "Cannot get history blocks from {} to {}: {:?}",
+                    next_slot, end_slot, &err
+                );
+                sleep(Duration::from_millis(HISTORY_DELAY_MS)).await;
+                continue;
+            }
+        };
+        for batch in slots.chunks(HISTORY_BATCH_SIZE) {
+            let blocks = adapters.get_blocks_data(batch).await;
+            // Account changes are only notified for the broadcast blocks
+            indexer.send_blocks(&blocks, &HashMap::new()).await;
+        }
+        next_slot = end_slot + 1;
     }
 }
@@ -16,6 +16,7 @@ pub use crate::adapter::{
     TriggerFilter,
 };
 pub use crate::chain::Chain;
+pub use ethabi;
 
 // ETHDEP: These concrete types should probably not be exposed.
 pub use data_source::{DataSource, DataSourceTemplate, Mapping, MappingABI, TemplateSource};
@@ -0,0 +1,21 @@
+use std::env;
+use std::process::Command;
+
+/// Exports the compiler version and the target to the SDK, they are embedded in the plugins
+/// and compared by the loader of the indexer-manager.
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=MASSBIT_RUSTC_VERSION={}", rustc_version);
+    println!(
+        "cargo:rustc-env=MASSBIT_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+    println!("cargo:rerun-if-env-changed=RUSTC");
+}
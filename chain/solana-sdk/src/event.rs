@@ -0,0 +1,583 @@
+//! Decoding of the events Anchor programs emit into the transaction logs.
+//! An event is logged as `Program data: <base64>` (`Program log: <base64>`
+//! by older programs); the payload is the first 8 bytes of
+//! `sha256("event:<name>")` followed by the Borsh serialized fields as
+//! described by the `events` and `types` of the program IDL.
+use crate::types::{SolanaBlock, SolanaLogMessages};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use solana_program::hash::hash;
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+pub type Discriminator = [u8; 8];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdlType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    U128,
+    I128,
+    F32,
+    F64,
+    String,
+    Bytes,
+    PublicKey,
+    Vec(Box<IdlType>),
+    Option(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    /// A type of the `types` section of the IDL
+    Defined(String),
+}
+
+impl IdlType {
+    /// Parse the IDL notation, e.g. `"u64"`, `{"vec": "publicKey"}` or
+    /// `{"array": ["u8", 32]}`
+    pub fn from_json(value: &Value) -> Result<Self, anyhow::Error> {
+        match value {
+            Value::String(name) => match name.as_str() {
+                "bool" => Ok(IdlType::Bool),
+                "u8" => Ok(IdlType::U8),
+                "i8" => Ok(IdlType::I8),
+                "u16" => Ok(IdlType::U16),
+                "i16" => Ok(IdlType::I16),
+                "u32" => Ok(IdlType::U32),
+                "i32" => Ok(IdlType::I32),
+                "u64" => Ok(IdlType::U64),
+                "i64" => Ok(IdlType::I64),
+                "u128" => Ok(IdlType::U128),
+                "i128" => Ok(IdlType::I128),
+                "f32" => Ok(IdlType::F32),
+                "f64" => Ok(IdlType::F64),
+                "string" => Ok(IdlType::String),
+                "bytes" => Ok(IdlType::Bytes),
+                "publicKey" | "pubkey" => Ok(IdlType::PublicKey),
+                _ => Err(anyhow!("Unsupported IDL type {}", name)),
+            },
+            Value::Object(map) => {
+                if let Some(inner) = map.get("vec") {
+                    Ok(IdlType::Vec(Box::new(IdlType::from_json(inner)?)))
+                } else if let Some(inner) = map.get("option") {
+                    Ok(IdlType::Option(Box::new(IdlType::from_json(inner)?)))
+                } else if let Some(Value::Array(array)) = map.get("array") {
+                    match (array.first(), array.get(1).and_then(Value::as_u64)) {
+                        (Some(inner), Some(len)) => Ok(IdlType::Array(
+                            Box::new(IdlType::from_json(inner)?),
+                            len as usize,
+                        )),
+                        _ => Err(anyhow!("Invalid IDL array {}", value)),
+                    }
+                } else if let Some(defined) = map.get("defined") {
+                    // Newer IDLs have `{"defined": {"name": ...}}`
+                    defined
+                        .as_str()
+                        .or_else(|| defined.get("name").and_then(Value::as_str))
+                        .map(|name| IdlType::Defined(name.to_string()))
+                        .ok_or_else(|| anyhow!("Invalid IDL defined type {}", value))
+                } else {
+                    Err(anyhow!("Unsupported IDL type {}", value))
+                }
+            }
+            _ => Err(anyhow!("Unsupported IDL type {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IdlField {
+    pub name: String,
+    pub ty: IdlType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IdlTypeDef {
+    Struct(Vec<IdlField>),
+    /// Variants with their fields, in the order of the Borsh discriminant
+    Enum(Vec<(String, Vec<IdlField>)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventLayout {
+    pub name: String,
+    pub fields: Vec<IdlField>,
+}
+
+/// An event decoded from the logs of a transaction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgramEvent {
+    pub program_id: String,
+    pub name: String,
+    /// Index of the transaction in its block, 0 if the logs are decoded
+    /// without their block
+    #[serde(default)]
+    pub tx_index: usize,
+    /// Index of the log message which holds the event
+    pub log_index: usize,
+    /// The fields of the event by name. 64 bit integers are numbers, 128 bit
+    /// integers strings, public keys base58 and bytes base64 strings. Null for
+    /// the events which are only unpacked from their payload
+    pub data: Value,
+    /// The logged payload, discriminator included, for handlers which
+    /// unpack the event into their own types
+    #[serde(skip)]
+    pub payload: Vec<u8>,
+}
+
+/// Decodes the events of one program
+#[derive(Clone, Debug)]
+pub struct EventDecoder {
+    program_id: Pubkey,
+    events: HashMap<Discriminator, EventLayout>,
+    types: HashMap<String, IdlTypeDef>,
+}
+
+impl EventDecoder {
+    /// Create the decoder from the JSON IDL of the program
+    pub fn from_idl(program_id: Pubkey, idl: &str) -> Result<Self, anyhow::Error> {
+        let idl: Value = serde_json::from_str(idl).context("Invalid IDL")?;
+        let mut types = HashMap::default();
+        for def in idl
+            .get("types")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = json_str(def, "name")?;
+            let ty = def
+                .get("type")
+                .ok_or_else(|| anyhow!("IDL type {} has no definition", name))?;
+            let type_def = match ty.get("kind").and_then(Value::as_str) {
+                Some("struct") => IdlTypeDef::Struct(parse_fields(ty.get("fields"))?),
+                Some("enum") => {
+                    let mut variants = Vec::default();
+                    for variant in ty
+                        .get("variants")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                    {
+                        variants.push((
+                            json_str(variant, "name")?,
+                            parse_fields(variant.get("fields"))?,
+                        ));
+                    }
+                    IdlTypeDef::Enum(variants)
+                }
+                _ => return Err(anyhow!("Unsupported kind of IDL type {}", name)),
+            };
+            types.insert(name, type_def);
+        }
+        let mut events = HashMap::default();
+        for event in idl
+            .get("events")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = json_str(event, "name")?;
+            let layout = EventLayout {
+                name: name.clone(),
+                fields: parse_fields(event.get("fields"))?,
+            };
+            events.insert(event_discriminator(&name), layout);
+        }
+        Ok(EventDecoder {
+            program_id,
+            events,
+            types,
+        })
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &EventLayout> {
+        self.events.values()
+    }
+
+    /// Decode an event payload, `None` if it is not an event of the IDL
+    pub fn decode(&self, data: &[u8]) -> Option<(String, Value)> {
+        if data.len() < 8 {
+            return None;
+        }
+        let discriminator: Discriminator = data[..8].try_into().ok()?;
+        let layout = self.events.get(&discriminator)?;
+        let mut reader = BorshReader::new(&data[8..]);
+        let value = self.decode_fields(&layout.fields, &mut reader)?;
+        Some((layout.name.clone(), value))
+    }
+
+    /// Decode the events which the program emitted in `log_messages`, see
+    /// [`program_payloads`]
+    pub fn decode_logs(&self, log_messages: &[String]) -> Vec<ProgramEvent> {
+        let program_id = self.program_id.to_string();
+        program_payloads(&self.program_id, log_messages)
+            .into_iter()
+            .filter_map(|(log_index, payload)| {
+                let (name, data) = self.decode(&payload)?;
+                Some(ProgramEvent {
+                    program_id: program_id.clone(),
+                    name,
+                    tx_index: 0,
+                    log_index,
+                    data,
+                    payload,
+                })
+            })
+            .collect()
+    }
+
+    /// The events of the successful transactions of `block`
+    pub fn decode_block(&self, block: &SolanaBlock) -> Vec<ProgramEvent> {
+        let mut events = Vec::default();
+        for (tx_index, tran) in block.block.transactions.iter().enumerate() {
+            let log_messages = match &tran.meta {
+                Some(meta) if meta.status.is_ok() => match &meta.log_messages {
+                    Some(log_messages) => log_messages,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            events.extend(
+                self.decode_logs(log_messages)
+                    .into_iter()
+                    .map(|event| ProgramEvent { tx_index, ..event }),
+            );
+        }
+        events
+    }
+
+    /// The events of the transaction of `message`
+    pub fn decode_log_messages(&self, message: &SolanaLogMessages) -> Vec<ProgramEvent> {
+        message
+            .log_messages
+            .as_ref()
+            .map(|log_messages| self.decode_logs(log_messages))
+            .unwrap_or_default()
+    }
+
+    fn decode_fields(&self, fields: &[IdlField], reader: &mut BorshReader) -> Option<Value> {
+        let mut map = Map::new();
+        for field in fields {
+            map.insert(field.name.clone(), self.decode_value(&field.ty, reader)?);
+        }
+        Some(Value::Object(map))
+    }
+
+    fn decode_value(&self, ty: &IdlType, reader: &mut BorshReader) -> Option<Value> {
+        let value = match ty {
+            IdlType::Bool => json!(reader.take(1)?[0] != 0),
+            IdlType::U8 => json!(reader.take(1)?[0]),
+            IdlType::I8 => json!(reader.take(1)?[0] as i8),
+            IdlType::U16 => json!(u16::from_le_bytes(reader.array()?)),
+            IdlType::I16 => json!(i16::from_le_bytes(reader.array()?)),
+            IdlType::U32 => json!(u32::from_le_bytes(reader.array()?)),
+            IdlType::I32 => json!(i32::from_le_bytes(reader.array()?)),
+            IdlType::U64 => json!(u64::from_le_bytes(reader.array()?)),
+            IdlType::I64 => json!(i64::from_le_bytes(reader.array()?)),
+            IdlType::U128 => json!(u128::from_le_bytes(reader.array()?).to_string()),
+            IdlType::I128 => json!(i128::from_le_bytes(reader.array()?).to_string()),
+            IdlType::F32 => json!(f32::from_le_bytes(reader.array()?)),
+            IdlType::F64 => json!(f64::from_le_bytes(reader.array()?)),
+            IdlType::String => {
+                let len = reader.read_len()?;
+                json!(String::from_utf8(reader.take(len)?.to_vec()).ok()?)
+            }
+            IdlType::Bytes => {
+                let len = reader.read_len()?;
+                json!(base64::encode(reader.take(len)?))
+            }
+            IdlType::PublicKey => json!(Pubkey::new(reader.take(32)?).to_string()),
+            IdlType::Vec(inner) => {
+                let len = reader.read_len()?;
+                // Elements of empty types take no bytes, bound their count as
+                // well so that a corrupt length can not loop up to 2^32 times
+                if len > reader.remaining() {
+                    return None;
+                }
+                self.decode_values(inner, len, reader)?
+            }
+            IdlType::Option(inner) => match reader.take(1)?[0] {
+                0 => Value::Null,
+                _ => self.decode_value(inner, reader)?,
+            },
+            IdlType::Array(inner, len) => self.decode_values(inner, *len, reader)?,
+            IdlType::Defined(name) => match self.types.get(name)? {
+                IdlTypeDef::Struct(fields) => self.decode_fields(fields, reader)?,
+                IdlTypeDef::Enum(variants) => {
+                    let (variant, fields) = variants.get(reader.take(1)?[0] as usize)?;
+                    if fields.is_empty() {
+                        json!(variant)
+                    } else {
+                        json!({ variant: self.decode_fields(fields, reader)? })
+                    }
+                }
+            },
+        };
+        Some(value)
+    }
+
+    fn decode_values(&self, ty: &IdlType, len: usize, reader: &mut BorshReader) -> Option<Value> {
+        let mut values = Vec::with_capacity(len.min(reader.remaining()));
+        for _ in 0..len {
+            values.push(self.decode_value(ty, reader)?);
+        }
+        Some(Value::Array(values))
+    }
+}
+
+/// The base64 payloads which `program_id` logged in `log_messages` with the
+/// index of their log message. The invocation stack is followed, so payloads
+/// logged by other programs (e.g. a program invoked through CPI) are skipped
+pub fn program_payloads(program_id: &Pubkey, log_messages: &[String]) -> Vec<(usize, Vec<u8>)> {
+    let program_id = program_id.to_string();
+    let mut stack: Vec<&str> = Vec::default();
+    let mut payloads = Vec::default();
+    for (log_index, log) in log_messages.iter().enumerate() {
+        let rest = match log.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        if let Some(payload) = rest
+            .strip_prefix("data: ")
+            .or_else(|| rest.strip_prefix("log: "))
+        {
+            if stack.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            if let Ok(payload) = base64::decode(payload.trim()) {
+                payloads.push((log_index, payload));
+            }
+            continue;
+        }
+        let mut parts = rest.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(program), Some("invoke")) => stack.push(program),
+            (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    payloads
+}
+
+/// The first 8 bytes of `sha256("<namespace>:<name>")`, the way Anchor tags
+/// instructions (`global`), accounts (`account`) and events (`event`)
+pub fn discriminator(namespace: &str, name: &str) -> Discriminator {
+    let mut discriminator = Discriminator::default();
+    discriminator
+        .copy_from_slice(&hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// The first 8 bytes of `sha256("event:<name>")`
+pub fn event_discriminator(name: &str) -> Discriminator {
+    discriminator("event", name)
+}
+
+fn json_str(value: &Value, key: &str) -> Result<String, anyhow::Error> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| anyhow!("Missing {} in IDL item {}", key, value))
+}
+
+/// Named fields `[{"name": .., "type": ..}]`, or the types of a tuple
+/// variant which are named by their position
+fn parse_fields(fields: Option<&Value>) -> Result<Vec<IdlField>, anyhow::Error> {
+    fields
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, field)| match field.get("type") {
+            Some(ty) => Ok(IdlField {
+                name: json_str(field, "name")?,
+                ty: IdlType::from_json(ty)?,
+            }),
+            None => Ok(IdlField {
+                name: index.to_string(),
+                ty: IdlType::from_json(field)?,
+            }),
+        })
+        .collect()
+}
+
+struct BorshReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BorshReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BorshReader { data, pos: 0 }
+    }
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.remaining() {
+            return None;
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Some(slice)
+    }
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+    /// Length prefix of strings, bytes and vectors
+    fn read_len(&mut self) -> Option<usize> {
+        Some(u32::from_le_bytes(self.array()?) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDL: &str = r#"{
+        "types": [
+            {"name": "Side", "type": {"kind": "enum", "variants": [
+                {"name": "Bid"},
+                {"name": "Ask", "fields": [{"name": "price", "type": "u64"}]}
+            ]}},
+            {"name": "Empty", "type": {"kind": "struct", "fields": []}}
+        ],
+        "events": [
+            {"name": "Trade", "fields": [
+                {"name": "owner", "type": "publicKey", "index": false},
+                {"name": "amount", "type": "u64", "index": false},
+                {"name": "total", "type": "u128", "index": false},
+                {"name": "memo", "type": {"option": "string"}, "index": false},
+                {"name": "sides", "type": {"vec": {"defined": "Side"}}, "index": false},
+                {"name": "flags", "type": {"array": ["bool", 2]}, "index": false}
+            ]},
+            {"name": "Empties", "fields": [
+                {"name": "items", "type": {"vec": {"defined": "Empty"}}, "index": false}
+            ]}
+        ]
+    }"#;
+
+    fn decoder(program_id: Pubkey) -> EventDecoder {
+        EventDecoder::from_idl(program_id, IDL).unwrap()
+    }
+
+    fn trade_payload(owner: &Pubkey) -> Vec<u8> {
+        let mut data = event_discriminator("Trade").to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&u128::MAX.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"hi");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.push(0);
+        data.push(1);
+        data.extend_from_slice(&5u64.to_le_bytes());
+        data.extend_from_slice(&[1, 0]);
+        data
+    }
+
+    #[test]
+    fn decodes_event_fields() {
+        let owner = Pubkey::new_unique();
+        let (name, data) = decoder(Pubkey::new_unique())
+            .decode(&trade_payload(&owner))
+            .unwrap();
+        assert_eq!(name, "Trade");
+        assert_eq!(
+            data,
+            json!({
+                "owner": owner.to_string(),
+                "amount": 7,
+                "total": u128::MAX.to_string(),
+                "memo": "hi",
+                "sides": ["Bid", {"Ask": {"price": 5}}],
+                "flags": [true, false]
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_events() {
+        let decoder = decoder(Pubkey::new_unique());
+        let mut payload = trade_payload(&Pubkey::new_unique());
+        assert!(decoder.decode(&payload[..payload.len() - 1]).is_none());
+        payload[0] ^= 0xff;
+        assert!(decoder.decode(&payload).is_none());
+        assert!(decoder.decode(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn bounds_vec_of_empty_types() {
+        let decoder = decoder(Pubkey::new_unique());
+        let mut data = event_discriminator("Empties").to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decoder.decode(&data).is_none());
+
+        let mut data = event_discriminator("Empties").to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            decoder.decode(&data).map(|(_, data)| data),
+            Some(json!({"items": [{}, {}]}))
+        );
+    }
+
+    #[test]
+    fn decodes_only_events_logged_by_the_program() {
+        let program_id = Pubkey::new_unique();
+        let other_id = Pubkey::new_unique();
+        let payload = base64::encode(trade_payload(&Pubkey::new_unique()));
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", program_id),
+            format!("Program data: {}", payload),
+            format!("Program {} invoke [2]", other_id),
+            format!("Program data: {}", payload),
+            format!("Program {} success", other_id),
+            format!("Program log: {}", payload),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", other_id),
+            format!("Program data: {}", payload),
+            format!("Program {} failed: custom program error: 0x1", other_id),
+            format!("Program data: {}", payload),
+        ];
+        let events = decoder(program_id).decode_logs(&logs);
+        let indexes: Vec<usize> = events.iter().map(|event| event.log_index).collect();
+        assert_eq!(indexes, vec![1, 5]);
+        assert!(events
+            .iter()
+            .all(|event| event.name == "Trade" && event.program_id == program_id.to_string()));
+        assert!(events
+            .iter()
+            .all(|event| event.payload.starts_with(&event_discriminator("Trade"))));
+    }
+
+    #[test]
+    fn skips_logs_which_are_not_base64() {
+        let program_id = Pubkey::new_unique();
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", program_id),
+            String::from("Program log: Instruction: Trade"),
+            format!("Program data: {}", base64::encode([1, 2, 3])),
+            format!("Program {} success", program_id),
+        ];
+        assert_eq!(
+            program_payloads(&program_id, &logs),
+            vec![(2, vec![1, 2, 3])]
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod event;
 pub mod plugin;
 pub mod store;
 pub mod types;
@@ -8,5 +9,7 @@ lazy_static! {
     pub static ref COMPONENT_NAME: String = String::from("[Solana-SDK]");
 }
 pub mod entity {
+    pub use massbit_data::store::entity::{EntityFilter, EntityOrder, EntityRange};
+    pub use massbit_data::store::scalar::{BigDecimal, BigInt, Bytes};
     pub use massbit_data::store::{Attribute, Entity, Value};
 }
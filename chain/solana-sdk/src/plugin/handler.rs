@@ -1,3 +1,4 @@
+use crate::event::ProgramEvent;
 use crate::types::{SolanaBlock, SolanaLogMessages, SolanaTransaction};
 use std::error::Error;
 
@@ -11,6 +12,15 @@ pub trait SolanaHandler {
     fn handle_log_messages(&self, _message: &SolanaLogMessages) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
+    /// Events the program of the handler emitted in `block`, in the order of their transactions
+    /// and log messages. Decode them with an `EventDecoder` or unpack their payload
+    fn handle_events(
+        &self,
+        _block: &SolanaBlock,
+        _events: &[ProgramEvent],
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
     fn handle_blocks(&self, _blocks: &Vec<SolanaBlock>) -> Result<i64, Box<dyn Error>> {
         Ok(0)
     }
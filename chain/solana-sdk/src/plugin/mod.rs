@@ -7,6 +7,12 @@ use std::sync::{Arc, Mutex};
 
 pub mod handler;
 pub mod proxy;
+pub mod version;
+
+pub use version::{
+    check_plugin_exports, check_plugin_library, check_plugin_version, version_field, PluginVersion,
+    VersionField, ABI_FINGERPRINT, RUSTC_VERSION, SDK_VERSION,
+};
 
 pub trait PluginRegistrar {
     fn register_solana_handler(&mut self, handler: Box<dyn SolanaHandler + Send + Sync>);
@@ -41,5 +47,16 @@ macro_rules! export_plugin {
             $crate::plugin::AdapterDeclaration {
                 register: $register,
             };
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_sdk_version: $crate::plugin::VersionField =
+            $crate::plugin::version_field($crate::plugin::SDK_VERSION);
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_rustc_version: $crate::plugin::VersionField =
+            $crate::plugin::version_field($crate::plugin::RUSTC_VERSION);
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static massbit_abi_fingerprint: u64 = $crate::plugin::ABI_FINGERPRINT;
     };
 }
@@ -1,3 +1,4 @@
+use crate::event::ProgramEvent;
 use crate::plugin::handler::SolanaHandler;
 use crate::types::{SolanaBlock, SolanaTransaction};
 use std::error::Error;
@@ -17,4 +18,11 @@ impl SolanaHandler for SolanaHandlerProxy {
     fn handle_blocks(&self, blocks: &Vec<SolanaBlock>) -> Result<i64, Box<dyn Error>> {
         self.handler.handle_blocks(blocks)
     }
+    fn handle_events(
+        &self,
+        block: &SolanaBlock,
+        events: &[ProgramEvent],
+    ) -> Result<(), Box<dyn Error>> {
+        self.handler.handle_events(block, events)
+    }
 }
@@ -0,0 +1,321 @@
+use crate::entity::{Entity, Value};
+use crate::plugin::AdapterDeclaration;
+use crate::store::IndexStore;
+use crate::types::SolanaBlock;
+use libloading::Library;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+use std::str;
+
+/// Version of the SDK, exported by the plugins built with `export_plugin!`
+pub const SDK_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Compiler of the SDK, plugins and loaders must be built by the same compiler
+pub const RUSTC_VERSION: &str = env!("MASSBIT_RUSTC_VERSION");
+pub const TARGET: &str = env!("MASSBIT_TARGET");
+/// Version of the interface shared with the plugins. Layouts do not tell field reorders or changed
+/// trait methods apart, bump it on any change of `IndexStore`, `SolanaHandler`, `AdapterDeclaration` or the block types
+pub const ABI_VERSION: u32 = 3;
+/// Fingerprint of `ABI_VERSION`, the compiler, the target and the layouts of the types shared with the plugins
+pub const ABI_FINGERPRINT: u64 = abi_fingerprint();
+
+pub const SDK_VERSION_SYMBOL: &[u8] = b"massbit_sdk_version\0";
+pub const RUSTC_VERSION_SYMBOL: &[u8] = b"massbit_rustc_version\0";
+pub const ABI_FINGERPRINT_SYMBOL: &[u8] = b"massbit_abi_fingerprint\0";
+pub const ADAPTER_DECLARATION_SYMBOL: &[u8] = b"adapter_declaration\0";
+pub const STORE_SYMBOL: &[u8] = b"STORE\0";
+/// Size of the version fields exported by the plugins, padded with zeros
+pub const VERSION_FIELD_LENGTH: usize = 64;
+pub type VersionField = [u8; VERSION_FIELD_LENGTH];
+
+/// Fixed size field of a version, readable whatever the compiler of the plugin
+pub const fn version_field(version: &str) -> VersionField {
+    let bytes = version.as_bytes();
+    let mut field = [0u8; VERSION_FIELD_LENGTH];
+    let mut i = 0;
+    while i < bytes.len() && i < VERSION_FIELD_LENGTH {
+        field[i] = bytes[i];
+        i += 1;
+    }
+    field
+}
+
+pub fn read_version_field(field: &VersionField) -> Option<&str> {
+    let end = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    str::from_utf8(&field[..end]).ok()
+}
+
+/// FNV-1a hash, computed at compile time
+const fn hash_bytes(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+const fn abi_fingerprint() -> u64 {
+    let hash = layout_fingerprint(&[
+        size_of::<AdapterDeclaration>(),
+        align_of::<AdapterDeclaration>(),
+        size_of::<Option<&mut dyn IndexStore>>(),
+        size_of::<SolanaBlock>(),
+        align_of::<SolanaBlock>(),
+        size_of::<Entity>(),
+        size_of::<Value>(),
+    ]);
+    hash_bytes(hash, &ABI_VERSION.to_le_bytes())
+}
+
+/// Fingerprint of the compiler, the target and `layouts`, the sizes and alignments of the types
+/// a loader shares with its plugins
+pub const fn layout_fingerprint(layouts: &[usize]) -> u64 {
+    let mut hash = hash_bytes(0xcbf2_9ce4_8422_2325, RUSTC_VERSION.as_bytes());
+    hash = hash_bytes(hash, TARGET.as_bytes());
+    let mut i = 0;
+    while i < layouts.len() {
+        hash = hash_bytes(hash, &(layouts[i] as u64).to_le_bytes());
+        i += 1;
+    }
+    hash
+}
+
+/// Plugins are compatible with the SDK of the same major version, or of the same minor version before 1.0
+pub fn is_compatible_sdk_version(version: &str) -> bool {
+    is_compatible_version(version, SDK_VERSION)
+}
+
+/// `version` is compatible with `expected` if they have the same major version, or the same minor
+/// version before 1.0
+pub fn is_compatible_version(version: &str, expected: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split('.')
+            .take(2)
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()
+    };
+    match (parse(version), parse(expected)) {
+        (Some(plugin), Some(expected)) if plugin.len() == 2 && expected.len() == 2 => {
+            plugin[0] == expected[0] && (expected[0] > 0 || plugin[1] == expected[1])
+        }
+        _ => false,
+    }
+}
+
+/// Versions exported by a plugin, None for the plugins built with an older SDK
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PluginVersion {
+    pub sdk_version: Option<String>,
+    pub rustc_version: Option<String>,
+    pub abi_fingerprint: Option<u64>,
+}
+
+impl PluginVersion {
+    /// Reads the version symbols only, they have the same layout whatever the compiler of the plugin
+    ///
+    /// # Safety
+    ///
+    /// The library must export these symbols with the types of `export_plugin!`, or not at all.
+    pub unsafe fn read(lib: &Library) -> Self {
+        let read_field = |symbol: &[u8]| {
+            lib.get::<*const VersionField>(symbol)
+                .ok()
+                .and_then(|field| read_version_field(&**field).map(String::from))
+        };
+        PluginVersion {
+            sdk_version: read_field(SDK_VERSION_SYMBOL),
+            rustc_version: read_field(RUSTC_VERSION_SYMBOL),
+            abi_fingerprint: lib
+                .get::<*const u64>(ABI_FINGERPRINT_SYMBOL)
+                .ok()
+                .map(|fingerprint| **fingerprint),
+        }
+    }
+
+    /// Refuses the plugins built with an incompatible SDK, another compiler or other type layouts:
+    /// using their `adapter_declaration` or `STORE` would be undefined behaviour.
+    pub fn check(&self) -> Result<(), String> {
+        self.check_with("massbit-solana-sdk", SDK_VERSION, ABI_FINGERPRINT)
+    }
+
+    /// Refuses the plugins built with a version of `crate_name` which is not compatible with
+    /// `version`, another compiler or types whose layouts do not match `abi_fingerprint`
+    pub fn check_with(
+        &self,
+        crate_name: &str,
+        version: &str,
+        abi_fingerprint: u64,
+    ) -> Result<(), String> {
+        let plugin_version = self.sdk_version.as_ref().ok_or_else(|| {
+            format!(
+                "The plugin is built with a {} older than {} which does not export its version, \
+                 update it with `cargo update -p {}` and rebuild",
+                crate_name, version, crate_name
+            )
+        })?;
+        if !is_compatible_version(plugin_version, version) {
+            return Err(format!(
+                "The plugin is built with {} {} which is not compatible with {}, update it and rebuild",
+                crate_name, plugin_version, version
+            ));
+        }
+        match &self.rustc_version {
+            Some(rustc_version) if rustc_version == RUSTC_VERSION => {}
+            rustc_version => {
+                return Err(format!(
+                    "The plugin is built with {} but its loader with {}, rebuild it with the same compiler",
+                    rustc_version.as_deref().unwrap_or("an unknown compiler"),
+                    RUSTC_VERSION
+                ))
+            }
+        }
+        if self.abi_fingerprint != Some(abi_fingerprint) {
+            return Err(format!(
+                "The types shared with the plugin do not have the same layout for {}, \
+                 rebuild it with {} {} and {}",
+                TARGET, crate_name, version, RUSTC_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the versions of a loaded plugin before any of its other symbols is used
+///
+/// # Safety
+///
+/// Same as [`PluginVersion::read`].
+pub unsafe fn check_plugin_version(lib: &Library) -> Result<(), String> {
+    PluginVersion::read(lib).check()
+}
+
+/// Checks that a plugin library can be loaded by the indexer-manager:
+/// it exports the handler declaration and the store, built with a compatible SDK and the same compiler.
+/// The library is loaded to read its symbols, so its initializers are run:
+/// only use it on local libraries, uploaded libraries are checked without loading them.
+pub fn check_plugin_library(library_path: &Path) -> Vec<String> {
+    let lib = match unsafe { Library::new(library_path) } {
+        Ok(lib) => lib,
+        Err(err) => {
+            return vec![format!(
+                "Cannot load the library: {}, build it with `cargo build --release`",
+                err
+            )];
+        }
+    };
+    unsafe {
+        check_plugin_exports(
+            |symbol| lib.get::<*const u8>(symbol).is_ok(),
+            &PluginVersion::read(&lib),
+        )
+    }
+}
+
+/// Checks the exports of a plugin library, `is_exported` tells if a symbol like `STORE_SYMBOL` is exported
+/// and `version` holds the versions it exports
+pub fn check_plugin_exports<F>(is_exported: F, version: &PluginVersion) -> Vec<String>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    let mut errors = vec![];
+    if !is_exported(ADAPTER_DECLARATION_SYMBOL) {
+        errors.push(String::from(
+            "Symbol `adapter_declaration` is not exported, register the handler with `export_plugin!(register)`",
+        ));
+    }
+    if !is_exported(STORE_SYMBOL) {
+        errors.push(String::from(
+            "Symbol `STORE` is not exported, declare `#[no_mangle] pub static mut STORE: Option<&mut dyn IndexStore> = None;` in lib.rs",
+        ));
+    }
+    if let Err(err) = version.check() {
+        errors.push(err);
+    }
+    errors
+}
+
+/// Name of a symbol like `STORE_SYMBOL` in the symbol table, without the trailing zero
+pub fn symbol_name(symbol: &[u8]) -> &str {
+    let name = symbol.strip_suffix(b"\0").unwrap_or(symbol);
+    str::from_utf8(name).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_version() -> PluginVersion {
+        PluginVersion {
+            sdk_version: Some(SDK_VERSION.to_string()),
+            rustc_version: Some(RUSTC_VERSION.to_string()),
+            abi_fingerprint: Some(ABI_FINGERPRINT),
+        }
+    }
+
+    #[test]
+    fn compares_major_versions_then_minor_versions_before_1_0() {
+        assert!(is_compatible_sdk_version(SDK_VERSION));
+        assert!(is_compatible_version("1.2.0", "1.5.3"));
+        assert!(!is_compatible_version("2.0.0", "1.5.3"));
+        assert!(is_compatible_version("0.1.7", "0.1.0"));
+        assert!(!is_compatible_version("0.2.0", "0.1.0"));
+        assert!(!is_compatible_version("1", "1.0.0"));
+        assert!(!is_compatible_version("1.x.0", "1.0.0"));
+        assert!(!is_compatible_version("", "1.0.0"));
+    }
+
+    #[test]
+    fn reads_version_fields() {
+        assert_eq!(read_version_field(&version_field("0.1.0")), Some("0.1.0"));
+        assert_eq!(read_version_field(&version_field("")), Some(""));
+        let long_version = "9".repeat(VERSION_FIELD_LENGTH + 1);
+        assert_eq!(
+            read_version_field(&version_field(&long_version)),
+            Some(&long_version[..VERSION_FIELD_LENGTH])
+        );
+        let mut field = version_field("0.1.0");
+        field[0] = 0xff;
+        assert_eq!(read_version_field(&field), None);
+    }
+
+    #[test]
+    fn accepts_plugins_of_the_same_sdk_and_compiler() {
+        assert_eq!(current_version().check(), Ok(()));
+    }
+
+    #[test]
+    fn refuses_other_versions_compilers_and_layouts() {
+        let older_sdk = PluginVersion::default();
+        assert!(older_sdk
+            .check()
+            .unwrap_err()
+            .contains("does not export its version"));
+
+        let mut version = current_version();
+        version.sdk_version = Some(String::from("99.0.0"));
+        assert!(version.check().unwrap_err().contains("99.0.0"));
+
+        let mut version = current_version();
+        version.rustc_version = Some(String::from("rustc 0.0.0"));
+        assert!(version.check().unwrap_err().contains("rustc 0.0.0"));
+        version.rustc_version = None;
+        assert!(version.check().unwrap_err().contains("an unknown compiler"));
+
+        let mut version = current_version();
+        version.abi_fingerprint = Some(ABI_FINGERPRINT.wrapping_add(1));
+        assert!(version.check().unwrap_err().contains("same layout"));
+        version.abi_fingerprint = None;
+        assert!(version.check().is_err());
+    }
+
+    #[test]
+    fn strips_the_trailing_zero_of_symbols() {
+        assert_eq!(symbol_name(STORE_SYMBOL), "STORE");
+        assert_eq!(symbol_name(b"STORE"), "STORE");
+    }
+}
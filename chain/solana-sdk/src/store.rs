@@ -1,15 +1,21 @@
+use massbit_data::store::entity::{EntityFilter, EntityOrder, EntityRange};
 use massbit_data::store::Entity;
 use std::error::Error;
 
 pub trait IndexStore: Sync + Send {
     fn save(&mut self, entity_name: String, data: Entity);
     fn get(&mut self, entity_name: String, entity_id: &String) -> Option<Entity>;
-    // fn query(
-    //     &self,
-    //     entity_type: String,
-    //     filter: Option<EntityFilter>,
-    //     order: EntityOrder,
-    //     range: EntityRange,
-    // ) -> Vec<Entity>;
+    /// Removes an entity, the removal is written with the next flush
+    fn remove(&mut self, entity_name: String, entity_id: &String);
+    /// Queries the flushed entities of a type. The changes since the last flush, which are the
+    /// changes of the block being processed, are not seen: an entity saved by the block is not
+    /// returned, an entity removed by the block still is
+    fn query(
+        &self,
+        entity_name: String,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>>;
     fn flush(&mut self, block_hash: &String, block_slot: u64) -> Result<(), Box<dyn Error>>;
 }
@@ -17,6 +17,18 @@ pub struct ExtBlock {
     pub block_number: u64,
     pub block: Block,
     pub list_log_messages: Vec<LogMessages>,
+    /// States of the accounts of the filtered programs after the block
+    #[serde(default)]
+    pub accounts: Vec<AccountSnapshot>,
+}
+
+/// State of an account after the last change in a slot, as notified by the node
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
 }
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct ExtTransaction {
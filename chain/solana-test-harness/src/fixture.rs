@@ -0,0 +1,112 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use massbit_solana_sdk::types::SolanaBlock;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Extension of the compressed fixtures written by the recorder
+pub const FIXTURE_EXTENSION: &str = "json.gz";
+
+/// Name of the fixture of the blocks from `first_slot` to `last_slot`, sorted like the slots
+pub fn fixture_name(first_slot: u64, last_slot: u64) -> String {
+    format!("{:012}_{:012}.{}", first_slot, last_slot, FIXTURE_EXTENSION)
+}
+
+fn is_fixture(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| {
+            name.ends_with(".json") || name.ends_with(".json.gz")
+        })
+}
+
+/// Fixture files of a path: the path itself or the `.json` and `.json.gz` files of a directory, sorted by name
+pub fn fixture_files(path: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    if path.is_dir() {
+        let mut files = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| is_fixture(file))
+            .collect::<Vec<PathBuf>>();
+        files.sort();
+        Ok(files)
+    } else {
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+/// Reads the blocks of a fixture: a block or a list of blocks like the payload of the chain-reader `BlockResponse`,
+/// compressed with gzip if the file name ends with `.gz`
+pub fn read_fixture(file: &Path) -> Result<Vec<SolanaBlock>, anyhow::Error> {
+    let mut content = vec![];
+    if file.extension().map_or(false, |ext| ext == "gz") {
+        GzDecoder::new(File::open(file)?).read_to_end(&mut content)?;
+    } else {
+        File::open(file)?.read_to_end(&mut content)?;
+    }
+    match serde_json::from_slice::<Vec<SolanaBlock>>(&content) {
+        Ok(blocks) => Ok(blocks),
+        Err(_) => serde_json::from_slice::<SolanaBlock>(&content)
+            .map(|block| vec![block])
+            .map_err(|err| anyhow::anyhow!("Invalid block fixture {:?}: {}", file, err)),
+    }
+}
+
+/// Writes a `BlockResponse` payload (the JSON list of blocks) compressed with gzip
+pub fn write_fixture(file: &Path, payload: &[u8]) -> Result<(), anyhow::Error> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut encoder = GzEncoder::new(File::create(file)?, Compression::default());
+    encoder.write_all(payload)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(slot: u64) -> SolanaBlock {
+        SolanaBlock {
+            version: "1.7".to_string(),
+            timestamp: 1634000000,
+            block_number: slot,
+            block: Default::default(),
+            list_log_messages: vec![],
+            accounts: vec![],
+        }
+    }
+
+    #[test]
+    fn read_written_fixtures() {
+        let dir = std::env::temp_dir().join(format!("fixture_{}", std::process::id()));
+        let blocks = vec![block(11), block(12)];
+        write_fixture(
+            &dir.join(fixture_name(11, 12)),
+            &serde_json::to_vec(&blocks).unwrap(),
+        )
+        .unwrap();
+        // A single block is read as a list of one block
+        fs::write(
+            dir.join("000000000010.json"),
+            serde_json::to_vec(&block(10)).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "Not a fixture").unwrap();
+
+        let files = fixture_files(&dir).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("000000000010.json"),
+                dir.join("000000000011_000000000012.json.gz")
+            ]
+        );
+        assert_eq!(read_fixture(&files[0]).unwrap(), vec![block(10)]);
+        assert_eq!(read_fixture(&files[1]).unwrap(), blocks);
+        assert!(read_fixture(&dir.join("README.md")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+pub mod fixture;
+pub mod snapshot;
+pub mod store;
+
+pub use snapshot::EntitySnapshot;
+pub use store::{MemoryStore, SharedStore};
+
+use massbit_solana_sdk::entity::Entity;
+use massbit_solana_sdk::plugin::handler::SolanaHandler;
+use massbit_solana_sdk::store::IndexStore;
+use std::path::Path;
+use std::sync::MutexGuard;
+
+/// Replays block fixtures through the handler of a mapping, the entities are saved in a `MemoryStore`.
+///
+/// ```ignore
+/// let harness = ReplayHarness::new(|store| unsafe { STORE = Some(store) });
+/// harness.replay(&SolanaHandlerAdapter, "fixtures/serum")?;
+/// harness.assert_snapshot("snapshots/serum.json");
+/// ```
+pub struct ReplayHarness {
+    store: SharedStore<MemoryStore>,
+}
+
+impl ReplayHarness {
+    /// Creates the store and gives a handle of it to `inject`, which sets the `STORE` of the mapping.
+    /// The handle is leaked because the mapping keeps a `'static` reference to it,
+    /// the harness reads the entities through its own handle.
+    pub fn new<F>(inject: F) -> Self
+    where
+        F: FnOnce(&'static mut dyn IndexStore),
+    {
+        let store = SharedStore::new(MemoryStore::new());
+        inject(Box::leak(Box::new(store.clone())));
+        ReplayHarness { store }
+    }
+
+    /// Calls the handler with the blocks of each fixture file, returns the last handled slot
+    pub fn replay<H, P>(&self, handler: &H, fixtures: P) -> Result<i64, anyhow::Error>
+    where
+        H: SolanaHandler + ?Sized,
+        P: AsRef<Path>,
+    {
+        let mut block_slot = -1_i64;
+        for file in fixture::fixture_files(fixtures.as_ref())? {
+            let blocks = fixture::read_fixture(&file)?;
+            let slot = handler
+                .handle_blocks(&blocks)
+                .map_err(|err| anyhow::anyhow!("Error while handling {:?}: {}", &file, err))?;
+            block_slot = block_slot.max(slot);
+        }
+        Ok(block_slot)
+    }
+
+    /// Locks the store, the handler must not be called while the guard is alive
+    pub fn store(&self) -> MutexGuard<'_, MemoryStore> {
+        self.store.lock()
+    }
+
+    /// Flushed entities of a type ordered by id
+    pub fn entities(&self, entity_name: &str) -> Vec<Entity> {
+        self.store()
+            .entities()
+            .get(entity_name)
+            .map(|entities| entities.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn snapshot(&self) -> EntitySnapshot {
+        snapshot::create_snapshot(self.store().entities())
+    }
+
+    /// Panics if the entities do not match the snapshot file, see `snapshot::check_snapshot`
+    pub fn assert_snapshot<P: AsRef<Path>>(&self, path: P) {
+        if let Err(err) = snapshot::check_snapshot(&self.snapshot(), path.as_ref()) {
+            panic!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use massbit_solana_sdk::entity::Value;
+    use massbit_solana_sdk::types::SolanaBlock;
+    use std::cell::RefCell;
+    use std::error::Error;
+
+    /// Saves an entity by block, like the handler of a generated mapping
+    #[derive(Default)]
+    struct BlockHandler {
+        store: RefCell<Option<&'static mut dyn IndexStore>>,
+    }
+
+    impl SolanaHandler for BlockHandler {
+        fn handle_blocks(&self, blocks: &Vec<SolanaBlock>) -> Result<i64, Box<dyn Error>> {
+            let mut store = self.store.borrow_mut();
+            let store = store.as_mut().ok_or("Store is not injected")?;
+            let mut block_slot = -1_i64;
+            for block in blocks {
+                let entity = Entity::from(vec![
+                    ("id", Value::from(block.block_number.to_string())),
+                    ("timestamp", Value::from(block.timestamp as i32)),
+                ]);
+                store.save("Block".to_string(), entity);
+                store.flush(&block.block.blockhash, block.block_number)?;
+                block_slot = block_slot.max(block.block_number as i64);
+            }
+            Ok(block_slot)
+        }
+    }
+
+    fn block(slot: u64) -> SolanaBlock {
+        SolanaBlock {
+            version: "1.7".to_string(),
+            timestamp: slot as i64 * 10,
+            block_number: slot,
+            block: Default::default(),
+            list_log_messages: vec![],
+            accounts: vec![],
+        }
+    }
+
+    #[test]
+    fn replay_fixtures() {
+        let dir = std::env::temp_dir().join(format!("replay_{}", std::process::id()));
+        for (first_slot, last_slot) in vec![(1, 2), (3, 3)] {
+            let blocks = (first_slot..=last_slot).map(block).collect::<Vec<_>>();
+            fixture::write_fixture(
+                &dir.join(fixture::fixture_name(first_slot, last_slot)),
+                &serde_json::to_vec(&blocks).unwrap(),
+            )
+            .unwrap();
+        }
+        let handler = BlockHandler::default();
+        let harness = ReplayHarness::new(|store| *handler.store.borrow_mut() = Some(store));
+
+        assert_eq!(harness.replay(&handler, &dir).unwrap(), 3);
+        let ids = harness
+            .entities("Block")
+            .into_iter()
+            .map(|entity| entity.id().unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert_eq!(
+            serde_json::to_value(harness.snapshot()).unwrap()["Block"]["3"],
+            serde_json::json!({"id": "3", "timestamp": 30})
+        );
+        assert!(harness.replay(&handler, dir.join("missing.json")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use massbit_solana_sdk::entity::{Entity, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Environment variable to overwrite the snapshot files with the current entities
+pub const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+/// Entities by entity type and id, with their fields ordered by name
+pub type EntitySnapshot = BTreeMap<String, BTreeMap<String, BTreeMap<String, serde_json::Value>>>;
+
+pub fn create_snapshot(entities: &BTreeMap<String, BTreeMap<String, Entity>>) -> EntitySnapshot {
+    entities
+        .iter()
+        .filter(|(_, entities)| !entities.is_empty())
+        .map(|(entity_name, entities)| {
+            let entities = entities
+                .iter()
+                .map(|(entity_id, entity)| {
+                    let fields = entity
+                        .clone()
+                        .sorted()
+                        .into_iter()
+                        .map(|(attribute, value)| (attribute, to_json(&value)))
+                        .collect();
+                    (entity_id.clone(), fields)
+                })
+                .collect();
+            (entity_name.clone(), entities)
+        })
+        .collect()
+}
+
+/// Big numbers and bytes are written as strings to keep their precision
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(text) => serde_json::Value::from(text.as_str()),
+        Value::Int(number) => serde_json::Value::from(*number),
+        Value::Bool(flag) => serde_json::Value::from(*flag),
+        Value::Null => serde_json::Value::Null,
+        Value::List(values) => serde_json::Value::Array(values.iter().map(to_json).collect()),
+        Value::BigInt(_) | Value::BigDecimal(_) | Value::Bytes(_) => {
+            serde_json::Value::from(value.to_string())
+        }
+    }
+}
+
+/// Compares the entities with a snapshot file, the differences or a missing file are returned as an error.
+/// The file is written instead if `UPDATE_SNAPSHOTS` is set.
+pub fn check_snapshot(actual: &EntitySnapshot, path: &Path) -> Result<(), anyhow::Error> {
+    if env::var(UPDATE_SNAPSHOTS).is_err() {
+        if !path.exists() {
+            anyhow::bail!(
+                "Snapshot {:?} does not exist, run with {}=1 to write it",
+                path,
+                UPDATE_SNAPSHOTS
+            );
+        }
+        let expected: EntitySnapshot = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| anyhow::anyhow!("Invalid snapshot {:?}: {}", path, err))?;
+        if &expected != actual {
+            anyhow::bail!(
+                "Entities do not match the snapshot {:?}, run with {}=1 to update it:\n{}",
+                path,
+                UPDATE_SNAPSHOTS,
+                diff_snapshots(&expected, actual)
+            );
+        }
+    } else {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(actual)? + "\n")?;
+        println!("Snapshot {:?} is written", path);
+    }
+    Ok(())
+}
+
+/// Missing (`-`), unexpected (`+`) and changed (`~`) entities
+pub fn diff_snapshots(expected: &EntitySnapshot, actual: &EntitySnapshot) -> String {
+    let mut out = String::new();
+    let entity_names = expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<BTreeSet<&String>>();
+    for entity_name in entity_names {
+        let expected_entities = expected.get(entity_name);
+        let actual_entities = actual.get(entity_name);
+        let entity_ids = expected_entities
+            .into_iter()
+            .chain(actual_entities)
+            .flat_map(|entities| entities.keys())
+            .collect::<BTreeSet<&String>>();
+        for entity_id in entity_ids {
+            let expected_entity = expected_entities.and_then(|entities| entities.get(entity_id));
+            let actual_entity = actual_entities.and_then(|entities| entities.get(entity_id));
+            match (expected_entity, actual_entity) {
+                (Some(_), None) => {
+                    let _ = writeln!(out, "- {} {}", entity_name, entity_id);
+                }
+                (None, Some(_)) => {
+                    let _ = writeln!(out, "+ {} {}", entity_name, entity_id);
+                }
+                (Some(expected_entity), Some(actual_entity))
+                    if expected_entity != actual_entity =>
+                {
+                    let _ = writeln!(out, "~ {} {}", entity_name, entity_id);
+                    let attributes = expected_entity
+                        .keys()
+                        .chain(actual_entity.keys())
+                        .collect::<BTreeSet<&String>>();
+                    for attribute in attributes {
+                        let expected_value = expected_entity.get(attribute);
+                        let actual_value = actual_entity.get(attribute);
+                        if expected_value != actual_value {
+                            let _ = writeln!(
+                                out,
+                                "    {}: {} -> {}",
+                                attribute,
+                                expected_value.unwrap_or(&serde_json::Value::Null),
+                                actual_value.unwrap_or(&serde_json::Value::Null)
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+    use massbit_solana_sdk::entity::{EntityFilter, EntityOrder, EntityRange};
+    use massbit_solana_sdk::store::IndexStore;
+
+    fn market(id: &str, volume: i32) -> Entity {
+        Entity::from(vec![
+            ("id", Value::from(id)),
+            ("volume", Value::from(volume)),
+        ])
+    }
+
+    #[test]
+    fn snapshot_of_flushed_entities() {
+        let mut store = MemoryStore::new();
+        store.save("Market".to_string(), market("a", 1));
+        store.save("Market".to_string(), market("b", 2));
+        assert!(create_snapshot(store.entities()).is_empty());
+        store.flush(&String::from("hash"), 1).unwrap();
+        store.save("Market".to_string(), market("a", 3));
+        store.remove("Market".to_string(), &String::from("b"));
+        store.flush(&String::from("hash"), 2).unwrap();
+
+        let snapshot = create_snapshot(store.entities());
+        assert_eq!(
+            serde_json::to_value(&snapshot).unwrap(),
+            serde_json::json!({"Market": {"a": {"id": "a", "volume": 3}}})
+        );
+        let markets = store
+            .query(
+                "Market".to_string(),
+                Some(EntityFilter::new_equal("volume", 3)),
+                EntityOrder::Default,
+                EntityRange {
+                    first: None,
+                    skip: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(markets, vec![market("a", 3)]);
+        // Changes of the block being processed are not queried
+        store.save("Market".to_string(), market("c", 3));
+        store.remove("Market".to_string(), &String::from("a"));
+        let markets = store
+            .query(
+                "Market".to_string(),
+                Some(EntityFilter::new_equal("volume", 3)),
+                EntityOrder::Default,
+                EntityRange {
+                    first: None,
+                    skip: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(markets, vec![market("a", 3)]);
+    }
+
+    #[test]
+    fn diff_of_snapshots() {
+        let mut expected = MemoryStore::new();
+        expected.save("Market".to_string(), market("a", 1));
+        expected.save("Market".to_string(), market("b", 2));
+        expected.flush(&String::from("hash"), 1).unwrap();
+        let mut actual = MemoryStore::new();
+        actual.save("Market".to_string(), market("a", 5));
+        actual.save("Market".to_string(), market("c", 2));
+        actual.flush(&String::from("hash"), 1).unwrap();
+
+        let diff = diff_snapshots(
+            &create_snapshot(expected.entities()),
+            &create_snapshot(actual.entities()),
+        );
+        assert_eq!(
+            diff,
+            "~ Market a\n    volume: 1 -> 5\n- Market b\n+ Market c\n"
+        );
+    }
+
+    #[test]
+    fn check_missing_snapshot() {
+        if env::var(UPDATE_SNAPSHOTS).is_ok() {
+            return;
+        }
+        let mut store = MemoryStore::new();
+        store.save("Market".to_string(), market("a", 1));
+        store.flush(&String::from("hash"), 1).unwrap();
+        let snapshot = create_snapshot(store.entities());
+        let dir = env::temp_dir().join(format!("snapshot_{}", std::process::id()));
+        let path = dir.join("entities.json");
+
+        let err = check_snapshot(&snapshot, &path).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+        assert!(!path.exists());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, serde_json::to_string(&snapshot).unwrap()).unwrap();
+        assert!(check_snapshot(&snapshot, &path).is_ok());
+        store.save("Market".to_string(), market("a", 2));
+        store.flush(&String::from("hash"), 2).unwrap();
+        let err = check_snapshot(&create_snapshot(store.entities()), &path).unwrap_err();
+        assert!(err.to_string().contains("volume: 1 -> 2"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+use massbit_solana_sdk::entity::{BigInt, Entity, EntityFilter, EntityOrder, EntityRange, Value};
+use massbit_solana_sdk::store::IndexStore;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Key of an entity: entity type and entity id
+pub type EntityKey = (String, String);
+
+/// Store keeping the entities in memory, used to replay blocks in tests and to run indexers without database.
+/// Like the indexer-manager store, the saved entities are written by `flush`
+/// and `query` only returns the flushed entities.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entities: BTreeMap<String, BTreeMap<String, Entity>>,
+    pending: BTreeMap<EntityKey, Option<Entity>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Flushed entities by entity type and id
+    pub fn entities(&self) -> &BTreeMap<String, BTreeMap<String, Entity>> {
+        &self.entities
+    }
+    fn get_flushed(&self, entity_name: &str, entity_id: &str) -> Option<&Entity> {
+        self.entities
+            .get(entity_name)
+            .and_then(|entities| entities.get(entity_id))
+    }
+}
+
+impl IndexStore for MemoryStore {
+    fn save(&mut self, entity_name: String, data: Entity) {
+        if let Ok(entity_id) = data.id() {
+            let key = (entity_name, entity_id);
+            // Like an update of the entity cache, the saved fields are merged into the current entity
+            let mut entity = match self.pending.get(&key) {
+                Some(pending) => pending.clone(),
+                None => self.get_flushed(&key.0, &key.1).cloned(),
+            }
+            .unwrap_or_default();
+            entity.merge_remove_null_fields(data);
+            self.pending.insert(key, Some(entity));
+        }
+    }
+
+    fn get(&mut self, entity_name: String, entity_id: &String) -> Option<Entity> {
+        match self.pending.get(&(entity_name.clone(), entity_id.clone())) {
+            Some(pending) => pending.clone(),
+            None => self.get_flushed(&entity_name, entity_id).cloned(),
+        }
+    }
+
+    fn remove(&mut self, entity_name: String, entity_id: &String) {
+        self.pending.insert((entity_name, entity_id.clone()), None);
+    }
+
+    fn query(
+        &self,
+        entity_name: String,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>> {
+        let mut entities = self
+            .entities
+            .get(&entity_name)
+            .map(|entities| {
+                entities
+                    .values()
+                    .filter(|entity| filter.as_ref().map_or(true, |f| matches(entity, f)))
+                    .cloned()
+                    .collect::<Vec<Entity>>()
+            })
+            .unwrap_or_default();
+        // Entities are already ordered by id
+        match &order {
+            EntityOrder::Ascending(attribute, _) => {
+                entities.sort_by(|a, b| compare(a.get(attribute), b.get(attribute)))
+            }
+            EntityOrder::Descending(attribute, _) => {
+                entities.sort_by(|a, b| compare(b.get(attribute), a.get(attribute)))
+            }
+            EntityOrder::Default | EntityOrder::Unordered => {}
+        }
+        Ok(entities
+            .into_iter()
+            .skip(range.skip as usize)
+            .take(range.first.map_or(usize::MAX, |first| first as usize))
+            .collect())
+    }
+
+    fn flush(&mut self, _block_hash: &String, _block_slot: u64) -> Result<(), Box<dyn Error>> {
+        for ((entity_name, entity_id), entity) in std::mem::take(&mut self.pending) {
+            let entities = self.entities.entry(entity_name).or_default();
+            match entity {
+                Some(entity) => {
+                    entities.insert(entity_id, entity);
+                }
+                None => {
+                    entities.remove(&entity_id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Store given to a mapping while it is also read outside of the mapping.
+/// The clones share the inner store, each call of the mapping locks it,
+/// so the mapping never holds a reference to the store between two calls.
+#[derive(Debug, Default)]
+pub struct SharedStore<S> {
+    inner: Arc<Mutex<S>>,
+}
+
+impl<S> Clone for SharedStore<S> {
+    fn clone(&self) -> Self {
+        SharedStore {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S> SharedStore<S> {
+    pub fn new(store: S) -> Self {
+        SharedStore {
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+    /// The store is still readable after a handler panicked while using it
+    pub fn lock(&self) -> MutexGuard<'_, S> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<S: IndexStore> IndexStore for SharedStore<S> {
+    fn save(&mut self, entity_name: String, data: Entity) {
+        self.lock().save(entity_name, data)
+    }
+
+    fn get(&mut self, entity_name: String, entity_id: &String) -> Option<Entity> {
+        self.lock().get(entity_name, entity_id)
+    }
+
+    fn remove(&mut self, entity_name: String, entity_id: &String) {
+        self.lock().remove(entity_name, entity_id)
+    }
+
+    fn query(
+        &self,
+        entity_name: String,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>> {
+        self.lock().query(entity_name, filter, order, range)
+    }
+
+    fn flush(&mut self, block_hash: &String, block_slot: u64) -> Result<(), Box<dyn Error>> {
+        self.lock().flush(block_hash, block_slot)
+    }
+}
+
+fn matches(entity: &Entity, filter: &EntityFilter) -> bool {
+    let value = |attribute: &String| entity.get(attribute).unwrap_or(&Value::Null).clone();
+    let text = |attribute: &String| match entity.get(attribute) {
+        Some(Value::String(text)) => Some(text.clone()),
+        _ => None,
+    };
+    match filter {
+        EntityFilter::And(filters) => filters.iter().all(|filter| matches(entity, filter)),
+        EntityFilter::Or(filters) => filters.iter().any(|filter| matches(entity, filter)),
+        EntityFilter::Equal(attribute, expected) => &value(attribute) == expected,
+        EntityFilter::Not(attribute, expected) => &value(attribute) != expected,
+        EntityFilter::GreaterThan(attribute, expected) => {
+            compare(entity.get(attribute), Some(expected)) == Ordering::Greater
+        }
+        EntityFilter::LessThan(attribute, expected) => {
+            compare(entity.get(attribute), Some(expected)) == Ordering::Less
+        }
+        EntityFilter::GreaterOrEqual(attribute, expected) => {
+            compare(entity.get(attribute), Some(expected)) != Ordering::Less
+        }
+        EntityFilter::LessOrEqual(attribute, expected) => {
+            compare(entity.get(attribute), Some(expected)) != Ordering::Greater
+        }
+        EntityFilter::In(attribute, values) => values.contains(&value(attribute)),
+        EntityFilter::NotIn(attribute, values) => !values.contains(&value(attribute)),
+        EntityFilter::Contains(attribute, expected) => contains(&value(attribute), expected),
+        EntityFilter::NotContains(attribute, expected) => !contains(&value(attribute), expected),
+        EntityFilter::StartsWith(attribute, Value::String(prefix)) => {
+            text(attribute).map_or(false, |text| text.starts_with(prefix.as_str()))
+        }
+        EntityFilter::NotStartsWith(attribute, Value::String(prefix)) => {
+            !text(attribute).map_or(false, |text| text.starts_with(prefix.as_str()))
+        }
+        EntityFilter::EndsWith(attribute, Value::String(suffix)) => {
+            text(attribute).map_or(false, |text| text.ends_with(suffix.as_str()))
+        }
+        EntityFilter::NotEndsWith(attribute, Value::String(suffix)) => {
+            !text(attribute).map_or(false, |text| text.ends_with(suffix.as_str()))
+        }
+        EntityFilter::StartsWith(..)
+        | EntityFilter::NotStartsWith(..)
+        | EntityFilter::EndsWith(..)
+        | EntityFilter::NotEndsWith(..) => false,
+    }
+}
+
+fn contains(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::String(text), Value::String(part)) => text.contains(part.as_str()),
+        (Value::List(values), Value::List(parts)) => parts.iter().all(|part| values.contains(part)),
+        (Value::Bytes(bytes), Value::Bytes(part)) => bytes
+            .as_slice()
+            .windows(part.as_slice().len().max(1))
+            .any(|window| window == part.as_slice()),
+        _ => false,
+    }
+}
+
+/// Order of the values of an attribute, missing and null values are first
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let a = a.filter(|value| **value != Value::Null);
+    let b = b.filter(|value| **value != Value::Null);
+    match (a, b) {
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(Value::Int(a)), Some(Value::Int(b))) => a.cmp(b),
+        (Some(Value::BigInt(a)), Some(Value::BigInt(b))) => a.cmp(b),
+        // Filters on BigInt attributes are built with Int values
+        (Some(Value::Int(a)), Some(Value::BigInt(b))) => BigInt::from(*a).cmp(b),
+        (Some(Value::BigInt(a)), Some(Value::Int(b))) => a.cmp(&BigInt::from(*b)),
+        (Some(Value::BigDecimal(a)), Some(Value::BigDecimal(b))) => a.cmp(b),
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(id: &str, name: &str, volume: Value) -> Entity {
+        Entity::from(vec![
+            ("id", Value::from(id)),
+            ("name", Value::from(name)),
+            ("volume", volume),
+        ])
+    }
+
+    fn query(store: &MemoryStore, filter: EntityFilter, range: EntityRange) -> Vec<String> {
+        store
+            .query(
+                "Market".to_string(),
+                Some(filter),
+                EntityOrder::Default,
+                range,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|entity| entity.id().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn query_flushed_entities() {
+        let mut store = MemoryStore::new();
+        store.save(
+            "Market".to_string(),
+            market("a", "SOL/USDC", Value::from(5)),
+        );
+        store.save(
+            "Market".to_string(),
+            market("b", "SRM/USDC", Value::from(10u64)),
+        );
+        store.save("Market".to_string(), market("c", "SOL/USDT", Value::Null));
+        store.flush(&String::from("hash"), 1).unwrap();
+        let all = EntityRange {
+            first: None,
+            skip: 0,
+        };
+
+        // BigInt attributes are compared with Int values
+        let filter = EntityFilter::GreaterThan("volume".to_string(), Value::from(7));
+        assert_eq!(query(&store, filter, all.clone()), vec!["b"]);
+        let filter = EntityFilter::LessOrEqual("volume".to_string(), Value::from(10));
+        assert_eq!(query(&store, filter, all.clone()), vec!["a", "b", "c"]);
+        let filter = EntityFilter::And(vec![
+            EntityFilter::StartsWith("name".to_string(), Value::from("SOL")),
+            EntityFilter::Not("volume".to_string(), Value::Null),
+        ]);
+        assert_eq!(query(&store, filter, all.clone()), vec!["a"]);
+        let filter = EntityFilter::Or(vec![
+            EntityFilter::EndsWith("name".to_string(), Value::from("USDT")),
+            EntityFilter::Contains("name".to_string(), Value::from("SRM")),
+        ]);
+        assert_eq!(query(&store, filter, all.clone()), vec!["b", "c"]);
+        let filter = EntityFilter::new_in("id", vec!["a", "c"]);
+        assert_eq!(query(&store, filter, all), vec!["a", "c"]);
+        let filter = EntityFilter::NotIn("id".to_string(), vec![Value::from("a")]);
+        let range = EntityRange {
+            first: Some(1),
+            skip: 1,
+        };
+        assert_eq!(query(&store, filter, range), vec!["c"]);
+    }
+
+    #[test]
+    fn compare_values() {
+        let big = Value::from(10u64);
+        assert_eq!(compare(Some(&Value::from(5)), Some(&big)), Ordering::Less);
+        assert_eq!(
+            compare(Some(&big), Some(&Value::from(5))),
+            Ordering::Greater
+        );
+        assert_eq!(compare(Some(&Value::from(10)), Some(&big)), Ordering::Equal);
+        assert_eq!(
+            compare(Some(&Value::from("a")), Some(&Value::from("b"))),
+            Ordering::Less
+        );
+        // Missing and null values are first
+        assert_eq!(
+            compare(Some(&Value::Null), Some(&Value::from(0))),
+            Ordering::Less
+        );
+        assert_eq!(compare(Some(&Value::from(0)), None), Ordering::Greater);
+        assert_eq!(compare(None, Some(&Value::Null)), Ordering::Equal);
+    }
+}
@@ -75,6 +75,9 @@ impl SolanaAdapter {
     pub fn get_block_slots(&self, slot: Slot) -> ClientResult<Vec<Slot>> {
         self.rpc_client.clone().get_blocks(slot, None)
     }
+    pub fn get_block_slots_until(&self, slot: Slot, end_slot: Slot) -> ClientResult<Vec<Slot>> {
+        self.rpc_client.clone().get_blocks(slot, Some(end_slot))
+    }
     pub async fn get_block_data(
         &self,
         block_slot: Slot,
@@ -492,6 +495,67 @@ impl SolanaNetworkAdapters {
 
         Ok(Vec::default())
     }
+    ///Get available blocks in [start_slot, end_slot].
+    /// Unlike get_block_slots, the error of the last adapter is returned
+    /// so that an unreachable network is not taken for skipped slots
+    pub fn get_block_slots_until(
+        &mut self,
+        start_slot: Slot,
+        end_slot: Slot,
+    ) -> ClientResult<Vec<Slot>> {
+        let network_adapters = self.get_adapters("get_block_slots_until");
+        let mut res = Ok(Vec::default());
+        for adapter in network_adapters {
+            res = adapter.get_block_slots_until(start_slot, end_slot);
+            if res.is_ok() {
+                return res;
+            }
+        }
+        res
+    }
+    ///Get data of the blocks in `slots` in the same order, sharing the request permits
+    /// with the chain reader loop. A block that can not be got from any adapter
+    /// is returned without data, like the chain reader loop does
+    pub async fn get_blocks_data(&mut self, slots: &[Slot]) -> Vec<ConfirmedBlockWithSlot> {
+        let mut tasks = vec![];
+        for slot in slots.iter().cloned() {
+            let adapters = self.get_adapters("get_block");
+            let permit = match adapters.get(0) {
+                Some(adapter) => adapter.acquire_owned().await.unwrap(),
+                None => break,
+            };
+            tasks.push(tokio::spawn(async move {
+                let mut block = ConfirmedBlockWithSlot {
+                    block_slot: slot,
+                    block: None,
+                };
+                for adapter in adapters.iter() {
+                    match timeout(
+                        Duration::from_secs(GET_BLOCK_TIMEOUT_SEC),
+                        adapter.get_block_data(slot),
+                    )
+                    .await
+                    {
+                        Ok(Ok(data)) => {
+                            block = data;
+                            break;
+                        }
+                        _ => info!("Retry get data of block {:?} with next adapter", &slot),
+                    }
+                }
+                drop(permit);
+                block
+            }));
+        }
+        let mut blocks = vec![];
+        for (slot, task) in slots.iter().zip(tasks) {
+            blocks.push(task.await.unwrap_or(ConfirmedBlockWithSlot {
+                block_slot: *slot,
+                block: None,
+            }));
+        }
+        blocks
+    }
 
     pub async fn start(&mut self) {
         let mut last_block: Option<u64> = None;
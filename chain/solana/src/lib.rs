@@ -4,6 +4,7 @@ pub mod data_source;
 pub mod manifest;
 pub mod trigger;
 pub mod types;
+pub mod validation;
 
 pub use chain::Chain;
 pub use manifest::SolanaIndexerManifest;
@@ -9,7 +9,7 @@ use massbit::prelude::{serde_yaml, DeploymentHash, Deserialize, LinkResolver, Se
 use massbit::slog::Logger;
 use semver::Version;
 /// IndexerManifest with IPFS links unresolved
-type UnresolvedSolanaIndexerManifest = BaseIndexerManifest<
+pub(crate) type UnresolvedSolanaIndexerManifest = BaseIndexerManifest<
     Chain,
     UnresolvedSolanaSchema,
     <Chain as Blockchain>::UnresolvedDataSource,
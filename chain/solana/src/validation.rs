@@ -0,0 +1,163 @@
+use crate::manifest::UnresolvedSolanaIndexerManifest;
+use crate::types::Pubkey;
+use crate::SOLANA_NETWORKS;
+use massbit::prelude::{serde_yaml, Serialize};
+use massbit_data::indexer::DeploymentHash;
+use massbit_data::schema::Schema;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Id injected into the manifest before parsing, like the indexer hash at deploy
+const VALIDATION_INDEXER_ID: &str = "validation";
+const SOLANA_KIND: &str = "solana";
+const RUST_LANGUAGE: &str = "rust";
+
+/// Error of an indexer file, with the change to make
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ValidationError {
+    pub file: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(file: &str, message: impl Into<String>) -> Self {
+        ValidationError {
+            file: file.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", &self.file, &self.message)
+    }
+}
+
+/// Checks a `subgraph.yaml` against the Solana manifest types and the rules of the indexer-manager
+pub fn validate_manifest(file: &str, content: &[u8]) -> Vec<ValidationError> {
+    let mut raw = match serde_yaml::from_slice::<serde_yaml::Value>(content) {
+        Ok(serde_yaml::Value::Mapping(raw)) => raw,
+        Ok(_) => {
+            return vec![ValidationError::new(
+                file,
+                "The manifest must be a YAML mapping",
+            )]
+        }
+        Err(err) => return vec![ValidationError::new(file, format!("Invalid YAML: {}", err))],
+    };
+    raw.insert(
+        serde_yaml::Value::from("id"),
+        serde_yaml::Value::from(VALIDATION_INDEXER_ID),
+    );
+    let manifest: UnresolvedSolanaIndexerManifest =
+        match serde_yaml::from_value(serde_yaml::Value::Mapping(raw)) {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                return vec![ValidationError::new(
+                    file,
+                    format!("Does not match the Solana manifest: {}", err),
+                )]
+            }
+        };
+    let mut errors = vec![];
+    if manifest.data_sources.len() != 1 {
+        errors.push(ValidationError::new(
+            file,
+            format!(
+                "`dataSources` must contain exactly one data source, found {}",
+                manifest.data_sources.len()
+            ),
+        ));
+    }
+    for data_source in &manifest.data_sources {
+        let name = &data_source.name;
+        if data_source.kind != SOLANA_KIND {
+            errors.push(ValidationError::new(
+                file,
+                format!(
+                    "Data source `{}` has kind `{}`, set `kind: {}`",
+                    name, &data_source.kind, SOLANA_KIND
+                ),
+            ));
+        }
+        if let Some(network) = &data_source.network {
+            if !SOLANA_NETWORKS
+                .values()
+                .any(|config| &config.network == network)
+            {
+                let mut networks = SOLANA_NETWORKS
+                    .values()
+                    .map(|config| config.network.as_str())
+                    .collect::<Vec<&str>>();
+                networks.sort_unstable();
+                errors.push(ValidationError::new(
+                    file,
+                    format!(
+                        "Data source `{}` uses the unknown network `{}`, use one of {}",
+                        name,
+                        network,
+                        networks.join(", ")
+                    ),
+                ));
+            }
+        }
+        match &data_source.source.address {
+            None => errors.push(ValidationError::new(
+                file,
+                format!(
+                    "Data source `{}` has no `source.address`, set the address of the indexed program",
+                    name
+                ),
+            )),
+            Some(address) if Pubkey::from_str(address).is_err() => {
+                errors.push(ValidationError::new(
+                    file,
+                    format!(
+                        "Address `{}` of data source `{}` is not a base58 Solana public key",
+                        address, name
+                    ),
+                ))
+            }
+            _ => {}
+        }
+        if data_source.mapping.language != RUST_LANGUAGE {
+            errors.push(ValidationError::new(
+                file,
+                format!(
+                    "Mapping of data source `{}` has language `{}`, set `language: {}`",
+                    name, &data_source.mapping.language, RUST_LANGUAGE
+                ),
+            ));
+        }
+        if let Err(err) = semver::Version::parse(&data_source.mapping.api_version) {
+            errors.push(ValidationError::new(
+                file,
+                format!(
+                    "Mapping of data source `{}` has an invalid `apiVersion` `{}`: {}",
+                    name, &data_source.mapping.api_version, err
+                ),
+            ));
+        }
+    }
+    errors
+}
+
+/// Checks a `schema.graphql` with the rules applied to the indexer schemas
+pub fn validate_schema(file: &str, content: &str) -> Vec<ValidationError> {
+    let id = DeploymentHash::new(VALIDATION_INDEXER_ID).expect("Invalid validation indexer id");
+    match Schema::parse(content, id) {
+        Ok(schema) => match schema.validate(&HashMap::new()) {
+            Ok(_) => vec![],
+            Err(errors) => errors
+                .iter()
+                .map(|err| ValidationError::new(file, err.to_string()))
+                .collect(),
+        },
+        Err(err) => vec![ValidationError::new(
+            file,
+            format!("Invalid GraphQL schema: {}", err),
+        )],
+    }
+}
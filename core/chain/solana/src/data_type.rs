@@ -59,6 +59,14 @@ impl SolanaFilter {
         })
     }
 
+    /// Account snapshots of the accounts owned by one of the keys
+    pub fn filter_accounts(&self, accounts: Vec<AccountSnapshot>) -> Vec<AccountSnapshot> {
+        accounts
+            .into_iter()
+            .filter(|account| self.keys.contains(&account.owner))
+            .collect()
+    }
+
     pub fn filter_block(&self, block: Block) -> Block {
         // If there are no key, then accept all transactions
         if self.keys.is_empty() {
@@ -102,7 +110,8 @@ fn to_transaction_token_balance(ui_ttb: &UiTransactionTokenBalance) -> Transacti
     }
 }
 
-fn to_ui_instructions(ui_inner_instruction: UiInnerInstructions) -> InnerInstructions {
+/// Compiled form of the inner instructions of an encoded transaction
+pub fn to_ui_instructions(ui_inner_instruction: UiInnerInstructions) -> InnerInstructions {
     InnerInstructions {
         index: ui_inner_instruction.index,
         //instructions: compiled_instructions,
@@ -283,6 +292,18 @@ pub struct ExtBlock {
     pub block_number: u64,
     pub block: Block,
     pub list_log_messages: Vec<LogMessages>,
+    /// States of the accounts of the filtered programs after the block
+    #[serde(default)]
+    pub accounts: Vec<AccountSnapshot>,
+}
+
+/// State of an account after the last change in a slot, as notified by the node
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
 }
 
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
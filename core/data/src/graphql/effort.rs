@@ -230,8 +230,9 @@ pub struct LoadManager {
     logger: Logger,
     effort: QueryEffort,
     /// List of query shapes that have been statically blocked through
-    /// configuration
-    blocked_queries: HashSet<u64>,
+    /// configuration. They can be replaced at runtime with
+    /// `set_blocked_queries` when the configuration changes
+    blocked_queries: RwLock<HashSet<u64>>,
     /// List of query shapes that have caused more than `JAIL_THRESHOLD`
     /// proportion of the work while the system was overloaded. Currently,
     /// there is no way for a query to get out of jail other than
@@ -249,10 +250,7 @@ impl LoadManager {
         registry: Arc<dyn MetricsRegistry>,
     ) -> Self {
         let logger = logger.new(o!("component" => "LoadManager"));
-        let blocked_queries = blocked_queries
-            .into_iter()
-            .map(|doc| shape_hash(&doc))
-            .collect::<HashSet<_>>();
+        let blocked_queries = Self::blocked_shapes(blocked_queries);
 
         let mode = if *LOAD_MANAGEMENT_DISABLED {
             "disabled"
@@ -287,7 +285,7 @@ impl LoadManager {
         Self {
             logger,
             effort: QueryEffort::default(),
-            blocked_queries,
+            blocked_queries: RwLock::new(blocked_queries),
             jailed_queries: RwLock::new(HashSet::new()),
             kill_state: RwLock::new(KillState::new()),
             effort_gauge,
@@ -295,6 +293,22 @@ impl LoadManager {
         }
     }
 
+    fn blocked_shapes(blocked_queries: Vec<Arc<q::Document>>) -> HashSet<u64> {
+        blocked_queries
+            .into_iter()
+            .map(|doc| shape_hash(&doc))
+            .collect::<HashSet<_>>()
+    }
+
+    /// Replace the statically configured list of blocked queries, e.g.
+    /// after the file they are read from has been edited by an operator
+    pub fn set_blocked_queries(&self, blocked_queries: Vec<Arc<q::Document>>) {
+        let blocked_queries = Self::blocked_shapes(blocked_queries);
+        info!(self.logger, "Updating blocked queries";
+            "count" => blocked_queries.len());
+        *self.blocked_queries.write().unwrap() = blocked_queries;
+    }
+
     /// Record that we spent `duration` amount of work for the query
     /// `shape_hash`, where `cache_status` indicates whether the query
     /// was cached or had to actually run
@@ -350,13 +364,13 @@ impl LoadManager {
     ///
     /// If `GRAPH_LOAD_THRESHOLD` is set to 0, we bypass all this logic, and
     /// only ever decline to run statically configured queries (1). In that
-    /// case, we also do not take any locks when asked to update statistics,
-    /// or to check whether we are overloaded; these operations amount to
-    /// noops.
+    /// case, we only take the read lock on the blocked queries, and do not
+    /// take any other locks when asked to update statistics, or to check
+    /// whether we are overloaded; these operations amount to noops.
     pub fn decide(&self, wait_stats: &PoolWaitStats, shape_hash: u64, query: &str) -> Decision {
         use Decision::*;
 
-        if self.blocked_queries.contains(&shape_hash) {
+        if self.blocked_queries.read().unwrap().contains(&shape_hash) {
             return TooExpensive;
         }
         if *LOAD_MANAGEMENT_DISABLED {
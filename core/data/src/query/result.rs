@@ -61,6 +61,30 @@ impl QueryResults {
     pub fn first(&self) -> Option<&Arc<QueryResult>> {
         self.results.first()
     }
+
+    pub fn has_errors(&self) -> bool {
+        self.results.iter().any(|r| r.has_errors())
+    }
+
+    /// The data of all results merged into one map, the same way they are
+    /// merged when serializing.
+    pub fn data(&self) -> Option<Data> {
+        let mut merged: Option<Data> = None;
+        for data in self.results.iter().filter_map(|r| r.data.as_ref()) {
+            merged
+                .get_or_insert_with(Data::new)
+                .extend(data.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        merged
+    }
+
+    pub fn errors(&self) -> Vec<QueryError> {
+        self.results
+            .iter()
+            .map(|r| r.errors.iter().cloned())
+            .flatten()
+            .collect()
+    }
 }
 
 impl Serialize for QueryResults {
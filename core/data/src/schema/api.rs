@@ -8,8 +8,9 @@ use inflector::Inflector;
 use crate::prelude::s::{Value, *};
 use crate::prelude::*;
 use crate::schema::ast;
+use crate::store::entity::{AggregateFunction, AggregateInterval};
 use crate::{
-    graphql::ext::{DirectiveExt, DocumentExt, ValueExt},
+    graphql::ext::{DirectiveExt, DocumentExt, TypeExt, ValueExt},
     indexer::IndexerFeature,
     schema::{META_FIELD_NAME, META_FIELD_TYPE, SCHEMA_TYPE_NAME},
 };
@@ -28,6 +29,34 @@ pub enum APISchemaError {
 
 const BLOCK_HEIGHT: &str = "Block_height";
 
+const AGGREGATE_INTERVAL_TYPE: &str = "AggregateInterval";
+
+/// The scalar types that aggregates like `sum` can be computed over
+const NUMERIC_TYPES: [&str; 3] = ["Int", "BigInt", "BigDecimal"];
+
+/// The type of the `pageInfo` field of connections. The name is purposely
+/// awkward to minimize the risk of colliding with user-supplied types
+pub const PAGE_INFO_TYPE: &str = "_PageInfo_";
+
+/// Suffix of the object types returned by the cursor-paginated connection
+/// fields on the root `Query` type, e.g. `Transfer_connection`
+pub const CONNECTION_TYPE_SUFFIX: &str = "_connection";
+
+/// Suffix of the object types that hold the groups returned by the
+/// aggregation fields on the root `Query` type, e.g. `Transfer_aggregate`
+pub const AGGREGATE_TYPE_SUFFIX: &str = "_aggregate";
+
+/// Directive on the generated connection types, whose `entity` argument is
+/// the name of the type they paginate
+pub const CONNECTION_DIRECTIVE: &str = "connection";
+
+/// Directive on the generated aggregation types, whose `entity` argument is
+/// the name of the type they aggregate
+pub const AGGREGATE_DIRECTIVE: &str = "aggregate";
+
+/// The argument of `CONNECTION_DIRECTIVE` and `AGGREGATE_DIRECTIVE`
+pub const GENERATED_FOR_ARGUMENT: &str = "entity";
+
 const ERROR_POLICY_TYPE: &str = "_SubgraphErrorPolicy_";
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -79,7 +108,9 @@ pub fn api_schema(
     add_directives(&mut schema);
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
+    add_aggregate_interval_enum(&mut schema);
     add_block_height_type(&mut schema);
+    add_page_info_type(&mut schema);
     add_meta_field_type(&mut schema);
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
@@ -172,9 +203,42 @@ fn add_directives(schema: &mut Document) {
         locations: vec![DirectiveLocation::Object],
     });
 
+    let generated_for = |name: &str| {
+        Definition::DirectiveDefinition(DirectiveDefinition {
+            position: Pos::default(),
+            description: None,
+            name: name.to_owned(),
+            arguments: vec![InputValue {
+                position: Pos::default(),
+                description: None,
+                name: GENERATED_FOR_ARGUMENT.to_owned(),
+                value_type: Type::NonNullType(Box::new(Type::NamedType("String".to_owned()))),
+                default_value: None,
+                directives: vec![],
+            }],
+            repeatable: false,
+            locations: vec![DirectiveLocation::Object],
+        })
+    };
+
     schema.definitions.push(entity);
     schema.definitions.push(derived_from);
     schema.definitions.push(subgraph_id);
+    schema.definitions.push(generated_for(CONNECTION_DIRECTIVE));
+    schema.definitions.push(generated_for(AGGREGATE_DIRECTIVE));
+}
+
+/// The directive that marks a type as generated for the type `type_name`,
+/// see `CONNECTION_DIRECTIVE` and `AGGREGATE_DIRECTIVE`
+fn generated_for_directive(name: &str, type_name: &str) -> Directive {
+    Directive {
+        position: Pos::default(),
+        name: name.to_owned(),
+        arguments: vec![(
+            GENERATED_FOR_ARGUMENT.to_owned(),
+            Value::String(type_name.to_owned()),
+        )],
+    }
 }
 
 /// Adds a global `OrderDirection` type to the schema.
@@ -229,6 +293,53 @@ fn add_block_height_type(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `_PageInfo_` type to the schema. Connection fields
+/// return a value of this type in their `pageInfo` field
+fn add_page_info_type(schema: &mut Document) {
+    let field = |name: &str, field_type: Type, description: &str| Field {
+        position: Pos::default(),
+        description: Some(description.to_owned()),
+        name: name.to_owned(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    };
+    let boolean = Type::NonNullType(Box::new(Type::NamedType("Boolean".to_owned())));
+    let cursor = Type::NamedType("String".to_owned());
+
+    let typedef = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: PAGE_INFO_TYPE.to_string(),
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            field(
+                "hasNextPage",
+                boolean.clone(),
+                "Whether there are more entities after `endCursor`",
+            ),
+            field(
+                "hasPreviousPage",
+                boolean,
+                "Whether there are more entities before `startCursor`",
+            ),
+            field(
+                "startCursor",
+                cursor.clone(),
+                "The cursor of the first edge, or null if there are no edges",
+            ),
+            field(
+                "endCursor",
+                cursor,
+                "The cursor of the last edge, or null if there are no edges",
+            ),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
 /// Adds a global `_Meta_` type to the schema. The `_meta` field
 /// accepts values of this type
 fn add_meta_field_type(schema: &mut Document) {
@@ -252,8 +363,220 @@ fn add_types_for_object_types(
         if !object_type.name.eq(SCHEMA_TYPE_NAME) {
             add_order_by_type(schema, &object_type.name, &object_type.fields)?;
             add_filter_type(schema, &object_type.name, &object_type.fields)?;
+            add_aggregate_types(schema, &object_type.name, &object_type.fields)?;
+            add_connection_types(schema, &object_type.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds a global `AggregateInterval` enum to the schema. The `interval`
+/// argument of aggregation fields accepts values of this type
+fn add_aggregate_interval_enum(schema: &mut Document) {
+    let typedef = TypeDefinition::Enum(EnumType {
+        position: Pos::default(),
+        description: None,
+        name: AGGREGATE_INTERVAL_TYPE.to_string(),
+        directives: vec![],
+        values: AggregateInterval::ALL
+            .iter()
+            .map(|interval| EnumValue {
+                position: Pos::default(),
+                description: None,
+                name: interval.as_str().to_string(),
+                directives: vec![],
+            })
+            .collect(),
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
+/// Returns `true` if aggregates like `sum` can be computed over `field`
+fn is_numeric_field(field: &Field) -> bool {
+    match &field.field_type {
+        Type::NamedType(name) => NUMERIC_TYPES.contains(&name.as_str()),
+        Type::NonNullType(inner) => match inner.as_ref() {
+            Type::NamedType(name) => NUMERIC_TYPES.contains(&name.as_str()),
+            _ => false,
+        },
+        Type::ListType(_) => false,
+    }
+}
+
+/// Returns the field of the `<type_name>_aggregateGroup` type that holds
+/// the value of `field` for a group, or `None` if aggregations can not be
+/// grouped by `field`. References to other entities are grouped by their id
+fn aggregate_group_field(schema: &Document, field: &Field) -> Option<Field> {
+    if ast::is_list_or_non_null_list_field(field)
+        || ast::get_derived_from_directive(field).is_some()
+    {
+        return None;
+    }
+    let base_type = field.field_type.get_base_type();
+    let field_type = match schema.get_named_type(base_type)? {
+        TypeDefinition::Scalar(_) | TypeDefinition::Enum(_) => base_type.to_owned(),
+        TypeDefinition::Object(_) | TypeDefinition::Interface(_) => "String".to_owned(),
+        TypeDefinition::Union(_) | TypeDefinition::InputObject(_) => return None,
+    };
+    Some(output_field(&field.name, Type::NamedType(field_type)))
+}
+
+fn output_field(name: &str, field_type: Type) -> Field {
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: name.to_owned(),
+        arguments: vec![],
+        field_type,
+        directives: vec![],
+    }
+}
+
+/// Adds the types needed to query aggregations over the entities of type
+/// `type_name` to the schema:
+///
+/// - `<type_name>_groupBy`, an enum of the fields aggregations can be
+///   grouped by, and `<type_name>_aggregateGroup` holding their values
+/// - `<type_name>_aggregateField`, an enum of the numeric fields, and
+///   `<type_name>_aggregateValues` holding an aggregate for each of them
+/// - `<type_name>_aggregate`, the type of one group in the result
+///
+/// The types for numeric fields are only added if `type_name` has any
+fn add_aggregate_types(
+    schema: &mut Document,
+    type_name: &str,
+    fields: &[Field],
+) -> Result<(), APISchemaError> {
+    let group_fields: Vec<Field> = fields
+        .iter()
+        .filter_map(|field| aggregate_group_field(schema, field))
+        .collect();
+    let numeric_fields: Vec<&Field> = fields.iter().filter(|f| is_numeric_field(f)).collect();
+
+    let group_by_name = format!("{}_groupBy", type_name);
+    let group_name = format!("{}_aggregateGroup", type_name);
+    let field_name = format!("{}_aggregateField", type_name);
+    let values_name = format!("{}_aggregateValues", type_name);
+    let aggregate_name = format!("{}{}", type_name, AGGREGATE_TYPE_SUFFIX);
+
+    let enum_type = |name: &str, values: Vec<&String>| {
+        TypeDefinition::Enum(EnumType {
+            position: Pos::default(),
+            description: None,
+            name: name.to_owned(),
+            directives: vec![],
+            values: values
+                .into_iter()
+                .map(|name| EnumValue {
+                    position: Pos::default(),
+                    description: None,
+                    name: name.to_owned(),
+                    directives: vec![],
+                })
+                .collect(),
+        })
+    };
+    let object_type = |name: &str, fields: Vec<Field>, directives: Vec<Directive>| {
+        TypeDefinition::Object(ObjectType {
+            position: Pos::default(),
+            description: None,
+            name: name.to_owned(),
+            implements_interfaces: vec![],
+            directives,
+            fields,
+        })
+    };
+    let non_null = |name: &str| Type::NonNullType(Box::new(Type::NamedType(name.to_owned())));
+
+    let mut aggregate_fields = vec![
+        output_field("count", non_null("BigInt")),
+        output_field("bucket", Type::NamedType("BigInt".to_owned())),
+        output_field("group", non_null(&group_name)),
+    ];
+    let mut typedefs = vec![
+        enum_type(
+            &group_by_name,
+            group_fields.iter().map(|field| &field.name).collect(),
+        ),
+        object_type(&group_name, group_fields.clone(), vec![]),
+    ];
+    if !numeric_fields.is_empty() {
+        typedefs.push(enum_type(
+            &field_name,
+            numeric_fields.iter().map(|field| &field.name).collect(),
+        ));
+        typedefs.push(object_type(
+            &values_name,
+            numeric_fields
+                .iter()
+                .map(|field| output_field(&field.name, Type::NamedType("BigDecimal".to_owned())))
+                .collect(),
+            vec![],
+        ));
+        for function in AggregateFunction::ALL.iter() {
+            aggregate_fields.push(output_field(function.as_str(), non_null(&values_name)));
         }
     }
+    typedefs.push(object_type(
+        &aggregate_name,
+        aggregate_fields,
+        vec![generated_for_directive(AGGREGATE_DIRECTIVE, type_name)],
+    ));
+
+    for typedef in typedefs {
+        let name = ast::get_type_name(&typedef);
+        if schema.get_named_type(name).is_some() {
+            return Err(APISchemaError::TypeExists(name.to_owned()));
+        }
+        schema.definitions.push(Definition::TypeDefinition(typedef));
+    }
+    Ok(())
+}
+
+/// Adds the `<type_name>_connection` and `<type_name>_edge` types used
+/// for cursor-based pagination over the entities of type `type_name`
+fn add_connection_types(schema: &mut Document, type_name: &str) -> Result<(), APISchemaError> {
+    let non_null = |name: &str| Type::NonNullType(Box::new(Type::NamedType(name.to_owned())));
+    let edge_name = format!("{}_edge", type_name);
+    let connection_name = format!("{}{}", type_name, CONNECTION_TYPE_SUFFIX);
+
+    let edge = ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: edge_name.clone(),
+        implements_interfaces: vec![],
+        directives: vec![],
+        fields: vec![
+            output_field("cursor", non_null("String")),
+            output_field("node", non_null(type_name)),
+        ],
+    };
+    let connection = ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: connection_name,
+        implements_interfaces: vec![],
+        directives: vec![generated_for_directive(CONNECTION_DIRECTIVE, type_name)],
+        fields: vec![
+            output_field(
+                "edges",
+                Type::NonNullType(Box::new(Type::ListType(Box::new(non_null(&edge_name))))),
+            ),
+            output_field("pageInfo", non_null(PAGE_INFO_TYPE)),
+        ],
+    };
+
+    for object_type in vec![edge, connection] {
+        if schema.get_named_type(&object_type.name).is_some() {
+            return Err(APISchemaError::TypeExists(object_type.name));
+        }
+        schema
+            .definitions
+            .push(Definition::TypeDefinition(TypeDefinition::Object(
+                object_type,
+            )));
+    }
     Ok(())
 }
 
@@ -265,6 +588,7 @@ fn add_types_for_interface_types(
     for interface_type in interface_types {
         add_order_by_type(schema, &interface_type.name, &interface_type.fields)?;
         add_filter_type(schema, &interface_type.name, &interface_type.fields)?;
+        add_connection_types(schema, &interface_type.name)?;
     }
     Ok(())
 }
@@ -528,6 +852,20 @@ fn add_query_type(
         .chain(interface_types.iter().map(|t| &t.name))
         .flat_map(|name| query_fields_for_type(name, features))
         .collect::<Vec<Field>>();
+    fields.extend(
+        object_types
+            .iter()
+            .filter(|t| !t.name.eq(SCHEMA_TYPE_NAME))
+            .map(|t| aggregate_query_field_for_type(t, features)),
+    );
+    fields.extend(
+        object_types
+            .iter()
+            .map(|t| &t.name)
+            .filter(|name| !name.eq(&SCHEMA_TYPE_NAME))
+            .chain(interface_types.iter().map(|t| &t.name))
+            .map(|name| connection_query_field_for_type(name, features)),
+    );
     let mut fulltext_fields = schema
         .get_fulltext_directives()
         .map_err(|_| APISchemaError::FulltextSearchNonDeterministic)?
@@ -720,6 +1058,137 @@ fn query_fields_for_type(type_name: &str, features: &BTreeSet<IndexerFeature>) -
     ]
 }
 
+/// Generates the aggregation field on `Query` for the given object type
+/// (e.g. `usersAggregate`)
+fn aggregate_query_field_for_type(
+    object_type: &ObjectType,
+    features: &BTreeSet<IndexerFeature>,
+) -> Field {
+    let type_name = &object_type.name;
+
+    let mut skip = input_value(&"skip".to_string(), "", Type::NamedType("Int".to_string()));
+    skip.default_value = Some(Value::Int(0.into()));
+
+    let mut first = input_value(&"first".to_string(), "", Type::NamedType("Int".to_string()));
+    first.default_value = Some(Value::Int(100.into()));
+
+    let mut arguments = vec![
+        skip,
+        first,
+        input_value(
+            &"where".to_string(),
+            "",
+            Type::NamedType(format!("{}_filter", type_name)),
+        ),
+        input_value(
+            &"groupBy".to_string(),
+            "",
+            Type::ListType(Box::new(Type::NonNullType(Box::new(Type::NamedType(
+                format!("{}_groupBy", type_name),
+            ))))),
+        ),
+    ];
+    if object_type.fields.iter().any(is_numeric_field) {
+        let mut interval = input_value(
+            &"interval".to_string(),
+            "",
+            Type::NamedType(AGGREGATE_INTERVAL_TYPE.to_string()),
+        );
+        interval.description =
+            Some("Group into time buckets of this width. Requires `timestamp`".to_owned());
+        let mut timestamp = input_value(
+            &"timestamp".to_string(),
+            "",
+            Type::NamedType(format!("{}_aggregateField", type_name)),
+        );
+        timestamp.description = Some(
+            "The field holding the time, in seconds since the epoch, used for `interval`"
+                .to_owned(),
+        );
+        arguments.push(interval);
+        arguments.push(timestamp);
+    }
+    arguments.push(block_argument());
+    if features.contains(&IndexerFeature::nonFatalErrors) {
+        arguments.push(subgraph_error_argument());
+    }
+
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: format!("{}Aggregate", type_name.to_plural().to_camel_case()),
+        arguments,
+        field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+            Box::new(Type::NamedType(format!(
+                "{}{}",
+                type_name, AGGREGATE_TYPE_SUFFIX
+            ))),
+        ))))),
+        directives: vec![],
+    }
+}
+
+/// Generates the cursor-paginated field on `Query` for the given type name
+/// (e.g. `usersConnection`)
+fn connection_query_field_for_type(type_name: &str, features: &BTreeSet<IndexerFeature>) -> Field {
+    let mut first = input_value(&"first".to_string(), "", Type::NamedType("Int".to_string()));
+    first.default_value = Some(Value::Int(100.into()));
+
+    let mut after = input_value(
+        &"after".to_string(),
+        "",
+        Type::NamedType("String".to_string()),
+    );
+    after.description = Some("Return the entities that come after this cursor".to_owned());
+
+    let mut before = input_value(
+        &"before".to_string(),
+        "",
+        Type::NamedType("String".to_string()),
+    );
+    before.description = Some(
+        "Return the entities that come right before this cursor. Can not be combined with `after`"
+            .to_owned(),
+    );
+
+    let mut arguments = vec![
+        first,
+        after,
+        before,
+        input_value(
+            &"orderBy".to_string(),
+            "",
+            Type::NamedType(format!("{}_orderBy", type_name)),
+        ),
+        input_value(
+            &"orderDirection".to_string(),
+            "",
+            Type::NamedType("OrderDirection".to_string()),
+        ),
+        input_value(
+            &"where".to_string(),
+            "",
+            Type::NamedType(format!("{}_filter", type_name)),
+        ),
+        block_argument(),
+    ];
+    if features.contains(&IndexerFeature::nonFatalErrors) {
+        arguments.push(subgraph_error_argument());
+    }
+
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: format!("{}Connection", type_name.to_plural().to_camel_case()),
+        arguments,
+        field_type: Type::NonNullType(Box::new(Type::NamedType(format!(
+            "{}{}",
+            type_name, CONNECTION_TYPE_SUFFIX
+        )))),
+        directives: vec![],
+    }
+}
+
 fn meta_field() -> Field {
     lazy_static! {
         static ref META_FIELD: Field = Field {
@@ -860,7 +1329,7 @@ mod tests {
     use crate::prelude::DocumentExt;
     use graphql_parser::schema::*;
 
-    use super::api_schema;
+    use super::{api_schema, AGGREGATE_DIRECTIVE, CONNECTION_DIRECTIVE};
     use crate::schema::ast;
 
     #[test]
@@ -1249,4 +1718,180 @@ type Gravatar @entity {
         }
         .expect("\"metadata\" field is missing on Query type");
     }
+
+    #[test]
+    fn api_schema_contains_aggregate_types_and_query_field() {
+        let input_schema = parse_schema(
+            "type Token { id: ID! }
+             type Transfer { id: ID!, token: Token!, amount: BigInt!, timestamp: Int!, tags: [String!] }",
+        )
+        .expect("Failed to parse input schema");
+        let schema =
+            api_schema(&input_schema, &BTreeSet::new()).expect("Failed to derive API schema");
+
+        let enum_values = |name: &str| match schema.get_named_type(name) {
+            Some(TypeDefinition::Enum(t)) => t
+                .values
+                .iter()
+                .map(|value| value.name.to_owned())
+                .collect::<Vec<String>>(),
+            _ => panic!("{} is missing or not an enum", name),
+        };
+        let object_fields = |name: &str| match schema.get_named_type(name) {
+            Some(TypeDefinition::Object(t)) => t
+                .fields
+                .iter()
+                .map(|field| field.name.to_owned())
+                .collect::<Vec<String>>(),
+            _ => panic!("{} is missing or not an object type", name),
+        };
+
+        assert_eq!(
+            enum_values("AggregateInterval"),
+            ["minute", "hour", "day", "week", "month"]
+        );
+        assert_eq!(
+            enum_values("Transfer_groupBy"),
+            ["id", "token", "amount", "timestamp"]
+        );
+        assert_eq!(
+            enum_values("Transfer_aggregateField"),
+            ["amount", "timestamp"]
+        );
+        assert_eq!(
+            object_fields("Transfer_aggregate"),
+            ["count", "bucket", "group", "sum", "avg", "min", "max"]
+        );
+        assert_eq!(
+            object_fields("Transfer_aggregateGroup"),
+            ["id", "token", "amount", "timestamp"]
+        );
+        assert_eq!(
+            object_fields("Transfer_aggregateValues"),
+            ["amount", "timestamp"]
+        );
+
+        // Types without numeric fields can only be counted
+        assert_eq!(
+            object_fields("Token_aggregate"),
+            ["count", "bucket", "group"]
+        );
+        assert!(schema.get_named_type("Token_aggregateValues").is_none());
+
+        // Aggregation types are marked with the entity they aggregate
+        let aggregated_entity = match schema.get_named_type("Transfer_aggregate") {
+            Some(TypeDefinition::Object(t)) => {
+                ast::get_object_type_directive(t, AGGREGATE_DIRECTIVE.to_owned())
+            }
+            _ => None,
+        }
+        .map(|dir| dir.arguments.clone());
+        assert_eq!(
+            aggregated_entity,
+            Some(vec![(
+                "entity".to_owned(),
+                Value::String("Transfer".to_owned())
+            )])
+        );
+
+        let query_type = schema
+            .get_named_type("Query")
+            .expect("Query type is missing in derived API schema");
+        let transfers_aggregate = match query_type {
+            TypeDefinition::Object(t) => ast::get_field(t, &"transfersAggregate".to_string()),
+            _ => None,
+        }
+        .expect("\"transfersAggregate\" field is missing on Query type");
+        assert_eq!(
+            transfers_aggregate.field_type,
+            Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+                Box::new(Type::NamedType("Transfer_aggregate".to_string()))
+            )))))
+        );
+        assert_eq!(
+            transfers_aggregate
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            [
+                "skip",
+                "first",
+                "where",
+                "groupBy",
+                "interval",
+                "timestamp",
+                "block"
+            ]
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_connection_types_and_query_field() {
+        let input_schema = parse_schema("type Transfer { id: ID!, amount: BigInt! }")
+            .expect("Failed to parse input schema");
+        let schema =
+            api_schema(&input_schema, &BTreeSet::new()).expect("Failed to derive API schema");
+
+        let object_fields = |name: &str| match schema.get_named_type(name) {
+            Some(TypeDefinition::Object(t)) => t
+                .fields
+                .iter()
+                .map(|field| field.name.to_owned())
+                .collect::<Vec<String>>(),
+            _ => panic!("{} is missing or not an object type", name),
+        };
+
+        assert_eq!(
+            object_fields("_PageInfo_"),
+            ["hasNextPage", "hasPreviousPage", "startCursor", "endCursor"]
+        );
+        assert_eq!(object_fields("Transfer_edge"), ["cursor", "node"]);
+        assert_eq!(object_fields("Transfer_connection"), ["edges", "pageInfo"]);
+
+        // Connection types are marked with the entity they paginate
+        let connected_entity = match schema.get_named_type("Transfer_connection") {
+            Some(TypeDefinition::Object(t)) => {
+                ast::get_object_type_directive(t, CONNECTION_DIRECTIVE.to_owned())
+            }
+            _ => None,
+        }
+        .map(|dir| dir.arguments.clone());
+        assert_eq!(
+            connected_entity,
+            Some(vec![(
+                "entity".to_owned(),
+                Value::String("Transfer".to_owned())
+            )])
+        );
+
+        let query_type = schema
+            .get_named_type("Query")
+            .expect("Query type is missing in derived API schema");
+        let transfers_connection = match query_type {
+            TypeDefinition::Object(t) => ast::get_field(t, &"transfersConnection".to_string()),
+            _ => None,
+        }
+        .expect("\"transfersConnection\" field is missing on Query type");
+        assert_eq!(
+            transfers_connection.field_type,
+            Type::NonNullType(Box::new(Type::NamedType("Transfer_connection".to_string())))
+        );
+        assert_eq!(
+            transfers_connection
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            [
+                "first",
+                "after",
+                "before",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "block"
+            ]
+        );
+    }
 }
@@ -14,6 +14,7 @@ use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
 use std::iter::FromIterator;
+use std::str::FromStr;
 
 /// An entity attribute name is represented as a string.
 pub type Attribute = String;
@@ -415,6 +416,158 @@ impl EntityQuery {
     }
 }
 
+/// An aggregate function that can be computed over a numeric attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AggregateFunction {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub const ALL: [AggregateFunction; 4] = [
+        AggregateFunction::Sum,
+        AggregateFunction::Avg,
+        AggregateFunction::Min,
+        AggregateFunction::Max,
+    ];
+
+    /// The name of the function, both in the GraphQL API and in SQL
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        }
+    }
+}
+
+impl FromStr for AggregateFunction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        AggregateFunction::ALL
+            .iter()
+            .find(|function| function.as_str() == s)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown aggregate function `{}`", s))
+    }
+}
+
+/// The width of the time buckets used when aggregating over a timestamp
+/// attribute. Timestamps are seconds since the epoch and are bucketed in UTC
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AggregateInterval {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl AggregateInterval {
+    pub const ALL: [AggregateInterval; 5] = [
+        AggregateInterval::Minute,
+        AggregateInterval::Hour,
+        AggregateInterval::Day,
+        AggregateInterval::Week,
+        AggregateInterval::Month,
+    ];
+
+    /// The name of the interval, both in the GraphQL API and as the field
+    /// argument for Postgres' `date_trunc`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateInterval::Minute => "minute",
+            AggregateInterval::Hour => "hour",
+            AggregateInterval::Day => "day",
+            AggregateInterval::Week => "week",
+            AggregateInterval::Month => "month",
+        }
+    }
+}
+
+impl FromStr for AggregateInterval {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        AggregateInterval::ALL
+            .iter()
+            .find(|interval| interval.as_str() == s)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown aggregate interval `{}`", s))
+    }
+}
+
+/// A query that groups the entities of one type and computes aggregates
+/// for each group. Groups are formed by the distinct values of the
+/// `group_by` attributes and, if `bucket` is set, the time bucket that
+/// the timestamp attribute falls into
+#[derive(Clone, Debug)]
+pub struct AggregateQuery {
+    /// ID of the subgraph.
+    pub indexer_hash: DeploymentHash,
+
+    /// The block height at which to execute the query
+    pub block: BlockNumber,
+
+    /// The entity type whose entities are aggregated
+    pub entity_type: EntityType,
+
+    /// Filter to restrict the entities that are aggregated
+    pub filter: Option<EntityFilter>,
+
+    /// The attributes to group by
+    pub group_by: Vec<Attribute>,
+
+    /// The timestamp attribute and interval to bucket entities by
+    pub bucket: Option<(Attribute, AggregateInterval)>,
+
+    /// The aggregates to compute for each group; the number of entities
+    /// in each group is always computed
+    pub aggregates: Vec<(AggregateFunction, Attribute)>,
+
+    /// A range to limit the number of groups returned
+    pub range: EntityRange,
+
+    /// Optional logger for anything related to this query
+    pub logger: Option<Logger>,
+
+    pub query_id: Option<String>,
+}
+
+impl AggregateQuery {
+    pub fn new(indexer_hash: DeploymentHash, block: BlockNumber, entity_type: EntityType) -> Self {
+        AggregateQuery {
+            indexer_hash,
+            block,
+            entity_type,
+            filter: None,
+            group_by: vec![],
+            bucket: None,
+            aggregates: vec![],
+            range: EntityRange::first(100),
+            logger: None,
+            query_id: None,
+        }
+    }
+}
+
+/// One group in the result of an `AggregateQuery`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregateRow {
+    /// The values of the `group_by` attributes for this group
+    pub group: BTreeMap<Attribute, q::Value>,
+    /// The start of the time bucket, in seconds since the epoch
+    pub bucket: Option<q::Value>,
+    /// The number of entities in the group
+    pub count: i64,
+    /// The computed aggregates, keyed by function and attribute
+    pub values: BTreeMap<(AggregateFunction, Attribute), q::Value>,
+}
+
 /// Operation types that lead to entity changes.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
@@ -11,7 +11,8 @@ use crate::prelude::{q, QueryExecutionError};
 use crate::schema::{ApiSchema, Schema};
 pub use crate::store::chain::{BlockNumber, BlockPtr};
 pub use crate::store::entity::{
-    Attribute, Entity, EntityKey, EntityModification, EntityQuery, EntityType,
+    AggregateQuery, AggregateRow, Attribute, Entity, EntityKey, EntityModification, EntityQuery,
+    EntityType,
 };
 pub use event::*;
 use massbit_common::prelude::{anyhow::Error, async_trait::async_trait, serde_json, tokio};
@@ -146,6 +147,13 @@ pub trait QueryStore: Send + Sync {
         query: EntityQuery,
     ) -> Result<Vec<BTreeMap<String, q::Value>>, QueryExecutionError>;
 
+    /// Group the entities selected by `query` and compute the requested
+    /// aggregates for each group
+    fn find_aggregates(
+        &self,
+        query: AggregateQuery,
+    ) -> Result<Vec<AggregateRow>, QueryExecutionError>;
+
     async fn is_deployment_synced(&self) -> Result<bool, Error>;
 
     fn block_ptr(&self) -> Result<Option<BlockPtr>, Error>;
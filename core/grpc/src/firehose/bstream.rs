@@ -10,6 +10,9 @@ pub struct BlockRequest {
     pub network: ::prost::alloc::string::String,
     #[prost(bytes = "vec", tag = "5")]
     pub filter: ::prost::alloc::vec::Vec<u8>,
+    /// Get the blocks from start_block_number to the live blocks first
+    #[prost(bool, tag = "6")]
+    pub replay_history: bool,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BlockResponse {
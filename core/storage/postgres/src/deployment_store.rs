@@ -16,7 +16,7 @@ use massbit_data::indexer::{DeploymentHash, DeploymentState};
 use massbit_data::prelude::{EntityQuery, QueryExecutionError, StoreError};
 use massbit_data::schema::api::api_schema;
 use massbit_data::schema::{ApiSchema, Schema};
-use massbit_data::store::{BlockNumber, BlockPtr, PoolWaitStats};
+use massbit_data::store::{AggregateQuery, AggregateRow, BlockNumber, BlockPtr, PoolWaitStats};
 use massbit_data::utils::futures::{CancelHandle, CancelableError};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
@@ -199,6 +199,18 @@ impl DeploymentStore {
             query.query_id,
         )
     }
+
+    pub(crate) fn execute_aggregate_query(
+        &self,
+        conn: &PgConnection,
+        site: Arc<Site>,
+        query: AggregateQuery,
+    ) -> Result<Vec<AggregateRow>, QueryExecutionError> {
+        let layout = self.layout(conn, site)?;
+
+        let logger = query.logger.clone().unwrap_or(self.logger.clone());
+        layout.aggregate(&logger, conn, query)
+    }
     /// Return the layout for a deployment. Since constructing a `Layout`
     /// object takes a bit of computation, we cache layout objects that do
     /// not have a pending migration in the Store, i.e., for the lifetime of
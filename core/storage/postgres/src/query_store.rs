@@ -11,7 +11,9 @@ use std::sync::Arc;
 use crate::deployment_store::{DeploymentStore, ReplicaId};
 use massbit_data::indexer::DeploymentState;
 use massbit_data::prelude::q;
-use massbit_data::store::{PoolWaitStats, QueryStore as QueryStoreTrait, StoreError};
+use massbit_data::store::{
+    AggregateQuery, AggregateRow, PoolWaitStats, QueryStore as QueryStoreTrait, StoreError,
+};
 
 use crate::primary::Site;
 
@@ -44,6 +46,18 @@ impl QueryStoreTrait for QueryStore {
         self.store.execute_query(&conn, self.site.clone(), query)
     }
 
+    fn find_aggregates(
+        &self,
+        query: AggregateQuery,
+    ) -> Result<Vec<AggregateRow>, QueryExecutionError> {
+        let conn = self
+            .store
+            .get_replica_conn(self.replica_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        self.store
+            .execute_aggregate_query(&conn, self.site.clone(), query)
+    }
+
     /// Return true if the deployment with the given id is fully synced,
     /// and return false otherwise. Errors from the store are passed back up
     async fn is_deployment_synced(&self) -> Result<bool, Error> {
@@ -10,8 +10,9 @@ use crate::{
     deployment,
     primary::Site,
     relational_queries::{
-        ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterCollection, FilterQuery,
-        FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        AggregateData, ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterCollection,
+        FilterQuery, FindManyQuery, FindQuery, GroupByQuery, InsertQuery, RevertClampQuery,
+        RevertRemoveQuery,
     },
 };
 use diesel::{connection::SimpleConnection, Connection};
@@ -33,7 +34,7 @@ use massbit_data::store::entity::{
     EntityType,
 };
 use massbit_data::store::value::{ValueType, BYTES_SCALAR};
-use massbit_data::store::StoreEvent;
+use massbit_data::store::{AggregateQuery, AggregateRow, StoreEvent};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::{From, TryFrom};
 use std::env;
@@ -680,6 +681,56 @@ impl Layout {
             .collect()
     }
 
+    /// Group the entities of `query.entity_type` and compute the aggregates
+    /// requested in `query` for each group
+    pub fn aggregate(
+        &self,
+        logger: &Logger,
+        conn: &PgConnection,
+        query: AggregateQuery,
+    ) -> Result<Vec<AggregateRow>, QueryExecutionError> {
+        let table = self.table_for_entity(&query.entity_type)?;
+        let group_by_query = GroupByQuery::new(
+            table,
+            query.filter.as_ref(),
+            &query.group_by,
+            query.bucket.as_ref(),
+            &query.aggregates,
+            query.range.clone(),
+            query.block,
+            query.query_id.clone(),
+        )?;
+
+        let start = Instant::now();
+        let values = conn
+            .transaction(|| {
+                if let Some(ref timeout_sql) = *STATEMENT_TIMEOUT {
+                    conn.batch_execute(timeout_sql)?;
+                }
+                group_by_query.clone().load::<AggregateData>(conn)
+            })
+            .map_err(|e| {
+                QueryExecutionError::ResolveEntitiesError(format!(
+                    "{}, query = {:?}",
+                    e,
+                    debug_query(&group_by_query).to_string()
+                ))
+            })?;
+        if *massbit_data::log::LOG_SQL_TIMING {
+            info!(
+                logger,
+                "Query timing (SQL)";
+                "query" => debug_query(&group_by_query).to_string().replace("\n", "\t"),
+                "time_ms" => start.elapsed().as_millis(),
+                "group_count" => values.len()
+            );
+        }
+        values
+            .into_iter()
+            .map(|data| group_by_query.deserialize(data).map_err(|e| e.into()))
+            .collect()
+    }
+
     pub fn update(
         &self,
         conn: &PgConnection,
@@ -1783,6 +1834,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn generate_group_by_query() {
+        use diesel::pg::Pg;
+        use massbit_data::store::entity::{AggregateFunction, AggregateInterval};
+
+        let layout = test_layout(TRANSFER_GQL);
+        let table = layout
+            .table(&"transfer".into())
+            .expect("failed to get 'transfer' table");
+        let group_by = vec!["token".to_owned()];
+        let aggregates = vec![(AggregateFunction::Sum, "amount".to_owned())];
+        let range = EntityRange {
+            first: Some(10),
+            skip: 0,
+        };
+        let group_by_query = |timestamp: &str| {
+            let bucket = (timestamp.to_owned(), AggregateInterval::Day);
+            GroupByQuery::new(
+                table,
+                None,
+                &group_by,
+                Some(&bucket),
+                &aggregates,
+                range.clone(),
+                BLOCK_NUMBER_MAX,
+                None,
+            )
+            .map(|query| debug_query::<Pg, _>(&query).to_string())
+        };
+
+        let bucket = "extract(epoch from date_trunc('day', \
+                      to_timestamp(c.\"timestamp\"::float8) at time zone 'utc'))::bigint";
+        let sql = group_by_query("timestamp").expect("Failed to build group by query");
+        assert!(sql.starts_with(&format!(
+            "select jsonb_build_object('count', count(*), 'g0', c.\"token\", \
+             'bucket', {}, 'a0', sum(c.\"amount\")) as data\n  from \"sgd0815\".\"transfer\" c",
+            bucket
+        )));
+        assert!(sql.contains(&format!(
+            "\n group by {}, c.\"token\"\n order by {}, c.\"token\"\n limit 10",
+            bucket, bucket
+        )));
+
+        // Timestamps can be strings of digits, but not decimals
+        group_by_query("day").expect("Should bucket by String timestamp");
+        group_by_query("amount").expect_err("Should not bucket by BigDecimal timestamp");
+    }
+
+    const TRANSFER_GQL: &str = "
+        type Transfer @entity {
+            id: ID!
+            token: String!
+            amount: BigDecimal!
+            timestamp: Int!
+            day: String!
+        }";
+
     const THING_GQL: &str = "
         type Thing @entity {
             id: ID!
@@ -18,9 +18,11 @@ use massbit_data::prelude::{q, QueryExecutionError, StoreError};
 use massbit_data::schema::FulltextAlgorithm;
 use massbit_data::store::chain::BlockNumber;
 use massbit_data::store::entity::{
-    Attribute, AttributeNames, ChildMultiplicity, Entity, EntityCollection, EntityFilter,
-    EntityKey, EntityLink, EntityOrder, EntityRange, EntityType, EntityWindow, ParentLink,
+    AggregateFunction, AggregateInterval, Attribute, AttributeNames, ChildMultiplicity, Entity,
+    EntityCollection, EntityFilter, EntityKey, EntityLink, EntityOrder, EntityRange, EntityType,
+    EntityWindow, ParentLink,
 };
+use massbit_data::store::AggregateRow;
 use massbit_data::store::{scalar, Value};
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
@@ -2533,6 +2535,255 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FilterQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FilterQuery<'a> {}
 
+/// Helper struct for retrieving the groups of an aggregation. Like for
+/// `EntityData`, the values for each group are returned as one Jsonb object
+#[derive(QueryableByName)]
+pub struct AggregateData {
+    #[sql_type = "Jsonb"]
+    data: serde_json::Value,
+}
+
+/// The parallel to `AggregateQuery`. Generates
+///
+///   select jsonb_build_object('count', count(*),
+///                             'g0', c.{group_by[0]}, ...,
+///                             'bucket', {bucket},
+///                             'a0', {function}(c.{column}), ...) as data
+///     from schema.table c
+///    where block_range @> $block
+///      and filter
+///    group by {bucket}, c.{group_by[0]}, ...
+///    order by {bucket}, c.{group_by[0]}, ...
+///    limit .. offset ..
+///
+/// where `{bucket}` truncates the timestamp column, which holds seconds
+/// since the epoch, to the start of its interval in UTC
+#[derive(Debug, Clone)]
+pub struct GroupByQuery<'a> {
+    table: &'a Table,
+    filter: Option<QueryFilter<'a>>,
+    group_by: Vec<&'a Column>,
+    bucket: Option<(&'a Column, AggregateInterval)>,
+    aggregates: Vec<(AggregateFunction, &'a Column)>,
+    range: FilterRange,
+    block: BlockNumber,
+    query_id: Option<String>,
+}
+
+impl<'a> GroupByQuery<'a> {
+    pub fn new(
+        table: &'a Table,
+        filter: Option<&'a EntityFilter>,
+        group_by: &'a [Attribute],
+        bucket: Option<&'a (Attribute, AggregateInterval)>,
+        aggregates: &'a [(AggregateFunction, Attribute)],
+        range: EntityRange,
+        block: BlockNumber,
+        query_id: Option<String>,
+    ) -> Result<Self, QueryExecutionError> {
+        fn numeric_column<'b>(table: &'b Table, attr: &str) -> Result<&'b Column, StoreError> {
+            let column = table.column_for_field(attr)?;
+            match column.column_type {
+                ColumnType::Int | ColumnType::BigInt | ColumnType::BigDecimal
+                    if !column.is_list() =>
+                {
+                    Ok(column)
+                }
+                _ => Err(StoreError::QueryExecutionError(format!(
+                    "can not aggregate over attribute `{}` of `{}` since it is not numeric",
+                    attr,
+                    table.object.as_str()
+                ))),
+            }
+        }
+
+        // The timestamp holds whole seconds since the epoch, as a number or
+        // as a string of digits
+        fn timestamp_column<'b>(table: &'b Table, attr: &str) -> Result<&'b Column, StoreError> {
+            let column = table.column_for_field(attr)?;
+            match column.column_type {
+                ColumnType::Int | ColumnType::BigInt | ColumnType::String if !column.is_list() => {
+                    Ok(column)
+                }
+                _ => Err(StoreError::QueryExecutionError(format!(
+                    "can not bucket `{}` by attribute `{}` since it is not an Int, BigInt \
+                     or String timestamp",
+                    table.object.as_str(),
+                    attr
+                ))),
+            }
+        }
+
+        let filter = filter
+            .map(|filter| QueryFilter::new(filter, table))
+            .transpose()?;
+        let group_by = group_by
+            .iter()
+            .map(|attr| {
+                let column = table.column_for_field(attr)?;
+                if column.is_list() || column.is_fulltext() {
+                    return Err(StoreError::QueryExecutionError(format!(
+                        "can not group `{}` by attribute `{}`",
+                        table.object.as_str(),
+                        attr
+                    )));
+                }
+                Ok(column)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let bucket = bucket
+            .map(|(attr, interval)| timestamp_column(table, attr).map(|column| (column, *interval)))
+            .transpose()?;
+        let aggregates = aggregates
+            .iter()
+            .map(|(function, attr)| numeric_column(table, attr).map(|column| (*function, column)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(GroupByQuery {
+            table,
+            filter,
+            group_by,
+            bucket,
+            aggregates,
+            range: FilterRange(range),
+            block,
+            query_id,
+        })
+    }
+
+    fn bucket(
+        column: &Column,
+        interval: AggregateInterval,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        out.push_sql("extract(epoch from date_trunc('");
+        out.push_sql(interval.as_str());
+        out.push_sql("', to_timestamp(c.");
+        out.push_identifier(column.name.as_str())?;
+        out.push_sql("::float8) at time zone 'utc'))::bigint");
+        Ok(())
+    }
+
+    /// Generate the list of grouping expressions, used both for the
+    /// `group by` and `order by` clauses
+    fn groups(&self, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        let mut first = true;
+        if let Some((column, interval)) = &self.bucket {
+            Self::bucket(column, *interval, out)?;
+            first = false;
+        }
+        for column in &self.group_by {
+            if !first {
+                out.push_sql(", ");
+            }
+            first = false;
+            out.push_sql("c.");
+            out.push_identifier(column.name.as_str())?;
+        }
+        Ok(())
+    }
+
+    /// Turn the `data` returned for one group into an `AggregateRow`
+    pub fn deserialize(&self, data: AggregateData) -> Result<AggregateRow, StoreError> {
+        let mut map = match data.data {
+            serde_json::Value::Object(map) => map,
+            _ => unreachable!("we use `jsonb_build_object` and always get an object back"),
+        };
+        let mut take = |key: &str| map.remove(key).unwrap_or(serde_json::Value::Null);
+
+        let count = take("count").as_i64().unwrap_or(0);
+        let mut group = BTreeMap::new();
+        for (i, column) in self.group_by.iter().enumerate() {
+            let value = q::Value::from_column_value(&column.column_type, take(&format!("g{}", i)))?;
+            group.insert(column.field.clone(), value);
+        }
+        let bucket = match self.bucket {
+            Some(_) => Some(q::Value::from_column_value(
+                &ColumnType::BigInt,
+                take("bucket"),
+            )?),
+            None => None,
+        };
+        let mut values = BTreeMap::new();
+        for (i, (function, column)) in self.aggregates.iter().enumerate() {
+            let value =
+                q::Value::from_column_value(&ColumnType::BigDecimal, take(&format!("a{}", i)))?;
+            values.insert((*function, column.field.clone()), value);
+        }
+        Ok(AggregateRow {
+            group,
+            bucket,
+            count,
+            values,
+        })
+    }
+}
+
+impl<'a> QueryFragment<Pg> for GroupByQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        if let Some(qid) = &self.query_id {
+            out.push_sql("/* qid: ");
+            out.push_sql(qid);
+            out.push_sql(" */\n");
+        }
+        out.push_sql("select jsonb_build_object('count', count(*)");
+        for (i, column) in self.group_by.iter().enumerate() {
+            out.push_sql(", 'g");
+            out.push_sql(&i.to_string());
+            out.push_sql("', c.");
+            out.push_identifier(column.name.as_str())?;
+        }
+        if let Some((column, interval)) = &self.bucket {
+            out.push_sql(", 'bucket', ");
+            Self::bucket(column, *interval, &mut out)?;
+        }
+        for (i, (function, column)) in self.aggregates.iter().enumerate() {
+            out.push_sql(", 'a");
+            out.push_sql(&i.to_string());
+            out.push_sql("', ");
+            out.push_sql(function.as_str());
+            out.push_sql("(c.");
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(")");
+        }
+        out.push_sql(") as data");
+
+        out.push_sql("\n  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" c");
+        out.push_sql("\n where ");
+        BlockRangeContainsClause::new(&self.table, "c.", self.block).walk_ast(out.reborrow())?;
+        if let Some(filter) = &self.filter {
+            out.push_sql(" and ");
+            filter.walk_ast(out.reborrow())?;
+        }
+
+        if self.bucket.is_some() || !self.group_by.is_empty() {
+            out.push_sql("\n group by ");
+            self.groups(&mut out)?;
+            out.push_sql("\n order by ");
+            self.groups(&mut out)?;
+        }
+        self.range.walk_ast(out.reborrow())
+    }
+}
+
+impl<'a> QueryId for GroupByQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, AggregateData> for GroupByQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<AggregateData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for GroupByQuery<'a> {}
+
 /// Reduce the upper bound of the current entry's block range to `block` as
 /// long as that does not result in an empty block range
 #[derive(Debug, Clone, Constructor)]
@@ -0,0 +1,103 @@
+//! Operator maintained list of queries that are refused by the `LoadManager`.
+//!
+//! The file contains one GraphQL query per line; empty lines and lines
+//! starting with `#` are ignored. Queries are matched by their shape hash, so
+//! a listed query also blocks variants that only differ in argument values.
+use massbit_common::prelude::{
+    anyhow::{anyhow, Error},
+    slog::{error, info, Logger},
+    tokio,
+};
+use massbit_data::prelude::{q, LoadManager};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Reads and parses the expensive queries in the file at `path`.
+pub fn read_expensive_queries(path: &Path) -> Result<Vec<Arc<q::Document>>, Error> {
+    let content = fs::read_to_string(path)?;
+    parse_expensive_queries(&content)
+}
+
+fn parse_expensive_queries(content: &str) -> Result<Vec<Arc<q::Document>>, Error> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            graphql_parser::parse_query(line)
+                .map(|doc| Arc::new(doc.into_static()))
+                .map_err(|e| anyhow!("invalid query on line {}: {}", line_number, e))
+        })
+        .collect()
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Spawns a task that checks the file at `path` every `interval` and hands
+/// its queries to `load_manager` whenever it was modified. If the file can
+/// not be parsed, the previously loaded queries stay in effect.
+pub fn watch_expensive_queries(
+    logger: Logger,
+    path: PathBuf,
+    interval: Duration,
+    load_manager: Arc<LoadManager>,
+) {
+    tokio::spawn(async move {
+        let mut last_modified = modified_at(&path);
+        loop {
+            tokio::time::sleep(interval).await;
+            let modified = modified_at(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            match read_expensive_queries(&path) {
+                Ok(queries) => {
+                    info!(logger, "Reloaded expensive queries";
+                        "path" => path.display().to_string(),
+                        "count" => queries.len());
+                    load_manager.set_blocked_queries(queries);
+                }
+                Err(e) => {
+                    error!(logger, "Failed to reload expensive queries, keeping the previous list";
+                        "path" => path.display().to_string(),
+                        "error" => e.to_string());
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_expensive_queries;
+    use massbit_data::graphql::shape_hash::shape_hash;
+
+    #[test]
+    fn skips_comments_and_empty_lines() {
+        let content = "# Blocked on 2021-12-01\n\n{ trades(first: 1000) { id } }\n  \n";
+        let queries = parse_expensive_queries(content).expect("Should parse queries");
+        assert_eq!(queries.len(), 1);
+    }
+
+    #[test]
+    fn blocks_queries_by_shape() {
+        let queries = parse_expensive_queries("{ trades(first: 1000) { id } }").unwrap();
+        let variant = graphql_parser::parse_query("{ trades(first: 10) { id } }")
+            .unwrap()
+            .into_static();
+        assert_eq!(shape_hash(&queries[0]), shape_hash(&variant));
+    }
+
+    #[test]
+    fn reports_line_of_invalid_query() {
+        let err = parse_expensive_queries("{ trades { id } }\n{ trades {")
+            .expect_err("Should reject invalid query");
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,1136 @@
+//! Federation of several indexers behind a single GraphQL endpoint.
+//!
+//! A federation maps namespaces to indexer deployments. The root fields of a
+//! federated query are namespaces, and the selection set of each namespace is
+//! run as an ordinary query against its indexer through the `GraphQlRunner`:
+//!
+//! ```graphql
+//! query($owner: String!, $mints: [String!]) {
+//!   serum { trades(where: { owner: $owner }) { baseMint @export(as: "mints") } }
+//!   saber { swaps(where: { mint_in: $mints }) { id amount } }
+//! }
+//! ```
+//!
+//! Namespaces are executed in the order in which they appear in the query.
+//! Values of fields marked with `@export(as: "name")` are collected into a
+//! list and passed as the variable `$name` to the namespaces that follow,
+//! which is how results of different indexers are joined on shared ids such
+//! as token mints or owner pubkeys.
+//!
+//! Queries are validated against the federated schema, see
+//! `Federation::schema`, so fragments use the prefixed type names, e.g.
+//! `... on Serum_Trade`, and variables, e.g. `$where: Serum_Trade_filter`; the
+//! prefix is removed before the selection set is run against its indexer and
+//! added back to the `__typename` values of its results. `__schema`, `__type`
+//! and `__typename` on the root are resolved against the federated schema as
+//! well.
+use crate::execution::{execute_root_selection_set_uncached, ExecutionContext};
+use crate::introspection::IntrospectionResolver;
+use crate::server::GraphQlRunner;
+use crossbeam::atomic::AtomicCell;
+use inflector::Inflector;
+use massbit_common::prelude::anyhow::{anyhow, Error};
+use massbit_common::prelude::slog::{warn, Logger};
+use massbit_data::indexer::DeploymentHash;
+use massbit_data::prelude::{q, s, QueryExecutionError};
+use massbit_data::query::{CacheStatus, Query, QueryResult, QueryResults, QueryVariables};
+use massbit_data::schema::{ApiSchema, Schema};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+const EXPORT_DIRECTIVE: &str = "export";
+const EXPORT_ARGUMENT: &str = "as";
+
+/// The federations served by this node, read from a TOML file like
+///
+/// ```toml
+/// [federations.portfolio]
+/// serum = "QmSerumIndexerHash"
+/// saber = "QmSaberIndexerHash"
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Federations {
+    federations: BTreeMap<String, Federation>,
+}
+
+#[derive(Deserialize)]
+struct FederationsConfig {
+    #[serde(default)]
+    federations: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Federations {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let config: FederationsConfig = toml::from_str(&fs::read_to_string(path)?)?;
+        let mut federations = BTreeMap::new();
+        for (name, namespaces) in config.federations {
+            // The name is used as the id of the federated schema
+            DeploymentHash::new(name.as_str()).map_err(|_| {
+                anyhow!(
+                    "federation `{}`: names can only contain alphanumeric characters, '_' or '-'",
+                    name
+                )
+            })?;
+            let mut hashes = BTreeMap::new();
+            for (namespace, hash) in namespaces {
+                if !is_valid_name(&namespace) {
+                    return Err(anyhow!(
+                        "federation `{}`: namespace `{}` is not a valid GraphQL name",
+                        name,
+                        namespace
+                    ));
+                }
+                let hash = DeploymentHash::new(hash.as_str()).map_err(|_| {
+                    anyhow!(
+                        "federation `{}`: invalid indexer hash `{}` for namespace `{}`",
+                        name,
+                        hash,
+                        namespace
+                    )
+                })?;
+                hashes.insert(namespace, hash);
+            }
+            federations.insert(name.clone(), Federation::new(name, hashes));
+        }
+        Ok(Federations { federations })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Federation> {
+        self.federations.get(name).cloned()
+    }
+
+    /// Builds the API schemas of all federations, so that the first queries
+    /// don't have to. A federation whose indexers are not deployed yet is
+    /// built on its first query instead.
+    pub async fn build_schemas<Q: GraphQlRunner>(&self, logger: &Logger, runner: Arc<Q>) {
+        for federation in self.federations.values() {
+            if let Err(e) = federation.api_schema(runner.clone()).await {
+                warn!(logger, "Failed to build federated schema";
+                      "federation" => &federation.name,
+                      "error" => e.to_string());
+            }
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// A set of indexers that can be queried together, each under its namespace.
+#[derive(Clone, Debug)]
+pub struct Federation {
+    pub name: String,
+    pub namespaces: BTreeMap<String, DeploymentHash>,
+    /// The federated API schema, shared by the clones handed out by
+    /// `Federations::get` and built once
+    api_schema: Arc<RwLock<Option<Arc<ApiSchema>>>>,
+}
+
+impl Federation {
+    fn new(name: String, namespaces: BTreeMap<String, DeploymentHash>) -> Self {
+        Federation {
+            name,
+            namespaces,
+            api_schema: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Runs a federated query by splitting it into one query per namespace.
+    pub async fn run_query<Q: GraphQlRunner>(
+        &self,
+        logger: &Logger,
+        runner: Arc<Q>,
+        query: Query,
+    ) -> QueryResults {
+        match self.execute(logger, runner, query).await {
+            Ok(result) => result.into(),
+            Err(e) => QueryResults::from(e),
+        }
+    }
+
+    async fn execute<Q: GraphQlRunner>(
+        &self,
+        logger: &Logger,
+        runner: Arc<Q>,
+        query: Query,
+    ) -> Result<QueryResult, Vec<QueryExecutionError>> {
+        let schema = self.api_schema(runner.clone()).await.map_err(|e| vec![e])?;
+        let federated_query = crate::execution::Query::new(
+            logger,
+            schema,
+            None,
+            query.clone(),
+            None,
+            runner.max_depth(),
+        )?;
+
+        let mut operation = None;
+        let mut fragments = HashMap::new();
+        for defn in query.document.definitions {
+            match defn {
+                q::Definition::Operation(op) => match operation {
+                    None => operation = Some(op),
+                    Some(_) => return Err(vec![QueryExecutionError::OperationNameRequired]),
+                },
+                q::Definition::Fragment(frag) => {
+                    fragments.insert(frag.name.clone(), frag);
+                }
+            }
+        }
+        let (name, variable_definitions, selection_set) = match operation {
+            Some(q::OperationDefinition::Query(query)) => {
+                (query.name, query.variable_definitions, query.selection_set)
+            }
+            Some(q::OperationDefinition::SelectionSet(selection_set)) => {
+                (None, vec![], selection_set)
+            }
+            Some(_) => {
+                return Err(vec![QueryExecutionError::NotSupported(
+                    "Only queries can be federated".to_owned(),
+                )])
+            }
+            None => return Err(vec![QueryExecutionError::OperationNameRequired]),
+        };
+
+        let mut variables: HashMap<String, q::Value> = query
+            .variables
+            .map(|variables| (*variables).clone())
+            .unwrap_or_default();
+        let mut data = BTreeMap::new();
+        let mut errors = Vec::new();
+        let mut root_meta_fields = Vec::new();
+        for item in selection_set.items {
+            let field = match item {
+                q::Selection::Field(field) => field,
+                _ => {
+                    return Err(vec![QueryExecutionError::NotSupported(
+                        "Fragments are not supported on the root of a federated query".to_owned(),
+                    )])
+                }
+            };
+            if field.name.starts_with("__") {
+                root_meta_fields.push(field);
+                continue;
+            }
+            let hash = self.namespaces.get(&field.name).ok_or_else(|| {
+                vec![QueryExecutionError::UnknownField(
+                    field.position,
+                    "Query".to_owned(),
+                    field.name.clone(),
+                )]
+            })?;
+
+            let document = namespace_document(
+                name.clone(),
+                &variable_definitions,
+                &field.selection_set,
+                &fragments,
+                &type_prefix(&field.name),
+            );
+            let used_variables = document_variables(&document);
+            let namespace_variables = variables
+                .iter()
+                .filter(|(name, _)| used_variables.contains(*name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<HashMap<_, _>>();
+            let result = runner
+                .clone()
+                .run_query(
+                    Query::new(document, Some(QueryVariables::new(namespace_variables))),
+                    hash.clone(),
+                )
+                .await;
+
+            errors.extend(result.errors());
+            if let Some(mut namespace_data) = result.data() {
+                prefix_typenames(
+                    &field.selection_set,
+                    &mut namespace_data,
+                    &fragments,
+                    &type_prefix(&field.name),
+                );
+                let mut exports = BTreeMap::new();
+                collect_exports(
+                    &field.selection_set,
+                    &namespace_data,
+                    &fragments,
+                    &mut exports,
+                );
+                for (name, values) in exports {
+                    variables.insert(name, q::Value::List(values.into_iter().collect()));
+                }
+                let key = field.alias.unwrap_or(field.name);
+                data.insert(key, q::Value::Object(namespace_data));
+            }
+        }
+
+        if !root_meta_fields.is_empty() {
+            match resolve_root_meta_fields(&federated_query, root_meta_fields) {
+                Ok(values) => data.extend(values),
+                Err(e) => errors.extend(e),
+            }
+        }
+
+        let mut result = QueryResult::new(data);
+        result.errors_mut().extend(errors);
+        Ok(result)
+    }
+
+    /// The federated schema, against which federated queries are validated.
+    /// It is built on first use and kept for the lifetime of the federation.
+    async fn api_schema<Q: GraphQlRunner>(
+        &self,
+        runner: Arc<Q>,
+    ) -> Result<Arc<ApiSchema>, QueryExecutionError> {
+        if let Some(schema) = self.api_schema.read().unwrap().as_ref() {
+            return Ok(schema.clone());
+        }
+        let document = self.schema(runner).await?;
+        // Unwrap: federation names are validated in `Federations::load`
+        let id = DeploymentHash::new(self.name.as_str()).unwrap();
+        let schema = ApiSchema::from_api_schema(Schema::new(id, document))
+            .map(Arc::new)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        *self.api_schema.write().unwrap() = Some(schema.clone());
+        Ok(schema)
+    }
+
+    /// Builds the schema of the federation, in which the types of each
+    /// indexer's API schema are prefixed with the namespace, e.g. `Serum_Trade`,
+    /// and the root `Query` type has one field per namespace.
+    pub async fn schema<Q: GraphQlRunner>(
+        &self,
+        runner: Arc<Q>,
+    ) -> Result<s::Document, QueryExecutionError> {
+        let mut schemas = Vec::new();
+        for (namespace, hash) in &self.namespaces {
+            let schema = runner.clone().api_schema(hash.clone()).await?;
+            schemas.push((namespace.clone(), schema.document().clone()));
+        }
+        Ok(federated_schema(schemas))
+    }
+}
+
+/// Resolves `__typename`, `__schema` and `__type` on the root of a federated
+/// query against the federated schema.
+fn resolve_root_meta_fields(
+    query: &Arc<crate::execution::Query>,
+    fields: Vec<q::Field>,
+) -> Result<BTreeMap<String, q::Value>, Vec<QueryExecutionError>> {
+    let mut values = BTreeMap::new();
+    let mut introspection_set = q::SelectionSet {
+        span: query.selection_set.span.clone(),
+        items: Vec::new(),
+    };
+    for field in fields {
+        if field.name == "__typename" {
+            let key = field.alias.unwrap_or(field.name);
+            values.insert(key, q::Value::String(query.schema.query_type.name.clone()));
+        } else {
+            introspection_set.items.push(q::Selection::Field(field));
+        }
+    }
+    if !introspection_set.items.is_empty() {
+        let ctx = ExecutionContext {
+            logger: query.logger.clone(),
+            resolver: IntrospectionResolver::new(&query.logger, query.schema.schema()),
+            query: query.clone(),
+            deadline: None,
+            max_first: std::u32::MAX,
+            max_skip: std::u32::MAX,
+            cache_status: AtomicCell::new(CacheStatus::Miss),
+        };
+        values.extend(execute_root_selection_set_uncached(
+            &ctx,
+            &introspection_set,
+            &query.schema.query_type,
+        )?);
+    }
+    Ok(values)
+}
+
+/// The prefix of the types of a namespace in the federated schema.
+fn type_prefix(namespace: &str) -> String {
+    format!("{}_", namespace.to_pascal_case())
+}
+
+/// Builds the query that runs the selection set of one namespace against its
+/// indexer. Only the variables and fragments the selection set uses are
+/// included, `@export` directives are removed and the namespace `type_prefix`
+/// is removed from type conditions and variable types.
+fn namespace_document(
+    name: Option<String>,
+    variable_definitions: &[q::VariableDefinition],
+    selection_set: &q::SelectionSet,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    type_prefix: &str,
+) -> q::Document {
+    let mut fragment_names = BTreeSet::new();
+    collect_fragment_names(selection_set, fragments, &mut fragment_names);
+    let used_fragments = fragment_names
+        .iter()
+        .filter_map(|name| fragments.get(name))
+        .map(|fragment| {
+            let mut fragment = fragment.clone();
+            strip_exports(&mut fragment.selection_set);
+            strip_type_prefix(&mut fragment.type_condition, type_prefix);
+            strip_type_prefixes(&mut fragment.selection_set, type_prefix);
+            fragment
+        })
+        .collect::<Vec<_>>();
+
+    let mut selection_set = selection_set.clone();
+    strip_exports(&mut selection_set);
+    strip_type_prefixes(&mut selection_set, type_prefix);
+
+    let mut used_variables = BTreeSet::new();
+    collect_selection_variables(&selection_set, &mut used_variables);
+    for fragment in &used_fragments {
+        collect_selection_variables(&fragment.selection_set, &mut used_variables);
+    }
+    let variable_definitions = variable_definitions
+        .iter()
+        .filter(|defn| used_variables.contains(&defn.name))
+        .map(|defn| {
+            let mut defn = defn.clone();
+            strip_variable_type_prefix(&mut defn.var_type, type_prefix);
+            defn
+        })
+        .collect();
+
+    let mut definitions = vec![q::Definition::Operation(q::OperationDefinition::Query(
+        q::Query {
+            position: selection_set.span.0,
+            name,
+            variable_definitions,
+            directives: vec![],
+            selection_set,
+        },
+    ))];
+    definitions.extend(used_fragments.into_iter().map(q::Definition::Fragment));
+    q::Document { definitions }
+}
+
+fn collect_fragment_names(
+    selection_set: &q::SelectionSet,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    names: &mut BTreeSet<String>,
+) {
+    for item in &selection_set.items {
+        match item {
+            q::Selection::Field(field) => {
+                collect_fragment_names(&field.selection_set, fragments, names)
+            }
+            q::Selection::InlineFragment(fragment) => {
+                collect_fragment_names(&fragment.selection_set, fragments, names)
+            }
+            q::Selection::FragmentSpread(spread) => {
+                if names.insert(spread.fragment_name.clone()) {
+                    if let Some(fragment) = fragments.get(&spread.fragment_name) {
+                        collect_fragment_names(&fragment.selection_set, fragments, names);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn strip_exports(selection_set: &mut q::SelectionSet) {
+    for item in selection_set.items.iter_mut() {
+        match item {
+            q::Selection::Field(field) => {
+                field.directives.retain(|dir| dir.name != EXPORT_DIRECTIVE);
+                strip_exports(&mut field.selection_set);
+            }
+            q::Selection::InlineFragment(fragment) => strip_exports(&mut fragment.selection_set),
+            q::Selection::FragmentSpread(_) => {}
+        }
+    }
+}
+
+fn strip_type_prefix(type_condition: &mut q::TypeCondition, type_prefix: &str) {
+    let q::TypeCondition::On(type_name) = type_condition;
+    if let Some(name) = type_name.strip_prefix(type_prefix) {
+        *type_name = name.to_owned();
+    }
+}
+
+fn strip_variable_type_prefix(var_type: &mut q::Type, type_prefix: &str) {
+    match var_type {
+        q::Type::NamedType(type_name) => {
+            if let Some(name) = type_name.strip_prefix(type_prefix) {
+                *type_name = name.to_owned();
+            }
+        }
+        q::Type::ListType(inner) | q::Type::NonNullType(inner) => {
+            strip_variable_type_prefix(inner, type_prefix)
+        }
+    }
+}
+
+fn strip_type_prefixes(selection_set: &mut q::SelectionSet, type_prefix: &str) {
+    for item in selection_set.items.iter_mut() {
+        match item {
+            q::Selection::Field(field) => {
+                strip_type_prefixes(&mut field.selection_set, type_prefix)
+            }
+            q::Selection::InlineFragment(fragment) => {
+                if let Some(type_condition) = fragment.type_condition.as_mut() {
+                    strip_type_prefix(type_condition, type_prefix);
+                }
+                strip_type_prefixes(&mut fragment.selection_set, type_prefix);
+            }
+            q::Selection::FragmentSpread(_) => {}
+        }
+    }
+}
+
+fn collect_value_variables(value: &q::Value, names: &mut BTreeSet<String>) {
+    match value {
+        q::Value::Variable(name) => {
+            names.insert(name.clone());
+        }
+        q::Value::List(values) => values
+            .iter()
+            .for_each(|value| collect_value_variables(value, names)),
+        q::Value::Object(map) => map
+            .values()
+            .for_each(|value| collect_value_variables(value, names)),
+        _ => {}
+    }
+}
+
+fn collect_directive_variables(directives: &[q::Directive], names: &mut BTreeSet<String>) {
+    for directive in directives {
+        for (_, value) in &directive.arguments {
+            collect_value_variables(value, names);
+        }
+    }
+}
+
+fn collect_selection_variables(selection_set: &q::SelectionSet, names: &mut BTreeSet<String>) {
+    for item in &selection_set.items {
+        match item {
+            q::Selection::Field(field) => {
+                for (_, value) in &field.arguments {
+                    collect_value_variables(value, names);
+                }
+                collect_directive_variables(&field.directives, names);
+                collect_selection_variables(&field.selection_set, names);
+            }
+            q::Selection::InlineFragment(fragment) => {
+                collect_directive_variables(&fragment.directives, names);
+                collect_selection_variables(&fragment.selection_set, names);
+            }
+            q::Selection::FragmentSpread(spread) => {
+                collect_directive_variables(&spread.directives, names);
+            }
+        }
+    }
+}
+
+/// The names of the variables declared by the operation in `document`.
+fn document_variables(document: &q::Document) -> BTreeSet<String> {
+    document
+        .definitions
+        .iter()
+        .filter_map(|defn| match defn {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => Some(
+                query
+                    .variable_definitions
+                    .iter()
+                    .map(|var| var.name.clone()),
+            ),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+fn export_name(field: &q::Field) -> Option<String> {
+    field
+        .directives
+        .iter()
+        .find(|dir| dir.name == EXPORT_DIRECTIVE)
+        .and_then(|dir| {
+            dir.arguments
+                .iter()
+                .find(|(name, _)| name == EXPORT_ARGUMENT)
+                .and_then(|(_, value)| match value {
+                    q::Value::String(name) => Some(name.clone()),
+                    _ => None,
+                })
+        })
+}
+
+fn push_export(value: &q::Value, values: &mut Vec<q::Value>) {
+    match value {
+        q::Value::List(items) => items.iter().for_each(|item| push_export(item, values)),
+        q::Value::Null => {}
+        value => {
+            if !values.contains(value) {
+                values.push(value.clone())
+            }
+        }
+    }
+}
+
+/// Walks the result `data` of a namespace along its `selection_set` and
+/// collects the values of all fields marked with `@export(as: "name")`.
+fn collect_exports(
+    selection_set: &q::SelectionSet,
+    data: &BTreeMap<String, q::Value>,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    exports: &mut BTreeMap<String, Vec<q::Value>>,
+) {
+    for item in &selection_set.items {
+        match item {
+            q::Selection::Field(field) => {
+                let key = field.alias.as_ref().unwrap_or(&field.name);
+                let value = match data.get(key) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                if let Some(name) = export_name(field) {
+                    push_export(value, exports.entry(name).or_default());
+                }
+                collect_value_exports(&field.selection_set, value, fragments, exports);
+            }
+            q::Selection::InlineFragment(fragment) => {
+                collect_exports(&fragment.selection_set, data, fragments, exports)
+            }
+            q::Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = fragments.get(&spread.fragment_name) {
+                    collect_exports(&fragment.selection_set, data, fragments, exports)
+                }
+            }
+        }
+    }
+}
+
+fn collect_value_exports(
+    selection_set: &q::SelectionSet,
+    value: &q::Value,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    exports: &mut BTreeMap<String, Vec<q::Value>>,
+) {
+    match value {
+        q::Value::Object(map) => collect_exports(selection_set, map, fragments, exports),
+        q::Value::List(values) => values
+            .iter()
+            .for_each(|value| collect_value_exports(selection_set, value, fragments, exports)),
+        _ => {}
+    }
+}
+
+/// Walks the result `data` of a namespace along its `selection_set` and adds
+/// the namespace `type_prefix` to the values of `__typename`, so that they
+/// name types of the federated schema.
+fn prefix_typenames(
+    selection_set: &q::SelectionSet,
+    data: &mut BTreeMap<String, q::Value>,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    type_prefix: &str,
+) {
+    for item in &selection_set.items {
+        match item {
+            q::Selection::Field(field) => {
+                let key = field.alias.as_ref().unwrap_or(&field.name);
+                let value = match data.get_mut(key) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                match value {
+                    // The same field can be selected more than once through
+                    // fragments, only prefix it the first time
+                    q::Value::String(name)
+                        if field.name == "__typename" && !name.starts_with(type_prefix) =>
+                    {
+                        *name = format!("{}{}", type_prefix, name);
+                    }
+                    value => {
+                        prefix_value_typenames(&field.selection_set, value, fragments, type_prefix)
+                    }
+                }
+            }
+            q::Selection::InlineFragment(fragment) => {
+                prefix_typenames(&fragment.selection_set, data, fragments, type_prefix)
+            }
+            q::Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = fragments.get(&spread.fragment_name) {
+                    prefix_typenames(&fragment.selection_set, data, fragments, type_prefix)
+                }
+            }
+        }
+    }
+}
+
+fn prefix_value_typenames(
+    selection_set: &q::SelectionSet,
+    value: &mut q::Value,
+    fragments: &HashMap<String, q::FragmentDefinition>,
+    type_prefix: &str,
+) {
+    match value {
+        q::Value::Object(map) => prefix_typenames(selection_set, map, fragments, type_prefix),
+        q::Value::List(values) => values
+            .iter_mut()
+            .for_each(|value| prefix_value_typenames(selection_set, value, fragments, type_prefix)),
+        _ => {}
+    }
+}
+
+fn rename_type(field_type: &mut s::Type, renames: &HashMap<String, String>) {
+    match field_type {
+        s::Type::NamedType(name) => {
+            if let Some(new_name) = renames.get(name) {
+                *name = new_name.clone();
+            }
+        }
+        s::Type::ListType(inner) | s::Type::NonNullType(inner) => rename_type(inner, renames),
+    }
+}
+
+fn rename_input_values(values: &mut Vec<s::InputValue>, renames: &HashMap<String, String>) {
+    for value in values.iter_mut() {
+        rename_type(&mut value.value_type, renames);
+    }
+}
+
+fn rename_names(names: &mut Vec<String>, renames: &HashMap<String, String>) {
+    for name in names.iter_mut() {
+        if let Some(new_name) = renames.get(name) {
+            *name = new_name.clone();
+        }
+    }
+}
+
+/// Merges the API schemas of the namespaces into one schema, see
+/// `Federation::schema`.
+fn federated_schema(schemas: Vec<(String, s::Document)>) -> s::Document {
+    let mut definitions = Vec::new();
+    // Scalars and directives are shared between all namespaces
+    let mut shared = BTreeSet::new();
+    let mut root_fields = Vec::new();
+
+    for (namespace, document) in schemas {
+        let prefix = type_prefix(&namespace);
+        let renames = document
+            .definitions
+            .iter()
+            .filter_map(|defn| match defn {
+                s::Definition::TypeDefinition(typedef) => match typedef {
+                    s::TypeDefinition::Scalar(_) => None,
+                    s::TypeDefinition::Object(t) => Some(t.name.clone()),
+                    s::TypeDefinition::Interface(t) => Some(t.name.clone()),
+                    s::TypeDefinition::Union(t) => Some(t.name.clone()),
+                    s::TypeDefinition::Enum(t) => Some(t.name.clone()),
+                    s::TypeDefinition::InputObject(t) => Some(t.name.clone()),
+                },
+                _ => None,
+            })
+            .map(|name| (name.clone(), format!("{}{}", prefix, name)))
+            .collect::<HashMap<_, _>>();
+
+        for defn in document.definitions {
+            match defn {
+                s::Definition::TypeDefinition(s::TypeDefinition::Scalar(scalar)) => {
+                    if shared.insert(scalar.name.clone()) {
+                        definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Scalar(
+                            scalar,
+                        )));
+                    }
+                }
+                s::Definition::TypeDefinition(s::TypeDefinition::Object(mut object)) => {
+                    if object.name == "Subscription" {
+                        continue;
+                    }
+                    if object.name == "Query" {
+                        root_fields.push(s::Field {
+                            position: object.position,
+                            description: None,
+                            name: namespace.clone(),
+                            arguments: vec![],
+                            field_type: s::Type::NonNullType(Box::new(s::Type::NamedType(
+                                renames["Query"].clone(),
+                            ))),
+                            directives: vec![],
+                        });
+                    }
+                    object.name = renames[&object.name].clone();
+                    rename_names(&mut object.implements_interfaces, &renames);
+                    for field in object.fields.iter_mut() {
+                        rename_type(&mut field.field_type, &renames);
+                        rename_input_values(&mut field.arguments, &renames);
+                    }
+                    definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Object(
+                        object,
+                    )));
+                }
+                s::Definition::TypeDefinition(s::TypeDefinition::Interface(mut interface)) => {
+                    interface.name = renames[&interface.name].clone();
+                    for field in interface.fields.iter_mut() {
+                        rename_type(&mut field.field_type, &renames);
+                        rename_input_values(&mut field.arguments, &renames);
+                    }
+                    definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Interface(
+                        interface,
+                    )));
+                }
+                s::Definition::TypeDefinition(s::TypeDefinition::Union(mut union)) => {
+                    union.name = renames[&union.name].clone();
+                    rename_names(&mut union.types, &renames);
+                    definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Union(
+                        union,
+                    )));
+                }
+                s::Definition::TypeDefinition(s::TypeDefinition::Enum(mut enum_type)) => {
+                    enum_type.name = renames[&enum_type.name].clone();
+                    definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Enum(
+                        enum_type,
+                    )));
+                }
+                s::Definition::TypeDefinition(s::TypeDefinition::InputObject(mut input)) => {
+                    input.name = renames[&input.name].clone();
+                    rename_input_values(&mut input.fields, &renames);
+                    definitions.push(s::Definition::TypeDefinition(
+                        s::TypeDefinition::InputObject(input),
+                    ));
+                }
+                s::Definition::DirectiveDefinition(mut directive) => {
+                    if shared.insert(format!("@{}", directive.name)) {
+                        rename_input_values(&mut directive.arguments, &renames);
+                        definitions.push(s::Definition::DirectiveDefinition(directive));
+                    }
+                }
+                s::Definition::SchemaDefinition(_) | s::Definition::TypeExtension(_) => {}
+            }
+        }
+    }
+
+    let export = graphql_parser::parse_schema::<String>(
+        "directive @export(as: String!) on FIELD\n\
+         schema { query: Query }",
+    )
+    .expect("federation schema definitions are valid")
+    .into_static();
+    definitions.extend(export.definitions);
+    definitions.push(s::Definition::TypeDefinition(s::TypeDefinition::Object(
+        s::ObjectType {
+            position: Default::default(),
+            description: None,
+            name: "Query".to_owned(),
+            implements_interfaces: vec![],
+            directives: vec![],
+            fields: root_fields,
+        },
+    )));
+    s::Document { definitions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use massbit_common::prelude::slog::{o, Discard};
+    use massbit_data::prelude::q;
+
+    fn parse(query: &str) -> q::Document {
+        graphql_parser::parse_query(query).unwrap().into_static()
+    }
+
+    fn fragments(document: &q::Document) -> HashMap<String, q::FragmentDefinition> {
+        document
+            .definitions
+            .iter()
+            .filter_map(|defn| match defn {
+                q::Definition::Fragment(frag) => Some((frag.name.clone(), frag.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn namespace_selection(document: &q::Document, namespace: &str) -> q::SelectionSet {
+        let selection_set = match &document.definitions[0] {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => &query.selection_set,
+            q::Definition::Operation(q::OperationDefinition::SelectionSet(set)) => set,
+            _ => unreachable!(),
+        };
+        selection_set
+            .items
+            .iter()
+            .find_map(|item| match item {
+                q::Selection::Field(field) if field.name == namespace => {
+                    Some(field.selection_set.clone())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn namespace_document_keeps_only_used_variables_and_fragments() {
+        let document = parse(
+            "query q($owner: String, $mints: [String!]) { \
+               serum { trades(where: { owner: $owner }) { ...TradeFields } } \
+               saber { swaps(where: { mint_in: $mints }) { id } } \
+             } \
+             fragment TradeFields on Serum_Trade { id baseMint @export(as: \"mints\") }",
+        );
+        let variable_definitions = match &document.definitions[0] {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => {
+                query.variable_definitions.clone()
+            }
+            _ => unreachable!(),
+        };
+        let serum = namespace_document(
+            Some("q".to_owned()),
+            &variable_definitions,
+            &namespace_selection(&document, "serum"),
+            &fragments(&document),
+            &type_prefix("serum"),
+        );
+        let expected = parse(
+            "query q($owner: String) { trades(where: { owner: $owner }) { ...TradeFields } } \
+             fragment TradeFields on Trade { id baseMint }",
+        );
+        assert_eq!(serum.to_string(), expected.to_string());
+        assert_eq!(
+            document_variables(&serum),
+            vec!["owner".to_owned()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn collects_exported_values() {
+        let document =
+            parse("{ serum { trades { baseMint @export(as: \"mints\") owner { id } } } }");
+        let selection_set = namespace_selection(&document, "serum");
+        let trade = |mint: &str| {
+            let mut trade = BTreeMap::new();
+            trade.insert("baseMint".to_owned(), q::Value::String(mint.to_owned()));
+            q::Value::Object(trade)
+        };
+        let mut data = BTreeMap::new();
+        data.insert(
+            "trades".to_owned(),
+            q::Value::List(vec![trade("mint1"), trade("mint2"), trade("mint1")]),
+        );
+
+        let mut exports = BTreeMap::new();
+        collect_exports(&selection_set, &data, &HashMap::new(), &mut exports);
+        assert_eq!(
+            exports.get("mints"),
+            Some(&vec![
+                q::Value::String("mint1".to_owned()),
+                q::Value::String("mint2".to_owned())
+            ])
+        );
+    }
+
+    #[test]
+    fn federated_schema_prefixes_types() {
+        let serum = graphql_parser::parse_schema::<String>(
+            "scalar ID type Trade { id: ID! } type Query { trades(where: Trade_filter): [Trade!]! } \
+             input Trade_filter { id: ID }",
+        )
+        .unwrap()
+        .into_static();
+        let saber = graphql_parser::parse_schema::<String>(
+            "scalar ID type Swap { id: ID! } type Query { swaps: [Swap!]! }",
+        )
+        .unwrap()
+        .into_static();
+        let schema = federated_schema(vec![
+            ("serum".to_owned(), serum),
+            ("saber".to_owned(), saber),
+        ])
+        .to_string();
+
+        assert!(schema.contains("type Serum_Trade"));
+        assert!(schema.contains("trades(where: Serum_Trade_filter): [Serum_Trade!]!"));
+        assert!(schema.contains("type Saber_Query"));
+        assert!(schema.contains("serum: Serum_Query!"));
+        assert!(schema.contains("saber: Saber_Query!"));
+        assert_eq!(schema.matches("scalar ID").count(), 1);
+    }
+
+    fn federated_api_schema() -> Arc<ApiSchema> {
+        let serum = graphql_parser::parse_schema::<String>(
+            "scalar ID type Trade { id: ID! } type Query { trades(where: Trade_filter): [Trade!]! } \
+             input Trade_filter { id: ID }",
+        )
+        .unwrap()
+        .into_static();
+        let document = federated_schema(vec![("serum".to_owned(), serum)]);
+        let id = DeploymentHash::new("portfolio").unwrap();
+        Arc::new(ApiSchema::from_api_schema(Schema::new(id, document)).unwrap())
+    }
+
+    const MAX_DEPTH: u8 = 10;
+
+    fn federated_query_with(
+        query: &str,
+        variables: Option<QueryVariables>,
+        max_depth: u8,
+    ) -> Result<Arc<crate::execution::Query>, Vec<QueryExecutionError>> {
+        let logger = Logger::root(Discard, o!());
+        crate::execution::Query::new(
+            &logger,
+            federated_api_schema(),
+            None,
+            Query::new(parse(query), variables),
+            None,
+            max_depth,
+        )
+    }
+
+    fn federated_query(
+        query: &str,
+    ) -> Result<Arc<crate::execution::Query>, Vec<QueryExecutionError>> {
+        federated_query_with(query, None, MAX_DEPTH)
+    }
+
+    #[test]
+    fn resolves_root_introspection_against_federated_schema() {
+        let query = federated_query(
+            "{ __typename __schema { queryType { name } } \
+               trade: __type(name: \"Serum_Trade\") { name } }",
+        )
+        .expect("Should validate introspection query");
+        let fields = query
+            .selection_set
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                q::Selection::Field(field) => Some(field.clone()),
+                _ => None,
+            })
+            .collect();
+        let values = resolve_root_meta_fields(&query, fields).expect("Should resolve");
+        assert_eq!(
+            values.get("__typename"),
+            Some(&q::Value::String("Query".to_owned()))
+        );
+        assert_eq!(
+            values["__schema"].to_string(),
+            "{queryType: {name: \"Query\"}}"
+        );
+        assert_eq!(values["trade"].to_string(), "{name: \"Serum_Trade\"}");
+    }
+
+    #[test]
+    fn validates_queries_against_federated_schema() {
+        federated_query("{ serum { trades { id } } }").expect("Should accept valid query");
+        federated_query("{ serum { trades { id ...on Serum_Trade { id } } } }")
+            .expect("Should accept prefixed type conditions");
+        federated_query("{ serum { swaps { id } } }").expect_err("Should reject unknown field");
+        federated_query("{ saber { swaps { id } } }").expect_err("Should reject unknown namespace");
+    }
+
+    #[test]
+    fn strips_type_prefixes_of_namespace() {
+        let document = parse("{ serum { trades { ... on Serum_Trade { id } } } }");
+        let serum = namespace_document(
+            None,
+            &[],
+            &namespace_selection(&document, "serum"),
+            &HashMap::new(),
+            &type_prefix("serum"),
+        );
+        assert_eq!(
+            serum.to_string(),
+            parse("{ trades { ... on Trade { id } } }").to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_queries_deeper_than_max_depth() {
+        federated_query_with("{ serum { trades { id } } }", None, 2)
+            .expect_err("Should reject query deeper than max depth");
+    }
+
+    #[test]
+    fn strips_type_prefixes_of_variables() {
+        let query =
+            "query q($where: Serum_Trade_filter!) { serum { trades(where: $where) { id } } }";
+        let mut variables = HashMap::new();
+        let mut filter = BTreeMap::new();
+        filter.insert("id".to_owned(), q::Value::String("trade1".to_owned()));
+        variables.insert("where".to_owned(), q::Value::Object(filter));
+        federated_query_with(query, Some(QueryVariables::new(variables)), MAX_DEPTH)
+            .expect("Should accept prefixed variable types");
+
+        let document = parse(query);
+        let variable_definitions = match &document.definitions[0] {
+            q::Definition::Operation(q::OperationDefinition::Query(query)) => {
+                query.variable_definitions.clone()
+            }
+            _ => unreachable!(),
+        };
+        let serum = namespace_document(
+            Some("q".to_owned()),
+            &variable_definitions,
+            &namespace_selection(&document, "serum"),
+            &HashMap::new(),
+            &type_prefix("serum"),
+        );
+        assert_eq!(
+            serum.to_string(),
+            parse("query q($where: Trade_filter!) { trades(where: $where) { id } }").to_string()
+        );
+    }
+
+    #[test]
+    fn prefixes_typenames_of_namespace() {
+        let document = parse(
+            "{ serum { __typename trades { kind: __typename ...TradeFields } } } \
+             fragment TradeFields on Serum_Trade { __typename id }",
+        );
+        let trade = || {
+            let mut trade = BTreeMap::new();
+            trade.insert("kind".to_owned(), q::Value::String("Trade".to_owned()));
+            trade.insert(
+                "__typename".to_owned(),
+                q::Value::String("Trade".to_owned()),
+            );
+            trade.insert("id".to_owned(), q::Value::String("t1".to_owned()));
+            q::Value::Object(trade)
+        };
+        let mut data = BTreeMap::new();
+        data.insert(
+            "__typename".to_owned(),
+            q::Value::String("Query".to_owned()),
+        );
+        data.insert("trades".to_owned(), q::Value::List(vec![trade(), trade()]));
+
+        prefix_typenames(
+            &namespace_selection(&document, "serum"),
+            &mut data,
+            &fragments(&document),
+            &type_prefix("serum"),
+        );
+        assert_eq!(
+            q::Value::Object(data).to_string(),
+            "{__typename: \"Serum_Query\", trades: [\
+             {__typename: \"Serum_Trade\", id: \"t1\", kind: \"Serum_Trade\"}, \
+             {__typename: \"Serum_Trade\", id: \"t1\", kind: \"Serum_Trade\"}]}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_namespaces() {
+        assert!(is_valid_name("serum_v3"));
+        assert!(!is_valid_name("3serum"));
+        assert!(!is_valid_name("serum-v3"));
+    }
+}
@@ -1,7 +1,10 @@
 pub mod config;
 pub mod execution;
+pub mod expensive_queries;
+pub mod federation;
 pub mod introspection;
 pub mod opt;
+pub mod persisted_queries;
 pub mod query;
 pub mod runner;
 pub mod server;
@@ -8,6 +8,9 @@ use massbit_data::log::logger;
 use massbit_data::metrics::registry::MetricsRegistry;
 use massbit_data::prelude::LoadManager;
 use massbit_graphql::config::{AccessControl, Config};
+use massbit_graphql::expensive_queries::{read_expensive_queries, watch_expensive_queries};
+use massbit_graphql::federation::Federations;
+use massbit_graphql::persisted_queries::PersistedQueries;
 use massbit_graphql::store_builder::StoreBuilder;
 use massbit_graphql::{
     opt,
@@ -15,7 +18,9 @@ use massbit_graphql::{
     server::{GraphQLServer as GraphQLQueryServer, GraphQLServerTrait},
 };
 use std::future::pending;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 
 #[tokio::main]
@@ -46,21 +51,60 @@ async fn main() {
     ));
     let store_builder =
         StoreBuilder::new(&logger, &node_id, &config, metrics_registry.cheap_clone()).await;
-    //Todo: Read expensive queries from a static file
-    let expensive_queries = vec![]; //read_expensive_queries().unwrap();
+    let expensive_queries = match &opt.expensive_queries_file {
+        Some(path) => read_expensive_queries(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("failed to read expensive queries from {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => vec![],
+    };
     let load_manager = Arc::new(LoadManager::new(
         &logger,
         expensive_queries,
         metrics_registry.clone(),
     ));
+    if let Some(path) = &opt.expensive_queries_file {
+        if opt.expensive_queries_reload_interval == 0 {
+            eprintln!("the expensive queries reload interval must be at least 1 second");
+            std::process::exit(1);
+        }
+        watch_expensive_queries(
+            logger.clone(),
+            PathBuf::from(path),
+            Duration::from_secs(opt.expensive_queries_reload_interval),
+            load_manager.clone(),
+        );
+    }
+    let persisted_queries = match &opt.persisted_queries_file {
+        Some(path) => PersistedQueries::load(&logger, PathBuf::from(path)).unwrap_or_else(|e| {
+            eprintln!("failed to load persisted queries from {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => PersistedQueries::new(&logger),
+    }
+    .with_registration(
+        opt.persisted_queries_registration_token.clone(),
+        opt.persisted_queries_max_count,
+        opt.persisted_queries_max_size,
+    );
+    let federations = match &opt.federation_config {
+        Some(path) => Federations::load(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("failed to load federations from {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => Federations::default(),
+    };
     let store_manager = store_builder.store_manager().await;
     let arc_store_manager = Arc::new(store_manager);
     let graphql_runner = Arc::new(GraphQlRunner::new(&logger, arc_store_manager, load_manager));
+    federations.build_schemas(&logger, graphql_runner.clone()).await;
     let graphql_metrics_registry = metrics_registry.clone();
     let mut graphql_server = GraphQLQueryServer::new(
         &logger_factory,
         graphql_metrics_registry,
         graphql_runner.clone(),
+        Arc::new(persisted_queries),
+        Arc::new(federations),
     );
     graphql_server
         .serve(http_port, ws_port, access_control)
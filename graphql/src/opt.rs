@@ -92,6 +92,58 @@ pub struct Opt {
         help = "Content type"
     )]
     pub content_type: String,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        env = "EXPENSIVE_QUERIES_FILE",
+        help = "File with queries, one per line, that are never executed"
+    )]
+    pub expensive_queries_file: Option<String>,
+    #[structopt(
+        long,
+        value_name = "SECONDS",
+        default_value = "30",
+        env = "EXPENSIVE_QUERIES_RELOAD_INTERVAL",
+        help = "How often, in seconds, the expensive queries file is checked for changes, at least 1"
+    )]
+    pub expensive_queries_reload_interval: u64,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        env = "PERSISTED_QUERIES_FILE",
+        help = "JSON file the persisted queries are loaded from and saved to"
+    )]
+    pub persisted_queries_file: Option<String>,
+    #[structopt(
+        long,
+        value_name = "TOKEN",
+        env = "PERSISTED_QUERIES_REGISTRATION_TOKEN",
+        help = "Bearer token required to register persisted queries, registration is disabled without it"
+    )]
+    pub persisted_queries_registration_token: Option<String>,
+    #[structopt(
+        long,
+        value_name = "COUNT",
+        default_value = "1000",
+        env = "PERSISTED_QUERIES_MAX_COUNT",
+        help = "Maximum number of persisted queries"
+    )]
+    pub persisted_queries_max_count: usize,
+    #[structopt(
+        long,
+        value_name = "BYTES",
+        default_value = "16384",
+        env = "PERSISTED_QUERIES_MAX_SIZE",
+        help = "Maximum size in bytes of a persisted query"
+    )]
+    pub persisted_queries_max_size: usize,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        env = "FEDERATION_CONFIG",
+        help = "TOML file with the federations of indexers served under /federations/<NAME>"
+    )]
+    pub federation_config: Option<String>,
 }
 
 impl From<Opt> for config::Opt {
@@ -0,0 +1,255 @@
+//! Persisted queries are query documents that are registered once and then
+//! executed by referring to their id, which is the hex encoded shape hash of
+//! the document. Clients only send the id and the variables, which keeps
+//! requests small and makes `GET` requests cacheable by CDNs.
+//!
+//! Since the id does not depend on argument values, persisted queries should
+//! pass arguments as variables; registering a second document with the same
+//! shape but different literals is rejected.
+//!
+//! Registration requires a bearer token configured by the operator and is
+//! disabled without one. The number and the size of registered queries are
+//! capped, so that the memory and the file can not grow without bounds.
+use crate::server::error::GraphQLServerError;
+use massbit_common::prelude::{
+    anyhow::Error,
+    serde_json,
+    slog::{info, Logger},
+    tokio,
+};
+use massbit_common::util::task_spawn;
+use massbit_data::graphql::shape_hash::shape_hash;
+use massbit_data::query::QueryError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Returns the id under which a query with the given shape hash is persisted.
+pub fn persisted_query_id(shape_hash: u64) -> String {
+    format!("{:016x}", shape_hash)
+}
+
+pub const DEFAULT_MAX_PERSISTED_QUERIES: usize = 1000;
+pub const DEFAULT_MAX_PERSISTED_QUERY_SIZE: usize = 16 * 1024;
+
+pub struct PersistedQueries {
+    logger: Logger,
+    /// JSON file mapping query ids to query texts. Newly registered queries
+    /// are written back to it so they survive restarts
+    path: Option<PathBuf>,
+    /// Bearer token required to register queries, registration is disabled
+    /// without it
+    registration_token: Option<String>,
+    max_count: usize,
+    /// Maximum size in bytes of a registered query text
+    max_query_size: usize,
+    queries: RwLock<BTreeMap<String, String>>,
+    /// Serializes the writes of the file, so that the last write contains
+    /// every registered query
+    file_lock: tokio::sync::Mutex<()>,
+}
+
+impl PersistedQueries {
+    /// Creates an empty, in-memory only set of persisted queries.
+    pub fn new(logger: &Logger) -> Self {
+        PersistedQueries::with_queries(logger, None, BTreeMap::new())
+    }
+
+    fn with_queries(
+        logger: &Logger,
+        path: Option<PathBuf>,
+        queries: BTreeMap<String, String>,
+    ) -> Self {
+        PersistedQueries {
+            logger: logger.clone(),
+            path,
+            registration_token: None,
+            max_count: DEFAULT_MAX_PERSISTED_QUERIES,
+            max_query_size: DEFAULT_MAX_PERSISTED_QUERY_SIZE,
+            queries: RwLock::new(queries),
+            file_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Enables registration for requests with the bearer `token`, up to
+    /// `max_count` queries of at most `max_query_size` bytes.
+    pub fn with_registration(
+        mut self,
+        token: Option<String>,
+        max_count: usize,
+        max_query_size: usize,
+    ) -> Self {
+        self.registration_token = token.filter(|token| !token.is_empty());
+        self.max_count = max_count;
+        self.max_query_size = max_query_size;
+        self
+    }
+
+    pub fn registration_enabled(&self) -> bool {
+        self.registration_token.is_some()
+    }
+
+    /// Checks the value of the `Authorization` header of a registration.
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        match (&self.registration_token, authorization) {
+            (Some(token), Some(authorization)) => {
+                authorization.strip_prefix("Bearer ") == Some(token.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// Loads persisted queries from the file at `path`. A missing file is
+    /// treated as an empty set of queries and created on first registration.
+    pub fn load(logger: &Logger, path: PathBuf) -> Result<Self, Error> {
+        let queries: BTreeMap<String, String> = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        info!(logger, "Loaded persisted queries";
+            "path" => path.display().to_string(),
+            "count" => queries.len());
+        Ok(PersistedQueries::with_queries(logger, Some(path), queries))
+    }
+
+    /// Returns the text of the query persisted under `id`.
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.queries.read().unwrap().get(id).cloned()
+    }
+
+    /// Registers `query_text` and returns its id. Registering the same
+    /// document again returns the same id. Newly registered queries are only
+    /// written to the file by `save`.
+    pub fn register(&self, query_text: &str) -> Result<String, GraphQLServerError> {
+        if query_text.len() > self.max_query_size {
+            return Err(GraphQLServerError::ClientError(format!(
+                "The query has {} bytes, persisted queries can have at most {} bytes",
+                query_text.len(),
+                self.max_query_size
+            )));
+        }
+        let document = graphql_parser::parse_query(query_text)
+            .map_err(|e| GraphQLServerError::from(QueryError::ParseError(Arc::new(e.into()))))?
+            .into_static();
+        let id = persisted_query_id(shape_hash(&document));
+        let query_text = document.to_string();
+
+        let mut queries = self.queries.write().unwrap();
+        match queries.get(&id) {
+            Some(existing) if existing == &query_text => return Ok(id),
+            Some(_) => {
+                return Err(GraphQLServerError::ClientError(format!(
+                    "A different query with the same shape is already persisted as `{}`, \
+                     pass arguments as variables instead of literals",
+                    id
+                )))
+            }
+            None if queries.len() >= self.max_count => {
+                return Err(GraphQLServerError::ClientError(format!(
+                    "The limit of {} persisted queries is reached",
+                    self.max_count
+                )))
+            }
+            None => {}
+        }
+        queries.insert(id.clone(), query_text);
+        info!(self.logger, "Registered persisted query"; "id" => &id);
+        Ok(id)
+    }
+
+    /// Writes the persisted queries to their file, if any. The file is written
+    /// on a blocking thread, off the async threads serving the requests.
+    pub async fn save(&self) -> Result<(), GraphQLServerError> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        let _file_lock = self.file_lock.lock().await;
+        let content = serde_json::to_string_pretty(&*self.queries.read().unwrap())
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?;
+        task_spawn::spawn_blocking_allow_panic(move || fs::write(path, content))
+            .await
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?
+            .map_err(|e| {
+                GraphQLServerError::InternalError(format!(
+                    "Failed to write persisted queries: {}",
+                    e
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PersistedQueries;
+    use massbit_common::prelude::slog::{o, Discard, Logger};
+
+    fn persisted_queries() -> PersistedQueries {
+        PersistedQueries::new(&Logger::root(Discard, o!()))
+    }
+
+    #[test]
+    fn registers_and_returns_queries() {
+        let queries = persisted_queries();
+        let id = queries
+            .register("query trades($first: Int) { trades(first: $first) { id } }")
+            .expect("Should register valid query");
+        assert_eq!(id.len(), 16);
+        let text = queries.get(&id).expect("Should find registered query");
+        assert!(text.contains("trades"));
+        assert_eq!(queries.get("0000000000000000"), None);
+    }
+
+    #[test]
+    fn registering_twice_is_idempotent() {
+        let queries = persisted_queries();
+        let first = queries.register("{ trades { id } }").unwrap();
+        let second = queries.register("{ trades { id } }").unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn rejects_same_shape_with_different_literals() {
+        let queries = persisted_queries();
+        queries.register("{ trades(first: 10) { id } }").unwrap();
+        queries
+            .register("{ trades(first: 1000) { id } }")
+            .expect_err("Should reject a conflicting query");
+    }
+
+    #[test]
+    fn limits_count_and_size_of_queries() {
+        let queries = persisted_queries().with_registration(Some("secret".to_string()), 1, 32);
+        queries.register("{ trades { id } }").unwrap();
+        queries
+            .register("{ trades { id } }")
+            .expect("Should accept an already registered query");
+        queries
+            .register("{ pairs { id } }")
+            .expect_err("Should reject queries over the limit");
+        queries
+            .register("{ trades { id name symbol decimals } }")
+            .expect_err("Should reject queries over the size limit");
+    }
+
+    #[test]
+    fn requires_registration_token() {
+        assert!(!persisted_queries().registration_enabled());
+        assert!(!persisted_queries().is_authorized(Some("Bearer ")));
+        let queries = persisted_queries().with_registration(Some("secret".to_string()), 1, 32);
+        assert!(queries.registration_enabled());
+        assert!(queries.is_authorized(Some("Bearer secret")));
+        assert!(!queries.is_authorized(Some("Bearer other")));
+        assert!(!queries.is_authorized(Some("secret")));
+        assert!(!queries.is_authorized(None));
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        persisted_queries()
+            .register("{ trades {")
+            .expect_err("Should reject invalid query");
+    }
+}
@@ -10,6 +10,7 @@ use massbit_common::prelude::{
 use massbit_data::indexer::{DeploymentHash, DeploymentState};
 use massbit_data::prelude::{LoadManager, QueryExecutionError};
 use massbit_data::query::{Query, QueryResults};
+use massbit_data::schema::ApiSchema;
 use massbit_data::store::{QueryStore, QueryStoreManager};
 use std::env;
 use std::str::FromStr;
@@ -262,6 +263,18 @@ where
     //     .await
     // }
 
+    async fn api_schema(
+        self: Arc<Self>,
+        hash: DeploymentHash,
+    ) -> Result<Arc<ApiSchema>, QueryExecutionError> {
+        let store = self.store.query_store(hash, false).await?;
+        store.api_schema()
+    }
+
+    fn max_depth(&self) -> u8 {
+        *GRAPHQL_MAX_DEPTH
+    }
+
     fn load_manager(&self) -> Arc<LoadManager> {
         self.load_manager.clone()
     }
@@ -1,8 +1,10 @@
+use crate::federation::Federation;
 use massbit_common::prelude::async_trait::async_trait;
 //use graphql_parser::query::Subscription;
 use massbit_data::indexer::DeploymentHash;
 use massbit_data::prelude::LoadManager;
-use massbit_data::query::{CacheStatus, Query, QueryResults};
+use massbit_data::query::{CacheStatus, Query, QueryExecutionError, QueryResults};
+use massbit_data::schema::ApiSchema;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -10,9 +12,11 @@ use std::time::Duration;
 // pub type SubscriptionResultFuture =
 //     Box<dyn Future<Item = SubscriptionResult, Error = SubscriptionError> + Send>;
 
+/// What a GraphQL request is run against: a single indexer deployment, or a
+/// federation of several of them.
 pub enum GraphQlTarget {
-    SubgraphName(String),
     Deployment(DeploymentHash),
+    Federation(Federation),
 }
 
 /// A component that can run GraphqL queries against a [Store](../store/trait.Store.html).
@@ -39,6 +43,15 @@ pub trait GraphQlRunner: Send + Sync + 'static {
     //     target: QueryTarget,
     // ) -> Result<SubscriptionResult, SubscriptionError>;
 
+    /// Returns the API schema that queries against the indexer `hash` are validated against.
+    async fn api_schema(
+        self: Arc<Self>,
+        hash: DeploymentHash,
+    ) -> Result<Arc<ApiSchema>, QueryExecutionError>;
+
+    /// The maximum depth of the queries this runner accepts.
+    fn max_depth(&self) -> u8;
+
     fn load_manager(&self) -> Arc<LoadManager>;
 }
 
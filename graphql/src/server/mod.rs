@@ -4,6 +4,8 @@ pub mod request;
 pub mod service;
 
 use crate::config::AccessControl;
+use crate::federation::Federations;
+use crate::persisted_queries::PersistedQueries;
 pub use crate::server::graphql::GraphQlRunner;
 use crate::server::service::{GraphQLService, GraphQLServiceMetrics};
 use futures::prelude::*;
@@ -54,6 +56,8 @@ pub struct GraphQLServer<Q> {
     logger: Logger,
     metrics: Arc<GraphQLServiceMetrics>,
     graphql_runner: Arc<Q>,
+    persisted_queries: Arc<PersistedQueries>,
+    federations: Arc<Federations>,
 }
 
 impl<Q> GraphQLServer<Q> {
@@ -62,6 +66,8 @@ impl<Q> GraphQLServer<Q> {
         logger_factory: &LoggerFactory,
         metrics_registry: Arc<impl MetricsRegistry>,
         graphql_runner: Arc<Q>,
+        persisted_queries: Arc<PersistedQueries>,
+        federations: Arc<Federations>,
     ) -> Self {
         let logger = logger_factory.component_logger(
             "GraphQLServer",
@@ -76,6 +82,8 @@ impl<Q> GraphQLServer<Q> {
             logger,
             metrics,
             graphql_runner,
+            persisted_queries,
+            federations,
         }
     }
 }
@@ -106,11 +114,15 @@ where
         let logger_for_service = self.logger.clone();
         let graphql_runner = self.graphql_runner.clone();
         let metrics = self.metrics.clone();
+        let persisted_queries = self.persisted_queries.clone();
+        let federations = self.federations.clone();
         let new_service = make_service_fn(move |_| {
             futures03::future::ok::<_, Error>(GraphQLService::new(
                 logger_for_service.clone(),
                 metrics.clone(),
                 graphql_runner.clone(),
+                persisted_queries.clone(),
+                federations.clone(),
                 ws_port,
                 access_control.clone(),
             ))
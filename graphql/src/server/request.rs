@@ -4,8 +4,10 @@ use hyper::body::Bytes;
 use massbit_common::prelude::serde_json;
 use massbit_data::query::{Query, QueryError};
 use std::sync::Arc;
+use url::form_urlencoded;
 
 use super::error::GraphQLServerError;
+use crate::persisted_queries::PersistedQueries;
 
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
@@ -17,6 +19,66 @@ impl GraphQLRequest {
     pub fn new(body: Bytes) -> Self {
         GraphQLRequest { body }
     }
+
+    /// Creates a new GraphQLRequest from the query string of a `GET` request.
+    ///
+    /// The `query`, `id` and (JSON encoded) `variables` parameters are repacked into
+    /// the same JSON body a `POST` request carries, so both go through the same parsing.
+    pub fn from_query_string(query_string: &str) -> Result<Self, GraphQLServerError> {
+        let mut obj = serde_json::Map::new();
+        for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+            match key.as_ref() {
+                "query" | "id" => {
+                    obj.insert(key.into(), serde_json::Value::String(value.into()));
+                }
+                "variables" => {
+                    let variables = serde_json::from_str(value.as_ref()).map_err(|_| {
+                        GraphQLServerError::ClientError(
+                            "Invalid query variables provided".to_string(),
+                        )
+                    })?;
+                    obj.insert(String::from("variables"), variables);
+                }
+                _ => {}
+            }
+        }
+        let body = serde_json::to_vec(&serde_json::Value::Object(obj))
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?;
+        Ok(GraphQLRequest::new(Bytes::from(body)))
+    }
+
+    /// Replaces the `id` of a persisted query in the request with the text of
+    /// that query. Requests that carry a `query` are left untouched.
+    pub fn resolve_persisted_query(
+        self,
+        persisted_queries: &PersistedQueries,
+    ) -> Result<Self, GraphQLServerError> {
+        let mut json: serde_json::Value = match serde_json::from_slice(&self.body) {
+            Ok(json) => json,
+            // Let `poll` report the malformed body
+            Err(_) => return Ok(self),
+        };
+        let obj = match json.as_object_mut() {
+            Some(obj) if !obj.contains_key("query") => obj,
+            _ => return Ok(self),
+        };
+        let id = match obj.get("id") {
+            Some(serde_json::Value::String(id)) => id.clone(),
+            Some(_) => {
+                return Err(GraphQLServerError::ClientError(String::from(
+                    "The \"id\" field is not a string",
+                )))
+            }
+            None => return Ok(self),
+        };
+        let query_text = persisted_queries.get(&id).ok_or_else(|| {
+            GraphQLServerError::ClientError(format!("Unknown persisted query `{}`", id))
+        })?;
+        obj.insert(String::from("query"), serde_json::Value::String(query_text));
+        let body = serde_json::to_vec(&json)
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?;
+        Ok(GraphQLRequest::new(Bytes::from(body)))
+    }
 }
 
 impl Future for GraphQLRequest {
@@ -66,6 +128,81 @@ impl Future for GraphQLRequest {
         Ok(Async::Ready(Query::new(document, variables)))
     }
 }
+
+#[cfg(test)]
+mod query_string_tests {
+    use futures::Future;
+    use graphql_parser;
+    use massbit_common::prelude::slog::{o, Discard, Logger};
+
+    use super::GraphQLRequest;
+    use crate::persisted_queries::PersistedQueries;
+
+    #[test]
+    fn accepts_url_encoded_queries() {
+        let request =
+            GraphQLRequest::from_query_string("query=%7B%20user%20%7B%20name%20%7D%20%7D")
+                .expect("Should accept url encoded query string");
+        let query = request.wait().expect("Should accept valid queries");
+        assert_eq!(
+            query.document,
+            graphql_parser::parse_query("{ user { name } }")
+                .unwrap()
+                .into_static()
+        );
+        assert_eq!(query.variables, None);
+    }
+
+    #[test]
+    fn parses_json_encoded_variables() {
+        let request = GraphQLRequest::from_query_string(
+            "query=query%20q(%24id%3A%20ID)%20%7B%20user(id%3A%20%24id)%20%7B%20name%20%7D%20%7D\
+             &variables=%7B%22id%22%3A%20%221%22%7D",
+        )
+        .expect("Should accept url encoded query string");
+        let query = request.wait().expect("Should accept valid queries");
+        assert!(query.variables.is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_variables() {
+        GraphQLRequest::from_query_string("query=%7B%20user%20%7D&variables=%7Bnope")
+            .err()
+            .expect("Should reject variables that are not JSON");
+    }
+
+    #[test]
+    fn resolves_persisted_queries() {
+        let persisted_queries = PersistedQueries::new(&Logger::root(Discard, o!()));
+        let id = persisted_queries
+            .register("query q($id: ID) { user(id: $id) { name } }")
+            .unwrap();
+        let request = GraphQLRequest::from_query_string(&format!(
+            "id={}&variables=%7B%22id%22%3A%20%221%22%7D",
+            id
+        ))
+        .unwrap()
+        .resolve_persisted_query(&persisted_queries)
+        .expect("Should resolve a registered query");
+        let query = request.wait().expect("Should accept persisted queries");
+        assert!(query.variables.is_some());
+
+        GraphQLRequest::from_query_string("id=0000000000000000")
+            .unwrap()
+            .resolve_persisted_query(&persisted_queries)
+            .err()
+            .expect("Should reject unknown persisted queries");
+    }
+
+    #[test]
+    fn rejects_query_string_without_query() {
+        let request = GraphQLRequest::from_query_string("variables=%7B%7D")
+            .expect("Should build a request without query");
+        request
+            .wait()
+            .expect_err("Should reject request without query field");
+    }
+}
 /*
 #[cfg(test)]
 mod tests {
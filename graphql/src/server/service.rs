@@ -2,28 +2,43 @@ use crate::config::AccessControl;
 use core::task::Poll;
 use http::header::{
     ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
-    CONTENT_TYPE, LOCATION,
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION,
 };
 use http::{header, Method};
 use hyper::service::Service;
 use hyper::{Body, Request, Response, StatusCode};
 use massbit_common::prelude::futures03::compat::Future01CompatExt;
 use massbit_common::prelude::futures03::{FutureExt, TryFutureExt};
+use massbit_common::prelude::lazy_static::lazy_static;
+use massbit_common::prelude::serde_json;
 use massbit_common::prelude::slog::{error, Logger};
 use massbit_data::indexer::DeploymentHash;
 use massbit_data::metrics::{HistogramVec, MetricsRegistry};
-use massbit_data::query::QueryResult;
+use massbit_data::query::{QueryResult, QueryResults};
 use std::convert::TryFrom;
+use std::env;
 use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
 use std::time::Instant;
 
+use crate::federation::Federations;
+use crate::persisted_queries::PersistedQueries;
 use crate::server::error::GraphQLServerError;
-use crate::server::graphql::GraphQlRunner;
+use crate::server::graphql::{GraphQlRunner, GraphQlTarget};
 use crate::server::request::GraphQLRequest;
 
+lazy_static! {
+    /// `max-age` in seconds of the `Cache-Control` header set on successful `GET` query
+    /// responses, so that they can be cached by proxies and CDNs. `0` disables caching.
+    static ref GRAPHQL_HTTP_CACHE_MAX_AGE: u64 = env::var("GRAPHQL_HTTP_CACHE_MAX_AGE")
+        .ok()
+        .map(|s| s.parse::<u64>()
+            .unwrap_or_else(|_| panic!("failed to parse env var GRAPHQL_HTTP_CACHE_MAX_AGE")))
+        .unwrap_or(0);
+}
+
 pub struct GraphQLServiceMetrics {
     query_execution_time: Box<HistogramVec>,
     failed_query_execution_time: Box<HistogramVec>,
@@ -85,6 +100,8 @@ pub struct GraphQLService<Q> {
     logger: Logger,
     metrics: Arc<GraphQLServiceMetrics>,
     graphql_runner: Arc<Q>,
+    persisted_queries: Arc<PersistedQueries>,
+    federations: Arc<Federations>,
     ws_port: u16,
     access_control: AccessControl,
 }
@@ -95,6 +112,8 @@ impl<Q> Clone for GraphQLService<Q> {
             logger: self.logger.clone(),
             metrics: self.metrics.clone(),
             graphql_runner: self.graphql_runner.clone(),
+            persisted_queries: self.persisted_queries.clone(),
+            federations: self.federations.clone(),
             ws_port: self.ws_port,
             access_control: self.access_control.clone(),
         }
@@ -110,6 +129,8 @@ where
         logger: Logger,
         metrics: Arc<GraphQLServiceMetrics>,
         graphql_runner: Arc<Q>,
+        persisted_queries: Arc<PersistedQueries>,
+        federations: Arc<Federations>,
         ws_port: u16,
         access_control: AccessControl,
     ) -> Self {
@@ -117,112 +138,250 @@ where
             logger,
             metrics,
             graphql_runner,
+            persisted_queries,
+            federations,
             ws_port,
             access_control,
         }
     }
 
-    // fn graphiql_html(&self) -> String {
-    //     include_str!("../assets/index.html")
-    //         .replace("__WS_PORT__", format!("{}", self.ws_port).as_str())
-    // }
-
-    // async fn index(self) -> GraphQLServiceResult {
-    //     Ok(Response::builder()
-    //         .status(200)
-    //         .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-    //         .header(CONTENT_TYPE, "text/plain")
-    //         .body(Body::from(String::from(
-    //             "Access deployed subgraphs by deployment ID at \
-    //             /subgraphs/id/<ID> or by name at /subgraphs/name/<NAME>",
-    //         )))
-    //         .unwrap())
-    // }
-
-    // /// Serves a static file.
-    // fn serve_file(
-    //     &self,
-    //     contents: &'static str,
-    //     content_type: &'static str,
-    // ) -> GraphQLServiceResponse {
-    //     async move {
-    //         Ok(Response::builder()
-    //             .status(200)
-    //             .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-    //             .header(CONTENT_TYPE, content_type)
-    //             .body(Body::from(contents))
-    //             .unwrap())
-    //     }
-    //     .boxed()
-    // }
-
-    // /// Serves a dynamically created file.
-    // fn serve_dynamic_file(&self, contents: String) -> GraphQLServiceResponse {
-    //     async {
-    //         Ok(Response::builder()
-    //             .status(200)
-    //             .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-    //             .header(CONTENT_TYPE, "text/html")
-    //             .body(Body::from(contents))
-    //             .unwrap())
-    //     }
-    //     .boxed()
-    // }
-    //
-    // fn handle_graphiql(&self) -> GraphQLServiceResponse {
-    //     self.serve_dynamic_file(self.graphiql_html())
-    // }
-
-    async fn handle_graphql_query_by_name(
+    async fn index(self) -> GraphQLServiceResult {
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from(String::from(
+                "Access deployed indexers by deployment hash at /indexers/<HASH>/graphql \
+                and federations of indexers at /federations/<NAME>/graphql, download \
+                their API schema at /indexers/<HASH>/schema.graphql and \
+                /federations/<NAME>/schema.graphql, and register persisted queries \
+                at /persisted_queries",
+            )))
+            .unwrap())
+    }
+
+    /// Serves a static file.
+    fn serve_file(
+        &self,
+        contents: &'static str,
+        content_type: &'static str,
+    ) -> GraphQLServiceResponse {
+        async move {
+            Ok(Response::builder()
+                .status(200)
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(contents))
+                .unwrap())
+        }
+        .boxed()
+    }
+
+    /// Serves a dynamically created file.
+    fn serve_dynamic_file(&self, contents: String) -> GraphQLServiceResponse {
+        async {
+            Ok(Response::builder()
+                .status(200)
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .header(CONTENT_TYPE, "text/html")
+                .body(Body::from(contents))
+                .unwrap())
+        }
+        .boxed()
+    }
+
+    fn handle_graphiql(&self, endpoint: &str) -> GraphQLServiceResponse {
+        self.serve_dynamic_file(graphiql_html(endpoint))
+    }
+
+    fn indexer_target(indexer_hash: &str) -> Result<GraphQlTarget, GraphQLServerError> {
+        DeploymentHash::new(indexer_hash)
+            .map(GraphQlTarget::Deployment)
+            .map_err(|_| {
+                GraphQLServerError::ClientError(format!("Invalid indexer hash `{}`", indexer_hash))
+            })
+    }
+
+    fn federation_target(&self, name: &str) -> Result<GraphQlTarget, GraphQLServerError> {
+        self.federations
+            .get(name)
+            .map(GraphQlTarget::Federation)
+            .ok_or_else(|| {
+                GraphQLServerError::ClientError(format!("Unknown federation `{}`", name))
+            })
+    }
+
+    /// Serves the API schema of an indexer or federation as SDL, e.g. for client
+    /// code generators.
+    async fn handle_schema_download(
+        self,
+        target: Result<GraphQlTarget, GraphQLServerError>,
+        file_name: String,
+    ) -> GraphQLServiceResult {
+        let document = match target? {
+            GraphQlTarget::Deployment(hash) => self
+                .graphql_runner
+                .clone()
+                .api_schema(hash)
+                .await
+                .map(|schema| schema.document().clone()),
+            GraphQlTarget::Federation(federation) => {
+                federation.schema(self.graphql_runner.clone()).await
+            }
+        }
+        .map_err(|e| GraphQLServerError::ClientError(e.to_string()))?;
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/graphql; charset=utf-8")
+            .header(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.graphql\"", file_name),
+            )
+            .body(Body::from(document.to_string()))
+            .unwrap())
+    }
+
+    /// Handles `GET` requests on a GraphQL endpoint: with a `query` or persisted
+    /// query `id` parameter the query is executed, otherwise the GraphiQL explorer
+    /// is served for a valid indexer or federation.
+    fn handle_graphql_get(
         self,
-        indexer_hash: String,
+        target: Result<GraphQlTarget, GraphQLServerError>,
         request: Request<Body>,
+    ) -> GraphQLServiceResponse {
+        match (request.uri().query(), target) {
+            (Some(query_string), target) if Self::has_query_param(query_string) => {
+                let query_string = query_string.to_string();
+                self.handle_graphql_get_query(target, query_string).boxed()
+            }
+            (_, Ok(_)) => self.handle_graphiql(request.uri().path()),
+            (_, Err(err)) => async move { Err(err) }.boxed(),
+        }
+    }
+
+    fn has_query_param(query_string: &str) -> bool {
+        url::form_urlencoded::parse(query_string.as_bytes())
+            .any(|(key, _)| key == "query" || key == "id")
+    }
+
+    async fn handle_graphql_get_query(
+        self,
+        target: Result<GraphQlTarget, GraphQLServerError>,
+        query_string: String,
     ) -> GraphQLServiceResult {
-        let hash = DeploymentHash::new(indexer_hash).unwrap();
-        self.handle_graphql_query(hash, request.into_body()).await
+        let request = GraphQLRequest::from_query_string(query_string.as_str())?;
+        let result = self.run_graphql_request(target?, request).await?;
+        let mut response = result.as_http_response::<Body>();
+        // Only successful results may be cached by proxies and CDNs; errors are
+        // often transient, e.g. the indexer not having reached a block yet
+        if !result.has_errors() && *GRAPHQL_HTTP_CACHE_MAX_AGE > 0 {
+            let cache_control = format!("public, max-age={}", *GRAPHQL_HTTP_CACHE_MAX_AGE);
+            response.headers_mut().insert(
+                CACHE_CONTROL,
+                header::HeaderValue::from_str(cache_control.as_str()).unwrap(),
+            );
+        }
+        Ok(response)
     }
-    //
-    // fn handle_graphql_query_by_id(
-    //     self,
-    //     id: String,
-    //     request: Request<Body>,
-    // ) -> GraphQLServiceResponse {
-    //     let res = DeploymentHash::new(id)
-    //         .map_err(|id| GraphQLServerError::ClientError(format!("Invalid subgraph id `{}`", id)));
-    //     match res {
-    //         Err(_) => self.handle_not_found(),
-    //         Ok(id) => self
-    //             .handle_graphql_query(id.into(), request.into_body())
-    //             .boxed(),
-    //     }
-    // }
 
     async fn handle_graphql_query(
         self,
-        indexer_hash: DeploymentHash,
-        request_body: Body,
+        target: Result<GraphQlTarget, GraphQLServerError>,
+        request: Request<Body>,
     ) -> GraphQLServiceResult {
+        let target = target?;
+        let body = hyper::body::to_bytes(request.into_body())
+            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
+            .await?;
+        let result = self
+            .run_graphql_request(target, GraphQLRequest::new(body))
+            .await?;
+        Ok(result.as_http_response())
+    }
+
+    async fn run_graphql_request(
+        self,
+        target: GraphQlTarget,
+        request: GraphQLRequest,
+    ) -> Result<QueryResults, GraphQLServerError> {
         let service = self.clone();
         let service_metrics = self.metrics.clone();
 
         let start = Instant::now();
-        let body = hyper::body::to_bytes(request_body)
-            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
-            .await?;
-        let query = GraphQLRequest::new(body).compat().await;
+        let request = request.resolve_persisted_query(&self.persisted_queries)?;
+        let query = request.compat().await;
 
-        let result = match query {
-            Ok(query) => service.graphql_runner.run_query(query, indexer_hash).await,
-            Err(GraphQLServerError::QueryError(e)) => QueryResult::from(e).into(),
-            Err(e) => return Err(e),
+        let result = match (query, target) {
+            (Ok(query), GraphQlTarget::Deployment(hash)) => {
+                service.graphql_runner.run_query(query, hash).await
+            }
+            (Ok(query), GraphQlTarget::Federation(federation)) => {
+                federation
+                    .run_query(&service.logger, service.graphql_runner.clone(), query)
+                    .await
+            }
+            (Err(GraphQLServerError::QueryError(e)), _) => QueryResult::from(e).into(),
+            (Err(e), _) => return Err(e),
         };
 
         if let Some(id) = result.first().and_then(|res| res.deployment.clone()) {
             service_metrics
                 .observe_query_execution_time(start.elapsed().as_secs_f64(), id.to_string());
         }
-        println!("{:?}", &result);
-        Ok(result.as_http_response())
+        Ok(result)
+    }
+
+    /// Registers the query in the request body as a persisted query and
+    /// responds with its id. Requires the registration token as bearer token.
+    async fn handle_persisted_query_registration(
+        self,
+        request: Request<Body>,
+    ) -> GraphQLServiceResult {
+        let authorization = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        if !self.persisted_queries.is_authorized(authorization) {
+            let (status, message) = if self.persisted_queries.registration_enabled() {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "Registering persisted queries requires the registration token",
+                )
+            } else {
+                (
+                    StatusCode::FORBIDDEN,
+                    "Registration of persisted queries is disabled",
+                )
+            };
+            return Ok(Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "text/plain")
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(Body::from(message))
+                .unwrap());
+        }
+        let body = hyper::body::to_bytes(request.into_body())
+            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
+            .await?;
+        let json: serde_json::Value = serde_json::from_slice(&body)
+            .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
+        let query_text = json
+            .get("query")
+            .and_then(|query| query.as_str())
+            .ok_or_else(|| {
+                GraphQLServerError::ClientError(String::from(
+                    "The \"query\" field is missing in request data",
+                ))
+            })?;
+        let id = self.persisted_queries.register(query_text)?;
+        self.persisted_queries.save().await?;
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "id": id }).to_string()))
+            .unwrap())
     }
 
     // Handles OPTIONS requests
@@ -289,60 +448,73 @@ where
 
         match (method, path_segments.as_slice()) {
             (Method::POST, ["indexers", hash, "graphql"]) => {
-                let indexer_hash = hash.to_string();
-                self.handle_graphql_query_by_name(indexer_hash, req).boxed()
+                let target = Self::indexer_target(hash);
+                self.handle_graphql_query(target, req).boxed()
+            }
+            (Method::GET, ["indexers", hash, "graphql"]) => {
+                let target = Self::indexer_target(hash);
+                self.handle_graphql_get(target, req)
+            }
+            (Method::GET, ["indexers", hash, "schema.graphql"]) => {
+                let target = Self::indexer_target(hash);
+                let file_name = hash.to_string();
+                self.handle_schema_download(target, file_name).boxed()
+            }
+            (Method::POST, ["federations", name, "graphql"]) => {
+                let target = self.federation_target(name);
+                self.handle_graphql_query(target, req).boxed()
+            }
+            (Method::GET, ["federations", name, "graphql"]) => {
+                let target = self.federation_target(name);
+                self.handle_graphql_get(target, req)
+            }
+            (Method::GET, ["federations", name, "schema.graphql"]) => {
+                let target = self.federation_target(name);
+                let file_name = name.to_string();
+                self.handle_schema_download(target, file_name).boxed()
             }
+            (Method::OPTIONS, ["federations", _, "graphql"]) => self.handle_graphql_options(req),
             (Method::OPTIONS, ["indexers", _, "graphql"]) => self.handle_graphql_options(req),
-            // (Method::GET, [""]) => self.index().boxed(),
-            // (Method::GET, ["graphiql.css"]) => {
-            //     self.serve_file(include_str!("../assets/graphiql.css"), "text/css")
-            // }
-            // (Method::GET, ["graphiql.min.js"]) => {
-            //     self.serve_file(include_str!("../assets/graphiql.min.js"), "text/javascript")
-            // }
-            //
-            // (Method::GET, &["subgraphs", "id", _, "graphql"])
-            // | (Method::GET, &["subgraphs", "name", _, "graphql"])
-            // | (Method::GET, &["subgraphs", "name", _, _, "graphql"])
-            // | (Method::GET, &["subgraphs", "network", _, _, "graphql"])
-            // | (Method::GET, &["subgraphs", "graphql"]) => self.handle_graphiql(),
-            //
-            // (Method::GET, path @ ["subgraphs", "id", _])
-            // | (Method::GET, path @ ["subgraphs", "name", _])
-            // | (Method::GET, path @ ["subgraphs", "name", _, _])
-            // | (Method::GET, path @ ["subgraphs", "network", _, _])
-            // | (Method::GET, path @ ["subgraphs"]) => {
-            //     let dest = format!("/{}/graphql", path.join("/"));
-            //     self.handle_temp_redirect(dest).boxed()
-            // }
-            //
-            // (Method::POST, &["subgraphs", "id", subgraph_id]) => {
-            //     self.handle_graphql_query_by_id(subgraph_id.to_owned(), req)
-            // }
-            // (Method::OPTIONS, ["subgraphs", "id", _]) => self.handle_graphql_options(req),
-            // (Method::POST, &["subgraphs", "name", subgraph_name]) => self
-            //     .handle_graphql_query_by_name(subgraph_name.to_owned(), req)
-            //     .boxed(),
-            // (Method::POST, ["subgraphs", "name", subgraph_name_part1, subgraph_name_part2]) => {
-            //     let subgraph_name = format!("{}/{}", subgraph_name_part1, subgraph_name_part2);
-            //     self.handle_graphql_query_by_name(subgraph_name, req)
-            //         .boxed()
-            // }
-            // (Method::POST, ["subgraphs", "network", subgraph_name_part1, subgraph_name_part2]) => {
-            //     let subgraph_name =
-            //         format!("network/{}/{}", subgraph_name_part1, subgraph_name_part2);
-            //     self.handle_graphql_query_by_name(subgraph_name, req)
-            //         .boxed()
-            // }
-            //
-            // (Method::OPTIONS, ["subgraphs", "name", _])
-            // | (Method::OPTIONS, ["subgraphs", "name", _, _])
-            // | (Method::OPTIONS, ["subgraphs", "network", _, _]) => self.handle_graphql_options(req),
+            (Method::POST, ["persisted_queries"]) => {
+                self.handle_persisted_query_registration(req).boxed()
+            }
+            (Method::OPTIONS, ["persisted_queries"]) => self.handle_graphql_options(req),
+            (Method::GET, [""]) => self.index().boxed(),
+            (Method::GET, ["graphiql.css"]) => {
+                self.serve_file(include_str!("../../assets/graphiql.css"), "text/css")
+            }
+            (Method::GET, path @ ["indexers", _]) => {
+                let dest = format!("/{}/graphql", path.join("/"));
+                self.handle_temp_redirect(dest).boxed()
+            }
             _ => self.handle_not_found(),
         }
     }
 }
 
+/// Escapes text for HTML contents and quoted attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the GraphiQL explorer of an endpoint. The endpoint comes from the
+/// request path, so it is only inserted HTML-escaped into the title and into a
+/// data attribute read by the script, never into the script itself.
+fn graphiql_html(endpoint: &str) -> String {
+    include_str!("../../assets/index.html").replace("__GRAPHQL_ENDPOINT__", &escape_html(endpoint))
+}
+
 impl<Q> Service<Request<Body>> for GraphQLService<Q>
 where
     Q: GraphQlRunner,
@@ -395,6 +567,22 @@ where
         })
     }
 }
+#[cfg(test)]
+mod graphiql_tests {
+    use super::graphiql_html;
+
+    #[test]
+    fn graphiql_escapes_endpoint() {
+        let html = graphiql_html("/federations/a'</title><script>alert(\"x\")</script>/graphql");
+        assert!(!html.contains("<script>alert"));
+        assert!(!html.contains("a'<"));
+        assert!(html.contains(
+            "data-endpoint=\"/federations/a&#39;&lt;/title&gt;&lt;script&gt;alert(&quot;x&quot;)"
+        ));
+        assert!(!html.contains("__GRAPHQL_ENDPOINT__"));
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
@@ -458,6 +646,10 @@ mod tests {
             unreachable!();
         }
 
+        fn max_depth(&self) -> u8 {
+            u8::max_value()
+        }
+
         fn load_manager(&self) -> Arc<LoadManager> {
             unimplemented!()
         }
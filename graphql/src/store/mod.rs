@@ -7,7 +7,7 @@ pub mod prefetch;
 pub mod query;
 pub mod resolver;
 
-pub use query::build_query;
+pub use query::{build_aggregate_query, build_query};
 pub use resolver::StoreResolver;
 
 lazy_static! {
@@ -11,15 +11,20 @@ use massbit_data::graphql::TypeExt;
 use massbit_data::prelude::{
     q, s, DirectiveFinder, DocumentExt, ObjectOrInterface, ObjectTypeExt, QueryExecutionError,
 };
+use massbit_data::schema::api::{
+    AGGREGATE_DIRECTIVE, CONNECTION_DIRECTIVE, GENERATED_FOR_ARGUMENT, PAGE_INFO_TYPE,
+};
 use massbit_data::schema::ApiSchema;
 use massbit_data::store::{chain::BlockNumber, entity::*, value::Value as StoreValue, QueryStore};
 
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::iter::once;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::iter::{once, FromIterator};
 use std::rc::Rc;
+use std::str::FromStr;
 use std::time::Instant;
 
-use super::{build_query, StoreResolver};
+use super::query::{build_connection_query, encode_cursor, ConnectionQuery};
+use super::{build_aggregate_query, build_query, StoreResolver};
 use crate::execution::{ExecutionContext, Resolver};
 use massbit_data::query::ast as qast;
 use massbit_data::schema::ast as sast;
@@ -526,6 +531,23 @@ fn execute_selection_set<'a>(
                 .object_or_interface(field.field_type.get_base_type())
                 .expect("we only collect fields that are objects or interfaces");
 
+            // Aggregation and connection fields on `Query` do not return
+            // entities directly, and need to be handled separately
+            if let Some(entity) = aggregated_entity(schema, child_type) {
+                match execute_aggregate(resolver, ctx, type_cond, entity, child_type, &fields) {
+                    Ok(children) => Join::perform(parents, children, response_key),
+                    Err(mut e) => errors.append(&mut e),
+                }
+                continue;
+            }
+            if let Some(entity) = connected_entity(schema, child_type) {
+                match execute_connection(resolver, ctx, type_cond, entity, child_type, &fields) {
+                    Ok(children) => Join::perform(parents, children, response_key),
+                    Err(mut e) => errors.append(&mut e),
+                }
+                continue;
+            }
+
             let join = Join::new(
                 ctx.query.schema.as_ref(),
                 type_cond,
@@ -840,6 +862,311 @@ fn execute_field(
     .map_err(|e| vec![e])
 }
 
+/// The name of the entity type that `object_type` was generated for, if
+/// it is marked with `directive` in the API schema
+fn generated_for<'a>(object_type: ObjectOrInterface<'a>, directive: &str) -> Option<&'a str> {
+    if !object_type.is_object() {
+        return None;
+    }
+    object_type
+        .directives()
+        .iter()
+        .find(|dir| dir.name == directive)
+        .and_then(|dir| {
+            dir.arguments
+                .iter()
+                .find(|(name, _)| name == GENERATED_FOR_ARGUMENT)
+        })
+        .and_then(|(_, value)| match value {
+            s::Value::String(name) => Some(name.as_str()),
+            _ => None,
+        })
+}
+
+/// If `object_type` is the `<Entity>_aggregate` type of an aggregation
+/// field, return the object type of the entities it aggregates
+fn aggregated_entity<'a>(
+    schema: &'a ApiSchema,
+    object_type: ObjectOrInterface<'_>,
+) -> Option<&'a s::ObjectType> {
+    generated_for(object_type, AGGREGATE_DIRECTIVE)
+        .and_then(|name| schema.document().get_object_type_definition(name))
+}
+
+/// What a field selected on an `<Entity>_aggregate` type resolves to
+enum AggregateSelection<'a> {
+    /// The values of the `groupBy` fields
+    Group,
+    /// The result of `function` for each of the selected `attributes`
+    Values(AggregateFunction, Vec<&'a str>),
+}
+
+/// Run the aggregation field `fields` for the entities of type `entity`
+/// and turn each group of the result into a node of `aggregate_type`. The
+/// nested `group`, `sum`, etc. objects become single children of that node
+/// so that they resolve just like prefetched objects
+fn execute_aggregate<'a>(
+    resolver: &StoreResolver,
+    ctx: &'a ExecutionContext<impl Resolver>,
+    object_type: ObjectOrInterface<'_>,
+    entity: &s::ObjectType,
+    aggregate_type: ObjectOrInterface<'a>,
+    fields: &[&'a q::Field],
+) -> Result<Vec<Node>, Vec<QueryExecutionError>> {
+    let schema = ctx.query.schema.document();
+    let argument_values =
+        crate::execution::coerce_argument_values(&ctx.query, object_type, fields[0])?;
+
+    // Figure out which aggregates are selected, so that we only compute those
+    let mut selections = vec![];
+    let mut aggregates = BTreeSet::new();
+    let grouped_field_set =
+        collect_fields(ctx, aggregate_type, fields.iter().map(|f| &f.selection_set));
+    for (response_key, collected) in grouped_field_set {
+        for (_, fields) in collected {
+            // Unwrap: the query was validated to contain only valid fields
+            let field_type = aggregate_type
+                .field(&fields[0].name)
+                .unwrap()
+                .field_type
+                .get_base_type();
+            let selection = match AggregateFunction::from_str(&fields[0].name) {
+                Ok(function) => {
+                    let values_type = schema.get_object_type_definition(field_type).unwrap();
+                    let attributes: Vec<&str> = crate::execution::collect_fields(
+                        ctx,
+                        values_type,
+                        fields.iter().map(|f| &f.selection_set),
+                    )
+                    .values()
+                    .map(|fields| fields[0].name.as_str())
+                    .filter(|name| !name.starts_with("__"))
+                    .collect();
+                    for attribute in &attributes {
+                        aggregates.insert((function, attribute.to_string()));
+                    }
+                    AggregateSelection::Values(function, attributes)
+                }
+                Err(_) => AggregateSelection::Group,
+            };
+            selections.push((response_key, field_type, selection));
+        }
+    }
+
+    let mut query = build_aggregate_query(
+        entity,
+        resolver.block_number(),
+        &argument_values,
+        ctx.max_first,
+        ctx.max_skip,
+        aggregates.into_iter().collect(),
+    )
+    .map_err(|e| vec![e])?;
+    query.query_id = Some(ctx.query.query_id.clone());
+    query.logger = Some(ctx.logger.clone());
+
+    let rows = resolver.store.find_aggregates(query).map_err(|e| vec![e])?;
+
+    Ok(rows
+        .into_iter()
+        .map(|mut row| {
+            let mut node = Node::from(BTreeMap::from_iter(vec![
+                (
+                    "__typename".to_owned(),
+                    q::Value::String(aggregate_type.name().to_owned()),
+                ),
+                ("count".to_owned(), q::Value::String(row.count.to_string())),
+                (
+                    "bucket".to_owned(),
+                    row.bucket.take().unwrap_or(q::Value::Null),
+                ),
+            ]));
+            for (response_key, type_name, selection) in &selections {
+                let mut object = match selection {
+                    AggregateSelection::Group => row.group.clone(),
+                    AggregateSelection::Values(function, attributes) => attributes
+                        .iter()
+                        .map(|attribute| {
+                            let value = row
+                                .values
+                                .get(&(*function, attribute.to_string()))
+                                .cloned()
+                                .unwrap_or(q::Value::Null);
+                            (attribute.to_string(), value)
+                        })
+                        .collect(),
+                };
+                object.insert(
+                    "__typename".to_owned(),
+                    q::Value::String(type_name.to_string()),
+                );
+                node.children
+                    .insert(response_key.to_string(), vec![Rc::new(Node::from(object))]);
+            }
+            node
+        })
+        .collect())
+}
+
+/// If `object_type` is the `<Entity>_connection` type of a connection
+/// field, return the type of the entities it paginates
+fn connected_entity<'a>(
+    schema: &'a ApiSchema,
+    object_type: ObjectOrInterface<'_>,
+) -> Option<ObjectOrInterface<'a>> {
+    generated_for(object_type, CONNECTION_DIRECTIVE)
+        .and_then(|name| schema.document().object_or_interface(name))
+}
+
+/// Run the connection field `fields` for the entities of type `entity`.
+/// The entities are prefetched together with everything selected below
+/// `edges { node { .. } }`, and then wrapped in edges and page info nodes
+/// that resolve like prefetched objects
+fn execute_connection<'a>(
+    resolver: &StoreResolver,
+    ctx: &'a ExecutionContext<impl Resolver>,
+    object_type: ObjectOrInterface<'_>,
+    entity: ObjectOrInterface<'a>,
+    connection_type: ObjectOrInterface<'a>,
+    fields: &[&'a q::Field],
+) -> Result<Vec<Node>, Vec<QueryExecutionError>> {
+    let schema = &ctx.query.schema;
+    let argument_values =
+        crate::execution::coerce_argument_values(&ctx.query, object_type, fields[0])?;
+    let ConnectionQuery {
+        mut query,
+        first,
+        order_by,
+        backward,
+        previous,
+    } = build_connection_query(
+        entity,
+        resolver.block_number(),
+        &argument_values,
+        schema.types_for_interface(),
+        ctx.max_first,
+    )
+    .map_err(|e| vec![e])?;
+    query.query_id = Some(ctx.query.query_id.clone());
+    query.logger = Some(ctx.logger.clone());
+
+    let mut entities = resolver
+        .store
+        .find_query_values(query)
+        .map_err(|e| vec![e])?;
+    let has_more = entities.len() > first;
+    let has_other_side = match previous {
+        Some(mut previous) => {
+            previous.query_id = Some(ctx.query.query_id.clone());
+            previous.logger = Some(ctx.logger.clone());
+            !resolver
+                .store
+                .find_query_values(previous)
+                .map_err(|e| vec![e])?
+                .is_empty()
+        }
+        None => false,
+    };
+    entities.truncate(first);
+    if backward {
+        entities.reverse();
+    }
+    let cursors: Vec<String> = entities
+        .iter()
+        .map(|entity| encode_cursor(entity, order_by.as_deref()))
+        .collect();
+
+    // Find the response keys for the edges, their nodes and the page info,
+    // and gather all selections on nodes so we can prefetch them together
+    let edge_type_name = format!("{}_edge", entity.name());
+    let edge_type = schema
+        .document()
+        .object_or_interface(&edge_type_name)
+        .expect("every connection type has an edge type");
+    let mut edges_keys = vec![];
+    let mut page_info_keys = vec![];
+    let mut node_fields: Vec<&'a q::Field> = vec![];
+    let grouped_field_set = collect_fields(
+        ctx,
+        connection_type,
+        fields.iter().map(|f| &f.selection_set),
+    );
+    for (response_key, collected) in grouped_field_set {
+        for (_, fields) in collected {
+            if fields[0].name != "edges" {
+                page_info_keys.push(response_key);
+                continue;
+            }
+            let mut node_keys = vec![];
+            let edge_field_set =
+                collect_fields(ctx, edge_type, fields.iter().map(|f| &f.selection_set));
+            for (node_key, collected) in edge_field_set {
+                for (_, fields) in collected {
+                    node_fields.extend(fields);
+                }
+                node_keys.push(node_key);
+            }
+            edges_keys.push((response_key, node_keys));
+        }
+    }
+
+    let grouped_field_set =
+        collect_fields(ctx, entity, node_fields.iter().map(|f| &f.selection_set));
+    let nodes = entities.into_iter().map(Node::from).collect();
+    let nodes: Vec<Rc<Node>> = execute_selection_set(resolver, ctx, nodes, grouped_field_set)?
+        .into_iter()
+        .map(Rc::new)
+        .collect();
+
+    let typename = |name: &str| ("__typename".to_owned(), q::Value::String(name.to_owned()));
+    let mut connection = Node::from(BTreeMap::from_iter(once(typename(connection_type.name()))));
+    for (response_key, node_keys) in edges_keys {
+        let edges = nodes
+            .iter()
+            .zip(&cursors)
+            .map(|(node, cursor)| {
+                let mut edge = Node::from(BTreeMap::from_iter(vec![
+                    typename(&edge_type_name),
+                    ("cursor".to_owned(), q::Value::String(cursor.clone())),
+                ]));
+                for node_key in &node_keys {
+                    edge.children
+                        .insert(node_key.to_string(), vec![node.clone()]);
+                }
+                Rc::new(edge)
+            })
+            .collect();
+        connection.children.insert(response_key.to_owned(), edges);
+    }
+
+    let (has_next_page, has_previous_page) = if backward {
+        (has_other_side, has_more)
+    } else {
+        (has_more, has_other_side)
+    };
+    let cursor_value = |cursor: Option<&String>| {
+        cursor
+            .map(|cursor| q::Value::String(cursor.clone()))
+            .unwrap_or(q::Value::Null)
+    };
+    let page_info = Rc::new(Node::from(BTreeMap::from_iter(vec![
+        typename(PAGE_INFO_TYPE),
+        ("hasNextPage".to_owned(), q::Value::Boolean(has_next_page)),
+        (
+            "hasPreviousPage".to_owned(),
+            q::Value::Boolean(has_previous_page),
+        ),
+        ("startCursor".to_owned(), cursor_value(cursors.first())),
+        ("endCursor".to_owned(), cursor_value(cursors.last())),
+    ])));
+    for response_key in page_info_keys {
+        connection
+            .children
+            .insert(response_key.to_owned(), vec![page_info.clone()]);
+    }
+    Ok(vec![connection])
+}
+
 /// Query child entities for `parents` from the store. The `join` indicates
 /// in which child field to look for the parent's id/join field. When
 /// `is_single` is `true`, there is at most one child per parent.
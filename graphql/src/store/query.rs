@@ -1,16 +1,20 @@
 use crate::store::prefetch::ObjectCondition;
+use massbit_common::prelude::{bs58, serde_json};
 use massbit_data::indexer::DeploymentHash;
 use massbit_data::prelude::{q, s, ObjectOrInterface, QueryExecutionError};
 use massbit_data::schema::ast as sast;
 use massbit_data::store::chain::BlockNumber;
 use massbit_data::store::entity::{
-    AttributeNames, EntityCollection, EntityFilter, EntityOrder, EntityQuery, EntityRange,
-    EntityType, SubscriptionFilter,
+    AggregateFunction, AggregateInterval, AggregateQuery, Attribute, AttributeNames,
+    EntityCollection, EntityFilter, EntityOrder, EntityQuery, EntityRange, EntityType,
+    SubscriptionFilter,
 };
 use massbit_data::store::value::ValueType;
 use massbit_data::store::Value;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::mem::discriminant;
+use std::str::FromStr;
 
 #[derive(Debug)]
 enum OrderDirection {
@@ -70,6 +74,278 @@ pub fn build_query<'a>(
     Ok(query)
 }
 
+/// The `EntityQuery` for a cursor-paginated connection field, together with
+/// what is needed to turn its result into edges and page info
+#[derive(Debug)]
+pub struct ConnectionQuery {
+    pub query: EntityQuery,
+    /// The number of entities requested; `query` asks for one more so we
+    /// know whether there is another page
+    pub first: usize,
+    /// The attribute entities are ordered by before their `id`, if any
+    pub order_by: Option<String>,
+    /// Set for `before`: `query` runs in reverse order, and its result has
+    /// to be reversed again
+    pub backward: bool,
+    /// For an `after` or `before` cursor, the query for one entity on the
+    /// other side of the cursor, to know whether there is a page there
+    pub previous: Option<EntityQuery>,
+}
+
+/// Builds the query for a connection field. Pagination uses the keyset of
+/// `orderBy` and `id` rather than an offset: a cursor `(value, id)` turns
+/// into the range predicate `attr >= value and (attr > value or id > id)`
+/// (reversed for descending order and `before`) so that the database can
+/// use the index on `attr` no matter how deep into the collection we are.
+///
+/// Since entities whose `orderBy` attribute is `null` can not be found
+/// that way, connections can only be ordered by non-null attributes.
+pub fn build_connection_query<'a>(
+    entity: impl Into<ObjectOrInterface<'a>>,
+    block: BlockNumber,
+    arguments: &HashMap<&str, q::Value>,
+    types_for_interface: &'a BTreeMap<EntityType, Vec<s::ObjectType>>,
+    max_first: u32,
+) -> Result<ConnectionQuery, QueryExecutionError> {
+    let entity = entity.into();
+    let mut query = build_query(
+        entity,
+        block,
+        arguments,
+        types_for_interface,
+        max_first,
+        0,
+        BTreeMap::new(),
+    )?;
+    let first = query.range.first.unwrap_or(100);
+    query.range.first = Some(first + 1);
+
+    let (order_by, ascending) = match &query.order {
+        EntityOrder::Ascending(attr, _) => (Some(attr.clone()), true),
+        EntityOrder::Descending(attr, _) => (Some(attr.clone()), false),
+        EntityOrder::Default | EntityOrder::Unordered => (None, true),
+    };
+    let order_by = order_by.filter(|attr| attr != "id");
+    if let Some(attr) = &order_by {
+        let nullable = sast::get_field(entity, attr)
+            .map(|field| !sast::is_non_null_type(&field.field_type))
+            .unwrap_or(true);
+        if nullable {
+            return Err(QueryExecutionError::OrderByNotSupportedError(
+                entity.name().to_owned(),
+                attr.clone(),
+            ));
+        }
+    }
+
+    let cursor = |name: &str| match arguments.get(name) {
+        Some(q::Value::String(cursor)) => Some(cursor),
+        _ => None,
+    };
+    let (cursor, backward) = match (cursor("after"), cursor("before")) {
+        (Some(_), Some(_)) => {
+            return Err(QueryExecutionError::ValueParseError(
+                "before".to_owned(),
+                "`after` and `before` can not be used together".to_owned(),
+            ))
+        }
+        (Some(after), None) => (Some(after), false),
+        (None, Some(before)) => (Some(before), true),
+        (None, None) => (None, false),
+    };
+
+    if backward {
+        query.order = match query.order {
+            EntityOrder::Ascending(attr, value_type) => EntityOrder::Descending(attr, value_type),
+            EntityOrder::Descending(attr, value_type) => EntityOrder::Ascending(attr, value_type),
+            EntityOrder::Default | EntityOrder::Unordered => {
+                EntityOrder::Descending("id".to_owned(), id_value_type(entity))
+            }
+        };
+    }
+    let mut previous = None;
+    if let Some(cursor) = cursor {
+        let (value, id) = decode_cursor(cursor)?;
+        let id = cursor_id(entity, id)?;
+        let forward = ascending != backward;
+        let filter = cursor_filter(
+            entity,
+            order_by.as_deref(),
+            value.clone(),
+            id.clone(),
+            forward,
+        )?;
+        // The entity of the cursor itself, or any entity beyond it
+        let other_side = EntityFilter::Or(vec![
+            EntityFilter::Equal("id".to_owned(), id.clone()),
+            cursor_filter(entity, order_by.as_deref(), value, id, !forward)?,
+        ]);
+        let mut previous_query = query.clone();
+        previous_query.filter = Some(other_side.and_maybe(query.filter.clone()));
+        previous_query.order = EntityOrder::Unordered;
+        previous_query.range = EntityRange::first(1);
+        previous = Some(previous_query);
+        query.filter = Some(filter.and_maybe(query.filter));
+    }
+
+    Ok(ConnectionQuery {
+        query,
+        first: first as usize,
+        order_by,
+        backward,
+        previous,
+    })
+}
+
+/// The type of the `id` of `entity`
+fn id_value_type(entity: ObjectOrInterface) -> ValueType {
+    sast::get_field(entity, "id")
+        .and_then(|field| sast::get_field_value_type(&field.field_type).ok())
+        .unwrap_or(ValueType::String)
+}
+
+/// The `id` of a cursor as a value of the type of the `id` of `entity`
+fn cursor_id(entity: ObjectOrInterface, id: String) -> Result<Value, QueryExecutionError> {
+    match sast::get_field(entity, "id") {
+        Some(field) => Value::from_query_value(&q::Value::String(id), &field.field_type),
+        None => Ok(Value::from(id)),
+    }
+}
+
+/// Encode the position of `entity` in a collection ordered by `order_by`
+/// and `id` as an opaque cursor
+pub fn encode_cursor(entity: &BTreeMap<String, q::Value>, order_by: Option<&str>) -> String {
+    use serde_json::Value as j;
+
+    let json = |value: Option<&q::Value>| match value {
+        Some(q::Value::String(s)) => j::String(s.clone()),
+        Some(q::Value::Int(i)) => i.as_i64().map(j::from).unwrap_or(j::Null),
+        Some(q::Value::Boolean(b)) => j::Bool(*b),
+        _ => j::Null,
+    };
+    let cursor = j::Array(vec![
+        json(order_by.and_then(|attr| entity.get(attr))),
+        json(entity.get("id")),
+    ]);
+    bs58::encode(cursor.to_string()).into_string()
+}
+
+/// Decode a cursor produced by `encode_cursor` into the value of the
+/// `orderBy` attribute and the id of the entity it points to
+fn decode_cursor(cursor: &str) -> Result<(q::Value, String), QueryExecutionError> {
+    use serde_json::Value as j;
+
+    let invalid = || QueryExecutionError::ValueParseError("cursor".to_owned(), cursor.to_owned());
+    let bytes = bs58::decode(cursor).into_vec().map_err(|_| invalid())?;
+    let values = match serde_json::from_slice(&bytes).map_err(|_| invalid())? {
+        j::Array(values) => values,
+        _ => return Err(invalid()),
+    };
+    match values.as_slice() {
+        [value, j::String(id)] => {
+            let value = match value {
+                j::String(s) => q::Value::String(s.clone()),
+                j::Number(n) => {
+                    let n = n.as_i64().ok_or_else(invalid)?;
+                    q::Value::Int(i32::try_from(n).map_err(|_| invalid())?.into())
+                }
+                j::Bool(b) => q::Value::Boolean(*b),
+                _ => q::Value::Null,
+            };
+            Ok((value, id.clone()))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// The filter selecting the entities that come after (`greater` is `true`)
+/// or before the entity with attribute `value` and `id` in a collection
+/// ordered ascending by `order_by` and `id`
+fn cursor_filter(
+    entity: ObjectOrInterface,
+    order_by: Option<&str>,
+    value: q::Value,
+    id: Value,
+    greater: bool,
+) -> Result<EntityFilter, QueryExecutionError> {
+    let id_filter = if greater {
+        EntityFilter::GreaterThan("id".to_owned(), id)
+    } else {
+        EntityFilter::LessThan("id".to_owned(), id)
+    };
+    let attr = match order_by {
+        Some(attr) => attr.to_owned(),
+        None => return Ok(id_filter),
+    };
+    let field = sast::get_field(entity, &attr).ok_or_else(|| {
+        QueryExecutionError::EntityFieldError(entity.name().to_owned(), attr.clone())
+    })?;
+    let value = Value::from_query_value(&value, &field.field_type)?;
+    let (bound, beyond) = if greater {
+        (
+            EntityFilter::GreaterOrEqual(attr.clone(), value.clone()),
+            EntityFilter::GreaterThan(attr, value),
+        )
+    } else {
+        (
+            EntityFilter::LessOrEqual(attr.clone(), value.clone()),
+            EntityFilter::LessThan(attr, value),
+        )
+    };
+    Ok(EntityFilter::And(vec![
+        bound,
+        EntityFilter::Or(vec![beyond, id_filter]),
+    ]))
+}
+
+/// Builds an `AggregateQuery` for the entities of type `entity` from the
+/// arguments of an aggregation field. Which `aggregates` to compute is
+/// determined by the caller from the selection set of the field.
+pub fn build_aggregate_query(
+    entity: &s::ObjectType,
+    block: BlockNumber,
+    arguments: &HashMap<&str, q::Value>,
+    max_first: u32,
+    max_skip: u32,
+    aggregates: Vec<(AggregateFunction, Attribute)>,
+) -> Result<AggregateQuery, QueryExecutionError> {
+    let enum_argument = |name: &str| match arguments.get(name) {
+        Some(q::Value::Enum(value)) => Some(value),
+        _ => None,
+    };
+
+    let mut query = AggregateQuery::new(parse_indexer_id(entity)?, block, entity.into());
+    query.range = build_range(arguments, max_first, max_skip)?;
+    query.filter = build_filter(entity.into(), arguments)?;
+    query.group_by = match arguments.get("groupBy") {
+        Some(q::Value::List(values)) => values
+            .iter()
+            .map(|value| match value {
+                q::Value::Enum(name) => name.to_owned(),
+                _ => unreachable!("groupBy is a list of enum values"),
+            })
+            .collect(),
+        _ => vec![],
+    };
+    query.bucket = match (enum_argument("interval"), enum_argument("timestamp")) {
+        (Some(interval), Some(timestamp)) => {
+            let interval = AggregateInterval::from_str(interval).map_err(|e| {
+                QueryExecutionError::ValueParseError("interval".to_owned(), e.to_string())
+            })?;
+            Some((timestamp.to_owned(), interval))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(QueryExecutionError::ValueParseError(
+                "interval".to_owned(),
+                "`interval` and `timestamp` must be used together".to_owned(),
+            ))
+        }
+    };
+    query.aggregates = aggregates;
+    Ok(query)
+}
+
 /// Parses GraphQL arguments into a EntityRange, if present.
 fn build_range(
     arguments: &HashMap<&str, q::Value>,
@@ -362,12 +638,17 @@ mod tests {
     use massbit_data::prelude::{graphql_parser::Pos, q, s};
     use massbit_data::store::chain::BLOCK_NUMBER_MAX;
     use massbit_data::store::entity::{
-        AttributeNames, EntityCollection, EntityFilter, EntityOrder, EntityRange,
+        AggregateFunction, AggregateInterval, AttributeNames, EntityCollection, EntityFilter,
+        EntityOrder, EntityRange,
     };
     use massbit_data::store::value::ValueType;
     use massbit_data::store::{EntityType, Value};
 
-    use super::build_query;
+    use massbit_common::prelude::bs58;
+
+    use super::{
+        build_aggregate_query, build_connection_query, build_query, decode_cursor, encode_cursor,
+    };
 
     fn default_object() -> ObjectType {
         let indexer_id_argument = (
@@ -765,4 +1046,173 @@ mod tests {
             )]))
         )
     }
+
+    #[test]
+    fn build_aggregate_query_parses_groups_and_buckets() {
+        let mut args = default_arguments();
+        args.insert(
+            "groupBy",
+            q::Value::List(vec![q::Value::Enum("name".to_string())]),
+        );
+        args.insert("interval", q::Value::Enum("day".to_string()));
+        args.insert("timestamp", q::Value::Enum("timestamp".to_string()));
+        let aggregates = vec![(AggregateFunction::Sum, "amount".to_string())];
+
+        let query = build_aggregate_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            std::u32::MAX,
+            std::u32::MAX,
+            aggregates.clone(),
+        )
+        .unwrap();
+        assert_eq!(query.entity_type, EntityType::from("Entity1"));
+        assert_eq!(query.group_by, vec!["name".to_string()]);
+        assert_eq!(
+            query.bucket,
+            Some(("timestamp".to_string(), AggregateInterval::Day))
+        );
+        assert_eq!(query.aggregates, aggregates);
+        assert_eq!(query.range, EntityRange::first(100));
+
+        // An interval without a timestamp field is an error
+        args.remove("timestamp");
+        assert!(build_aggregate_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            std::u32::MAX,
+            std::u32::MAX,
+            aggregates,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn build_connection_query_pages_with_cursors() {
+        let mut args = default_arguments();
+        args.insert("first", q::Value::Int(2.into()));
+        let connection = build_connection_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            &BTreeMap::new(),
+            std::u32::MAX,
+        )
+        .unwrap();
+        // One more entity than requested is fetched to detect further pages
+        assert_eq!(connection.first, 2);
+        assert_eq!(connection.query.range, EntityRange::first(3));
+        assert_eq!(connection.query.filter, None);
+        assert!(!connection.backward && connection.previous.is_none());
+
+        let entity =
+            BTreeMap::from_iter(vec![("id".to_string(), q::Value::String("a".to_string()))]);
+        args.insert("after", q::Value::String(encode_cursor(&entity, None)));
+        let connection = build_connection_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            &BTreeMap::new(),
+            std::u32::MAX,
+        )
+        .unwrap();
+        assert_eq!(
+            connection.query.filter,
+            Some(EntityFilter::GreaterThan(
+                "id".to_string(),
+                Value::String("a".to_string())
+            ))
+        );
+        // One entity at or before the cursor tells whether there is a
+        // previous page
+        let previous = connection.previous.expect("a cursor has a previous query");
+        assert_eq!(previous.range, EntityRange::first(1));
+        assert_eq!(
+            previous.filter,
+            Some(EntityFilter::Or(vec![
+                EntityFilter::Equal("id".to_string(), Value::String("a".to_string())),
+                EntityFilter::LessThan("id".to_string(), Value::String("a".to_string())),
+            ]))
+        );
+
+        // `after` and `before` can not be combined
+        args.insert("before", q::Value::String(encode_cursor(&entity, None)));
+        assert!(build_connection_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            &BTreeMap::new(),
+            std::u32::MAX,
+        )
+        .is_err());
+
+        // Paging backwards reverses the order
+        args.remove("after");
+        let connection = build_connection_query(
+            &object("Entity1"),
+            BLOCK_NUMBER_MAX,
+            &args,
+            &BTreeMap::new(),
+            std::u32::MAX,
+        )
+        .unwrap();
+        assert!(connection.backward);
+        assert_eq!(
+            connection.query.order,
+            EntityOrder::Descending("id".to_string(), ValueType::String)
+        );
+        assert_eq!(
+            connection.query.filter,
+            Some(EntityFilter::LessThan(
+                "id".to_string(),
+                Value::String("a".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn build_connection_query_uses_the_id_type() {
+        let bytes_id = Type::NonNullType(Box::new(Type::NamedType("Bytes".to_owned())));
+        let entity = ObjectType {
+            fields: vec![field("id", bytes_id)],
+            ..object("Entity1")
+        };
+        let mut args = default_arguments();
+        let cursor_entity = BTreeMap::from_iter(vec![(
+            "id".to_string(),
+            q::Value::String("0xaa".to_string()),
+        )]);
+        args.insert(
+            "before",
+            q::Value::String(encode_cursor(&cursor_entity, None)),
+        );
+        let connection = build_connection_query(
+            &entity,
+            BLOCK_NUMBER_MAX,
+            &args,
+            &BTreeMap::new(),
+            std::u32::MAX,
+        )
+        .unwrap();
+        assert_eq!(
+            connection.query.order,
+            EntityOrder::Descending("id".to_string(), ValueType::Bytes)
+        );
+        match connection.query.filter {
+            Some(EntityFilter::LessThan(attr, Value::Bytes(_))) => assert_eq!(attr, "id"),
+            filter => panic!("unexpected cursor filter {:?}", filter),
+        }
+    }
+
+    #[test]
+    fn decode_cursor_rejects_out_of_range_ints() {
+        let cursor = |json: &str| bs58::encode(json).into_string();
+        assert_eq!(
+            decode_cursor(&cursor("[7,\"a\"]")).unwrap(),
+            (q::Value::Int(7.into()), "a".to_string())
+        );
+        assert!(decode_cursor(&cursor("[3000000000,\"a\"]")).is_err());
+    }
 }
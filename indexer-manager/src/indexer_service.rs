@@ -54,7 +54,8 @@ impl IndexerService {
         self.get_connection().ok().and_then(|conn| {
             dsl::indexers
                 .filter(dsl::deleted.eq(false))
-                .filter(dsl::status.eq(IndexerStatus::Deployed))
+                // Deploying indexers were stopped before their library was loaded
+                .filter(dsl::status.eq_any(vec![IndexerStatus::Deployed, IndexerStatus::Deploying]))
                 .load::<Indexer>(conn.deref())
                 .map_err(|err| {
                     log::error!("{:?}", &err);
@@ -219,7 +220,8 @@ impl IndexerService {
                             dsl::name.eq(&indexer.name),
                             dsl::network.eq(&indexer.network),
                             dsl::address.eq(&indexer.address),
-                            dsl::status.eq(IndexerStatus::Deployed),
+                            // The runtime sets the indexer deployed once its mapping library is loaded
+                            dsl::status.eq(indexer.status.clone()),
                         ))
                         .execute(conn.deref())
                         .map_err(|err| {
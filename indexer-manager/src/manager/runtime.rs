@@ -8,7 +8,10 @@ use chain_solana::manifest::ManifestResolve;
 use chain_solana::types::{Pubkey, SolanaFilter};
 use chain_solana::SolanaIndexerManifest;
 use diesel::{Connection, EqAll};
-use indexer_orm::{models::Indexer, schema::*};
+use indexer_orm::{
+    models::{Indexer, IndexerHealth, IndexerStatus},
+    schema::*,
+};
 use libloading::Library;
 use log::info;
 use massbit::components::link_resolver::LinkResolver as _;
@@ -34,7 +37,9 @@ use massbit_grpc::firehose::bstream::stream_client::StreamClient;
 use massbit_grpc::firehose::bstream::{BlockRequest, ChainType};
 use massbit_solana_sdk::plugin::handler::SolanaHandler;
 use massbit_solana_sdk::plugin::proxy::SolanaHandlerProxy;
-use massbit_solana_sdk::plugin::{AdapterDeclaration, BlockResponse, PluginRegistrar};
+use massbit_solana_sdk::plugin::{
+    check_plugin_version, AdapterDeclaration, BlockResponse, PluginRegistrar,
+};
 use massbit_solana_sdk::store::IndexStore;
 use massbit_solana_sdk::types::{ExtBlock, SolanaBlock};
 use solana_sdk::signature::Signature;
@@ -239,9 +244,11 @@ impl<'a> IndexerRuntime {
                 match self.load_mapping_library(&mut store).await {
                     Ok(_) => {
                         log::info!("{} Load library successfully", &*COMPONENT_NAME);
+                        self.record_deployed();
                     }
                     Err(err) => {
                         log::error!("Load library with error {:?}", &err);
+                        self.record_fatal_error(&format!("Cannot load mapping library: {}", &err));
                         return Err(err);
                     }
                 };
@@ -252,6 +259,58 @@ impl<'a> IndexerRuntime {
         }
         Ok(())
     }
+    /// Marks the indexer as deployed, once its mapping library is loaded
+    fn record_deployed(&mut self) {
+        use indexers::dsl;
+        let result = self
+            .get_connection()
+            .map_err(|err| anyhow!(err))
+            .and_then(|conn| {
+                diesel::update(dsl::indexers.filter(dsl::hash.eq(&self.indexer.hash)))
+                    .set(dsl::status.eq(IndexerStatus::Deployed))
+                    .execute(conn.deref())
+                    .map_err(|err| anyhow!(err))
+            });
+        match result {
+            Ok(_) => self.indexer.status = IndexerStatus::Deployed,
+            Err(err) => log::error!(
+                "Cannot record deployment of indexer {}: {:?}",
+                &self.indexer.hash,
+                &err
+            ),
+        }
+    }
+    /// Marks the deployment of the indexer as failed and the indexer as invalid,
+    /// the error is returned by the indexer-api
+    fn record_fatal_error(&self, error: &str) {
+        use indexer_deployments::dsl as d;
+        use indexers::dsl;
+        let result = self
+            .get_connection()
+            .map_err(|err| anyhow!(err))
+            .and_then(|conn| {
+                conn.transaction(|| -> Result<_, anyhow::Error> {
+                    diesel::update(d::indexer_deployments.filter(d::hash.eq(&self.indexer.hash)))
+                        .set((
+                            d::failed.eq(true),
+                            d::health.eq(IndexerHealth::Failed),
+                            d::fatal_error.eq(Some(error)),
+                        ))
+                        .execute(conn.deref())?;
+                    diesel::update(dsl::indexers.filter(dsl::hash.eq(&self.indexer.hash)))
+                        .set(dsl::status.eq(IndexerStatus::Invalid))
+                        .execute(conn.deref())?;
+                    Ok(())
+                })
+            });
+        if let Err(err) = result {
+            log::error!(
+                "Cannot record error of indexer {}: {:?}",
+                &self.indexer.hash,
+                &err
+            );
+        }
+    }
     /// Load a plugin library
     /// A plugin library **must** be implemented using the
     /// [`model::adapter_declaration!()`] macro. Trying manually implement
@@ -263,6 +322,8 @@ impl<'a> IndexerRuntime {
     ) -> Result<(), Box<dyn Error>> {
         let library_path = self.mapping_path.as_ref().unwrap().as_os_str();
         let lib = Arc::new(Library::new(library_path)?);
+        // refuse the plugins built with another SDK or compiler before using their symbols
+        check_plugin_version(&lib)?;
         // inject store to plugin
         lib.get::<*mut Option<&dyn IndexStore>>(b"STORE\0")?
             .write(Some(store));
@@ -487,6 +548,7 @@ impl<'a> IndexerRuntime {
                                 block_number,
                                 block,
                                 list_log_messages: vec![],
+                                accounts: vec![],
                             })
                         })
                     })
@@ -526,6 +588,8 @@ impl<'a> IndexerRuntime {
             chain_type: chain_type as i32,
             network: data_source.network.clone().unwrap_or(Default::default()),
             filter: encoded_filter,
+            // The indexer gets the missing blocks itself
+            replay_history: false,
         };
         if let Ok(channel) = Channel::from_static(CHAIN_READER_URL.as_str())
             .connect()
@@ -86,6 +86,7 @@ impl BlockStream {
             chain_type: ChainType::Solana as i32,
             network: self.network.clone(),
             filter: encoded_filter,
+            replay_history: false,
         };
         if let Ok(channel) = Channel::from_static(CHAIN_READER_URL.as_str())
             .connect()
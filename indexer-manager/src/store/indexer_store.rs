@@ -13,6 +13,9 @@ use massbit_common::prelude::{anyhow, async_trait::async_trait, r2d2, slog::Logg
 use massbit_data::indexer::DeploymentHash;
 use massbit_data::prelude::{CloneableAnyhowError, QueryExecutionError, StoreError};
 use massbit_data::store::chain::BLOCK_NUMBER_MAX;
+use massbit_data::store::entity::{
+    AttributeNames, EntityCollection, EntityFilter, EntityOrder, EntityRange,
+};
 use massbit_data::store::{Entity, EntityKey, EntityModification, EntityType};
 use massbit_solana_sdk::store::IndexStore;
 use massbit_storage_postgres::{
@@ -36,6 +39,15 @@ pub trait IndexerStoreTrait: Sync + Send {
         ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
     ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError>;
 
+    /// Query the entities of a type matching a filter as of the latest block.
+    fn query(
+        &self,
+        entity_type: &EntityType,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, QueryExecutionError>;
+
     /// Transact the entity changes from a single block atomically into the store, and update the
     /// indexer block pointer to `block_ptr_to`.
     ///
@@ -111,6 +123,27 @@ impl IndexerStoreTrait for IndexerStore {
         // Ok(entities_for_type)
     }
 
+    fn query(
+        &self,
+        entity_type: &EntityType,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        let conn = self.get_conn()?;
+        let collection = EntityCollection::All(vec![(entity_type.clone(), AttributeNames::All)]);
+        self.layout.query::<Entity>(
+            &self.logger,
+            &conn,
+            collection,
+            filter,
+            order,
+            range,
+            BLOCK_NUMBER_MAX,
+            None,
+        )
+    }
+
     fn transact_block_operations(
         &self,
         block_ptr_to: BlockPtr,
@@ -341,6 +374,29 @@ impl IndexStore for CacheableStore {
         })
     }
 
+    fn remove(&mut self, entity_name: String, entity_id: &String) {
+        let key = EntityKey {
+            indexer_hash: DeploymentHash::new(self.indexer_id.clone()).unwrap(),
+            entity_type: EntityType::new(entity_name),
+            entity_id: entity_id.clone(),
+        };
+        self.entity_cache.remove(key);
+    }
+
+    fn query(
+        &self,
+        entity_name: String,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>> {
+        // The entity cache is not queried, see `IndexStore::query`
+        let entities = self
+            .store
+            .query(&EntityType::new(entity_name), filter, order, range)?;
+        Ok(entities)
+    }
+
     fn flush(&mut self, block_hash: &String, block_slot: u64) -> Result<(), Box<dyn Error>> {
         //let mut data = self.entity_cache.lock().unwrap();
         let entity_cache =
@@ -0,0 +1,81 @@
+use crate::generator::Generator;
+use crate::schema::{AccountLayout, Schema};
+use std::fmt::Write;
+
+const MODULES: &str = r#"
+use crate::generated::instruction::*;
+use serde::{Deserialize, Serialize};
+"#;
+
+impl<'a> Generator<'a> {
+    pub fn generate_account(&self, schema: &Schema) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", MODULES);
+        let layouts = schema
+            .accounts
+            .as_ref()
+            .map(|accounts| accounts.as_slice())
+            .unwrap_or_default();
+        let names = layouts
+            .iter()
+            .map(|layout| format!("\"{}\"", &layout.name))
+            .collect::<Vec<String>>();
+        let variants = layouts
+            .iter()
+            .map(|layout| format!("{}({})", &layout.name, &layout.inner_type))
+            .collect::<Vec<String>>();
+        let unpacks = layouts
+            .iter()
+            .map(|layout| self.expand_account_unpack(layout))
+            .collect::<Vec<String>>();
+        let _ = write!(
+            out,
+            r#"/// Names of the account layouts, the account states of the blocks are not decoded if there is none
+            pub const ACCOUNT_LAYOUTS: &[&str] = &[{names}];
+            #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+            pub enum AccountState {{
+                {variants}
+            }}
+            impl AccountState {{
+                /// Decode the data of an account owned by the program
+                pub fn unpack(data: &[u8]) -> Option<Self> {{
+                    {unpacks}
+                    None
+                }}
+            }}"#,
+            names = names.join(", "),
+            variants = variants.join(",\n"),
+            unpacks = unpacks.join("\n")
+        );
+        out
+    }
+    fn expand_account_unpack(&self, layout: &AccountLayout) -> String {
+        let mut conditions = Vec::default();
+        if let Some(size) = layout.size {
+            conditions.push(format!("data.len() == {}", size));
+        }
+        if let Some(discriminator) = &layout.discriminator {
+            let bytes = discriminator
+                .iter()
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>();
+            conditions.push(format!("data.starts_with(&[{}])", bytes.join(", ")));
+        }
+        if conditions.is_empty() {
+            conditions.push(String::from("true"));
+        }
+        format!(
+            r#"if {conditions} {{
+                if let Some(mut input) = data.get({offset}..) {{
+                    if let Some(state) = {inner_type}::unpack_from(&mut input) {{
+                        return Some(AccountState::{name}(state));
+                    }}
+                }}
+            }}"#,
+            conditions = conditions.join(" && "),
+            offset = layout.offset.unwrap_or_default(),
+            inner_type = &layout.inner_type,
+            name = &layout.name
+        )
+    }
+}
@@ -0,0 +1,73 @@
+use crate::generator::Generator;
+use crate::schema::{EventLayout, Schema};
+use std::fmt::Write;
+
+const MODULES: &str = r#"
+use crate::generated::instruction::*;
+use serde::{Deserialize, Serialize};
+"#;
+
+impl<'a> Generator<'a> {
+    pub fn generate_event(&self, schema: &Schema) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", MODULES);
+        let layouts = schema
+            .events
+            .as_ref()
+            .map(|events| events.as_slice())
+            .unwrap_or_default();
+        let variants = layouts
+            .iter()
+            .map(|layout| format!("{}({})", &layout.name, &layout.inner_type))
+            .collect::<Vec<String>>();
+        let names = layouts
+            .iter()
+            .map(|layout| format!("Event::{}(_) => \"{}\"", &layout.name, &layout.name))
+            .collect::<Vec<String>>();
+        let unpacks = layouts
+            .iter()
+            .map(|layout| self.expand_event_unpack(layout))
+            .collect::<Vec<String>>();
+        let _ = write!(
+            out,
+            r#"#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+            pub enum Event {{
+                {variants}
+            }}
+            impl Event {{
+                /// Decode the payload of an event logged by the program
+                pub fn unpack(data: &[u8]) -> Option<Self> {{
+                    {unpacks}
+                    None
+                }}
+                pub fn name(&self) -> &'static str {{
+                    match *self {{
+                        {names}
+                    }}
+                }}
+            }}"#,
+            variants = variants.join(",\n"),
+            names = names.join(",\n"),
+            unpacks = unpacks.join("\n")
+        );
+        out
+    }
+    fn expand_event_unpack(&self, layout: &EventLayout) -> String {
+        let bytes = layout
+            .discriminator
+            .iter()
+            .map(|byte| byte.to_string())
+            .collect::<Vec<String>>();
+        format!(
+            r#"if data.starts_with(&[{bytes}]) {{
+                if let Some(event) = {inner_type}::unpack(&data[{offset}..]) {{
+                    return Some(Event::{name}(event));
+                }}
+            }}"#,
+            bytes = bytes.join(", "),
+            offset = layout.discriminator.len(),
+            inner_type = &layout.inner_type,
+            name = &layout.name
+        )
+    }
+}
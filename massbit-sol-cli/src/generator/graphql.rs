@@ -1,5 +1,6 @@
 use crate::generator::Generator;
-use crate::schema::{Schema, Variant};
+use crate::schema::{AccountLayout, EventLayout, Schema, Variant};
+use inflector::Inflector;
 use lazy_static::lazy_static;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write;
@@ -92,8 +93,179 @@ impl<'a> Generator<'a> {
                 // }
             }
         }
+        if let Some(accounts) = &schema.accounts {
+            for layout in accounts {
+                let account_entity = self.generate_account_entity(layout, &self.definitions);
+                let _ = writeln!(out, "{}", &account_entity);
+            }
+        }
+        if let Some(events) = &schema.events {
+            for layout in events {
+                let event_entity = self.generate_event_entity(layout, &self.definitions);
+                let _ = writeln!(out, "{}", &event_entity);
+            }
+        }
+        for (entity_name, references) in self.get_relation_references(schema) {
+            let _ = writeln!(
+                out,
+                "{}",
+                self.generate_relation_entity(&entity_name, &references)
+            );
+        }
         out
     }
+    /// Entity referenced by an instruction account, from the `relations` of the config:
+    /// `"relations": {"market": "Market"}`. The instruction, account and event entities can not be referenced.
+    pub fn get_relation(&self, account_name: &str) -> Option<&str> {
+        let entity_name = self
+            .config
+            .as_ref()?
+            .get("relations")?
+            .get(account_name)?
+            .as_str()?;
+        if self.is_reserved_entity(entity_name) {
+            None
+        } else {
+            Some(entity_name)
+        }
+    }
+    fn is_reserved_entity(&self, entity_name: &str) -> bool {
+        self.schema.as_ref().map_or(false, |schema| {
+            schema
+                .variants
+                .iter()
+                .flatten()
+                .any(|variant| variant.name == entity_name)
+                || schema
+                    .accounts
+                    .iter()
+                    .flatten()
+                    .any(|layout| layout.name == entity_name)
+                || schema
+                    .events
+                    .iter()
+                    .flatten()
+                    .any(|layout| layout.name == entity_name)
+        })
+    }
+    /// Referencing instructions and accounts of each entity of the relations
+    fn get_relation_references(&self, schema: &Schema) -> BTreeMap<String, Vec<(String, String)>> {
+        let mut references: BTreeMap<String, Vec<(String, String)>> = BTreeMap::default();
+        if let Some(relations) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.get("relations"))
+            .and_then(|relations| relations.as_object())
+        {
+            for (account_name, entity_name) in relations {
+                match entity_name.as_str() {
+                    Some(entity_name) if self.is_reserved_entity(entity_name) => log::warn!(
+                        "Relation of account `{}` to the instruction, account or event entity `{}` is ignored",
+                        account_name, entity_name
+                    ),
+                    Some(entity_name) => {
+                        references.entry(entity_name.to_string()).or_default();
+                    }
+                    None => log::warn!(
+                        "Relation of account `{}` must be an entity name",
+                        account_name
+                    ),
+                }
+            }
+        }
+        for variant in schema.variants.iter().flatten() {
+            for account in variant.accounts.iter().flatten() {
+                if let Some(entity_name) = self.get_relation(&account.name) {
+                    references
+                        .entry(entity_name.to_string())
+                        .or_default()
+                        .push((variant.name.clone(), account.name.clone()));
+                }
+            }
+        }
+        references
+    }
+    /// Entity of the accounts referenced by the instructions, its id is the account pubkey
+    fn generate_relation_entity(
+        &self,
+        entity_name: &str,
+        references: &Vec<(String, String)>,
+    ) -> String {
+        let mut entity_properties: Vec<String> = vec![String::from("id: ID!")];
+        for (variant_name, account_name) in references {
+            entity_properties.push(format!(
+                "\t{}_by_{}: [{}!]! @derivedFrom(field: \"{}\")",
+                variant_name.to_snake_case().to_plural(),
+                account_name,
+                variant_name,
+                account_name
+            ));
+        }
+        format!(
+            r#"type {} @entity {{
+    {entity_properties}
+}}"#,
+            entity_name,
+            entity_properties = entity_properties.join(",\n")
+        )
+    }
+    fn generate_account_entity(
+        &self,
+        layout: &AccountLayout,
+        definitions: &BTreeMap<String, Schema>,
+    ) -> String {
+        let mut entity_properties: Vec<String> = vec![
+            String::from("id: ID!"),
+            String::from("\tpubkey: String"),
+            String::from("\tblock_slot: BigInt"),
+        ];
+        if let Some(def) = definitions.get(layout.inner_type.as_str()) {
+            self.expand_entity_properties(&mut entity_properties, def);
+        }
+        format!(
+            r#"type {} @entity {{
+    {entity_properties}
+}}"#,
+            &layout.name,
+            entity_properties = entity_properties.join(",\n")
+        )
+    }
+    fn generate_event_entity(
+        &self,
+        layout: &EventLayout,
+        definitions: &BTreeMap<String, Schema>,
+    ) -> String {
+        let mut entity_properties: Vec<String> = vec![
+            String::from("id: ID!"),
+            String::from("\tblock_slot: BigInt"),
+            String::from("\ttx_index: BigInt"),
+            String::from("\tlog_index: BigInt"),
+        ];
+        if let Some(def) = definitions.get(layout.inner_type.as_str()) {
+            self.expand_entity_properties(&mut entity_properties, def);
+        }
+        format!(
+            r#"type {} @entity {{
+    {entity_properties}
+}}"#,
+            &layout.name,
+            entity_properties = entity_properties.join(",\n")
+        )
+    }
+    fn expand_entity_properties(&self, entity_properties: &mut Vec<String>, def: &Schema) {
+        if let Some(properties) = &def.properties {
+            for property in properties {
+                let db_type = MAPPING_RUST_TYPES_TO_DB
+                    .get(property.data_type.as_str())
+                    .unwrap_or(&*DEFAULT_TYPE_DB);
+                if property.array_length.is_some() {
+                    entity_properties.push(format!("\t{}: [{}]", &property.name, db_type));
+                } else {
+                    entity_properties.push(format!("\t{}: {}", &property.name, db_type));
+                }
+            }
+        }
+    }
     fn generate_variant_entity(
         &self,
         variant: &Variant,
@@ -103,23 +275,13 @@ impl<'a> Generator<'a> {
         //Account assigment
         if let Some(accounts) = &variant.accounts {
             for account in accounts {
-                entity_properties.push(format!("\t{}: String", account.name));
+                let account_type = self.get_relation(&account.name).unwrap_or("String");
+                entity_properties.push(format!("\t{}: {}", account.name, account_type));
             }
         }
         if let Some(inner_type) = &variant.inner_type {
             if let Some(def) = definitions.get(inner_type.as_str()) {
-                if let Some(properties) = &def.properties {
-                    for property in properties {
-                        let db_type = MAPPING_RUST_TYPES_TO_DB
-                            .get(property.data_type.as_str())
-                            .unwrap_or(&*DEFAULT_TYPE_DB);
-                        if property.array_length.is_some() {
-                            entity_properties.push(format!("\t{}: [{}]", &property.name, db_type));
-                        } else {
-                            entity_properties.push(format!("\t{}: {}", &property.name, db_type));
-                        }
-                    }
-                }
+                self.expand_entity_properties(&mut entity_properties, def);
             } else if let Some(db_type) = MAPPING_RUST_TYPES_TO_DB.get(inner_type.as_str()) {
                 //Inner type is primitive. Store is as an value field
                 entity_properties.push(format!("\tvalue: {}", db_type));
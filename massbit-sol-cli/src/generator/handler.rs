@@ -1,13 +1,17 @@
 use crate::generator::graphql::MAPPING_RUST_TYPES_TO_DB;
 use crate::generator::Generator;
-use crate::schema::{Schema, Variant, VariantArray};
+use crate::schema::{AccountLayout, EventLayout, Schema, Variant, VariantArray};
 use inflector::Inflector;
 use std::fmt::Write;
 
 const MODULES: &str = r#"
+use crate::generated::account::AccountState;
+use crate::generated::event::Event;
 use crate::generated::instruction::*;
+use crate::mapping::InstructionContext;
 use crate::STORE;
 use massbit_solana_sdk::entity::{Attribute, Entity, Value};
+use massbit_solana_sdk::event::ProgramEvent;
 use massbit_solana_sdk::types::SolanaBlock;
 use serde_json;
 use solana_program::pubkey::Pubkey;
@@ -29,6 +33,17 @@ impl EntityExt for Entity {
         }
     }
 }
+/// Saves the entity referenced by an account with the pubkey as id if it is not stored yet
+fn save_reference(entity_name: &str, id: String) {
+    unsafe {
+        let store = STORE.as_mut().unwrap();
+        if store.get(String::from(entity_name), &id).is_none() {
+            let mut map: HashMap<Attribute, Value> = HashMap::default();
+            map.insert("id".to_string(), Value::from(id));
+            store.save(String::from(entity_name), Entity::from(map));
+        }
+    }
+}
 "#;
 
 impl<'a> Generator<'a> {
@@ -41,6 +56,8 @@ impl<'a> Generator<'a> {
             let patterns = self.expand_handler_patterns(&name, schema.variants.as_ref().unwrap());
             let handler_functions =
                 self.expand_handler_functions(schema.variants.as_ref().unwrap());
+            let account_handlers = self.expand_account_handlers(schema);
+            let event_handlers = self.expand_event_handlers(schema);
             let _ = write!(
                 &mut out,
                 r#"pub struct Handler {{}}
@@ -52,8 +69,9 @@ impl<'a> Generator<'a> {
                             program_id: &Pubkey, 
                             accounts: &Vec<Pubkey>, 
                             input: &[u8],
+                            context: &InstructionContext,
                         ) {{
-                            println!("Process block {{}} with input {{:?}}", block.block_number, input);
+                            println!("Process block {{}} with input {{:?}} at {{:?}}", block.block_number, input, context);
                             if let Some(instruction) = {name}::unpack(input) {{
                                 match instruction {{
                                     {patterns}
@@ -61,10 +79,14 @@ impl<'a> Generator<'a> {
                             }}
                         }}
                         {handler_functions}
+                        {account_handlers}
+                        {event_handlers}
                     }}"#,
                 name = name,
                 patterns = patterns.join(",\n"),
-                handler_functions = handler_functions.join("\n")
+                handler_functions = handler_functions.join("\n"),
+                account_handlers = account_handlers,
+                event_handlers = event_handlers
             );
         }
         out
@@ -84,7 +106,7 @@ impl<'a> Generator<'a> {
                 };
                 format!(
                     r#"{enum_name}::{var_name}{var_inner} => {{
-                        self.{method_name}(block,transaction,program_id, accounts{arg});
+                        self.{method_name}(block,transaction,program_id, accounts, context{arg});
                     }}"#,
                     enum_name = enum_name,
                     var_name = &variant.name,
@@ -117,6 +139,7 @@ impl<'a> Generator<'a> {
                                 transaction: &TransactionWithStatusMeta,
                                 program_id: &Pubkey,
                                 accounts: &Vec<Pubkey>,
+                                context: &InstructionContext,
                                 {inner_arg}
                             ) -> Result<(), anyhow::Error> {{
                                 {log}
@@ -130,6 +153,153 @@ impl<'a> Generator<'a> {
             })
             .collect::<Vec<String>>()
     }
+    /// Handlers of the decoded state of the accounts owned by the program,
+    /// each state is saved with the account and the slot of the block which updated it
+    pub fn expand_account_handlers(&self, schema: &Schema) -> String {
+        let layouts = schema
+            .accounts
+            .as_ref()
+            .map(|accounts| accounts.as_slice())
+            .unwrap_or_default();
+        let patterns = layouts
+            .iter()
+            .map(|layout| {
+                format!(
+                    r#"AccountState::{name}(state) => {{
+                        self.process_{function_name}_update(block, pubkey, state)
+                    }}"#,
+                    name = &layout.name,
+                    function_name = &layout.name.to_snake_case()
+                )
+            })
+            .collect::<Vec<String>>();
+        let functions = layouts
+            .iter()
+            .map(|layout| self.expand_account_function(layout))
+            .collect::<Vec<String>>();
+        format!(
+            r#"pub fn process_account(&self, block: &SolanaBlock, pubkey: &Pubkey, data: &[u8]) {{
+                if let Some(account_state) = AccountState::unpack(data) {{
+                    if let Err(err) = self.handle_account_update(block, pubkey, account_state) {{
+                        println!("Error while handling update of account {{}}: {{:?}}", pubkey, err);
+                    }}
+                }}
+            }}
+            pub fn handle_account_update(
+                &self,
+                block: &SolanaBlock,
+                pubkey: &Pubkey,
+                account_state: AccountState,
+            ) -> Result<(), anyhow::Error> {{
+                match account_state {{
+                    {patterns}
+                }}
+            }}
+            {functions}"#,
+            patterns = patterns.join(",\n"),
+            functions = functions.join("\n")
+        )
+    }
+    fn expand_account_function(&self, layout: &AccountLayout) -> String {
+        let mut assignments: Vec<String> = Vec::default();
+        if let Some(inner_schema) = self.definitions.get(layout.inner_type.as_str()) {
+            self.expand_entity_assignment(&mut assignments, inner_schema);
+        }
+        format!(
+            r#"pub fn process_{function_name}_update(
+                &self,
+                block: &SolanaBlock,
+                pubkey: &Pubkey,
+                arg: {inner_type},
+            ) -> Result<(), anyhow::Error> {{
+                let mut map : HashMap<Attribute, Value> = HashMap::default();
+                map.insert("id".to_string(), Value::from(Uuid::new_v4().to_simple().to_string()));
+                map.insert("pubkey".to_string(), Value::from(pubkey.to_string()));
+                map.insert("block_slot".to_string(), Value::from(block.block_number));
+                {assignments}
+                Entity::from(map).save("{entity_name}");
+                Ok(())
+            }}"#,
+            function_name = &layout.name.to_snake_case(),
+            inner_type = &layout.inner_type,
+            assignments = assignments.join("\n"),
+            entity_name = &layout.name
+        )
+    }
+    /// Handlers of the events logged by the program, each event is saved with the slot of its block
+    /// and its position in the logs of the block
+    pub fn expand_event_handlers(&self, schema: &Schema) -> String {
+        let layouts = schema
+            .events
+            .as_ref()
+            .map(|events| events.as_slice())
+            .unwrap_or_default();
+        let patterns = layouts
+            .iter()
+            .map(|layout| {
+                format!(
+                    r#"Event::{name}(arg) => {{
+                        self.process_{function_name}_event(block, event, arg)
+                    }}"#,
+                    name = &layout.name,
+                    function_name = &layout.name.to_snake_case()
+                )
+            })
+            .collect::<Vec<String>>();
+        let functions = layouts
+            .iter()
+            .map(|layout| self.expand_event_function(layout))
+            .collect::<Vec<String>>();
+        format!(
+            r#"pub fn process_event(&self, block: &SolanaBlock, event: &ProgramEvent) {{
+                if let Some(program_event) = Event::unpack(event.payload.as_slice()) {{
+                    if let Err(err) = self.handle_event(block, event, program_event) {{
+                        println!("Error while handling event {{}} of transaction {{}}: {{:?}}", event.log_index, event.tx_index, err);
+                    }}
+                }}
+            }}
+            pub fn handle_event(
+                &self,
+                block: &SolanaBlock,
+                event: &ProgramEvent,
+                program_event: Event,
+            ) -> Result<(), anyhow::Error> {{
+                match program_event {{
+                    {patterns}
+                }}
+            }}
+            {functions}"#,
+            patterns = patterns.join(",\n"),
+            functions = functions.join("\n")
+        )
+    }
+    fn expand_event_function(&self, layout: &EventLayout) -> String {
+        let mut assignments: Vec<String> = Vec::default();
+        if let Some(inner_schema) = self.definitions.get(layout.inner_type.as_str()) {
+            self.expand_entity_assignment(&mut assignments, inner_schema);
+        }
+        format!(
+            r#"pub fn process_{function_name}_event(
+                &self,
+                block: &SolanaBlock,
+                event: &ProgramEvent,
+                arg: {inner_type},
+            ) -> Result<(), anyhow::Error> {{
+                let mut map : HashMap<Attribute, Value> = HashMap::default();
+                map.insert("id".to_string(), Value::from(Uuid::new_v4().to_simple().to_string()));
+                map.insert("block_slot".to_string(), Value::from(block.block_number));
+                map.insert("tx_index".to_string(), Value::from(event.tx_index as u64));
+                map.insert("log_index".to_string(), Value::from(event.log_index as u64));
+                {assignments}
+                Entity::from(map).save("{entity_name}");
+                Ok(())
+            }}"#,
+            function_name = &layout.name.to_snake_case(),
+            inner_type = &layout.inner_type,
+            assignments = assignments.join("\n"),
+            entity_name = &layout.name
+        )
+    }
     pub fn expand_function_body(&self, variant: &Variant) -> String {
         let mut assignments: Vec<String> = Vec::default();
         //Account assigment
@@ -142,6 +312,14 @@ impl<'a> Generator<'a> {
                             .unwrap_or_default()));"#,
                     account.name, account.index
                 ));
+                if let Some(entity_name) = self.get_relation(&account.name) {
+                    assignments.push(format!(
+                        r#"if let Some(pubkey) = accounts.get({}) {{
+                            save_reference("{}", pubkey.to_string());
+                        }}"#,
+                        account.index, entity_name
+                    ));
+                }
             }
         }
         // Write table if there is inner_type
@@ -182,6 +360,21 @@ impl<'a> Generator<'a> {
         //If inner schema is a struct
         if let Some(properties) = &inner_schema.properties {
             for property in properties {
+                //Public keys are stored as their base58 string
+                if property.data_type == "Pubkey" {
+                    let property_value = match property.array_length {
+                        Some(_) => format!(
+                            "arg.{}.iter().map(|pubkey| Value::from(pubkey.to_string())).collect::<Vec<Value>>()",
+                            &property.name
+                        ),
+                        None => format!("arg.{}.to_string()", &property.name),
+                    };
+                    assignments.push(format!(
+                        r#"map.insert("{}".to_string(), Value::from({}));"#,
+                        &property.name, &property_value
+                    ));
+                    continue;
+                }
                 let db_type = MAPPING_RUST_TYPES_TO_DB.get(property.data_type.as_str());
                 //.unwrap_or(&*DEFAULT_TYPE_DB);
                 // If data_type is not primitive (e.g. Enum, Struct)
@@ -196,7 +389,7 @@ impl<'a> Generator<'a> {
                         let property_value = match property.array_length {
                             Some(_) => {
                                 format!(
-                                    r#"arg.{property_name}.iter().map(|&{property_name}| Value::from({elm_value})).collect::<Vec<Value>>()"#,
+                                    r#"arg.{property_name}.into_iter().map(|{property_name}| Value::from({elm_value})).collect::<Vec<Value>>()"#,
                                     property_name = &property.name,
                                     elm_value = elm_value
                                 )
@@ -3,6 +3,7 @@ pub mod generated;
 pub mod mapping;
 
 use massbit_solana_sdk::{
+    event::ProgramEvent,
     export_plugin,
     plugin::{handler::SolanaHandler, PluginRegistrar},
     store::IndexStore,
@@ -41,6 +42,7 @@ impl SolanaHandler for SolanaHandlerAdapter {
         // Todo: Rewrite the flush so it will flush after finish the array of blocks for better performance. For now, we flush after each block.
         for block in blocks {
             mapping::handle_block(block);
+            self.handle_events(block, &mapping::program_events(block))?;
             block_slot = block_slot.max(block.block_number as i64);
             unsafe {
                 if let Some(store) = &mut STORE {
@@ -50,5 +52,12 @@ impl SolanaHandler for SolanaHandlerAdapter {
         }
         Ok(block_slot)
     }
+    fn handle_events(
+        &self,
+        block: &SolanaBlock,
+        events: &[ProgramEvent],
+    ) -> Result<(), Box<dyn Error>> {
+        mapping::handle_events(block, events)
+    }
 }
 "#;
@@ -1,8 +1,14 @@
 pub const INDEXER_MAPPING: &str = r#"
+use crate::generated::account::ACCOUNT_LAYOUTS;
+use crate::generated::event::Event;
 use crate::generated::handler::Handler;
 use crate::ADDRESS;
+use massbit_solana_sdk::event::{program_payloads, ProgramEvent};
 use massbit_solana_sdk::types::SolanaBlock;
+use solana_program::instruction::CompiledInstruction;
+use solana_program::pubkey::Pubkey;
 use solana_transaction_status::TransactionWithStatusMeta;
+use std::str::FromStr;
 
 pub fn handle_block(block: &SolanaBlock) -> Result<(), Box<dyn std::error::Error>> {
     for (tx_ind, tran) in block.block.transactions.iter().enumerate() {
@@ -16,44 +22,126 @@ pub fn handle_block(block: &SolanaBlock) -> Result<(), Box<dyn std::error::Error
             parse_instructions(block, tran, tx_ind);
         }
     }
+    if !ACCOUNT_LAYOUTS.is_empty() {
+        update_accounts(block);
+    }
     Ok(())
 }
+/// Decode the accounts of the program changed in the block and call the account handlers.
+/// The block carries the state of each account after its slot, blocks which are not streamed
+/// at the head of the chain (history blocks, blocks read from the RPC node) carry none
+fn update_accounts(block: &SolanaBlock) {
+    let handler = Handler {};
+    for account in block.accounts.iter() {
+        if account.owner.to_string().as_str() == ADDRESS {
+            handler.process_account(block, &account.pubkey, account.data.as_slice());
+        }
+    }
+}
+/// The events of the program logged by the successful transactions of the block.
+/// Their `data` is left empty, the handler unpacks their payload into the generated types
+pub fn program_events(block: &SolanaBlock) -> Vec<ProgramEvent> {
+    let program_id = match Pubkey::from_str(ADDRESS) {
+        Ok(program_id) => program_id,
+        Err(_) => return Vec::default(),
+    };
+    let mut events = Vec::default();
+    for (tx_index, tran) in block.block.transactions.iter().enumerate() {
+        let log_messages = match &tran.meta {
+            Some(meta) if meta.status.is_ok() => match &meta.log_messages {
+                Some(log_messages) => log_messages,
+                None => continue,
+            },
+            _ => continue,
+        };
+        for (log_index, payload) in program_payloads(&program_id, log_messages) {
+            if let Some(event) = Event::unpack(payload.as_slice()) {
+                events.push(ProgramEvent {
+                    program_id: String::from(ADDRESS),
+                    name: String::from(event.name()),
+                    tx_index,
+                    log_index,
+                    data: serde_json::Value::Null,
+                    payload,
+                });
+            }
+        }
+    }
+    events
+}
+pub fn handle_events(
+    block: &SolanaBlock,
+    events: &[ProgramEvent],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let handler = Handler {};
+    for event in events {
+        handler.process_event(block, event);
+    }
+    Ok(())
+}
+/// Position of a dispatched instruction in its transaction
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionContext {
+    pub tx_index: usize,
+    /// Index of the top level instruction, for an inner instruction the one
+    /// whose execution invoked it
+    pub parent_index: usize,
+    /// Index among the inner instructions of the parent, `None` at top level.
+    /// The node reports the inner instructions of a parent as one flat list,
+    /// so how deeply an inner instruction is nested is not known
+    pub inner_index: Option<usize>,
+}
+/// Dispatch the instructions of the program
 fn parse_instructions(block: &SolanaBlock, tran: &TransactionWithStatusMeta, tx_ind: usize) {
     for (ind, inst) in tran.transaction.message.instructions.iter().enumerate() {
-        let program_key = inst.program_id(tran.transaction.message.account_keys.as_slice());
-        if program_key.to_string().as_str() == ADDRESS {
-            let mut accounts = Vec::default();
-            let mut work = |unique_ind: usize, acc_ind: usize| {
-                if let Some(key) = tran.transaction.message.account_keys.get(acc_ind) {
-                    accounts.push(key.clone());
-                };
-                Ok(())
+        let context = InstructionContext {
+            tx_index: tx_ind,
+            parent_index: ind,
+            inner_index: None,
+        };
+        parse_instruction(block, tran, inst, &context);
+    }
+    let inner_instructions = tran
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.inner_instructions.as_ref());
+    for inner in inner_instructions.into_iter().flatten() {
+        for (inner_ind, inst) in inner.instructions.iter().enumerate() {
+            let context = InstructionContext {
+                tx_index: tx_ind,
+                parent_index: inner.index as usize,
+                inner_index: Some(inner_ind),
             };
-            inst.visit_each_account(&mut work);
-            // if let Some(account_infos) = SOLANA_CLIENT
-            //     .get_multiple_accounts_with_config(
-            //         accounts.as_slice(),
-            //         RpcAccountInfoConfig {
-            //             encoding: Some(UiAccountEncoding::JsonParsed),
-            //             commitment: None,
-            //             data_slice: None,
-            //         },
-            //     )
-            //     .map(|res| {
-            //         res.value
-            //             .into_iter()
-            //             .filter_map(|elm| elm)
-            //             .collect::<Vec<Account>>()
-            //     })
-            //     .ok()
-            // {
-            //println!("account_infos {:?}", &account_infos);
-            let handler = Handler {};
-            // Fixme: Get account_infos from chain take a lot of time. For now, use empty vector.
-            handler.process(block, tran, program_key, &accounts, inst.data.as_slice());
-            // }
+            parse_instruction(block, tran, inst, &context);
         }
     }
 }
+fn parse_instruction(
+    block: &SolanaBlock,
+    tran: &TransactionWithStatusMeta,
+    inst: &CompiledInstruction,
+    context: &InstructionContext,
+) {
+    let program_key = inst.program_id(tran.transaction.message.account_keys.as_slice());
+    if program_key.to_string().as_str() == ADDRESS {
+        let mut accounts = Vec::default();
+        let mut work = |_unique_ind: usize, acc_ind: usize| {
+            if let Some(key) = tran.transaction.message.account_keys.get(acc_ind) {
+                accounts.push(key.clone());
+            };
+            Ok(())
+        };
+        inst.visit_each_account(&mut work);
+        let handler = Handler {};
+        handler.process(
+            block,
+            tran,
+            program_key,
+            &accounts,
+            inst.data.as_slice(),
+            context,
+        );
+    }
+}
 
 "#;
@@ -1,3 +1,6 @@
-pub const INDEXER_MOD: &str = r#"pub mod handler;
+pub const INDEXER_MOD: &str = r#"pub mod account;
+                            pub mod event;
+                            pub mod handler;
                             pub mod instruction;
+                            pub mod model;
                             "#;
@@ -59,6 +59,11 @@ package = "solana-program"
 git = "https://github.com/massbitprotocol/solana.git"
 branch = "massbit"
 
+[dev-dependencies.massbit-solana-test-harness]
+package = "massbit-solana-test-harness"
+git = "https://github.com/massbitprotocol/massbitprotocol.git"
+branch = "main"
+
 [lib]
 crate-type = ["cdylib"]
 
@@ -1,18 +1,68 @@
 use crate::generator::helper::is_integer_type;
 use crate::generator::Generator;
-use crate::schema::{Property, PropertyArray, Schema, Variant, VariantArray};
+use crate::schema::{DataType, Property, PropertyArray, Schema, Variant, VariantArray};
 use std::fmt::Write;
 
 const MODULES: &str = r#"
-use arrayref::{array_ref, array_refs};
+use arrayref::array_ref;
 use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
 use std::num::*;
 "#;
+/// Helpers used by the generated unpack functions.
+/// The input is a cursor: every helper consumes the bytes it reads.
+const UNPACK_HELPERS: &str = r#"
+/// Split the first `len` bytes from the input
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Some(head)
+}
+pub fn unpack_len(input: &mut &[u8]) -> Option<usize> {
+    take(input, 4).map(|bytes| u32::from_le_bytes(*array_ref![bytes, 0, 4]) as usize)
+}
+pub fn unpack_string(input: &mut &[u8]) -> Option<String> {
+    let len = unpack_len(input)?;
+    take(input, len).and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+}
+pub fn unpack_array<T>(
+    input: &mut &[u8],
+    len: usize,
+    unpack_elm: impl Fn(&mut &[u8]) -> Option<T>,
+) -> Option<Vec<T>> {
+    let mut values = Vec::with_capacity(len.min(input.len()));
+    for _ in 0..len {
+        values.push(unpack_elm(input)?);
+    }
+    Some(values)
+}
+pub fn unpack_vec<T>(
+    input: &mut &[u8],
+    unpack_elm: impl Fn(&mut &[u8]) -> Option<T>,
+) -> Option<Vec<T>> {
+    let len = unpack_len(input)?;
+    unpack_array(input, len, unpack_elm)
+}
+pub fn unpack_option<T>(
+    input: &mut &[u8],
+    unpack_value: impl Fn(&mut &[u8]) -> Option<T>,
+) -> Option<Option<T>> {
+    match take(input, 1)?[0] {
+        0 => Some(None),
+        1 => unpack_value(input).map(Some),
+        _ => None,
+    }
+}
+"#;
 impl<'a> Generator<'a> {
     pub fn generate_instruction(&self, schema: &Schema) -> String {
         let mut out = String::new();
         //Import modules for instruction
         let _ = writeln!(out, "{}", MODULES);
+        let _ = writeln!(out, "{}", UNPACK_HELPERS);
         //Expand definitions
         self.definitions.iter().for_each(|(name, def)| {
             self.expand_schema(&mut out, name, def);
@@ -80,86 +130,53 @@ impl<'a> Generator<'a> {
             .join(",\n")
     }
     pub fn expand_struct_unpack(&self, name: &String, schema: &Schema) -> String {
-        let struct_size = schema.get_size(&self.definitions);
-        let mut ref_names: Vec<String> = Vec::default();
-        let mut lengths: Vec<String> = Vec::default();
-        //Store optional assigments
-        let mut property_assignments: Vec<String> = Vec::default();
-        //Collect is_some() condition
-        let mut optional_conditions: Vec<String> = Vec::default();
-        //Struct fields
-        let mut properties: Vec<String> = Vec::default();
-        for property in schema.properties.as_ref().unwrap() {
-            ref_names.push(format!("{}", &property.name));
-            lengths.push(format!("{}", property.get_size(&self.definitions)));
-            //Expand struct field's data type.
-            //Use unpack for user defined type other use try_from_primitive
-            let field_value = self.expand_property_unpack(property);
-            if self.is_option_value(property) {
-                property_assignments.push(format!("let {} = {};", &property.name, &field_value));
-                optional_conditions.push(format!("{}.is_some()", &property.name));
-                properties.push(format!("{name}:{name}.unwrap()", name = &property.name));
-            } else {
-                properties.push(format!("{}: {}", &property.name, &field_value));
-            }
-        }
-        let str_size = if let Some(val) = struct_size {
-            format!("; {}", val)
-        } else {
-            String::from("")
-        };
-        let optional_flow = if optional_conditions.len() > 0 {
-            (
-                format!("if {} {{", optional_conditions.join("&&")),
-                String::from(
-                    r#"
-                } else {
-                    None
-                }"#,
-                ),
-            )
-        } else {
-            (String::default(), String::default())
-        };
+        let properties = schema
+            .properties
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|property| {
+                format!(
+                    "{}: {}?",
+                    &property.name,
+                    self.expand_property_unpack(property)
+                )
+            })
+            .collect::<Vec<String>>();
+        //Struct fields are evaluated in order, each one moves the cursor
         format!(
-            r#"pub fn unpack(input: &[u8{size}]) -> Option<Self> {{
-                let ({ref_names}) = array_refs![input, {lengths}];
-                {assignments}
-                {optional_if}
+            r#"pub fn unpack(input: &[u8]) -> Option<Self> {{
+                let mut input = input;
+                Self::unpack_from(&mut input)
+            }}
+            pub fn unpack_from(input: &mut &[u8]) -> Option<Self> {{
                 Some({name} {{
                     {properties}
                 }})
-                {optional_else}
             }}"#,
-            size = str_size,
-            ref_names = ref_names.join(","),
-            lengths = lengths.join(","),
-            assignments = property_assignments.join("\n"),
             name = name,
             properties = properties.join(",\n"),
-            optional_if = &optional_flow.0,
-            optional_else = &optional_flow.1,
         )
     }
     pub fn expand_enum_unpack(&self, name: &String, schema: &Schema) -> String {
         let tag_len = schema.variant_tag_length.unwrap_or(1);
         let offset = schema.offset.unwrap_or_default();
         let separation = if offset > 0 {
-            format!(
-                "let (&[offset], &tag_slice, data) = array_refs![input, {}, {}; ..;];",
-                offset, tag_len
-            )
+            format!("take(input, {})?;", offset)
         } else {
-            format!(
-                "let (&tag_slice, data) = array_refs![input, {}; ..;];",
-                tag_len
-            )
+            String::default()
         };
-        let tag_val = match tag_len {
-            1 => "let tag_val = u8::from_le_bytes(tag_slice) as u32;".to_string(),
-            2 => "let tag_val = u16::from_le_bytes(tag_slice) as u32;".to_string(),
-            _ => "let tag_val = u32::from_le_bytes(tag_slice) as u32;".to_string(),
+        let tag_type = match tag_len {
+            1 => "u8",
+            2 => "u16",
+            //Anchor sighash discriminator
+            8 => "u64",
+            _ => "u32",
         };
+        let tag_val = format!(
+            "let tag_val = {}? as u64;",
+            self.expand_data_unpack(&DataType::Named(tag_type.to_string()))
+        );
         let mut variants = schema
             .variants
             .as_ref()
@@ -172,8 +189,12 @@ impl<'a> Generator<'a> {
         let match_frag = format!("match tag_val {{{}}}", variants.join(",\n"));
         format!(
             r#"pub fn unpack(input: &[u8]) -> Option<Self> {{
+                let mut input = input;
+                Self::unpack_from(&mut input)
+            }}
+            pub fn unpack_from(input: &mut &[u8]) -> Option<Self> {{
                 {separation}
-                {tag_val}            
+                {tag_val}
                 {match_frag}
             }}"#,
             separation = separation,
@@ -181,109 +202,209 @@ impl<'a> Generator<'a> {
             match_frag = match_frag
         )
     }
-    pub fn expand_data_unpack(&self, field_name: &str, data_type: &str) -> String {
-        if data_type.starts_with("NonZero") {
-            let inner_type = &data_type[7..data_type.len()].to_lowercase();
-            format!(
-                "{}::new({}::from_le_bytes(*{}))",
-                data_type, inner_type, &field_name
-            )
-        } else if is_integer_type(data_type) {
-            format!("{}::from_le_bytes(*{})", data_type, field_name)
-        } else {
-            format!("{}::unpack({})", data_type, field_name)
-        }
-    }
-    /// Return true if value of a property is optional
-    /// A property can be none when unpacking if
-    /// Property is not a vector, data_type is NonZero* or some user defined struct or enum
-    ///
-    pub fn is_option_value(&self, property: &Property) -> bool {
-        let data_type = property.data_type.as_str();
-        //Property is not vector
-        property.array_length.unwrap_or_default() == 0 && !is_integer_type(data_type)
-    }
-    pub fn expand_property_unpack(&self, property: &Property) -> String {
-        let data_type = property.data_type.as_str();
-        match property.array_length {
-            Some(val) => {
-                let total_size = property.length.unwrap_or_default();
-                if val > 0 && total_size > 0 {
-                    //Size of a single vector element
-                    let elm_size = total_size / val;
-                    if elm_size * val < total_size {
-                        panic!("Error in property {}. Total size {} is not multiples of array length {}", &property.name, total_size, val)
-                    } else {
-                        let mut sizes = vec![];
-                        let mut indexes = vec![];
-                        for i in 0..val {
-                            sizes.push(format!("{}", elm_size));
-                            indexes.push(
-                                self.expand_data_unpack(format!("arr.{}", i).as_str(), data_type),
-                            );
-                        }
-                        format!(
-                            r#"{{
-                            let arr = array_refs![owner, {sizes}];
-                            vec![{indexes}]
-                        }}"#,
-                            sizes = sizes.join(","),
-                            indexes = indexes.join(",")
-                        )
+    /// Expression which unpacks a value of `data_type` from the cursor `input`, as an `Option`.
+    /// Panics for floats and integers without a fixed size, Borsh has no layout for the latter
+    /// and floats are not supported.
+    pub fn expand_data_unpack(&self, data_type: &DataType) -> String {
+        match data_type {
+            DataType::Named(type_name) => {
+                let type_name = type_name.as_str();
+                let non_zero_inner = type_name
+                    .strip_prefix("NonZero")
+                    .map(|inner_type| inner_type.to_lowercase())
+                    .filter(|inner_type| is_integer_type(inner_type));
+                if non_zero_inner.is_some() || is_integer_type(type_name) {
+                    let size = data_type.get_size(&self.definitions).unwrap_or_else(|| {
+                        panic!("Unsupported data type {}, its size is unknown", type_name)
+                    });
+                    match non_zero_inner {
+                        Some(inner_type) => format!(
+                            "take(input, {size}).and_then(|bytes| {}::new({}::from_le_bytes(*array_ref![bytes, 0, {size}])))",
+                            type_name,
+                            inner_type,
+                            size = size
+                        ),
+                        None => format!(
+                            "take(input, {size}).map(|bytes| {}::from_le_bytes(*array_ref![bytes, 0, {size}]))",
+                            type_name,
+                            size = size
+                        ),
                     }
+                } else if type_name == "bool" {
+                    String::from("take(input, 1).map(|bytes| bytes[0] != 0)")
+                } else if type_name == "Pubkey" {
+                    String::from(
+                        "take(input, 32).map(|bytes| Pubkey::new_from_array(*array_ref![bytes, 0, 32]))",
+                    )
+                } else if type_name == "String" {
+                    String::from("unpack_string(input)")
+                } else if matches!(
+                    type_name,
+                    "f32" | "f64" | "usize" | "isize" | "char" | "NonZeroUsize" | "NonZeroIsize"
+                ) {
+                    panic!("Unsupported data type {}", type_name)
                 } else {
-                    String::from("Vec::default()")
+                    format!("{}::unpack_from(input)", type_name)
                 }
             }
-            None => self.expand_data_unpack(property.name.as_str(), data_type),
+            DataType::Vec(inner) => format!(
+                "unpack_vec(input, |input| {})",
+                self.expand_data_unpack(inner)
+            ),
+            DataType::Option(inner) => format!(
+                "unpack_option(input, |input| {})",
+                self.expand_data_unpack(inner)
+            ),
+            DataType::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| format!("{}?", self.expand_data_unpack(element)))
+                    .collect::<Vec<String>>();
+                let elements = if elements.len() == 1 {
+                    format!("{},", elements[0])
+                } else {
+                    elements.join(", ")
+                };
+                format!("(|| -> Option<{}> {{ Some(({})) }})()", data_type, elements)
+            }
+        }
+    }
+    fn parse_data_type(&self, name: &str, data_type: &str) -> DataType {
+        DataType::parse(data_type)
+            .unwrap_or_else(|| panic!("Error in {}. Unsupported data type {}", name, data_type))
+    }
+    /// A property with a length reads exactly `length` bytes from the input,
+    /// its value is unpacked from them.
+    pub fn expand_property_unpack(&self, property: &Property) -> String {
+        let data_type = self.parse_data_type(&property.name, &property.data_type);
+        let value = match property.array_length {
+            Some(val) if val > 0 => format!(
+                "unpack_array(input, {}, |input| {})",
+                val,
+                self.expand_data_unpack(&data_type)
+            ),
+            Some(_) => String::from("Some(Vec::default())"),
+            None => self.expand_data_unpack(&data_type),
+        };
+        match property.length {
+            Some(length) => format!(
+                "take(input, {}).and_then(|mut field| {{ let input = &mut field; {} }})",
+                length, value
+            ),
+            None => value,
         }
     }
     pub fn expand_variant_unpack(&self, name: &String, variant: &Variant) -> String {
         let var_tag = variant.variant_tag;
         match &variant.inner_type {
             Some(inner_type) => {
-                let inner_schema = self.definitions.get(inner_type);
-                let variant_size = inner_schema
-                    .and_then(|schema| schema.get_size(&self.definitions))
-                    .or(variant.get_size());
-                let (inner_value, field_slice) = match variant_size {
-                    None => (
-                        self.expand_data_unpack("data", inner_type.as_str()),
-                        String::default(),
-                    ),
-                    Some(size) => (
-                        self.expand_data_unpack("field_slice", inner_type.as_str()),
-                        format!("let field_slice = array_ref![data, 0, {}];", size),
-                    ),
-                };
-                if is_integer_type(inner_type.as_str()) {
-                    format!(
-                        r#"{var_tag} => {{
-                                        {field_slice}
-                                        Some({name}::{var_name}({inner_value}))
-                                    }}"#,
-                        var_tag = var_tag,
-                        field_slice = field_slice,
-                        name = name,
-                        var_name = &variant.name,
-                        inner_value = inner_value
-                    )
-                } else {
-                    format!(
-                        r#"{var_tag} => {{
-                                        {field_slice}
-                                        let inner = {inner_value};
-                                        inner.and_then(|inner_val| Some({name}::{var_name}(inner_val)))
-                                    }}"#,
-                        var_tag = var_tag,
-                        field_slice = field_slice,
-                        name = name,
-                        var_name = &variant.name,
-                        inner_value = inner_value
-                    )
-                }
+                let data_type = self.parse_data_type(&variant.name, inner_type);
+                format!(
+                    "{var_tag} => Some({name}::{var_name}({inner_value}?))",
+                    var_tag = var_tag,
+                    name = name,
+                    var_name = &variant.name,
+                    inner_value = self.expand_data_unpack(&data_type)
+                )
             }
             None => format!("{} => Some({}::{})", var_tag, name, &variant.name),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn generator() -> Generator<'static> {
+        Generator {
+            structure_path: "",
+            config_path: "",
+            output_dir: "",
+            schema: None,
+            config: None,
+            definitions: BTreeMap::default(),
+        }
+    }
+
+    fn unpack(data_type: &str) -> String {
+        generator().expand_data_unpack(&DataType::parse(data_type).unwrap())
+    }
+
+    fn property(name: &str, data_type: &str, length: Option<usize>) -> Property {
+        Property {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            length,
+            array_length: None,
+            required: true,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn unpacks_fixed_size_types_from_the_cursor() {
+        assert_eq!(
+            unpack("u64"),
+            "take(input, 8).map(|bytes| u64::from_le_bytes(*array_ref![bytes, 0, 8]))"
+        );
+        assert_eq!(
+            unpack("NonZeroU16"),
+            "take(input, 2).and_then(|bytes| NonZeroU16::new(u16::from_le_bytes(*array_ref![bytes, 0, 2])))"
+        );
+        assert_eq!(unpack("bool"), "take(input, 1).map(|bytes| bytes[0] != 0)");
+        assert_eq!(unpack("MarketState"), "MarketState::unpack_from(input)");
+    }
+
+    #[test]
+    fn unpacks_nested_types_from_the_same_cursor() {
+        assert_eq!(
+            unpack("Vec<Option<String>>"),
+            "unpack_vec(input, |input| unpack_option(input, |input| unpack_string(input)))"
+        );
+        assert_eq!(
+            unpack("(u8, String)"),
+            "(|| -> Option<(u8, String)> { Some((take(input, 1).map(|bytes| u8::from_le_bytes(*array_ref![bytes, 0, 1]))?, unpack_string(input)?)) })()"
+        );
+    }
+
+    #[test]
+    fn struct_fields_are_unpacked_in_order() {
+        let schema = Schema {
+            properties: Some(vec![
+                property("name", "String", Some(8)),
+                property("amount", "u32", None),
+            ]),
+            ..Default::default()
+        };
+        let unpack = generator().expand_struct_unpack(&String::from("Order"), &schema);
+        let name = unpack
+            .find("name: take(input, 8).and_then(|mut field| { let input = &mut field; unpack_string(input) })?")
+            .unwrap();
+        let amount = unpack
+            .find(
+                "amount: take(input, 4).map(|bytes| u32::from_le_bytes(*array_ref![bytes, 0, 4]))?",
+            )
+            .unwrap();
+        assert!(name < amount);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported data type f32")]
+    fn floats_are_rejected() {
+        unpack("Vec<f32>");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported data type f64")]
+    fn doubles_are_rejected() {
+        unpack("f64");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported data type usize")]
+    fn integers_without_fixed_size_are_rejected() {
+        unpack("Option<usize>");
+    }
+}
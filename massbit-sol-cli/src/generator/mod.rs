@@ -1,3 +1,5 @@
+pub mod account;
+pub mod event;
 pub mod graphql;
 pub mod handler;
 pub mod helper;
@@ -6,9 +8,10 @@ pub mod indexer_mapping;
 pub mod indexer_mod;
 pub mod indexer_setting;
 pub mod instruction;
-//pub mod model;
+pub mod model;
 
 use crate::generator::indexer_mod::INDEXER_MOD;
+use crate::parser::anchor_idl::Idl;
 use crate::schema::Schema;
 use handlebars::Handlebars;
 use indexer_lib::INDEXER_LIB;
@@ -54,6 +57,20 @@ impl<'a> Generator<'a> {
                 &data,
                 true,
             )?;
+            //Account decoder
+            let data = self.generate_account(schema);
+            self.write_to_file(
+                &format!("{}/{}", self.output_dir, "src/generated/account.rs"),
+                &data,
+                true,
+            )?;
+            //Event decoder
+            let data = self.generate_event(schema);
+            self.write_to_file(
+                &format!("{}/{}", self.output_dir, "src/generated/event.rs"),
+                &data,
+                true,
+            )?;
             //Instruction handler
             let data = self.generate_handler(schema);
             self.write_to_file(
@@ -61,13 +78,6 @@ impl<'a> Generator<'a> {
                 &data,
                 true,
             )?;
-            //Models
-            // let data = schema.gen_models();
-            // self.write_to_file(
-            //     &format!("{}/{}", self.output_dir, "src/models.rs"),
-            //     &data,
-            //     true,
-            // )?;
 
             //libs
             let lib_content = &Handlebars::new()
@@ -118,6 +128,15 @@ impl<'a> Generator<'a> {
                 &data,
                 false,
             )?;
+            //Models
+            match self.generate_models(&data) {
+                Ok(models) => self.write_to_file(
+                    &format!("{}/{}", self.output_dir, "src/generated/model.rs"),
+                    &models,
+                    true,
+                )?,
+                Err(err) => log::error!("Cannot generate models: {:?}", err),
+            }
             //Cargo toml
             self.write_to_file(
                 &format!("{}/{}", self.output_dir, "Cargo.toml"),
@@ -195,6 +214,18 @@ impl<'a> GeneratorBuilder<'a> {
         self.inner.schema = Some(schema);
         self
     }
+    /// Use an Anchor IDL instead of an instruction structure file
+    pub fn with_idl_path(mut self, path: &'a str) -> Self {
+        self.inner.structure_path = path;
+        let json = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Unable to read `{}`: {}", path, err));
+        let idl = Idl::from_json(&json)
+            .unwrap_or_else(|err| panic!("Cannot parse `{}` as Anchor IDL: {}", path, err));
+        let schema = idl.to_schema();
+        self.collect_definitions(&schema);
+        self.inner.schema = Some(schema);
+        self
+    }
     pub fn with_config_path(mut self, path: &'a str) -> Self {
         self.inner.config_path = path;
         let json = std::fs::read_to_string(path)
@@ -1,188 +1,353 @@
-use crate::generator::graphql::{
-    DEFAULT_TYPE_DB, MAPPING_DB_TYPES_TO_RUST, MAPPING_RUST_TYPES_TO_DB,
+use crate::generator::Generator;
+use graphql_parser::schema::{
+    Definition, Document, Field, ObjectType, Type, TypeDefinition, Value,
 };
-use crate::schema::Schema;
+use std::collections::HashSet;
 use std::fmt::Write;
 
-impl Schema {
-    pub fn gen_models(&self) -> String {
-        let mut out = String::new();
-        // println!("Schema: {:#?}", self);
-        writeln!(out, "{}", Schema::gen_model_file_header());
-
-        if let Some(instructions) = self.variants.clone() {
-            // List instruction
-            for instruction in instructions {
-                // Write table if there is inner_type
-                if let Some(inner_type) = instruction.inner_type {
-                    // Get definitions
-                    if let Some(sub_schema) = self.definitions.get(&inner_type) {
-                        // get a table corresponding to sub_schema
-                        let str_entity: String =
-                            Schema::gen_entity_struct(sub_schema, &inner_type, &instruction.name);
-                        writeln!(out, "{}", str_entity);
-                    } else if MAPPING_RUST_TYPES_TO_DB.contains_key(inner_type.as_str()) {
-                        let str_entity: String = Schema::gen_entity_struct(
-                            &Schema::default(),
-                            &inner_type,
-                            &instruction.name.clone(),
-                        );
-                        writeln!(out, "{}", str_entity);
-                    }
-                    writeln!(
-                        out,
-                        "{}",
-                        Schema::gen_model_file_function(&instruction.name)
-                    );
-                }
-            }
-        }
+const MODULES: &str = r#"
+use crate::STORE;
+use massbit_solana_sdk::entity::{
+    Attribute, BigDecimal, BigInt, Bytes, Entity, EntityFilter, EntityOrder, EntityRange, Value,
+};
+use std::collections::HashMap;
+use std::error::Error;
 
-        out
+/// Conversion of a stored value into a field of a model
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+impl FromValue for String {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_string()
     }
-
-    pub fn gen_model_file_header() -> String {
-        let mut out = String::new();
-        // Write entity name
-        write!(
-            out,
-            r#"
-            use crate::STORE;
-            use crate::{{Entity, EntityFilter, EntityOrder, EntityRange, Value}};
-            use crate::{{EntityValue, FromEntity, FromValueTrait, ToMap, ValueFrom}};
-            pub use massbit_drive::{{FromEntity, ToMap}};
-            use std::collections::HashMap;
-        "#
-        );
-        out
+}
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_int()
     }
+}
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_bool()
+    }
+}
+impl FromValue for BigInt {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_bigint()
+    }
+}
+impl FromValue for BigDecimal {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_big_decimal()
+    }
+}
+impl FromValue for Bytes {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.clone().as_bytes()
+    }
+}
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Option<Self> {
+        value
+            .clone()
+            .as_list()
+            .map(|values| values.iter().filter_map(T::from_value).collect())
+    }
+}
+"#;
 
-    pub fn gen_entity_struct(
-        schema: &Schema,
-        entity_type: &String,
-        entity_name: &String,
-    ) -> String {
-        let mut out = String::new();
-        // Write derive
-        write!(
-            out,
-            r#"#[derive(Default, Debug, Clone, FromEntity, ToMap)]"#,
-        );
-
-        // Write entity name
-        write!(
-            out,
-            r#"
-                pub struct {} {{
-                    pub id: String,
-            "#,
-            &entity_name
-        );
+/// A field of an entity. Fields referencing another entity hold its id,
+/// fields derived from another entity are not stored.
+struct ModelField<'a> {
+    name: &'a str,
+    rust_type: String,
+    reference: Option<&'a str>,
+    derived_from: Option<(&'a str, String)>,
+}
 
-        match MAPPING_RUST_TYPES_TO_DB.get(entity_type.as_str()) {
-            // if it is primitive type
-            Some(db_type) => {
-                write!(
-                    out,
-                    r#"pub value: {},"#,
-                    MAPPING_DB_TYPES_TO_RUST
-                        .get(db_type)
-                        .unwrap_or(&Default::default())
-                );
-            }
-            // if it is not primitive type
-            None => {
-                if let Some(properties) = &schema.properties {
-                    for property in properties {
-                        let db_type = MAPPING_RUST_TYPES_TO_DB
-                            .get(property.data_type.as_str())
-                            .unwrap_or(&*DEFAULT_TYPE_DB);
-                        match property.array_length {
-                            Some(_array_length) => {
-                                write!(
-                                    out,
-                                    r#"pub {}: Vec<{}>,"#,
-                                    property.name,
-                                    MAPPING_DB_TYPES_TO_RUST
-                                        .get(db_type)
-                                        .unwrap_or(&Default::default())
-                                );
-                            }
-                            None => {
-                                write!(
-                                    out,
-                                    r#"pub {}: {},"#,
-                                    property.name,
-                                    MAPPING_DB_TYPES_TO_RUST
-                                        .get(db_type)
-                                        .unwrap_or(&Default::default())
-                                );
-                            }
-                        }
-                    }
+impl<'a> Generator<'a> {
+    /// Typed entity structs of the `@entity` types of a graphql schema
+    pub fn generate_models(&self, graphql: &str) -> Result<String, anyhow::Error> {
+        let document = graphql_parser::parse_schema::<String>(graphql)
+            .map_err(|err| anyhow::anyhow!("Invalid graphql schema: {}", err))?;
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", MODULES);
+        let entities = get_entity_types(&document);
+        let enums = document
+            .definitions
+            .iter()
+            .filter_map(|def| match def {
+                Definition::TypeDefinition(TypeDefinition::Enum(enum_type)) => {
+                    Some(enum_type.name.as_str())
                 }
-            }
-        };
-        writeln!(
-            out,
-            r#"
-}}"#
-        );
-        out
+                _ => None,
+            })
+            .collect::<HashSet<&str>>();
+        let entity_names = entities
+            .iter()
+            .map(|entity| entity.name.as_str())
+            .collect::<HashSet<&str>>();
+        for entity in entities.iter() {
+            let fields = entity
+                .fields
+                .iter()
+                .map(|field| create_model_field(field, &entity_names, &enums))
+                .collect::<Vec<ModelField>>();
+            let _ = writeln!(out, "{}", self.expand_model(&entity.name, &fields));
+        }
+        Ok(out)
     }
-
-    pub fn gen_model_file_function(struct_name: &String) -> String {
-        let mut out = String::new();
-        // Write entity name
-        write!(
-            out,
-            r#"
-                impl Into<Entity> for {struct_name} {{
-                    fn into(self) -> Entity {{
-                        let map = {struct_name}::to_map(self.clone());
-                        Entity::from(map)
-                    }}
+    fn expand_model(&self, name: &str, fields: &Vec<ModelField>) -> String {
+        let stored_fields = fields
+            .iter()
+            .filter(|field| field.derived_from.is_none())
+            .collect::<Vec<&ModelField>>();
+        let declarations = stored_fields
+            .iter()
+            .map(|field| format!("pub {}: {},", field.name, &field.rust_type))
+            .collect::<Vec<String>>();
+        let to_values = stored_fields
+            .iter()
+            .map(|field| {
+                format!(
+                    r#"map.insert("{name}".to_string(), Value::from(model.{name}));"#,
+                    name = field.name
+                )
+            })
+            .collect::<Vec<String>>();
+        let from_values = stored_fields
+            .iter()
+            .map(|field| {
+                //Required fields are not wrapped in an Option
+                if field.rust_type.starts_with("Option<") {
+                    format!(
+                        r#"{name}: entity.get("{name}").and_then(FromValue::from_value),"#,
+                        name = field.name
+                    )
+                } else {
+                    format!(
+                        r#"{name}: entity.get("{name}").and_then(FromValue::from_value)?,"#,
+                        name = field.name
+                    )
+                }
+            })
+            .collect::<Vec<String>>();
+        let relations = fields
+            .iter()
+            .filter_map(|field| self.expand_relation(field))
+            .collect::<Vec<String>>();
+        format!(
+            r#"#[derive(Clone, Debug, PartialEq)]
+            pub struct {name} {{
+                {declarations}
+            }}
+            impl From<{name}> for Entity {{
+                fn from(model: {name}) -> Self {{
+                    let mut map: HashMap<Attribute, Value> = HashMap::default();
+                    {to_values}
+                    Entity::from(map)
+                }}
+            }}
+            impl {name} {{
+                /// Returns None if a required field is missing
+                pub fn from_entity(entity: &Entity) -> Option<{name}> {{
+                    Some({name} {{
+                        {from_values}
+                    }})
                 }}
-                impl {struct_name} {{
-                    pub fn save(&self) {{
-                        unsafe {{
-                            STORE
-                                .as_mut()
-                                .unwrap()
-                                .save("{struct_name}".to_string(), self.clone().into());
-                        }}
+                pub fn save(&self) {{
+                    unsafe {{
+                        STORE
+                            .as_mut()
+                            .unwrap()
+                            .save("{name}".to_string(), self.clone().into());
                     }}
-                    pub fn get(entity_id: &String) -> Option<{struct_name}> {{
-                        unsafe {{
-                            let entity = STORE
-                                .as_mut()
-                                .unwrap()
-                                .get("{struct_name}".to_string(), entity_id);
-                            match entity {{
-                                Some(e) => Some({struct_name}::from_entity(&e)),
-                                None => None,
-                            }}
-                        }}
+                }}
+                pub fn get(entity_id: &String) -> Option<{name}> {{
+                    unsafe {{
+                        STORE
+                            .as_mut()
+                            .unwrap()
+                            .get("{name}".to_string(), entity_id)
+                            .and_then(|entity| {name}::from_entity(&entity))
                     }}
-                    pub fn query(
-                        filter: Option<EntityFilter>,
-                        order: EntityOrder,
-                        range: EntityRange,
-                    ) -> Vec<{struct_name}> {{
-                        unsafe {{
-                            STORE
-                                .as_ref()
-                                .unwrap()
-                                .query("{struct_name}".to_string(), filter, order, range)
-                                .iter()
-                                .map(|e| {struct_name}::from_entity(e))
-                                .collect::<Vec<{struct_name}>>()
-                        }}
+                }}
+                /// Queries the stored entities. The changes of the current block are not seen
+                /// before it is flushed: use `get` for the entities saved by the block
+                pub fn query(
+                    filter: Option<EntityFilter>,
+                    order: EntityOrder,
+                    range: EntityRange,
+                ) -> Result<Vec<{name}>, Box<dyn Error>> {{
+                    let entities = unsafe {{
+                        STORE
+                            .as_ref()
+                            .unwrap()
+                            .query("{name}".to_string(), filter, order, range)?
+                    }};
+                    Ok(entities
+                        .iter()
+                        .filter_map(|entity| {name}::from_entity(entity))
+                        .collect::<Vec<{name}>>())
+                }}
+                pub fn remove(entity_id: &String) {{
+                    unsafe {{
+                        STORE
+                            .as_mut()
+                            .unwrap()
+                            .remove("{name}".to_string(), entity_id);
                     }}
                 }}
-        "#,
-            struct_name = struct_name
-        );
-        out
+                {relations}
+            }}"#,
+            name = name,
+            declarations = declarations.join("\n"),
+            to_values = to_values.join("\n"),
+            from_values = from_values.join("\n"),
+            relations = relations.join("\n")
+        )
+    }
+    /// Getter of the entities referenced by a field or referencing the entity
+    fn expand_relation(&self, field: &ModelField) -> Option<String> {
+        if let Some((entity_type, derived_field)) = &field.derived_from {
+            return Some(format!(
+                r#"pub fn {name}(&self) -> Result<Vec<{entity_type}>, Box<dyn Error>> {{
+                    {entity_type}::query(
+                        Some(EntityFilter::new_equal("{derived_field}", self.id.clone())),
+                        EntityOrder::Default,
+                        EntityRange {{ first: None, skip: 0 }},
+                    )
+                }}"#,
+                name = field.name,
+                entity_type = entity_type,
+                derived_field = derived_field
+            ));
+        }
+        let entity_type = field.reference?;
+        let body = if field.rust_type.starts_with("Option<Vec<") {
+            format!(
+                "self.{name}.iter().flatten().filter_map(|id| {entity_type}::get(id)).collect()",
+                name = field.name,
+                entity_type = entity_type
+            )
+        } else if field.rust_type.starts_with("Vec<") {
+            format!(
+                "self.{name}.iter().filter_map(|id| {entity_type}::get(id)).collect()",
+                name = field.name,
+                entity_type = entity_type
+            )
+        } else if field.rust_type.starts_with("Option<") {
+            format!(
+                "self.{name}.as_ref().and_then(|id| {entity_type}::get(id))",
+                name = field.name,
+                entity_type = entity_type
+            )
+        } else {
+            format!(
+                "{entity_type}::get(&self.{name})",
+                name = field.name,
+                entity_type = entity_type
+            )
+        };
+        let return_type = if field.rust_type.contains("Vec<") {
+            format!("Vec<{}>", entity_type)
+        } else {
+            format!("Option<{}>", entity_type)
+        };
+        Some(format!(
+            r#"pub fn get_{name}(&self) -> {return_type} {{
+                {body}
+            }}"#,
+            name = field.name,
+            return_type = return_type,
+            body = body
+        ))
+    }
+}
+
+fn get_entity_types<'d, 'a>(document: &'d Document<'a, String>) -> Vec<&'d ObjectType<'a, String>> {
+    document
+        .definitions
+        .iter()
+        .filter_map(|def| match def {
+            Definition::TypeDefinition(TypeDefinition::Object(object_type))
+                if object_type
+                    .directives
+                    .iter()
+                    .any(|directive| directive.name == "entity") =>
+            {
+                Some(object_type)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn create_model_field<'f, 'a>(
+    field: &'f Field<'a, String>,
+    entity_names: &HashSet<&str>,
+    enums: &HashSet<&str>,
+) -> ModelField<'f> {
+    let named_type = get_named_type(&field.field_type);
+    let derived_from = field
+        .directives
+        .iter()
+        .find(|directive| directive.name == "derivedFrom")
+        .and_then(|directive| {
+            directive
+                .arguments
+                .iter()
+                .find_map(|(name, value)| match value {
+                    Value::String(derived_field) if name == "field" => {
+                        Some((named_type, derived_field.clone()))
+                    }
+                    _ => None,
+                })
+        });
+    let reference = if entity_names.contains(named_type) {
+        Some(named_type)
+    } else {
+        None
+    };
+    let scalar_type = if reference.is_some() || enums.contains(named_type) {
+        //References are stored as the id of the entity, enums as their name
+        "String"
+    } else {
+        match named_type {
+            "Boolean" | "Bool" => "bool",
+            "Int" | "Integer" => "i32",
+            "BigInt" => "BigInt",
+            "BigDecimal" | "Float" | "Double" => "BigDecimal",
+            "Bytes" => "Bytes",
+            _ => "String",
+        }
+    };
+    ModelField {
+        name: field.name.as_str(),
+        rust_type: get_rust_type(&field.field_type, scalar_type, true),
+        reference,
+        derived_from,
+    }
+}
+
+fn get_named_type<'f, 'a>(field_type: &'f Type<'a, String>) -> &'f str {
+    match field_type {
+        Type::NamedType(name) => name.as_str(),
+        Type::ListType(inner) | Type::NonNullType(inner) => get_named_type(inner),
+    }
+}
+
+/// Rust type of a field, the nullable fields are optional
+fn get_rust_type(field_type: &Type<String>, scalar_type: &str, nullable: bool) -> String {
+    let rust_type = match field_type {
+        Type::NonNullType(inner) => return get_rust_type(inner, scalar_type, false),
+        Type::ListType(inner) => format!("Vec<{}>", get_rust_type(inner, scalar_type, false)),
+        Type::NamedType(_) => scalar_type.to_string(),
+    };
+    if nullable {
+        format!("Option<{}>", rust_type)
+    } else {
+        rust_type
     }
 }
@@ -1,41 +1,124 @@
-use crate::{SCHEMA_FILE_NAME, SO_FILE_NAME, SO_FOLDER, SRC_FOLDER, SUBGRAPH_FILE_NAME};
-use reqwest::blocking::multipart::{Form, Part};
+use crate::indexer_login::Credentials;
+use crate::indexer_validate::validate_indexer;
+use crate::{
+    METHOD_DEPLOY, SCHEMA_FILE_NAME, SO_FILE_NAME, SO_FOLDER, SRC_FOLDER, SUBGRAPH_FILE_NAME,
+};
+use anyhow::{anyhow, bail};
+use massbit::ipfs_client::IpfsClient;
 use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::fs;
 use std::path::PathBuf;
-use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn deploy_indexer(indexer_url: &str, project_dir: &str) -> Result<String, anyhow::Error> {
+pub const STATUS_DEPLOYED: &str = "Deployed";
+pub const STATUS_INVALID: &str = "Invalid";
+pub const HEALTH_FAILED: &str = "Failed";
+const POLLING_INTERVAL: Duration = Duration::from_secs(2);
+/// Time to wait for the indexer-manager to start the indexer
+pub const DEPLOY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Deploys a built and valid indexer: pins its mapping, schema and manifest to IPFS,
+/// then asks the indexer-api to deploy them for the logged in account.
+/// Returns the hash of the new indexer.
+pub fn deploy_indexer(
+    indexer_url: &str,
+    ipfs_url: &str,
+    project_dir: &str,
+    name: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    let credentials = Credentials::load()?;
+    let errors = validate_indexer(project_dir);
+    if !errors.is_empty() {
+        for error in &errors {
+            println!("{}", error);
+        }
+        bail!(
+            "Indexer is not valid, fix the {} errors above before deploying",
+            errors.len()
+        );
+    }
     let project_dir = PathBuf::from(project_dir);
-    let so_file_path: PathBuf = project_dir.join(SO_FOLDER).join(SO_FILE_NAME);
-    let schema_file_path: PathBuf = project_dir.join(SRC_FOLDER).join(SCHEMA_FILE_NAME);
-    let manifest_file_path: PathBuf = project_dir.join(SRC_FOLDER).join(SUBGRAPH_FILE_NAME);
-    let multipart = Form::new()
-        .part(
-            "mapping",
-            Part::file(so_file_path.to_str().unwrap_or_default())?,
-        )
-        .part(
+    let files = vec![
+        ("mapping", project_dir.join(SO_FOLDER).join(SO_FILE_NAME)),
+        (
             "schema",
-            Part::file(schema_file_path.to_str().unwrap_or_default())?,
-        )
-        .part(
+            project_dir.join(SRC_FOLDER).join(SCHEMA_FILE_NAME),
+        ),
+        (
             "manifest",
-            Part::file(manifest_file_path.to_str().unwrap_or_default())?,
-        );
+            project_dir.join(SRC_FOLDER).join(SUBGRAPH_FILE_NAME),
+        ),
+    ];
+    let mut params = json!({ "name": name });
+    let ipfs_client = IpfsClient::new(ipfs_url)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    for (field, file) in &files {
+        let response = runtime
+            .block_on(ipfs_client.add(fs::read(file)?))
+            .map_err(|err| anyhow!("Cannot pin {:?} to IPFS {}: {}", file, ipfs_url, err))?;
+        println!("Pinned {:?} to IPFS: {}", file, &response.hash);
+        params[*field] = Value::from(response.hash);
+    }
+    drop(runtime);
 
-    // Compose a request
-    let client = Client::new();
-    let request_builder = client.post(&String::from(indexer_url)).multipart(multipart);
+    let response = Client::new()
+        .post(format!(
+            "{}/{}",
+            indexer_url.trim_end_matches('/'),
+            METHOD_DEPLOY
+        ))
+        .bearer_auth(&credentials.access_token)
+        .json(&params)
+        .send()?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        bail!("The access token is rejected, run `massbit-sol login` again");
+    }
+    let response: Value = response.error_for_status()?.json()?;
+    if let Some(error) = response.get("error") {
+        bail!("Indexer is not deployed: {}", error);
+    }
+    response
+        .get("id")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| anyhow!("Unexpected response of the indexer-api: {}", &response))
+}
 
-    // Send request
-    match request_builder.send() {
-        Ok(r) => {
-            println!("{:#?}", r);
+/// Polls `/indexers/{hash}` until the indexer is deployed, which the indexer-manager records once the mapping
+/// library is loaded, returns an error if it is invalid, if the health of its deployment is failed or at the timeout.
+pub fn wait_for_indexer(
+    indexer_url: &str,
+    hash: &str,
+    timeout: Duration,
+) -> Result<Value, anyhow::Error> {
+    let url = format!("{}/indexers/{}", indexer_url.trim_end_matches('/'), hash);
+    let client = Client::new();
+    let start = Instant::now();
+    loop {
+        let indexer: Value = client.get(&url).send()?.error_for_status()?.json()?;
+        if let Some(error) = indexer.get("fatal_error").and_then(Value::as_str) {
+            bail!("Indexer {} failed: {}", hash, error);
+        }
+        if indexer.get("health").and_then(Value::as_str) == Some(HEALTH_FAILED) {
+            bail!("Indexer {} failed", hash);
         }
-        Err(e) => {
-            println!("error: {:?}", e);
-            process::exit(0);
+        match indexer.get("status").and_then(Value::as_str) {
+            Some(STATUS_DEPLOYED) => return Ok(indexer),
+            Some(STATUS_INVALID) => bail!("Indexer {} is invalid, check its manifest", hash),
+            status => {
+                if start.elapsed() > timeout {
+                    bail!(
+                        "Indexer {} is still {:?} after {:?}",
+                        hash,
+                        status.unwrap_or("not found"),
+                        timeout
+                    );
+                }
+            }
         }
-    };
-    Ok(String::from("Success!"))
+        thread::sleep(POLLING_INTERVAL);
+    }
 }
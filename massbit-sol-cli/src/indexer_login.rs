@@ -0,0 +1,109 @@
+use anyhow::{anyhow, bail};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// Folder of the credential file in the home directory
+pub const CREDENTIALS_FOLDER: &str = ".massbit";
+pub const CREDENTIALS_FILE_NAME: &str = "credentials.json";
+
+/// Access token of a Massbit account, sent as a bearer JWT to the indexer-api
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub access_token: String,
+}
+
+impl Credentials {
+    /// `MASSBIT_CREDENTIALS` if it is set, otherwise `~/.massbit/credentials.json`
+    pub fn path() -> Result<PathBuf, anyhow::Error> {
+        if let Ok(path) = env::var("MASSBIT_CREDENTIALS") {
+            return Ok(PathBuf::from(path));
+        }
+        let home = env::var("HOME").map_err(|_| anyhow!("Cannot find the home directory"))?;
+        Ok(PathBuf::from(home)
+            .join(CREDENTIALS_FOLDER)
+            .join(CREDENTIALS_FILE_NAME))
+    }
+
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = Self::path()?;
+        let content = fs::read_to_string(&path).map_err(|_| {
+            anyhow!(
+                "No credentials in {:?}, run `massbit-sol login` first",
+                &path
+            )
+        })?;
+        serde_json::from_str(&content).map_err(|err| {
+            anyhow!(
+                "Invalid credentials in {:?}: {}, run `massbit-sol login` again",
+                &path,
+                err
+            )
+        })
+    }
+
+    pub fn save(&self) -> Result<PathBuf, anyhow::Error> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        // The mode is only set on creation, an existing file is restricted before the token is written
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(path)
+    }
+}
+
+/// Checks the access token against the indexer-api and saves it in the credential file.
+/// The token is read from the standard input if it is not given.
+pub fn login_indexer(
+    indexer_url: &str,
+    access_token: Option<&str>,
+) -> Result<PathBuf, anyhow::Error> {
+    let access_token = match access_token {
+        Some(token) => token.trim().to_string(),
+        None => read_access_token()?,
+    };
+    if access_token.split('.').count() != 3 {
+        bail!("The access token is not a JWT");
+    }
+    let response = Client::new()
+        .get(format!("{}/my-indexers", indexer_url.trim_end_matches('/')))
+        .query(&[("limit", 1)])
+        .bearer_auth(&access_token)
+        .send()?;
+    match response.status() {
+        StatusCode::UNAUTHORIZED => bail!("The access token is rejected by {}", indexer_url),
+        status if !status.is_success() => {
+            bail!(
+                "Cannot check the access token, {} returns {}",
+                indexer_url,
+                status
+            )
+        }
+        _ => {}
+    }
+    Credentials { access_token }.save()
+}
+
+fn read_access_token() -> Result<String, anyhow::Error> {
+    print!("Access token of your Massbit account: ");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
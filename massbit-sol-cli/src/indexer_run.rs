@@ -0,0 +1,31 @@
+use anyhow::anyhow;
+use std::env;
+use std::io::ErrorKind;
+use std::process::Command;
+
+/// Binary of the `massbit-sol-runner` crate, which links the indexer-manager store and Postgres
+pub const RUNNER_BIN: &str = "massbit-sol-runner";
+
+/// Run `massbit-sol-runner run` with `args`, returns its exit code.
+/// The binary is searched in the directory of `massbit-sol` first, then in the `PATH`
+pub fn run_indexer(args: &[&str]) -> Result<i32, anyhow::Error> {
+    let runner = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(RUNNER_BIN)))
+        .filter(|path| path.is_file())
+        .map(|path| path.into_os_string())
+        .unwrap_or_else(|| RUNNER_BIN.into());
+    let status = Command::new(&runner)
+        .arg("run")
+        .args(args)
+        .status()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow!(
+                "{} is not found, install it with `cargo install --path massbit-sol-runner`",
+                RUNNER_BIN
+            ),
+            _ => anyhow!("Cannot run {:?}: {}", &runner, err),
+        })?;
+    // Killed by a signal
+    Ok(status.code().unwrap_or(1))
+}
@@ -0,0 +1,48 @@
+use crate::{SCHEMA_FILE_NAME, SO_FILE_NAME, SO_FOLDER, SRC_FOLDER, SUBGRAPH_FILE_NAME};
+use chain_solana::validation::{validate_manifest, validate_schema, ValidationError};
+use massbit_solana_sdk::plugin::check_plugin_library;
+use std::fs;
+use std::path::PathBuf;
+
+/// Checks the files deployed for an indexer project, the same way as the indexer-api dry-run:
+/// the manifest, the GraphQL schema and the symbols of the built library.
+pub fn validate_indexer(project_dir: &str) -> Vec<ValidationError> {
+    let project_dir = PathBuf::from(project_dir);
+    let manifest_path = project_dir.join(SRC_FOLDER).join(SUBGRAPH_FILE_NAME);
+    let schema_path = project_dir.join(SRC_FOLDER).join(SCHEMA_FILE_NAME);
+    let so_file_path = project_dir.join(SO_FOLDER).join(SO_FILE_NAME);
+    let mut errors = vec![];
+    match fs::read(&manifest_path) {
+        Ok(content) => errors.append(&mut validate_manifest(
+            &manifest_path.to_string_lossy(),
+            &content,
+        )),
+        Err(err) => errors.push(ValidationError::new(
+            &manifest_path.to_string_lossy(),
+            format!("Cannot read the manifest: {}", err),
+        )),
+    }
+    match fs::read_to_string(&schema_path) {
+        Ok(content) => errors.append(&mut validate_schema(
+            &schema_path.to_string_lossy(),
+            &content,
+        )),
+        Err(err) => errors.push(ValidationError::new(
+            &schema_path.to_string_lossy(),
+            format!("Cannot read the schema: {}", err),
+        )),
+    }
+    if so_file_path.exists() {
+        errors.extend(
+            check_plugin_library(&so_file_path)
+                .into_iter()
+                .map(|message| ValidationError::new(&so_file_path.to_string_lossy(), message)),
+        );
+    } else {
+        errors.push(ValidationError::new(
+            &so_file_path.to_string_lossy(),
+            "The library is not built, run `cargo build --release` in the project",
+        ));
+    }
+    errors
+}
@@ -2,7 +2,10 @@
 extern crate serde_derive;
 pub mod generator;
 pub mod indexer_deploy;
+pub mod indexer_login;
 pub mod indexer_release;
+pub mod indexer_run;
+pub mod indexer_validate;
 pub mod parser;
 pub mod schema;
 
@@ -12,9 +15,11 @@ use std::path::PathBuf;
 lazy_static! {
     pub static ref COMPONENT_NAME: String = String::from("[SolanaCli]");
     pub static ref INDEXER_ENDPOINT: String =
-        env::var("INDEXER_ENDPOINT").unwrap_or(String::from("http://127.0.0.1:3032"));
+        env::var("INDEXER_ENDPOINT").unwrap_or(String::from("http://127.0.0.1:3031"));
+    pub static ref IPFS_ADDRESS: String =
+        env::var("IPFS_ADDRESS").unwrap_or(String::from("http://127.0.0.1:5001"));
 }
-pub const METHOD_DEPLOY: &str = "indexer/deploy";
+pub const METHOD_DEPLOY: &str = "indexers/ipfsdeploy";
 pub const SO_FILE_NAME: &str = "libblock.so";
 pub const SO_FOLDER: &str = "target/release";
 pub const SCHEMA_FILE_NAME: &str = "schema.graphql";
@@ -1,28 +1,39 @@
-use clap::{App, Arg};
-//use logger::core::init_logger;
+use clap::{App, AppSettings, Arg};
+use logger::core::init_logger;
 use massbit_sol::generator::Generator;
-use massbit_sol::indexer_deploy::deploy_indexer;
+use massbit_sol::indexer_deploy::{deploy_indexer, wait_for_indexer, DEPLOY_TIMEOUT};
+use massbit_sol::indexer_login::login_indexer;
 use massbit_sol::indexer_release::release_indexer;
+use massbit_sol::indexer_run::run_indexer;
+use massbit_sol::indexer_validate::validate_indexer;
 use massbit_sol::parser::SchemaBuilder;
-use massbit_sol::INDEXER_ENDPOINT;
+use massbit_sol::{INDEXER_ENDPOINT, IPFS_ADDRESS};
+use std::process;
 
 fn main() {
-    //let res = init_logger(&String::from("massbit-sol-cli"));
-    //println!("Log output: {}", res); // Print log output type
+    // Warnings of the parsers and generators are logged
+    let _res = init_logger(&String::from("massbit-sol-cli"));
     let matches = App::new("massbit-sol")
         .version("1.0")
         .about("Massbit Solana CLI")
         .subcommand(create_gencode_cmd())
+        .subcommand(create_login_cmd())
+        .subcommand(create_validate_cmd())
         .subcommand(create_deploy_cmd())
         .subcommand(create_genstructure_cmd())
+        .subcommand(create_genmodel_cmd())
         .subcommand(create_release_cmd())
+        .subcommand(create_run_cmd())
         .get_matches();
     if let Some(ref matches) = matches.subcommand_matches("gencode") {
         let structure_path = matches.value_of("structure").unwrap_or("instruction.rs");
         let config_path = matches.value_of("config").unwrap_or("config.json");
         let output = matches.value_of("output").unwrap_or("src");
-        let generator = Generator::builder()
-            .with_structure_path(structure_path)
+        let builder = match matches.value_of("idl") {
+            Some(idl_path) => Generator::builder().with_idl_path(idl_path),
+            None => Generator::builder().with_structure_path(structure_path),
+        };
+        let generator = builder
             .with_config_path(config_path)
             .with_output_dir(output)
             .build();
@@ -31,10 +42,44 @@ fn main() {
         let indexer_url = matches
             .value_of("indexer-url")
             .unwrap_or(INDEXER_ENDPOINT.as_str());
+        let ipfs_url = matches
+            .value_of("ipfs-url")
+            .unwrap_or(IPFS_ADDRESS.as_str());
         let project_dir = matches.value_of("project-dir").unwrap_or("./");
-        match deploy_indexer(indexer_url, project_dir) {
-            Ok(_) => {
-                println!("Deploy indexer successfully");
+        let name = matches.value_of("name");
+        match deploy_indexer(indexer_url, ipfs_url, project_dir, name) {
+            Ok(hash) => {
+                println!("Indexer {} is deploying", &hash);
+                if !matches.is_present("no-wait") {
+                    match wait_for_indexer(indexer_url, &hash, DEPLOY_TIMEOUT) {
+                        Ok(_) => println!("Indexer {} is running", &hash),
+                        Err(err) => println!("Error {:?}", &err),
+                    }
+                }
+            }
+            Err(err) => {
+                println!("Error {:?}", &err);
+            }
+        }
+    } else if let Some(ref matches) = matches.subcommand_matches("validate") {
+        let project_dir = matches.value_of("project-dir").unwrap_or("./");
+        let errors = validate_indexer(project_dir);
+        for error in &errors {
+            println!("{}", error);
+        }
+        if errors.is_empty() {
+            println!("Indexer is valid");
+        } else {
+            println!("Found {} errors", errors.len());
+            process::exit(1);
+        }
+    } else if let Some(ref matches) = matches.subcommand_matches("login") {
+        let indexer_url = matches
+            .value_of("indexer-url")
+            .unwrap_or(INDEXER_ENDPOINT.as_str());
+        match login_indexer(indexer_url, matches.value_of("token")) {
+            Ok(path) => {
+                println!("Credentials are saved in {:?}", &path);
             }
             Err(err) => {
                 println!("Error {:?}", &err);
@@ -42,12 +87,34 @@ fn main() {
         }
     } else if let Some(ref matches) = matches.subcommand_matches("genstructure") {
         let structure_path = matches.value_of("source").unwrap_or("instruction.rs");
-        let config_path = matches.value_of("config").unwrap_or("config.json");
-        let output = matches.value_of("output").unwrap_or("src");
-        let schema_builder = SchemaBuilder::builder()
+        let output = matches.value_of("output").unwrap_or("instruction.json");
+        let mut schema_builder = SchemaBuilder::builder()
             .with_instruction_path(structure_path)
             .with_output_dir(output);
-        schema_builder.build()
+        if let Some(name) = matches.value_of("name") {
+            schema_builder = schema_builder.with_instruction_name(name);
+        }
+        if let Err(err) = schema_builder.build() {
+            log::error!("Cannot build the instruction structure: {:#}", err);
+            process::exit(1);
+        }
+    } else if let Some(ref matches) = matches.subcommand_matches("genmodel") {
+        let schema_path = matches.value_of("schema").unwrap_or("src/schema.graphql");
+        let output = matches
+            .value_of("output")
+            .unwrap_or("src/generated/model.rs");
+        let generator = Generator::builder().build();
+        match std::fs::read_to_string(schema_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|graphql| generator.generate_models(&graphql))
+        {
+            Ok(models) => {
+                let _ = generator.write_to_file(&output.to_string(), &models, true);
+            }
+            Err(err) => {
+                println!("Cannot generate models from {}: {:?}", schema_path, &err);
+            }
+        }
     } else if let Some(ref matches) = matches.subcommand_matches("release") {
         let project_dir = matches.value_of("project-dir").unwrap_or("./");
         match release_indexer(project_dir) {
@@ -58,6 +125,18 @@ fn main() {
                 println!("Error {:?}", &err);
             }
         }
+    } else if let Some(ref matches) = matches.subcommand_matches("run") {
+        let args = matches
+            .values_of("args")
+            .map(|args| args.collect::<Vec<&str>>())
+            .unwrap_or_default();
+        match run_indexer(&args) {
+            Ok(code) => process::exit(code),
+            Err(err) => {
+                log::error!("{}", err);
+                process::exit(1);
+            }
+        }
     }
 }
 fn create_gencode_cmd() -> App<'static, 'static> {
@@ -71,6 +150,15 @@ fn create_gencode_cmd() -> App<'static, 'static> {
                 .help("Input instruction structure file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("idl")
+                .short("i")
+                .long("idl")
+                .value_name("idl")
+                .help("Input Anchor IDL file, used instead of the instruction structure")
+                .conflicts_with("structure")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("config")
                 .short("c")
@@ -89,6 +177,40 @@ fn create_gencode_cmd() -> App<'static, 'static> {
         )
 }
 
+fn create_login_cmd() -> App<'static, 'static> {
+    App::new("login")
+        .about("Save the access token used to deploy indexers")
+        .arg(
+            Arg::with_name("token")
+                .short("t")
+                .long("token")
+                .value_name("token")
+                .help("Access token (JWT) of the Massbit account, read from stdin if not set")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("indexer-url")
+                .short("u")
+                .long("indexer-url")
+                .value_name("indexer-url")
+                .help("Indexer-api url, by default INDEXER_ENDPOINT")
+                .takes_value(true),
+        )
+}
+
+fn create_validate_cmd() -> App<'static, 'static> {
+    App::new("validate")
+        .about("Check the manifest, the schema and the library of an indexer before deploying it")
+        .arg(
+            Arg::with_name("project-dir")
+                .short("d")
+                .long("project-dir")
+                .value_name("project-dir")
+                .help("Compiled directory")
+                .takes_value(true),
+        )
+}
+
 fn create_deploy_cmd() -> App<'static, 'static> {
     App::new("deploy")
         .about("Deploy compiled indexer binary")
@@ -97,7 +219,15 @@ fn create_deploy_cmd() -> App<'static, 'static> {
                 .short("u")
                 .long("indexer-url")
                 .value_name("indexer-url")
-                .help("Input indexer entry point")
+                .help("Indexer-api url, by default INDEXER_ENDPOINT")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ipfs-url")
+                .short("i")
+                .long("ipfs-url")
+                .value_name("ipfs-url")
+                .help("IPFS api url, by default IPFS_ADDRESS")
                 .takes_value(true),
         )
         .arg(
@@ -108,6 +238,19 @@ fn create_deploy_cmd() -> App<'static, 'static> {
                 .help("Compiled directory")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("name")
+                .short("n")
+                .long("name")
+                .value_name("name")
+                .help("Indexer name, by default the name of the data source")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-wait")
+                .long("no-wait")
+                .help("Do not wait for the indexer to be running"),
+        )
 }
 
 fn create_release_cmd() -> App<'static, 'static> {
@@ -121,6 +264,41 @@ fn create_release_cmd() -> App<'static, 'static> {
     )
 }
 
+fn create_run_cmd() -> App<'static, 'static> {
+    App::new("run")
+        .about("Build and run indexer locally with `massbit-sol-runner run`")
+        .setting(AppSettings::TrailingVarArg)
+        .setting(AppSettings::AllowLeadingHyphen)
+        .setting(AppSettings::DisableHelpFlags)
+        .arg(
+            Arg::with_name("args")
+                .value_name("args")
+                .help("Arguments of `massbit-sol-runner run`, see `massbit-sol run --help`")
+                .multiple(true),
+        )
+}
+
+fn create_genmodel_cmd() -> App<'static, 'static> {
+    App::new("genmodel")
+        .about("Generate Rust entity models from graphql schema")
+        .arg(
+            Arg::with_name("schema")
+                .short("s")
+                .long("schema")
+                .value_name("schema")
+                .help("Input graphql schema file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("output")
+                .help("Output file")
+                .takes_value(true),
+        )
+}
+
 fn create_genstructure_cmd() -> App<'static, 'static> {
     App::new("genstructure")
         .about("Generate Solana smartcontract instruction structure from source code.")
@@ -140,12 +318,20 @@ fn create_genstructure_cmd() -> App<'static, 'static> {
                 .help("Input config file")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("name")
+                .short("n")
+                .long("name")
+                .value_name("name")
+                .help("Name of the instruction enum, by default the largest `*Instruction` enum of the source")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("output")
                 .short("o")
                 .long("output")
                 .value_name("output")
-                .help("Output file")
+                .help("Output file or directory")
                 .takes_value(true),
         )
 }
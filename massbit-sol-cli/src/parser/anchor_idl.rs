@@ -0,0 +1,593 @@
+//! Conversion of an Anchor IDL into the instruction `Schema` used by the generator.
+//!
+//! Anchor programs identify an instruction by the first 8 bytes of
+//! `sha256("global:<instruction_name>")` followed by its Borsh encoded arguments.
+//! Each instruction becomes a variant tagged with this discriminator, its arguments
+//! become an `<Instruction>Args` struct and the types, accounts and events of the
+//! IDL become definitions, the accounts and events are decoded by their discriminator.
+use crate::generator::helper::replace_invalid_identifier_chars;
+use crate::schema::{AccountInfo, AccountLayout, EventLayout, Property, Schema, Variant};
+use inflector::Inflector;
+use massbit_solana_sdk::event::{discriminator, IdlType};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Length of the sighash discriminator of an Anchor instruction
+pub const DISCRIMINATOR_LENGTH: usize = 8;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Idl {
+    pub version: Option<String>,
+    pub name: String,
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub accounts: Vec<IdlTypeDefinition>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDefinition>,
+    #[serde(default)]
+    pub events: Vec<IdlEvent>,
+    #[serde(default)]
+    pub errors: Vec<IdlErrorCode>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(default)]
+    pub accounts: Vec<IdlAccountItem>,
+    #[serde(default)]
+    pub args: Vec<IdlField>,
+}
+
+/// A single account or a named group of accounts (an `Accounts` struct nested in another one)
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlAccountItem {
+    pub name: String,
+    #[serde(default, rename = "isMut")]
+    pub is_mut: bool,
+    #[serde(default, rename = "isSigner")]
+    pub is_signer: bool,
+    #[serde(default)]
+    pub accounts: Option<Vec<IdlAccountItem>>,
+}
+
+/// The types are kept as in the IDL, those the SDK can not parse are not supported
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlEvent {
+    pub name: String,
+    pub fields: Vec<IdlField>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlErrorCode {
+    pub code: u32,
+    pub name: String,
+    pub msg: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlTypeDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefinitionTy,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefinitionTy {
+    Struct { fields: Vec<IdlField> },
+    Enum { variants: Vec<IdlEnumVariant> },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<IdlEnumFields>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum IdlEnumFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<Value>),
+}
+
+impl Idl {
+    pub fn from_json(json: &str) -> Result<Idl, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+    /// Builds the instruction schema of the program. Types which are not
+    /// supported are skipped with everything using them, instructions with
+    /// such arguments are not decoded.
+    pub fn to_schema(&self) -> Schema {
+        let mut account_layouts = Vec::default();
+        // Types can use each other in any order, so the skipped ones are
+        // removed until all remaining types can be created
+        let mut defined: BTreeSet<String> = self
+            .types
+            .iter()
+            .chain(self.accounts.iter())
+            .map(|def| type_name(&def.name))
+            .collect();
+        let mut definitions = loop {
+            let mut definitions = BTreeMap::default();
+            let mut skipped = false;
+            for def in self.types.iter().chain(self.accounts.iter()) {
+                let name = type_name(&def.name);
+                if !defined.contains(&name) {
+                    continue;
+                }
+                let schema = match &def.ty {
+                    IdlTypeDefinitionTy::Struct { fields } => {
+                        self.create_struct(&name, fields, &defined)
+                    }
+                    IdlTypeDefinitionTy::Enum { variants } => {
+                        self.create_enum(&name, variants, &defined, &mut definitions)
+                    }
+                };
+                match schema {
+                    Some(schema) => {
+                        definitions.insert(name, schema);
+                    }
+                    None => {
+                        defined.remove(&name);
+                        skipped = true;
+                    }
+                }
+            }
+            if !skipped {
+                break definitions;
+            }
+        };
+        //Anchor accounts start with the discriminator `sha256("account:<AccountName>")[..8]`
+        for def in self.accounts.iter() {
+            let name = type_name(&def.name);
+            if definitions.contains_key(&name) {
+                account_layouts.push(AccountLayout {
+                    name: name.clone(),
+                    inner_type: name,
+                    offset: Some(DISCRIMINATOR_LENGTH),
+                    size: None,
+                    discriminator: Some(discriminator("account", &def.name).to_vec()),
+                });
+            }
+        }
+        //Anchor events are logged as `sha256("event:<EventName>")[..8]` followed by the fields
+        let mut event_layouts = Vec::default();
+        for event in self.events.iter() {
+            let name = type_name(&event.name);
+            if let Some(schema) = self.create_struct(&name, &event.fields, &defined) {
+                definitions.insert(name.clone(), schema);
+                event_layouts.push(EventLayout {
+                    name: name.clone(),
+                    inner_type: name,
+                    discriminator: discriminator("event", &event.name).to_vec(),
+                });
+            }
+        }
+        let mut variants = Vec::default();
+        for instruction in self.instructions.iter() {
+            let name = type_name(&instruction.name);
+            let mut inner_type = None;
+            if !instruction.args.is_empty() {
+                let args_name = format!("{}Args", &name);
+                match self.create_struct(&args_name, &instruction.args, &defined) {
+                    Some(schema) => {
+                        definitions.insert(args_name.clone(), schema);
+                        inner_type = Some(args_name);
+                    }
+                    // Without its arguments the instruction can not be decoded
+                    None => {
+                        log::warn!("Instruction `{}` is skipped", &instruction.name);
+                        continue;
+                    }
+                }
+            }
+            let mut accounts = Vec::default();
+            flatten_accounts(&mut accounts, "", &instruction.accounts);
+            variants.push(Variant {
+                name,
+                value: None,
+                inner_name: None,
+                inner_type,
+                inner_scope: None,
+                description: None,
+                offset: None,
+                // The discriminator is read from the instruction data as little endian integer
+                variant_tag: u64::from_le_bytes(discriminator(
+                    "global",
+                    &instruction.name.to_snake_case(),
+                )),
+                accounts: Some(accounts),
+            });
+        }
+        Schema {
+            name: Some(format!("{}Instruction", type_name(&self.name))),
+            variants: Some(variants),
+            properties: None,
+            definitions,
+            description: self
+                .version
+                .as_ref()
+                .map(|version| format!("Generated from Anchor IDL {} {}", &self.name, version)),
+            offset: None,
+            variant_tag_length: Some(DISCRIMINATOR_LENGTH),
+            accounts: Some(account_layouts),
+            events: Some(event_layouts),
+        }
+    }
+    fn create_struct(
+        &self,
+        name: &str,
+        fields: &Vec<IdlField>,
+        defined: &BTreeSet<String>,
+    ) -> Option<Schema> {
+        let mut properties = Vec::default();
+        for field in fields {
+            match self.create_property(field, defined) {
+                Some(property) => properties.push(property),
+                None => {
+                    log::warn!(
+                        "Field `{}` of `{}` has an unsupported type {}, `{}` is skipped",
+                        &field.name,
+                        name,
+                        &field.ty,
+                        name
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(Schema {
+            properties: Some(properties),
+            ..Default::default()
+        })
+    }
+    /// Borsh enums have an 1 byte tag. Variants with a single unnamed field have it as inner type,
+    /// variants with several fields have a tuple or a `<Enum><Variant>` struct for named fields.
+    fn create_enum(
+        &self,
+        name: &str,
+        idl_variants: &Vec<IdlEnumVariant>,
+        defined: &BTreeSet<String>,
+        definitions: &mut BTreeMap<String, Schema>,
+    ) -> Option<Schema> {
+        let mut variants = Vec::default();
+        // The structs of the variants are only kept if the whole enum is supported
+        let mut variant_structs = Vec::default();
+        for (ind, variant) in idl_variants.iter().enumerate() {
+            let variant_name = type_name(&variant.name);
+            let inner_type = match &variant.fields {
+                Some(IdlEnumFields::Named(fields)) if !fields.is_empty() => {
+                    let inner_name = format!("{}{}", name, &variant_name);
+                    let schema = self.create_struct(&inner_name, fields, defined)?;
+                    variant_structs.push((inner_name.clone(), schema));
+                    Some(inner_name)
+                }
+                Some(IdlEnumFields::Tuple(types)) if types.len() == 1 => {
+                    Some(self.field_data_type(name, &variant.name, &types[0], defined)?)
+                }
+                Some(IdlEnumFields::Tuple(types)) if types.len() > 1 => {
+                    let mut elements = Vec::default();
+                    for ty in types {
+                        elements.push(self.field_data_type(name, &variant.name, ty, defined)?);
+                    }
+                    Some(format!("({})", elements.join(", ")))
+                }
+                _ => None,
+            };
+            variants.push(Variant {
+                name: variant_name,
+                value: None,
+                inner_name: None,
+                inner_type,
+                inner_scope: None,
+                description: None,
+                offset: None,
+                variant_tag: ind as u64,
+                accounts: None,
+            });
+        }
+        definitions.extend(variant_structs);
+        Some(Schema {
+            variants: Some(variants),
+            variant_tag_length: Some(1),
+            ..Default::default()
+        })
+    }
+    /// Rust type of an idl type, with a warning if it is not supported
+    fn field_data_type(
+        &self,
+        name: &str,
+        field_name: &str,
+        ty: &Value,
+        defined: &BTreeSet<String>,
+    ) -> Option<String> {
+        let data_type = IdlType::from_json(ty)
+            .ok()
+            .and_then(|ty| self.data_type(&ty, defined));
+        if data_type.is_none() {
+            log::warn!(
+                "Field `{}` of `{}` has an unsupported type {}, `{}` is skipped",
+                field_name,
+                name,
+                ty,
+                name
+            );
+        }
+        data_type
+    }
+    fn create_property(&self, field: &IdlField, defined: &BTreeSet<String>) -> Option<Property> {
+        let (data_type, array_length) = match IdlType::from_json(&field.ty).ok()? {
+            IdlType::Array(elm_type, len) => (self.data_type(&elm_type, defined)?, Some(len)),
+            ty => (self.data_type(&ty, defined)?, None),
+        };
+        Some(Property {
+            name: replace_invalid_identifier_chars(&field.name.to_snake_case()),
+            data_type,
+            length: None,
+            array_length,
+            required: true,
+            description: None,
+        })
+    }
+    /// Rust type of an idl type. Fixed arrays are only supported as field types,
+    /// floats, which the generated unpack functions do not support, and the
+    /// `defined` types which are not in `defined` are not supported.
+    fn data_type(&self, ty: &IdlType, defined: &BTreeSet<String>) -> Option<String> {
+        let data_type = match ty {
+            IdlType::Bool => "bool",
+            IdlType::U8 => "u8",
+            IdlType::I8 => "i8",
+            IdlType::U16 => "u16",
+            IdlType::I16 => "i16",
+            IdlType::U32 => "u32",
+            IdlType::I32 => "i32",
+            IdlType::U64 => "u64",
+            IdlType::I64 => "i64",
+            IdlType::U128 => "u128",
+            IdlType::I128 => "i128",
+            IdlType::String => "String",
+            IdlType::Bytes => "Vec<u8>",
+            IdlType::PublicKey => "Pubkey",
+            IdlType::Defined(name) => {
+                return Some(type_name(name)).filter(|name| defined.contains(name))
+            }
+            IdlType::Option(inner) => {
+                return Some(format!("Option<{}>", self.data_type(inner, defined)?))
+            }
+            IdlType::Vec(inner) => {
+                return Some(format!("Vec<{}>", self.data_type(inner, defined)?))
+            }
+            IdlType::F32 | IdlType::F64 | IdlType::Array(..) => return None,
+        };
+        Some(String::from(data_type))
+    }
+}
+
+/// Nested account groups are flattened in order, their accounts are prefixed with the group name
+fn flatten_accounts(accounts: &mut Vec<AccountInfo>, prefix: &str, items: &Vec<IdlAccountItem>) {
+    for item in items {
+        let name = format!("{}{}", prefix, item.name.to_snake_case());
+        match &item.accounts {
+            Some(group) => flatten_accounts(accounts, &format!("{}_", name), group),
+            None => accounts.push(AccountInfo {
+                index: accounts.len(),
+                name: replace_invalid_identifier_chars(&name),
+            }),
+        }
+    }
+}
+
+fn type_name(name: &str) -> String {
+    replace_invalid_identifier_chars(&name.to_pascal_case())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ESCROW_IDL: &str =
+        include_str!("../../../user-example/solana/instructions/escrow/idl.json");
+
+    fn property_types(schema: &Schema) -> Vec<(String, String)> {
+        schema
+            .properties
+            .iter()
+            .flatten()
+            .map(|property| (property.name.clone(), property.data_type.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn discriminators_match_anchor() {
+        // `sha256("global:initialize")[..8]` of every Anchor program
+        assert_eq!(
+            discriminator("global", "initialize"),
+            [175, 175, 109, 31, 13, 152, 155, 237]
+        );
+        assert_eq!(
+            discriminator("global", "initialize_escrow"),
+            [243, 160, 77, 153, 11, 92, 48, 209]
+        );
+        assert_eq!(
+            discriminator("account", "EscrowAccount"),
+            [36, 69, 48, 18, 128, 225, 125, 135]
+        );
+    }
+
+    #[test]
+    fn escrow_idl_to_schema() {
+        let schema = Idl::from_json(ESCROW_IDL).unwrap().to_schema();
+        assert_eq!(schema.name.as_deref(), Some("EscrowInstruction"));
+        assert_eq!(schema.variant_tag_length, Some(DISCRIMINATOR_LENGTH));
+        let variants = schema.variants.as_ref().unwrap();
+        let names: Vec<&str> = variants
+            .iter()
+            .map(|variant| variant.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["InitializeEscrow", "CancelEscrow", "Exchange"]);
+        assert_eq!(variants[0].variant_tag, 0xd1305c0b994da0f3);
+        assert_eq!(
+            variants[0].inner_type.as_deref(),
+            Some("InitializeEscrowArgs")
+        );
+        assert_eq!(variants[1].inner_type, None);
+        let accounts: Vec<&str> = variants[0]
+            .accounts
+            .iter()
+            .flatten()
+            .map(|account| account.name.as_str())
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                "initializer",
+                "initializer_deposit_token_account",
+                "initializer_receive_token_account",
+                "escrow_account",
+                "token_program"
+            ]
+        );
+        assert_eq!(
+            property_types(&schema.definitions["InitializeEscrowArgs"]),
+            vec![
+                (String::from("initializer_amount"), String::from("u64")),
+                (String::from("taker_amount"), String::from("u64"))
+            ]
+        );
+        assert_eq!(
+            property_types(&schema.definitions["EscrowAccount"]).len(),
+            5
+        );
+        let layouts = schema.accounts.as_ref().unwrap();
+        assert_eq!(layouts.len(), 1);
+        assert_eq!(layouts[0].offset, Some(DISCRIMINATOR_LENGTH));
+        assert_eq!(
+            layouts[0].discriminator.as_deref(),
+            Some(&[36, 69, 48, 18, 128, 225, 125, 135][..])
+        );
+    }
+
+    #[test]
+    fn events_are_decoded_by_their_discriminator() {
+        let idl = Idl::from_json(
+            r#"{
+                "name": "trades",
+                "instructions": [],
+                "events": [
+                    {"name": "TradeEvent", "fields": [
+                        {"name": "owner", "type": "publicKey", "index": false},
+                        {"name": "amount", "type": "u64", "index": false}
+                    ]},
+                    {"name": "RatioEvent", "fields": [
+                        {"name": "ratio", "type": "f64", "index": false}
+                    ]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let schema = idl.to_schema();
+        let layouts = schema.events.as_ref().unwrap();
+        assert_eq!(layouts.len(), 1);
+        assert_eq!(layouts[0].name, "TradeEvent");
+        assert_eq!(layouts[0].inner_type, "TradeEvent");
+        assert_eq!(
+            layouts[0].discriminator,
+            vec![189, 219, 127, 211, 78, 230, 97, 238]
+        );
+        assert_eq!(
+            property_types(&schema.definitions["TradeEvent"]),
+            vec![
+                (String::from("owner"), String::from("Pubkey")),
+                (String::from("amount"), String::from("u64"))
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_defined_types_of_newer_idls() {
+        let idl = Idl::from_json(
+            r#"{
+                "name": "orders",
+                "instructions": [
+                    {"name": "placeOrder", "accounts": [], "args": [
+                        {"name": "order", "type": {"defined": {"name": "Order"}}}
+                    ]}
+                ],
+                "types": [
+                    {"name": "Order", "type": {"kind": "struct", "fields": [
+                        {"name": "owner", "type": "pubkey"},
+                        {"name": "sizes", "type": {"array": ["u64", 2]}}
+                    ]}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let schema = idl.to_schema();
+        assert_eq!(
+            property_types(&schema.definitions["PlaceOrderArgs"]),
+            vec![(String::from("order"), String::from("Order"))]
+        );
+        let order = &schema.definitions["Order"];
+        assert_eq!(
+            property_types(order),
+            vec![
+                (String::from("owner"), String::from("Pubkey")),
+                (String::from("sizes"), String::from("u64"))
+            ]
+        );
+        assert_eq!(order.properties.as_ref().unwrap()[1].array_length, Some(2));
+    }
+
+    #[test]
+    fn skips_unsupported_types_and_their_users() {
+        let idl = Idl::from_json(
+            r#"{
+                "name": "prices",
+                "instructions": [
+                    {"name": "setPrice", "accounts": [], "args": [
+                        {"name": "price", "type": {"defined": "Price"}}
+                    ]},
+                    {"name": "setLimit", "accounts": [], "args": [
+                        {"name": "limit", "type": {"defined": "Limit"}}
+                    ]},
+                    {"name": "setRatio", "accounts": [], "args": [
+                        {"name": "ratio", "type": "f64"}
+                    ]},
+                    {"name": "reset", "accounts": [], "args": []}
+                ],
+                "types": [
+                    {"name": "Limit", "type": {"kind": "struct", "fields": [
+                        {"name": "price", "type": {"option": {"defined": "Price"}}}
+                    ]}},
+                    {"name": "Side", "type": {"kind": "enum", "variants": [
+                        {"name": "Bid"},
+                        {"name": "Ask", "fields": [{"name": "limit", "type": {"defined": "Limit"}}]}
+                    ]}},
+                    {"name": "Price", "type": {"kind": "struct", "fields": [
+                        {"name": "value", "type": "f32"}
+                    ]}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let schema = idl.to_schema();
+        let names: Vec<&str> = schema
+            .variants
+            .iter()
+            .flatten()
+            .map(|variant| variant.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Reset"]);
+        assert!(schema.definitions.is_empty());
+    }
+}
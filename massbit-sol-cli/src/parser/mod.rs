@@ -1,2 +1,3 @@
+pub mod anchor_idl;
 pub mod schema_builder;
 pub use schema_builder::SchemaBuilder;
@@ -1,17 +1,45 @@
-use crate::schema::Schema;
-use std::path::Path;
+//! Builds the instruction structure of a program from its Rust source code.
+//!
+//! The instruction enum is read from the input file, the types it refers to are searched in
+//! the input file and in the files of the crate it imports with `use crate::`, `use super::`
+//! or `use self::`. Tags and layouts are inferred from:
+//! - the `array_refs!` of the `unpack` functions implemented for the enum and the structs,
+//! - `#[repr(u*)]` attributes of enums,
+//! - Borsh derives, which use an 1 byte tag.
+use crate::generator::helper::replace_invalid_identifier_chars;
+use crate::schema::{AccountInfo, Property, Schema, Variant};
+use anyhow::{anyhow, Context};
+use inflector::Inflector;
+use quote::ToTokens;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::{fs, io};
-use syn::{Attribute, Item, ItemEnum, ItemUse, Variant};
+use syn::visit::{self, Visit};
+use syn::{
+    Arm, Attribute, Expr, Fields, GenericArgument, ImplItem, ImplItemMethod, Item, ItemEnum,
+    ItemImpl, ItemStruct, Lit, Meta, NestedMeta, PathArguments, Stmt, Type, UseTree,
+};
+
+const PRIMITIVE_TYPES: &[&str] = &[
+    "bool", "u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "u128", "i128", "String",
+    "Pubkey",
+];
+/// Number of parent directories searched for the root of the crate
+const MAX_CRATE_DEPTH: usize = 4;
 
 pub struct SchemaBuilder<'a> {
     pub instruction_path: &'a str,
+    /// The output file, `instruction.json` in it if it is a directory
     pub output_dir: &'a str,
+    /// Name of the instruction enum
+    pub instruction_name: Option<&'a str>,
 }
 impl<'a> SchemaBuilder<'a> {
     fn default() -> Self {
         Self {
             instruction_path: "",
             output_dir: "",
+            instruction_name: None,
         }
     }
     pub fn builder() -> SchemaBuilder<'a> {
@@ -25,74 +53,61 @@ impl<'a> SchemaBuilder<'a> {
         self.output_dir = output_dir;
         self
     }
-    pub fn build(&self) {
-        let input_content = fs::read_to_string(self.instruction_path).expect(
-            format!(
-                "Something went wrong reading the file {}",
-                &self.instruction_path
+    pub fn with_instruction_name(mut self, name: &'a str) -> Self {
+        self.instruction_name = Some(name);
+        self
+    }
+    /// Write the structure of the instruction enum, returns the path of the written file
+    pub fn build(&self) -> Result<PathBuf, anyhow::Error> {
+        let mut sources = Vec::default();
+        let mut visited = HashSet::new();
+        load_source(Path::new(self.instruction_path), &mut sources, &mut visited)?;
+        let items = SourceItems::new(&sources);
+        let instruction_enum = match self.instruction_name {
+            Some(name) => items.enums.get(name).copied(),
+            // The enum of the input file with the most variants among those named `*Instruction`
+            None => sources[0]
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Enum(item_enum)
+                        if item_enum.ident.to_string().ends_with("Instruction") =>
+                    {
+                        Some(item_enum)
+                    }
+                    _ => None,
+                })
+                .max_by_key(|item_enum| item_enum.variants.len()),
+        };
+        let instruction_enum = instruction_enum.ok_or_else(|| {
+            anyhow!(
+                "No instruction enum found in {}, use --name to select it",
+                self.instruction_path
             )
-            .as_str(),
+        })?;
+        let schema = StructureBuilder::new(&items).build(instruction_enum);
+        let output_path = self.get_output_path();
+        let content =
+            serde_json::to_string_pretty(&schema).context("Cannot serialize structure")?;
+        self.write_to_file(&output_path, &content)
+            .with_context(|| format!("Cannot write to {:?}", &output_path))?;
+        log::info!(
+            "Write structure of {} to {:?}",
+            &instruction_enum.ident,
+            &output_path
         );
-        // let mut file = File::open(INPUT_RUST_CODE)?;
-        // let mut content = String::new();
-        // file.read_to_string(&mut content)?;
-
-        match syn::parse_file(&input_content) {
-            Ok(ast) => {
-                let schema = Schema::default();
-                if let Some(shebang) = ast.shebang {
-                    println!("{}", shebang);
-                }
-                ast.items.iter().for_each(|item| self.process_item(item));
-                ast.attrs.iter().for_each(|item| println!("{:?}", item));
-            }
-            Err(_) => {}
-        };
+        Ok(output_path)
     }
-    fn process_item(&self, item: &Item) {
-        match item {
-            Item::Const(_) => {}
-            Item::Enum(item_enum) => {
-                self.process_item_enum(item_enum);
-            }
-            Item::ExternCrate(_) => {}
-            Item::Fn(_) => {}
-            Item::ForeignMod(_) => {}
-            Item::Impl(_) => {}
-            Item::Macro(_) => {}
-            Item::Macro2(_) => {}
-            Item::Mod(_) => {}
-            Item::Static(_) => {}
-            Item::Struct(_) => {}
-            Item::Trait(_) => {}
-            Item::TraitAlias(_) => {}
-            Item::Type(_) => {}
-            Item::Union(_) => {}
-            Item::Use(item_use) => {
-                self.process_item_use(item_use);
-            }
-            Item::Verbatim(_) => {}
-            Item::__TestExhaustive(_) => {}
+    fn get_output_path(&self) -> PathBuf {
+        let path = Path::new(self.output_dir);
+        if self.output_dir.is_empty() {
+            PathBuf::from("instruction.json")
+        } else if path.is_dir() {
+            path.join("instruction.json")
+        } else {
+            path.to_path_buf()
         }
-        //println!("{:?}", item)
     }
-    fn process_item_enum(&self, item_enum: &ItemEnum) {
-        println!("Variant number: {}", item_enum.variants.len());
-        item_enum
-            .variants
-            .iter()
-            .for_each(|variant| self.process_item_variant(variant));
-    }
-    fn process_item_variant(&self, variant: &Variant) {
-        println!("Variant attrs {:?}", &variant.attrs);
-        println!("Variant ident {:?}", &variant.ident);
-        println!("Variant fields {:?}", &variant.fields);
-        println!("Variant discriminant {:?}", &variant.discriminant);
-    }
-    fn process_attribute(&self, attribute: &Attribute) {
-        //println!("{:?}", attribute)
-    }
-    fn process_item_use(&self, item_use: &ItemUse) {}
     pub fn write_to_file<P: ?Sized + AsRef<Path>>(
         &self,
         output_path: &P,
@@ -117,3 +132,880 @@ impl<'a> SchemaBuilder<'a> {
         }
     }
 }
+
+/// Parse a source file then the files of the crate it imports, the imported files
+/// which can not be parsed are skipped with a warning
+fn load_source(
+    path: &Path,
+    sources: &mut Vec<syn::File>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let path = path.canonicalize().unwrap_or(path.to_path_buf());
+    if !visited.insert(path.clone()) {
+        return Ok(());
+    }
+    let content = fs::read_to_string(&path).with_context(|| format!("Cannot read {:?}", &path))?;
+    let file = syn::parse_file(&content).with_context(|| format!("Cannot parse {:?}", &path))?;
+    let mut dependencies = Vec::default();
+    for item in file.items.iter() {
+        if let Item::Use(item_use) = item {
+            let mut use_paths = Vec::default();
+            collect_use_paths(&item_use.tree, &mut Vec::default(), &mut use_paths);
+            for use_path in use_paths {
+                if let Some(dependency) = resolve_module_file(&path, &use_path) {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+    }
+    sources.push(file);
+    for dependency in dependencies {
+        if let Err(err) = load_source(&dependency, sources, visited) {
+            log::warn!("{:#}", err);
+        }
+    }
+    Ok(())
+}
+
+fn collect_use_paths(tree: &UseTree, prefix: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+    match tree {
+        UseTree::Path(use_path) => {
+            prefix.push(use_path.ident.to_string());
+            collect_use_paths(&use_path.tree, prefix, paths);
+            prefix.pop();
+        }
+        UseTree::Name(use_name) => {
+            let mut path = prefix.clone();
+            path.push(use_name.ident.to_string());
+            paths.push(path);
+        }
+        UseTree::Rename(use_rename) => {
+            let mut path = prefix.clone();
+            path.push(use_rename.ident.to_string());
+            paths.push(path);
+        }
+        UseTree::Glob(_) => paths.push(prefix.clone()),
+        UseTree::Group(group) => {
+            for tree in group.items.iter() {
+                collect_use_paths(tree, prefix, paths);
+            }
+        }
+    }
+}
+
+/// File of the module of a `use` path, items of the path are ignored.
+/// Paths of other crates are not resolved.
+fn resolve_module_file(current: &Path, use_path: &Vec<String>) -> Option<PathBuf> {
+    let dir = current.parent()?;
+    let is_mod_file = current
+        .file_name()
+        .map(|name| name == "mod.rs" || name == "lib.rs" || name == "main.rs")
+        .unwrap_or_default();
+    let (roots, segments) = match use_path.first()?.as_str() {
+        "crate" => (
+            dir.ancestors()
+                .take(MAX_CRATE_DEPTH)
+                .map(|ancestor| ancestor.to_path_buf())
+                .collect::<Vec<PathBuf>>(),
+            &use_path[1..],
+        ),
+        "super" => {
+            let mut base = if is_mod_file {
+                dir.parent()?.to_path_buf()
+            } else {
+                dir.to_path_buf()
+            };
+            let supers = use_path.iter().take_while(|seg| *seg == "super").count();
+            for _ in 1..supers {
+                base = base.parent()?.to_path_buf();
+            }
+            (vec![base], &use_path[supers..])
+        }
+        "self" => {
+            let base = if is_mod_file {
+                dir.to_path_buf()
+            } else {
+                dir.join(current.file_stem()?)
+            };
+            (vec![base], &use_path[1..])
+        }
+        _ => return None,
+    };
+    for root in roots.iter() {
+        for len in (1..=segments.len()).rev() {
+            let module = segments[..len]
+                .iter()
+                .fold(root.clone(), |path, seg| path.join(seg));
+            let candidates = [module.with_extension("rs"), module.join("mod.rs")];
+            if let Some(file) = candidates.iter().find(|file| file.is_file()) {
+                return Some(file.clone());
+            }
+        }
+    }
+    None
+}
+
+/// Structs, enums, impls and constants of the sources by name, the first definition wins
+struct SourceItems<'a> {
+    structs: HashMap<String, &'a ItemStruct>,
+    enums: HashMap<String, &'a ItemEnum>,
+    impls: HashMap<String, Vec<&'a ItemImpl>>,
+    consts: HashMap<String, usize>,
+}
+impl<'a> SourceItems<'a> {
+    fn new(sources: &'a Vec<syn::File>) -> Self {
+        let mut items = SourceItems {
+            structs: HashMap::default(),
+            enums: HashMap::default(),
+            impls: HashMap::default(),
+            consts: HashMap::default(),
+        };
+        for item in sources.iter().flat_map(|file| file.items.iter()) {
+            match item {
+                Item::Struct(item_struct) => {
+                    items
+                        .structs
+                        .entry(item_struct.ident.to_string())
+                        .or_insert(item_struct);
+                }
+                Item::Enum(item_enum) => {
+                    items
+                        .enums
+                        .entry(item_enum.ident.to_string())
+                        .or_insert(item_enum);
+                }
+                Item::Impl(item_impl) => {
+                    if let Some(name) = type_ident(&item_impl.self_ty) {
+                        items.impls.entry(name).or_default().push(item_impl);
+                    }
+                }
+                Item::Const(item_const) => {
+                    if let Some(value) = int_value(&item_const.expr) {
+                        items
+                            .consts
+                            .entry(item_const.ident.to_string())
+                            .or_insert(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+        items
+    }
+    fn unpack_methods(&self, name: &str) -> Vec<&'a ImplItemMethod> {
+        self.impls
+            .get(name)
+            .map(|impls| impls.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|item_impl| item_impl.items.iter())
+            .filter_map(|impl_item| match impl_item {
+                ImplItem::Method(method) if method.sig.ident.to_string().starts_with("unpack") => {
+                    Some(method)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Lengths of the first `array_refs!` of the `unpack*` methods implemented for a type,
+    /// with a flag telling if the macro has a trailing `..` slice
+    fn unpack_layout(&self, name: &str) -> Option<(Vec<usize>, bool)> {
+        self.unpack_methods(name)
+            .into_iter()
+            .flat_map(|method| method.block.stmts.iter())
+            .find_map(|stmt| match stmt {
+                Stmt::Local(local) => match local.init.as_ref().map(|(_, expr)| expr.as_ref()) {
+                    Some(Expr::Macro(expr_macro)) => parse_array_refs(&expr_macro.mac),
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+    /// Field lengths of the variants of an enum, read from the match arms of its `unpack*` methods
+    fn variant_layouts(&self, name: &str) -> HashMap<String, Vec<usize>> {
+        let mut arm_layouts = ArmLayouts {
+            variant_prefix: format!("{} :: ", name),
+            layouts: HashMap::default(),
+        };
+        for method in self.unpack_methods(name) {
+            arm_layouts.visit_impl_item_method(method);
+        }
+        arm_layouts.layouts
+    }
+}
+
+/// Parse `array_refs![input, 1, 4; ..;]` into `([1, 4], true)`
+fn parse_array_refs(mac: &syn::Macro) -> Option<(Vec<usize>, bool)> {
+    if !mac.path.is_ident("array_refs") {
+        return None;
+    }
+    parse_array_refs_args(&mac.tokens.to_string())
+}
+
+fn parse_array_refs_args(args: &str) -> Option<(Vec<usize>, bool)> {
+    let args = args.replace(|c: char| c.is_whitespace(), "");
+    let mut parts = args.split(';');
+    let mut lengths = Vec::default();
+    for length in parts.next()?.split(',').skip(1) {
+        lengths.push(length.parse::<usize>().ok()?);
+    }
+    let has_rest = parts.any(|part| part.contains(".."));
+    Some((lengths, has_rest))
+}
+
+/// Collects the lengths of the first `array_refs!` of the match arms building a variant of an enum,
+/// like `(0, 34) => MarketInstruction::InitializeMarket({ let fields = array_refs![data, 8, 8]; ... })`
+struct ArmLayouts {
+    variant_prefix: String,
+    layouts: HashMap<String, Vec<usize>>,
+}
+impl<'ast> Visit<'ast> for ArmLayouts {
+    fn visit_arm(&mut self, arm: &'ast Arm) {
+        let body = arm.body.to_token_stream().to_string();
+        let variant = body.find(&self.variant_prefix).map(|pos| {
+            body[pos + self.variant_prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect::<String>()
+        });
+        let lengths = body.find("array_refs ! [").and_then(|pos| {
+            let args = &body[pos + "array_refs ! [".len()..];
+            parse_array_refs_args(&args[..args.find(']')?])
+        });
+        if let (Some(variant), Some((lengths, false))) = (variant, lengths) {
+            self.layouts.entry(variant).or_insert(lengths);
+        }
+        visit::visit_arm(self, arm);
+    }
+}
+
+fn int_value(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Lit(expr_lit) => match &expr_lit.lit {
+            Lit::Int(lit_int) => lit_int.base10_parse::<usize>().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn type_ident(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|seg| seg.ident.to_string()),
+        _ => None,
+    }
+}
+
+fn doc_lines(attrs: &Vec<Attribute>) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(name_value)) => match name_value.lit {
+                Lit::Str(lit_str) => Some(lit_str.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse an account line of the documentation of an instruction, like
+/// ``0. `[writable]` the market to initialize``, into its index and a name
+fn parse_account_doc(line: &str) -> Option<(usize, String)> {
+    let (index, rest) = line.split_at(line.find('.')?);
+    let index = index.trim().parse::<usize>().ok()?;
+    let rest = rest[1..].trim_start();
+    if !rest.starts_with("`[") {
+        return None;
+    }
+    let mut description = rest[rest[1..].find('`')? + 2..].to_string();
+    //Remove the comments in parentheses
+    while let (Some(start), Some(end)) = (description.find('('), description.find(')')) {
+        if end < start {
+            break;
+        }
+        description.replace_range(start..=end, "");
+    }
+    let mut words = Vec::default();
+    for word in description.split_whitespace() {
+        if ["the", "a", "an"].contains(&word.to_lowercase().as_str()) {
+            continue;
+        }
+        let end = word.find(|c: char| c == ',' || c == '.' || c == '(' || c == ';');
+        let word = &word[..end.unwrap_or(word.len())];
+        if !word.is_empty() {
+            words.push(word);
+        }
+        if end.is_some() || words.len() == 4 {
+            break;
+        }
+    }
+    Some((
+        index,
+        replace_invalid_identifier_chars(&words.join(" ").to_snake_case()),
+    ))
+}
+
+/// Offset and length of the tag of an enum
+fn tag_layout(items: &SourceItems, item_enum: &ItemEnum) -> (usize, usize) {
+    if let Some((lengths, true)) = items.unpack_layout(&item_enum.ident.to_string()) {
+        match lengths.as_slice() {
+            [tag_len] => return (0, *tag_len),
+            [offset, tag_len] => return (*offset, *tag_len),
+            _ => {}
+        }
+    }
+    for attr in item_enum.attrs.iter() {
+        if let Ok(Meta::List(meta_list)) = attr.parse_meta() {
+            for nested in meta_list.nested.iter() {
+                if let NestedMeta::Meta(Meta::Path(path)) = nested {
+                    if meta_list.path.is_ident("repr") {
+                        let tag_len = match path.get_ident().map(|ident| ident.to_string()) {
+                            Some(repr) if repr == "u16" || repr == "i16" => 2,
+                            Some(repr) if repr == "u32" || repr == "i32" => 4,
+                            Some(repr) if repr == "u64" || repr == "i64" => 8,
+                            _ => 1,
+                        };
+                        return (0, tag_len);
+                    }
+                }
+            }
+        }
+    }
+    //Borsh and the other cases
+    (0, 1)
+}
+
+/// Converts the instruction enum and collects the definitions of the types it uses
+struct StructureBuilder<'a, 'b> {
+    items: &'b SourceItems<'a>,
+    definitions: BTreeMap<String, Schema>,
+    pending: Vec<String>,
+    /// Field lengths of the structs read from the `unpack` of the enums using them
+    layouts: HashMap<String, Vec<usize>>,
+}
+impl<'a, 'b> StructureBuilder<'a, 'b> {
+    fn new(items: &'b SourceItems<'a>) -> Self {
+        StructureBuilder {
+            items,
+            definitions: BTreeMap::default(),
+            pending: Vec::default(),
+            layouts: HashMap::default(),
+        }
+    }
+    fn build(mut self, instruction_enum: &ItemEnum) -> Schema {
+        let mut schema = self.create_enum(instruction_enum, true);
+        let mut missing = HashSet::new();
+        while let Some(name) = self.pending.pop() {
+            if self.definitions.contains_key(&name) || missing.contains(&name) {
+                continue;
+            }
+            let definition = if let Some(item_struct) = self.items.structs.get(&name) {
+                self.create_struct(item_struct)
+            } else if let Some(item_enum) = self.items.enums.get(&name) {
+                self.create_enum(item_enum, false)
+            } else {
+                log::warn!("Definition of type {} is not found", &name);
+                missing.insert(name);
+                continue;
+            };
+            self.definitions.insert(name, definition);
+        }
+        schema.name = Some(instruction_enum.ident.to_string());
+        schema.definitions = self.definitions;
+        schema
+    }
+    fn create_enum(&mut self, item_enum: &ItemEnum, with_accounts: bool) -> Schema {
+        let (offset, tag_len) = tag_layout(self.items, item_enum);
+        let mut variant_layouts = self.items.variant_layouts(&item_enum.ident.to_string());
+        let mut next_tag = 0u64;
+        let mut variants = Vec::default();
+        for variant in item_enum.variants.iter() {
+            let variant_tag = match &variant.discriminant {
+                Some((_, expr)) => int_value(expr).map(|tag| tag as u64).unwrap_or(next_tag),
+                None => next_tag,
+            };
+            next_tag = variant_tag + 1;
+            let name = variant.ident.to_string();
+            let inner_type = match &variant.fields {
+                Fields::Unit => None,
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    let inner_type = self.data_type(&fields.unnamed[0].ty);
+                    if let Some(lengths) = variant_layouts.remove(&name) {
+                        self.layouts.entry(inner_type.clone()).or_insert(lengths);
+                    }
+                    Some(inner_type)
+                }
+                Fields::Unnamed(fields) => {
+                    let elements = fields
+                        .unnamed
+                        .iter()
+                        .map(|field| self.data_type(&field.ty))
+                        .collect::<Vec<String>>();
+                    Some(format!("({})", elements.join(", ")))
+                }
+                Fields::Named(_) => {
+                    let inner_name = format!("{}{}Args", &item_enum.ident, &name);
+                    let properties = self.create_properties(&variant.fields, None);
+                    self.definitions.insert(
+                        inner_name.clone(),
+                        Schema {
+                            properties: Some(properties),
+                            ..Default::default()
+                        },
+                    );
+                    Some(inner_name)
+                }
+            };
+            let docs = doc_lines(&variant.attrs);
+            let mut accounts = Vec::default();
+            for (index, account_name) in docs.iter().filter_map(|line| parse_account_doc(line)) {
+                let account_name = if account_name.is_empty()
+                    || accounts
+                        .iter()
+                        .any(|account: &AccountInfo| account.name == account_name)
+                {
+                    format!("account_{}", index)
+                } else {
+                    account_name
+                };
+                accounts.push(AccountInfo {
+                    index,
+                    name: account_name,
+                });
+            }
+            let description = docs
+                .iter()
+                .find(|line| !line.is_empty() && !line.contains("`["))
+                .cloned();
+            variants.push(Variant {
+                name,
+                value: None,
+                inner_name: None,
+                inner_type,
+                inner_scope: None,
+                description,
+                offset: None,
+                variant_tag,
+                accounts: if with_accounts { Some(accounts) } else { None },
+            });
+        }
+        Schema {
+            variants: Some(variants),
+            offset: if offset > 0 { Some(offset) } else { None },
+            variant_tag_length: Some(tag_len),
+            ..Default::default()
+        }
+    }
+    /// The lengths of the fields are taken from the `unpack` implementation of the struct if any
+    fn create_struct(&mut self, item_struct: &ItemStruct) -> Schema {
+        let lengths = self
+            .items
+            .unpack_layout(&item_struct.ident.to_string())
+            .map(|(lengths, _)| lengths)
+            .or_else(|| self.layouts.get(&item_struct.ident.to_string()).cloned())
+            .filter(|lengths| lengths.len() == item_struct.fields.len());
+        Schema {
+            properties: Some(self.create_properties(&item_struct.fields, lengths)),
+            ..Default::default()
+        }
+    }
+    fn create_properties(&mut self, fields: &Fields, lengths: Option<Vec<usize>>) -> Vec<Property> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(ind, field)| {
+                let name = match &field.ident {
+                    Some(ident) => ident.to_string(),
+                    None => format!("field_{}", ind),
+                };
+                let (data_type, array_length) = match &field.ty {
+                    Type::Array(type_array) => {
+                        let array_length =
+                            int_value(&type_array.len).or_else(|| match &type_array.len {
+                                Expr::Path(expr_path) => expr_path
+                                    .path
+                                    .get_ident()
+                                    .and_then(|ident| self.items.consts.get(&ident.to_string()))
+                                    .copied(),
+                                _ => None,
+                            });
+                        if array_length.is_none() {
+                            log::warn!("Length of array field {} is not found", &name);
+                        }
+                        (self.data_type(&type_array.elem), array_length)
+                    }
+                    ty => (self.data_type(ty), None),
+                };
+                let description = doc_lines(&field.attrs).join(" ");
+                Property {
+                    name,
+                    data_type,
+                    length: lengths.as_ref().map(|lengths| lengths[ind]),
+                    array_length,
+                    required: true,
+                    description: if description.is_empty() {
+                        None
+                    } else {
+                        Some(description)
+                    },
+                }
+            })
+            .collect()
+    }
+    /// Data type of a Rust type, the user defined types are added to the pending definitions.
+    /// Unsupported types are kept as written with a warning.
+    fn data_type(&mut self, ty: &Type) -> String {
+        match self.convert_type(ty) {
+            Some(data_type) => data_type,
+            None => {
+                let data_type = ty.to_token_stream().to_string();
+                log::warn!("Type {} is not supported", &data_type);
+                data_type
+            }
+        }
+    }
+    fn convert_type(&mut self, ty: &Type) -> Option<String> {
+        match ty {
+            Type::Path(type_path) => {
+                let segment = type_path.path.segments.last()?;
+                let ident = segment.ident.to_string();
+                let mut args = Vec::default();
+                if let PathArguments::AngleBracketed(generics) = &segment.arguments {
+                    for arg in generics.args.iter() {
+                        if let GenericArgument::Type(arg_type) = arg {
+                            args.push(self.convert_type(arg_type)?);
+                        }
+                    }
+                }
+                match (ident.as_str(), args.as_slice()) {
+                    ("Vec", [inner]) | ("Option", [inner]) => Some(format!("{}<{}>", ident, inner)),
+                    ("Box", [inner]) => Some(inner.clone()),
+                    (name, [])
+                        if PRIMITIVE_TYPES.contains(&name) || name.starts_with("NonZero") =>
+                    {
+                        Some(ident)
+                    }
+                    (_, []) => {
+                        self.pending.push(ident.clone());
+                        Some(ident)
+                    }
+                    _ => None,
+                }
+            }
+            Type::Tuple(type_tuple) if !type_tuple.elems.is_empty() => {
+                let mut elements = Vec::default();
+                for elem in type_tuple.elems.iter() {
+                    elements.push(self.convert_type(elem)?);
+                }
+                Some(format!("({})", elements.join(", ")))
+            }
+            Type::Paren(type_paren) => self.convert_type(&type_paren.elem),
+            Type::Group(type_group) => self.convert_type(&type_group.elem),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSTRUCTION_SOURCE: &str = r#"
+use crate::state::{Side, MAX_ORDERS};
+
+/// Instructions of the market
+#[repr(u32)]
+pub enum MarketInstruction {
+    /// Creates the market
+    ///
+    /// 0. `[writable]` the market to initialize
+    /// 1. `[signer]` the authority (the owner), which pays the fees
+    /// 2. `[]` market
+    InitializeMarket(InitializeMarketArgs),
+    /// Places an order
+    NewOrder {
+        side: Side,
+        limit_price: NonZeroU64,
+        client_ids: [u64; MAX_ORDERS],
+    },
+    CancelOrder(u128, Pubkey) = 5,
+    Settle,
+}
+
+pub struct InitializeMarketArgs {
+    /// Lot size of the base token
+    pub base_lot_size: u64,
+    pub fee_rate_bps: u16,
+    pub owner: Option<Pubkey>,
+}
+
+impl MarketInstruction {
+    pub fn unpack(input: &[u8]) -> Option<Self> {
+        let (tag, data) = array_refs![input, 4; ..;];
+        Some(match u32::from_le_bytes(*tag) {
+            0 => MarketInstruction::InitializeMarket({
+                let fields = array_refs![data, 8, 2, 33];
+                InitializeMarketArgs::default()
+            }),
+            _ => return None,
+        })
+    }
+}
+"#;
+
+    const STATE_SOURCE: &str = r#"
+pub const MAX_ORDERS: usize = 8;
+
+pub enum Side {
+    Bid,
+    Ask,
+}
+"#;
+
+    fn variant<'a>(schema: &'a Schema, name: &str) -> &'a Variant {
+        schema
+            .variants
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|variant| variant.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_array_refs_args() {
+        assert_eq!(
+            parse_array_refs_args("input , 1 , 4 ; .. ;"),
+            Some((vec![1, 4], true))
+        );
+        assert_eq!(
+            parse_array_refs_args("data, 8, 32, 2"),
+            Some((vec![8, 32, 2], false))
+        );
+        assert_eq!(parse_array_refs_args("data, LEN"), None);
+    }
+
+    #[test]
+    fn parses_account_docs() {
+        assert_eq!(
+            parse_account_doc("0. `[writable]` the market to initialize"),
+            Some((0, String::from("market_to_initialize")))
+        );
+        assert_eq!(
+            parse_account_doc("12. `[signer]` The owner (optional), pays the fees"),
+            Some((12, String::from("owner")))
+        );
+        assert_eq!(
+            parse_account_doc("3. `[]` a very long account description of the program"),
+            Some((3, String::from("very_long_account_description")))
+        );
+        assert_eq!(parse_account_doc("Creates the market."), None);
+        assert_eq!(parse_account_doc("1. without account flags"), None);
+    }
+
+    #[test]
+    fn collects_use_paths() {
+        let file = syn::parse_file(
+            "use crate::state::{Side, orders::*, MAX_ORDERS as MAX};\nuse super::error;",
+        )
+        .unwrap();
+        let mut paths = Vec::default();
+        for item in file.items.iter() {
+            if let Item::Use(item_use) = item {
+                collect_use_paths(&item_use.tree, &mut Vec::default(), &mut paths);
+            }
+        }
+        assert_eq!(
+            paths,
+            vec![
+                vec!["crate", "state", "Side"],
+                vec!["crate", "state", "orders"],
+                vec!["crate", "state", "MAX_ORDERS"],
+                vec!["super", "error"],
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_instruction_structure() {
+        let sources = vec![
+            syn::parse_file(INSTRUCTION_SOURCE).unwrap(),
+            syn::parse_file(STATE_SOURCE).unwrap(),
+        ];
+        let items = SourceItems::new(&sources);
+        let instruction_enum = items.enums.get("MarketInstruction").copied().unwrap();
+        let schema = StructureBuilder::new(&items).build(instruction_enum);
+
+        assert_eq!(schema.name.as_deref(), Some("MarketInstruction"));
+        // The tag length of the `array_refs!` wins over `#[repr(u32)]`
+        assert_eq!(schema.variant_tag_length, Some(4));
+        assert_eq!(schema.offset, None);
+
+        let initialize = variant(&schema, "InitializeMarket");
+        assert_eq!(initialize.variant_tag, 0);
+        assert_eq!(
+            initialize.inner_type.as_deref(),
+            Some("InitializeMarketArgs")
+        );
+        assert_eq!(
+            initialize.description.as_deref(),
+            Some("Creates the market")
+        );
+        let accounts = initialize
+            .accounts
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|account| (account.index, account.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            accounts,
+            vec![(0, "market_to_initialize"), (1, "authority"), (2, "market")]
+        );
+        assert_eq!(
+            variant(&schema, "NewOrder").inner_type.as_deref(),
+            Some("MarketInstructionNewOrderArgs")
+        );
+        let cancel = variant(&schema, "CancelOrder");
+        assert_eq!(cancel.variant_tag, 5);
+        assert_eq!(cancel.inner_type.as_deref(), Some("(u128, Pubkey)"));
+        let settle = variant(&schema, "Settle");
+        assert_eq!(settle.variant_tag, 6);
+        assert_eq!(settle.inner_type, None);
+
+        let args = schema.definitions.get("InitializeMarketArgs").unwrap();
+        let properties = args.properties.as_ref().unwrap();
+        let fields = properties
+            .iter()
+            .map(|property| {
+                (
+                    property.name.as_str(),
+                    property.data_type.as_str(),
+                    property.length,
+                )
+            })
+            .collect::<Vec<_>>();
+        // Lengths come from the `array_refs!` of the arm building the variant
+        assert_eq!(
+            fields,
+            vec![
+                ("base_lot_size", "u64", Some(8)),
+                ("fee_rate_bps", "u16", Some(2)),
+                ("owner", "Option<Pubkey>", Some(33)),
+            ]
+        );
+        assert_eq!(
+            properties[0].description.as_deref(),
+            Some("Lot size of the base token")
+        );
+
+        let new_order = schema
+            .definitions
+            .get("MarketInstructionNewOrderArgs")
+            .unwrap();
+        let properties = new_order.properties.as_ref().unwrap();
+        assert_eq!(properties[0].data_type, "Side");
+        assert_eq!(properties[1].data_type, "NonZeroU64");
+        assert_eq!(properties[2].data_type, "u64");
+        assert_eq!(properties[2].array_length, Some(8));
+
+        let side = schema.definitions.get("Side").unwrap();
+        assert_eq!(side.variant_tag_length, Some(1));
+        let side_variants = side.variants.as_ref().unwrap();
+        assert_eq!(side_variants.len(), 2);
+        assert!(side_variants
+            .iter()
+            .all(|variant| variant.accounts.is_none()));
+    }
+
+    #[test]
+    fn keeps_unsupported_types_as_written() {
+        let sources = vec![syn::parse_file(
+            "pub enum TokenInstruction { Transfer { amount: HashMap<u8, u64> }, Burn(&'static str) }",
+        )
+        .unwrap()];
+        let items = SourceItems::new(&sources);
+        let instruction_enum = items.enums.get("TokenInstruction").copied().unwrap();
+        let schema = StructureBuilder::new(&items).build(instruction_enum);
+        let transfer = schema
+            .definitions
+            .get("TokenInstructionTransferArgs")
+            .unwrap();
+        assert_eq!(
+            transfer.properties.as_ref().unwrap()[0].data_type,
+            "HashMap < u8 , u64 >"
+        );
+        assert_eq!(
+            variant(&schema, "Burn").inner_type.as_deref(),
+            Some("& 'static str")
+        );
+    }
+
+    #[test]
+    fn loads_modules_used_by_the_instruction_file() {
+        let dir = std::env::temp_dir().join(format!("schema_builder_{}", std::process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("state")).unwrap();
+        fs::write(src.join("lib.rs"), "pub mod instruction;\npub mod state;").unwrap();
+        fs::write(src.join("instruction.rs"), INSTRUCTION_SOURCE).unwrap();
+        fs::write(src.join("state").join("mod.rs"), STATE_SOURCE).unwrap();
+
+        let instruction_path = src.join("instruction.rs");
+        assert_eq!(
+            resolve_module_file(
+                &instruction_path,
+                &vec![
+                    String::from("crate"),
+                    String::from("state"),
+                    String::from("Side")
+                ]
+            ),
+            Some(src.join("state").join("mod.rs"))
+        );
+        assert_eq!(
+            resolve_module_file(
+                &instruction_path,
+                &vec![String::from("std"), String::from("mem")]
+            ),
+            None
+        );
+
+        let output_path = dir.join("instruction.json");
+        let builder = SchemaBuilder::builder()
+            .with_instruction_path(instruction_path.to_str().unwrap())
+            .with_output_dir(output_path.to_str().unwrap());
+        assert_eq!(builder.build().unwrap(), output_path);
+        assert!(builder
+            .with_instruction_name("UnknownInstruction")
+            .build()
+            .unwrap_err()
+            .to_string()
+            .contains("No instruction enum found"));
+        assert!(SchemaBuilder::builder()
+            .with_instruction_path(dir.join("missing.rs").to_str().unwrap())
+            .build()
+            .is_err());
+        let content = fs::read_to_string(&output_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let schema: Schema = serde_json::from_str(&content).unwrap();
+        assert_eq!(schema.name.as_deref(), Some("MarketInstruction"));
+        assert!(schema.definitions.contains_key("Side"));
+        assert_eq!(
+            schema
+                .definitions
+                .get("MarketInstructionNewOrderArgs")
+                .unwrap()
+                .properties
+                .as_ref()
+                .unwrap()[2]
+                .array_length,
+            Some(8)
+        );
+    }
+}
@@ -23,7 +23,7 @@ pub struct Variant {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub offset: Option<u16>,
     #[serde(rename = "variantTag")]
-    pub variant_tag: u32,
+    pub variant_tag: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub accounts: Option<Vec<AccountInfo>>,
 }
@@ -40,6 +40,32 @@ impl Variant {
         })
     }
 }
+/// Layout of the state stored in the accounts owned by the program.
+/// An account is decoded with the first layout whose `size` and `discriminator` match its data.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountLayout {
+    pub name: String,
+    #[serde(rename = "innerType")]
+    pub inner_type: String,
+    /// Bytes before the state, e.g. the padding of Serum accounts or the Anchor discriminator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// Length of the account data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// First bytes of the account data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discriminator: Option<Vec<u8>>,
+}
+/// Layout of an event the program logs as `Program data: <base64>`: the `discriminator`
+/// followed by the event unpacked as the `innerType` definition
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EventLayout {
+    pub name: String,
+    #[serde(rename = "innerType")]
+    pub inner_type: String,
+    pub discriminator: Vec<u8>,
+}
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AccountInfo {
     pub index: usize,
@@ -60,23 +86,122 @@ pub struct Property {
     pub description: Option<String>,
 }
 impl Property {
-    pub fn get_size(&self, definitions: &BTreeMap<String, Schema>) -> usize {
+    /// Size of the property in bytes, `None` if it has a variable size
+    pub fn get_size(&self, definitions: &BTreeMap<String, Schema>) -> Option<usize> {
         match self.length {
-            None => match self.data_type.as_str() {
-                "u8" | "i8" => 1_usize,
-                "u16" | "i16" => 2_usize,
-                "u32" | "i32" => 4_usize,
-                "u64" | "i64" => 8_usize,
-                "u128" | "i128" => 16_usize,
+            None => {
+                let size = DataType::parse(&self.data_type)?.get_size(definitions)?;
+                match self.array_length {
+                    Some(array_length) => Some(size * array_length),
+                    None => Some(size),
+                }
+            }
+            Some(len) => Some(len),
+        }
+    }
+}
+/// Rust type of a property or an inner type, e.g. `u64`, `Vec<Pubkey>` or `(u8, Option<String>)`.
+/// Data is unpacked in Borsh layout: `String` and `Vec` are prefixed by their u32 length,
+/// `Option` by an 1 byte tag.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DataType {
+    Named(String),
+    Vec(Box<DataType>),
+    Option(Box<DataType>),
+    Tuple(Vec<DataType>),
+}
+impl DataType {
+    pub fn parse(data_type: &str) -> Option<DataType> {
+        let data_type = data_type.trim();
+        if data_type.starts_with('(') && data_type.ends_with(')') {
+            let mut elements = Vec::default();
+            for element in split_top_level(&data_type[1..data_type.len() - 1]) {
+                if !element.trim().is_empty() {
+                    elements.push(DataType::parse(element)?);
+                }
+            }
+            Some(DataType::Tuple(elements))
+        } else if let (Some(start), true) = (data_type.find('<'), data_type.ends_with('>')) {
+            let inner = Box::new(DataType::parse(&data_type[start + 1..data_type.len() - 1])?);
+            match data_type[..start].trim() {
+                "Vec" => Some(DataType::Vec(inner)),
+                "Option" => Some(DataType::Option(inner)),
+                _ => None,
+            }
+        } else if !data_type.is_empty()
+            && data_type
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+        {
+            Some(DataType::Named(data_type.to_string()))
+        } else {
+            None
+        }
+    }
+    /// Size of the type in bytes, `None` if it has a variable size
+    pub fn get_size(&self, definitions: &BTreeMap<String, Schema>) -> Option<usize> {
+        match self {
+            DataType::Named(name) => match name.as_str() {
+                "bool" | "u8" | "i8" | "NonZeroU8" | "NonZeroI8" => Some(1),
+                "u16" | "i16" | "NonZeroU16" | "NonZeroI16" => Some(2),
+                "u32" | "i32" | "NonZeroU32" | "NonZeroI32" => Some(4),
+                "u64" | "i64" | "NonZeroU64" | "NonZeroI64" => Some(8),
+                "u128" | "i128" | "NonZeroU128" | "NonZeroI128" => Some(16),
+                "Pubkey" => Some(32),
                 type_name => definitions
                     .get(type_name)
-                    .and_then(|def_schema| def_schema.get_size(definitions))
-                    .unwrap_or(1usize),
+                    .and_then(|def_schema| def_schema.get_size(definitions)),
             },
-            Some(len) => len,
+            DataType::Tuple(elements) => {
+                let mut size = 0usize;
+                for element in elements {
+                    size = size + element.get_size(definitions)?;
+                }
+                Some(size)
+            }
+            DataType::Vec(_) | DataType::Option(_) => None,
+        }
+    }
+}
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Named(name) => write!(f, "{}", name),
+            DataType::Vec(inner) => write!(f, "Vec<{}>", inner),
+            DataType::Option(inner) => write!(f, "Option<{}>", inner),
+            DataType::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>();
+                if elements.len() == 1 {
+                    write!(f, "({},)", elements[0])
+                } else {
+                    write!(f, "({})", elements.join(", "))
+                }
+            }
         }
     }
 }
+/// Split a comma separated list of types, ignoring the commas of nested types
+fn split_top_level(types: &str) -> Vec<&str> {
+    let mut result = Vec::default();
+    let mut depth = 0;
+    let mut start = 0;
+    for (ind, c) in types.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&types[start..ind]);
+                start = ind + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&types[start..]);
+    result
+}
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename = "variantTag")]
 pub enum VariantTag {
@@ -126,20 +251,32 @@ pub struct Schema {
     pub offset: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "variantTagLength")]
     pub variant_tag_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<AccountLayout>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventLayout>>,
 }
 
 impl Schema {
     pub fn get_pascal_name(&self, name: &String) -> String {
         replace_invalid_identifier_chars(&name.as_str().to_pascal_case())
     }
-    //Get size of struct type
+    //Get size of struct type or of an enum without data, None if it is variable
     pub fn get_size(&self, definitions: &BTreeMap<String, Schema>) -> Option<usize> {
-        self.properties.as_ref().and_then(|properties| {
+        if let Some(properties) = &self.properties {
             let mut size = 0usize;
             for property in properties {
-                size = size + property.get_size(definitions);
+                size = size + property.get_size(definitions)?;
             }
             Some(size)
-        })
+        } else if let Some(variants) = &self.variants {
+            if variants.iter().all(|variant| variant.inner_type.is_none()) {
+                Some(self.offset.unwrap_or_default() + self.variant_tag_length.unwrap_or(1))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
     }
 }
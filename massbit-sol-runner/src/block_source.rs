@@ -0,0 +1,139 @@
+use chain_solana::adapter::SolanaAdapter;
+use chain_solana::types::{ChainConfig, ConfirmedBlockWithSlot, SolanaFilter};
+use massbit_solana_sdk::types::{ExtBlock, SolanaBlock};
+use massbit_solana_test_harness::fixture::{fixture_files, read_fixture};
+use std::path::PathBuf;
+
+/// Version of the blocks sent by chain-reader
+const BLOCK_VERSION: &str = "1.7.0";
+/// Number of blocks fetched from RPC before calling the indexer
+const RPC_BLOCK_BATCH_SIZE: usize = 10;
+
+/// Blocks fed to an indexer run locally
+#[derive(Clone, Debug)]
+pub enum BlockSource {
+    /// Blocks of the slot range `from_slot..=to_slot` fetched from a Solana RPC node
+    Rpc {
+        url: String,
+        from_slot: u64,
+        to_slot: u64,
+    },
+    /// Block fixtures: a file or a directory of `.json` and `.json.gz` files, each one with a block or a list of blocks
+    /// like the payload of the chain-reader `BlockResponse`
+    Fixture(PathBuf),
+}
+
+impl BlockSource {
+    /// Calls `handle` with the batches of blocks in slot order. Like chain-reader, only the transactions
+    /// matching the filter are kept and the blocks without matching transactions are skipped.
+    pub async fn read_blocks<F>(
+        &self,
+        filter: &SolanaFilter,
+        mut handle: F,
+    ) -> Result<(), anyhow::Error>
+    where
+        F: FnMut(Vec<SolanaBlock>) -> Result<(), anyhow::Error>,
+    {
+        match self {
+            BlockSource::Rpc {
+                url,
+                from_slot,
+                to_slot,
+            } => {
+                let adapter = SolanaAdapter::new(&ChainConfig {
+                    url: url.clone(),
+                    ws: String::default(),
+                    name: String::from("mainnet"),
+                    network: String::from("mainnet"),
+                    supports_eip_1898: false,
+                });
+                let slots = adapter.rpc_client.get_blocks(*from_slot, Some(*to_slot))?;
+                println!(
+                    "Fetching {} blocks from {} to {} from {}",
+                    slots.len(),
+                    from_slot,
+                    to_slot,
+                    url
+                );
+                for batch in slots.chunks(RPC_BLOCK_BATCH_SIZE) {
+                    let mut blocks = vec![];
+                    for slot in batch {
+                        match adapter.get_block_data(*slot).await {
+                            Ok(block) => blocks.extend(create_ext_block(block, filter)),
+                            Err(err) => log::warn!("Cannot get block {}: {:?}", slot, err),
+                        }
+                    }
+                    if !blocks.is_empty() {
+                        handle(blocks)?;
+                    }
+                }
+            }
+            BlockSource::Fixture(path) => {
+                for file in fixture_files(path)? {
+                    let mut blocks = read_fixture(&file)?
+                        .into_iter()
+                        .filter_map(|block| filter_block(block, filter))
+                        .collect::<Vec<SolanaBlock>>();
+                    blocks.sort_by_key(|block| block.block_number);
+                    println!("Read {} blocks from {:?}", blocks.len(), &file);
+                    if !blocks.is_empty() {
+                        handle(blocks)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn create_ext_block(block: ConfirmedBlockWithSlot, filter: &SolanaFilter) -> Option<SolanaBlock> {
+    let ConfirmedBlockWithSlot { block_slot, block } = block;
+    let block = block?;
+    filter_block(
+        ExtBlock {
+            version: BLOCK_VERSION.to_string(),
+            timestamp: block.block_time.unwrap_or_default(),
+            block_number: block_slot,
+            block,
+            list_log_messages: vec![],
+            // The node does not keep account states of past slots
+            accounts: vec![],
+        },
+        filter,
+    )
+}
+
+/// Keeps the matching transactions of a block, their log messages and the snapshots of the
+/// accounts owned by the filter keys
+fn filter_block(block: SolanaBlock, filter: &SolanaFilter) -> Option<SolanaBlock> {
+    let ExtBlock {
+        version,
+        timestamp,
+        block_number,
+        block,
+        accounts,
+        ..
+    } = block;
+    let block = filter.filter_block(block);
+    if block.transactions.is_empty() {
+        return None;
+    }
+    let list_log_messages = block
+        .transactions
+        .iter()
+        .map(|transaction| {
+            transaction
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.log_messages.clone())
+        })
+        .collect();
+    Some(ExtBlock {
+        version,
+        timestamp,
+        block_number,
+        block,
+        list_log_messages,
+        accounts: filter.filter_accounts(accounts),
+    })
+}
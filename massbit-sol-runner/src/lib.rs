@@ -0,0 +1,205 @@
+//! Runs the mapping library of an indexer project locally, on blocks read from a Solana RPC node
+//! or from fixtures, and records the blocks sent by chain-reader as fixtures. Kept apart from
+//! `massbit-sol` because it links the indexer-manager store and its Postgres dependencies.
+pub mod block_source;
+pub mod recorder;
+pub mod store;
+
+pub use block_source::BlockSource;
+pub use massbit_solana_test_harness::{MemoryStore, SharedStore};
+pub use recorder::record_blocks;
+pub use store::{BlockDiff, DiffStore, EntityChange};
+
+use anyhow::{anyhow, bail};
+use chain_solana::types::SolanaFilter;
+use indexer_manager::store::StoreBuilder;
+use lazy_static::lazy_static;
+use libloading::Library;
+use massbit_common::prelude::diesel::PgConnection;
+use massbit_data::indexer::DeploymentHash;
+use massbit_solana_sdk::plugin::handler::SolanaHandler;
+use massbit_solana_sdk::plugin::proxy::SolanaHandlerProxy;
+use massbit_solana_sdk::plugin::{check_plugin_version, AdapterDeclaration, PluginRegistrar};
+use massbit_solana_sdk::store::IndexStore;
+use massbit_storage_postgres::helper::create_r2d2_connection_pool;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::{env, fs};
+
+lazy_static! {
+    pub static ref CHAIN_READER_URL: String =
+        env::var("CHAIN_READER_URL").unwrap_or(String::from("http://127.0.0.1:50051"));
+}
+pub const SO_FILE_NAME: &str = "libblock.so";
+pub const SO_FOLDER: &str = "target/release";
+pub const SCHEMA_FILE_NAME: &str = "schema.graphql";
+pub const SUBGRAPH_FILE_NAME: &str = "subgraph.yaml";
+pub const SRC_FOLDER: &str = "src";
+/// Database schema and indexer hash of the indexers run locally
+pub const DEV_INDEXER_NAMESPACE: &str = "dev_indexer";
+
+/// Handler registered by the mapping library, the same way as in the indexer-manager runtime
+pub struct IndexerHandler {
+    pub lib: Arc<Library>,
+    pub handler_proxies: Option<Arc<SolanaHandlerProxy>>,
+}
+impl IndexerHandler {
+    fn new(lib: Arc<Library>) -> IndexerHandler {
+        IndexerHandler {
+            lib,
+            handler_proxies: None,
+        }
+    }
+}
+impl PluginRegistrar for IndexerHandler {
+    fn register_solana_handler(&mut self, handler: Box<dyn SolanaHandler + Send + Sync>) {
+        self.handler_proxies = Some(Arc::new(SolanaHandlerProxy::new(handler)));
+    }
+}
+
+/// Builds an indexer project and runs its mapping library on a range of blocks,
+/// printing the entities changed by each block.
+#[derive(Debug)]
+pub struct IndexerRunner {
+    pub project_dir: PathBuf,
+    pub source: BlockSource,
+    /// Postgres database of the entities, they are kept in memory if None
+    pub database_url: Option<String>,
+    pub build: bool,
+}
+
+impl IndexerRunner {
+    pub fn new(project_dir: &str, source: BlockSource) -> Self {
+        IndexerRunner {
+            project_dir: PathBuf::from(project_dir),
+            source,
+            database_url: None,
+            build: true,
+        }
+    }
+    pub fn with_database_url(mut self, database_url: Option<&str>) -> Self {
+        self.database_url = database_url.map(String::from);
+        self
+    }
+    pub fn with_build(mut self, build: bool) -> Self {
+        self.build = build;
+        self
+    }
+
+    pub fn run(&self) -> Result<(), anyhow::Error> {
+        if self.build {
+            self.build_indexer()?;
+        }
+        let runtime = tokio::runtime::Runtime::new()?;
+        runtime.block_on(self.run_indexer())
+    }
+
+    fn build_indexer(&self) -> Result<(), anyhow::Error> {
+        println!("Building indexer in {:?}", &self.project_dir);
+        let status = Command::new("cargo")
+            .args(&["build", "--release"])
+            .current_dir(&self.project_dir)
+            .status()?;
+        if !status.success() {
+            bail!("Cannot build indexer in {:?}", &self.project_dir);
+        }
+        Ok(())
+    }
+
+    async fn run_indexer(&self) -> Result<(), anyhow::Error> {
+        let (address, network) = read_manifest(&self.project_dir)?;
+        if let BlockSource::Rpc { url, .. } = &self.source {
+            // The RPC client of the generated library uses the same node
+            env::set_var("SOLANA_RPC_URL", url);
+        }
+        let inner_store: Box<dyn IndexStore> = match &self.database_url {
+            Some(database_url) => Box::new(self.create_postgres_store(database_url, &network)?),
+            None => Box::new(MemoryStore::new()),
+        };
+        // The mapping gets its own handle, the diffs are taken through the other one between the handler calls
+        let store = SharedStore::new(DiffStore::new(inner_store));
+        let mut mapping_store = store.clone();
+        let library_path = self.project_dir.join(SO_FOLDER).join(SO_FILE_NAME);
+        let handler = unsafe { load_mapping_library(&library_path, &mut mapping_store)? };
+        let proxy = handler
+            .handler_proxies
+            .clone()
+            .ok_or_else(|| anyhow!("No Solana handler is registered by {:?}", &library_path))?;
+        let filter = SolanaFilter::new(address.iter().map(String::as_str).collect());
+        let mut block_count = 0;
+        let mut entity_count = 0;
+        self.source
+            .read_blocks(&filter, |blocks| {
+                block_count += blocks.len();
+                proxy
+                    .handle_blocks(&blocks)
+                    .map_err(|err| anyhow!("Error while handling blocks: {}", err))?;
+                let diffs = store.lock().take_diffs();
+                for diff in diffs {
+                    entity_count += diff.changes.len();
+                    print!("{}", diff.to_text());
+                }
+                Ok(())
+            })
+            .await?;
+        println!(
+            "Processed {} blocks, {} entity changes",
+            block_count, entity_count
+        );
+        // The handler must be dropped before the library and the store it uses
+        drop(proxy);
+        drop(handler);
+        Ok(())
+    }
+
+    fn create_postgres_store(
+        &self,
+        database_url: &str,
+        network: &str,
+    ) -> Result<impl IndexStore, anyhow::Error> {
+        let connection_pool = create_r2d2_connection_pool::<PgConnection>(database_url, 1);
+        let schema_path = self.project_dir.join(SRC_FOLDER).join(SCHEMA_FILE_NAME);
+        let deployment_hash = DeploymentHash::new("_indexer").map_err(|err| anyhow!(err))?;
+        StoreBuilder::create_store(
+            Arc::new(connection_pool),
+            DEV_INDEXER_NAMESPACE,
+            network.to_string(),
+            DEV_INDEXER_NAMESPACE.to_string(),
+            schema_path,
+            deployment_hash,
+        )
+    }
+}
+
+/// Address and network of the first data source of the manifest of a project
+pub fn read_manifest(project_dir: &Path) -> Result<(Option<String>, String), anyhow::Error> {
+    let manifest_path = project_dir.join(SRC_FOLDER).join(SUBGRAPH_FILE_NAME);
+    let manifest: serde_yaml::Value = serde_yaml::from_str(&fs::read_to_string(&manifest_path)?)?;
+    let data_source = &manifest["dataSources"][0];
+    let address = data_source["source"]["address"].as_str().map(String::from);
+    let network = data_source["network"]
+        .as_str()
+        .unwrap_or("mainnet")
+        .to_string();
+    Ok((address, network))
+}
+
+/// Loads a mapping library like `IndexerRuntime::load_mapping_library`:
+/// injects the store into its `STORE` and registers its handler.
+/// The store must outlive the returned handler.
+pub unsafe fn load_mapping_library(
+    library_path: &Path,
+    store: &mut dyn IndexStore,
+) -> Result<IndexerHandler, anyhow::Error> {
+    let lib = Arc::new(Library::new(library_path)?);
+    check_plugin_version(&lib).map_err(|err| anyhow!(err))?;
+    lib.get::<*mut Option<&mut dyn IndexStore>>(b"STORE\0")?
+        .write(Some(store));
+    let adapter_decl = lib
+        .get::<*mut AdapterDeclaration>(b"adapter_declaration\0")?
+        .read();
+    let mut registrar = IndexerHandler::new(lib);
+    (adapter_decl.register)(&mut registrar);
+    Ok(registrar)
+}
@@ -0,0 +1,183 @@
+use clap::{App, Arg};
+use logger::core::init_logger;
+use massbit_sol_runner::{record_blocks, BlockSource, IndexerRunner, CHAIN_READER_URL};
+use std::process;
+
+fn main() {
+    let _res = init_logger(&String::from("massbit-sol-runner"));
+    let matches = App::new("massbit-sol-runner")
+        .version("1.0")
+        .about("Run Massbit Solana indexers locally")
+        .subcommand(create_run_cmd())
+        .subcommand(create_record_cmd())
+        .get_matches();
+    if let Some(ref matches) = matches.subcommand_matches("run") {
+        let project_dir = matches.value_of("project-dir").unwrap_or("./");
+        let source = match matches.value_of("blocks") {
+            Some(path) => BlockSource::Fixture(path.into()),
+            None => {
+                let from_slot = matches
+                    .value_of("from")
+                    .and_then(|slot| slot.parse::<u64>().ok());
+                let to_slot = matches
+                    .value_of("to")
+                    .and_then(|slot| slot.parse::<u64>().ok());
+                match (from_slot, to_slot) {
+                    (Some(from_slot), Some(to_slot)) => BlockSource::Rpc {
+                        url: matches
+                            .value_of("rpc-url")
+                            .map(String::from)
+                            .or_else(|| std::env::var("SOLANA_RPC_URL").ok())
+                            .unwrap_or_else(|| String::from("http://194.163.156.242:8899")),
+                        from_slot,
+                        to_slot,
+                    },
+                    _ => {
+                        log::error!(
+                            "Input the block fixtures or the slot range with --from and --to"
+                        );
+                        process::exit(1);
+                    }
+                }
+            }
+        };
+        let runner = IndexerRunner::new(project_dir, source)
+            .with_database_url(matches.value_of("database-url"))
+            .with_build(!matches.is_present("skip-build"));
+        if let Err(err) = runner.run() {
+            log::error!("Error {:?}", &err);
+            process::exit(1);
+        }
+    } else if let Some(ref matches) = matches.subcommand_matches("record") {
+        let project_dir = matches.value_of("project-dir").unwrap_or("./");
+        let chain_reader_url = matches
+            .value_of("chain-reader-url")
+            .unwrap_or(CHAIN_READER_URL.as_str());
+        let output = matches.value_of("output").unwrap_or("fixtures");
+        let start_block = matches
+            .value_of("start-block")
+            .and_then(|slot| slot.parse::<u64>().ok());
+        let block_count = matches
+            .value_of("count")
+            .and_then(|count| count.parse::<usize>().ok())
+            .unwrap_or(100);
+        match record_blocks(
+            project_dir,
+            chain_reader_url,
+            output,
+            start_block,
+            block_count,
+        ) {
+            Ok(recorded) => {
+                println!("Recorded {} blocks in {}", recorded, output);
+            }
+            Err(err) => {
+                log::error!("Error {:?}", &err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn create_run_cmd() -> App<'static, 'static> {
+    App::new("run")
+        .about("Build and run indexer locally on a range of blocks")
+        .arg(
+            Arg::with_name("project-dir")
+                .short("d")
+                .long("project-dir")
+                .value_name("project-dir")
+                .help("Indexer directory")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .value_name("from")
+                .help("First slot of the blocks fetched from RPC")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("to")
+                .short("t")
+                .long("to")
+                .value_name("to")
+                .help("Last slot of the blocks fetched from RPC")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-url")
+                .short("u")
+                .long("rpc-url")
+                .value_name("rpc-url")
+                .help("Solana RPC url, by default SOLANA_RPC_URL")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("blocks")
+                .short("b")
+                .long("blocks")
+                .value_name("blocks")
+                .help("JSON block fixture file or directory, used instead of RPC")
+                .conflicts_with_all(&["from", "to", "rpc-url"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("database-url")
+                .long("database-url")
+                .value_name("database-url")
+                .help("Postgres database of the entities, by default they are kept in memory")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("skip-build")
+                .long("skip-build")
+                .help("Run the last built library"),
+        )
+}
+
+fn create_record_cmd() -> App<'static, 'static> {
+    App::new("record")
+        .about("Record the blocks sent by chain-reader to an indexer as fixtures")
+        .arg(
+            Arg::with_name("project-dir")
+                .short("d")
+                .long("project-dir")
+                .value_name("project-dir")
+                .help("Indexer directory")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chain-reader-url")
+                .short("u")
+                .long("chain-reader-url")
+                .value_name("chain-reader-url")
+                .help("Chain reader url, by default CHAIN_READER_URL")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("output")
+                .help("Output directory of the fixtures")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("start-block")
+                .short("s")
+                .long("start-block")
+                .value_name("start-block")
+                .help("First slot requested to chain-reader")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("count")
+                .short("n")
+                .long("count")
+                .value_name("count")
+                .help("Number of blocks to record, 100 by default")
+                .takes_value(true),
+        )
+}
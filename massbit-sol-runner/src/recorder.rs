@@ -0,0 +1,77 @@
+use crate::read_manifest;
+use chain_solana::types::SolanaFilter;
+use massbit_grpc::firehose::bstream::stream_client::StreamClient;
+use massbit_grpc::firehose::bstream::{BlockRequest, ChainType};
+use massbit_solana_sdk::types::SolanaBlock;
+use massbit_solana_test_harness::fixture::{fixture_name, write_fixture};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::transport::Channel;
+use tonic::Request;
+
+/// Prefix of the indexer hash of a recorder in the block requests sent to chain-reader
+const RECORDER_INDEXER_HASH_PREFIX: &str = "massbit_sol_recorder";
+
+/// Records the block responses sent by chain-reader to the indexer of a project, with the filter of its manifest.
+/// Each payload is written as a compressed fixture in the output directory until `block_count` blocks are recorded.
+/// With `start_block` chain-reader sends the blocks from that slot first, these history blocks carry no account states.
+/// Without it the recording starts at the current broadcast slot.
+pub fn record_blocks(
+    project_dir: &str,
+    chain_reader_url: &str,
+    output_dir: &str,
+    start_block: Option<u64>,
+    block_count: usize,
+) -> Result<usize, anyhow::Error> {
+    let (address, network) = read_manifest(&PathBuf::from(project_dir))?;
+    let filter = SolanaFilter::new(address.iter().map(String::as_str).collect());
+    let request = BlockRequest {
+        indexer_hash: recorder_indexer_hash(),
+        start_block_number: start_block,
+        chain_type: ChainType::Solana as i32,
+        network,
+        filter: serde_json::to_vec(&filter)?,
+        replay_history: start_block.is_some(),
+    };
+    let output_dir = PathBuf::from(output_dir);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let channel = Channel::from_shared(chain_reader_url.to_string())?
+            .connect()
+            .await?;
+        let mut stream = StreamClient::new(channel)
+            .blocks(Request::new(request))
+            .await?
+            .into_inner();
+        let mut recorded = 0;
+        while recorded < block_count {
+            match stream.message().await? {
+                Some(response) => {
+                    let blocks: Vec<SolanaBlock> = serde_json::from_slice(&response.payload)?;
+                    if let (Some(first), Some(last)) = (blocks.first(), blocks.last()) {
+                        let file =
+                            output_dir.join(fixture_name(first.block_number, last.block_number));
+                        write_fixture(&file, &response.payload)?;
+                        recorded += blocks.len();
+                        println!("Recorded {} blocks in {:?}", blocks.len(), &file);
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok::<usize, anyhow::Error>(recorded)
+    })
+}
+
+/// Every recorder registers as another indexer of chain-reader, so concurrent recorders get their own hash
+fn recorder_indexer_hash() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    format!(
+        "{}_{}_{}",
+        RECORDER_INDEXER_HASH_PREFIX,
+        std::process::id(),
+        millis
+    )
+}
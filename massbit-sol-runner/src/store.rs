@@ -0,0 +1,212 @@
+use massbit_solana_sdk::entity::{Entity, EntityFilter, EntityOrder, EntityRange, Value};
+use massbit_solana_sdk::store::IndexStore;
+use massbit_solana_test_harness::store::EntityKey;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+
+/// Change of an entity by a block
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityChange {
+    Insert(Entity),
+    Update(Entity, Entity),
+    Remove(Entity),
+}
+
+/// Changes of the entities written by the flush of a block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockDiff {
+    pub block_slot: u64,
+    pub changes: BTreeMap<EntityKey, EntityChange>,
+}
+
+impl BlockDiff {
+    /// Human readable diff: `+` inserted, `~` updated and `-` removed entities
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Block {}: {} entities changed",
+            self.block_slot,
+            self.changes.len()
+        );
+        for ((entity_name, entity_id), change) in self.changes.iter() {
+            match change {
+                EntityChange::Insert(entity) => {
+                    let _ = writeln!(out, "+ {} {}", entity_name, entity_id);
+                    for (attribute, value) in entity.clone().sorted() {
+                        if attribute != "id" {
+                            let _ = writeln!(out, "    {}: {}", attribute, value);
+                        }
+                    }
+                }
+                EntityChange::Update(before, after) => {
+                    let _ = writeln!(out, "~ {} {}", entity_name, entity_id);
+                    let mut attributes = before.clone().sorted();
+                    attributes.extend(after.clone().sorted());
+                    let mut attributes = attributes
+                        .into_iter()
+                        .map(|(attribute, _)| attribute)
+                        .collect::<Vec<String>>();
+                    attributes.sort();
+                    attributes.dedup();
+                    for attribute in attributes {
+                        let old_value = before.get(&attribute).unwrap_or(&Value::Null);
+                        let new_value = after.get(&attribute).unwrap_or(&Value::Null);
+                        if old_value != new_value {
+                            let _ =
+                                writeln!(out, "    {}: {} -> {}", attribute, old_value, new_value);
+                        }
+                    }
+                }
+                EntityChange::Remove(_) => {
+                    let _ = writeln!(out, "- {} {}", entity_name, entity_id);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Wraps the store of an indexer to record the entities changed by each flushed block
+pub struct DiffStore {
+    store: Box<dyn IndexStore>,
+    /// Entities touched since the last flush, with their state before the first change
+    before: BTreeMap<EntityKey, Option<Entity>>,
+    diffs: Vec<BlockDiff>,
+}
+
+impl DiffStore {
+    pub fn new(store: Box<dyn IndexStore>) -> Self {
+        DiffStore {
+            store,
+            before: BTreeMap::default(),
+            diffs: vec![],
+        }
+    }
+    /// Diffs of the blocks flushed since the last call
+    pub fn take_diffs(&mut self) -> Vec<BlockDiff> {
+        std::mem::take(&mut self.diffs)
+    }
+    fn record(&mut self, entity_name: &str, entity_id: &str) {
+        let key = (entity_name.to_string(), entity_id.to_string());
+        if !self.before.contains_key(&key) {
+            let entity = self.store.get(key.0.clone(), &key.1);
+            self.before.insert(key, entity);
+        }
+    }
+}
+
+impl IndexStore for DiffStore {
+    fn save(&mut self, entity_name: String, data: Entity) {
+        if let Ok(entity_id) = data.id() {
+            self.record(&entity_name, &entity_id);
+        }
+        self.store.save(entity_name, data);
+    }
+
+    fn get(&mut self, entity_name: String, entity_id: &String) -> Option<Entity> {
+        self.store.get(entity_name, entity_id)
+    }
+
+    fn remove(&mut self, entity_name: String, entity_id: &String) {
+        self.record(&entity_name, entity_id);
+        self.store.remove(entity_name, entity_id);
+    }
+
+    fn query(
+        &self,
+        entity_name: String,
+        filter: Option<EntityFilter>,
+        order: EntityOrder,
+        range: EntityRange,
+    ) -> Result<Vec<Entity>, Box<dyn Error>> {
+        self.store.query(entity_name, filter, order, range)
+    }
+
+    fn flush(&mut self, block_hash: &String, block_slot: u64) -> Result<(), Box<dyn Error>> {
+        let mut diff = BlockDiff {
+            block_slot,
+            changes: BTreeMap::default(),
+        };
+        for ((entity_name, entity_id), before) in std::mem::take(&mut self.before) {
+            let after = self.store.get(entity_name.clone(), &entity_id);
+            let change = match (before, after) {
+                (None, Some(after)) => EntityChange::Insert(after),
+                (Some(before), Some(after)) if before != after => {
+                    EntityChange::Update(before, after)
+                }
+                (Some(before), None) => EntityChange::Remove(before),
+                _ => continue,
+            };
+            diff.changes.insert((entity_name, entity_id), change);
+        }
+        self.store.flush(block_hash, block_slot)?;
+        self.diffs.push(diff);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use massbit_solana_test_harness::MemoryStore;
+
+    fn market(id: &str, volume: i32) -> Entity {
+        Entity::from(vec![
+            ("id", Value::from(id)),
+            ("volume", Value::from(volume)),
+        ])
+    }
+
+    fn key(id: &str) -> EntityKey {
+        ("Market".to_string(), id.to_string())
+    }
+
+    #[test]
+    fn diffs_of_flushed_blocks() {
+        let mut store = DiffStore::new(Box::new(MemoryStore::new()));
+        store.save("Market".to_string(), market("a", 1));
+        store.save("Market".to_string(), market("b", 2));
+        store.flush(&String::from("hash"), 1).unwrap();
+        store.save("Market".to_string(), market("a", 3));
+        store.save("Market".to_string(), market("b", 2));
+        store.remove("Market".to_string(), &String::from("c"));
+        store.flush(&String::from("hash"), 2).unwrap();
+        store.remove("Market".to_string(), &String::from("b"));
+        store.flush(&String::from("hash"), 3).unwrap();
+
+        let diffs = store.take_diffs();
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[0].block_slot, 1);
+        assert_eq!(
+            diffs[0].changes.get(&key("a")),
+            Some(&EntityChange::Insert(market("a", 1)))
+        );
+        assert_eq!(
+            diffs[0].changes.get(&key("b")),
+            Some(&EntityChange::Insert(market("b", 2)))
+        );
+        // Saving the same fields again or removing a missing entity is not a change
+        assert_eq!(diffs[1].changes.len(), 1);
+        assert_eq!(
+            diffs[1].changes.get(&key("a")),
+            Some(&EntityChange::Update(market("a", 1), market("a", 3)))
+        );
+        assert_eq!(diffs[2].changes.len(), 1);
+        assert_eq!(
+            diffs[2].changes.get(&key("b")),
+            Some(&EntityChange::Remove(market("b", 2)))
+        );
+        assert!(store.take_diffs().is_empty());
+
+        assert_eq!(
+            diffs[1].to_text(),
+            "Block 2: 1 entities changed\n~ Market a\n    volume: 1 -> 3\n"
+        );
+        assert_eq!(
+            diffs[2].to_text(),
+            "Block 3: 1 entities changed\n- Market b\n"
+        );
+    }
+}
@@ -52,15 +52,16 @@ impl OrderV3 {
         filter: Option<EntityFilter>,
         order: EntityOrder,
         range: EntityRange,
-    ) -> Vec<OrderV3> {
-        unsafe {
+    ) -> Result<Vec<OrderV3>, Box<dyn std::error::Error>> {
+        let entities = unsafe {
             STORE
                 .as_ref()
                 .unwrap()
-                .query("OrderV3".to_string(), filter, order, range)
-                .iter()
-                .map(|e| OrderV3::from_entity(e))
-                .collect::<Vec<OrderV3>>()
-        }
+                .query("OrderV3".to_string(), filter, order, range)?
+        };
+        Ok(entities
+            .iter()
+            .map(|e| OrderV3::from_entity(e))
+            .collect::<Vec<OrderV3>>())
     }
 }